@@ -1,12 +1,17 @@
-use crate::client::{ClaudeClient, Message, ToolResult};
+use crate::client::{ClaudeClient, Message};
 use crate::compaction;
 use crate::config::{imp_home, Config};
 use crate::highlight;
+use crate::interrupt::Interrupt;
+use crate::knowledge_writer::KnowledgeWriter;
 use crate::context::ContextManager;
 use crate::db::Database;
 use crate::error::{ImpError, Result};
+use crate::events::EventLog;
 use crate::project::{self, ProjectInfo, ProjectRegistry};
-use crate::subagent::{SubAgent, SubAgentHandle, SubAgentResult};
+use crate::subagent::{SubAgent, SubAgentHandle, SubAgentProgress, SubAgentResult};
+use crate::tokens;
+use crate::tools::mcp::McpServerStatus;
 use crate::tools::ToolRegistry;
 use crate::usage::UsageTracker;
 use tracing::warn;
@@ -14,10 +19,11 @@ use chrono::Local;
 use console::style;
 use rustyline::ExternalPrinter as RustylineExternalPrinter;
 use serde_json::json;
+use std::collections::{BTreeMap, VecDeque};
 use std::fs;
+use std::path::PathBuf;
 use std::io::Write;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 // termimad used via highlight module
 
@@ -30,6 +36,26 @@ pub type SharedPrinter = Arc<Mutex<Box<dyn RustylineExternalPrinter + Send>>>;
 /// for proper distillation; the automatic version was too noisy.
 const AUTO_INSIGHTS: bool = false;
 
+/// Heading of the knowledge block prepended to a user prompt.
+const RETRIEVED_CONTEXT_HEADER: &str = "[Retrieved context]";
+
+/// What `Agent::undo_last_exchange` took out of the conversation.
+pub struct UndoneExchange {
+    /// Text of the removed user prompt.
+    pub prompt: String,
+    /// Messages removed, including the prompt itself.
+    pub removed: usize,
+}
+
+/// A tool call made while answering the latest prompt.
+#[derive(Debug, serde::Serialize)]
+pub struct TurnToolCall {
+    pub name: String,
+    pub input: serde_json::Value,
+    /// Whether the tool reported an error.
+    pub is_error: bool,
+}
+
 pub struct Agent {
     client: ClaudeClient,
     config: Config,
@@ -39,42 +65,61 @@ pub struct Agent {
     project: Option<ProjectInfo>,
     session_start: std::time::Instant,
     total_tool_calls: usize,
+    /// Files written or edited this session, each with the operations done
+    /// to it. Saved with the session.
+    files_changed: BTreeMap<String, Vec<String>>,
     usage: UsageTracker,
     db: Database,
     session_id: String,
     /// Handles for spawned sub-agents running as background tokio tasks.
     sub_agents: Vec<SubAgentHandle>,
-    /// Shared flag for Ctrl+C interrupt support.
-    interrupt_flag: Option<Arc<AtomicBool>>,
+    /// Sub-agents waiting for a free slot (`[subagent] max_concurrent`).
+    queued_sub_agents: VecDeque<SubAgent>,
+    /// Shared with the Ctrl+C handler; also cancels in-flight model requests.
+    interrupt: Option<Arc<Interrupt>>,
+    /// Per-session thinking switch (`--think`/`--no-think`, `/think`);
+    /// `None` follows `[thinking] enabled`.
+    thinking_override: Option<bool>,
     /// External printer for readline-safe output.
     printer: Option<SharedPrinter>,
+    /// Drop status output (tool calls, usage, warnings); see `set_quiet`.
+    quiet: bool,
+    /// `--deterministic`: no knowledge retrieved into prompts; see
+    /// `set_deterministic`.
+    deterministic: bool,
     /// Whether the knowledge graph is enabled. KG is opened per-operation
     /// to allow multiple sessions to share access (RocksDB lock released between ops).
     knowledge_enabled: bool,
+    /// Applies `store_knowledge` writes in the background; flushed on drop.
+    knowledge_writer: Option<KnowledgeWriter>,
+    /// Telemetry sink, when `[observability] events_file` is set.
+    events: Option<EventLog>,
 }
 
 impl Agent {
-    /// Render markdown text to a string for emission through the printer.
-    /// Code blocks with language tags get syntax highlighting via syntect.
-    fn render_markdown_to_string(text: &str, theme: &str) -> String {
-        if text.trim().is_empty() {
-            return String::new();
-        }
-        let highlighted = highlight::highlight_code_blocks(text, theme);
-        let skin = termimad::MadSkin::default();
-        format!("{}", skin.term_text(&highlighted))
-    }
-
     /// Emit a line of output through the ExternalPrinter (readline-safe) if
     /// available, otherwise fall back to stdout.
     pub fn emit(&self, msg: impl std::fmt::Display) {
+        if self.quiet {
+            return;
+        }
         emit_line(&self.printer, msg);
     }
 
+    /// Append a telemetry event if `[observability] events_file` is set.
+    /// `fields` is only built when it is.
+    fn record_event(&self, event: &str, fields: impl FnOnce() -> serde_json::Value) {
+        if let Some(ref events) = self.events {
+            events.record(&self.session_id, event, fields());
+        }
+    }
+
     /// Create an agent. Automatically detects the project from cwd and loads
     /// two-layer context (global + per-project).
     pub async fn new() -> Result<Self> {
-        let config = Config::load()?;
+        let mut config = Config::load()?;
+        // Settle `theme = "auto"` now, while nothing else is reading the terminal
+        config.display.theme = config.display.resolved_theme();
 
         let client = ClaudeClient::new(config.clone())?;
 
@@ -87,9 +132,21 @@ impl Agent {
         }
 
         // Load two-layer context
-        let context = ContextManager::load(project_info.as_ref())?;
+        let mut context = ContextManager::load(project_info.as_ref())?;
+        if config.context.include_git_diff {
+            context.set_git_root(project_info.as_ref().map(|p| PathBuf::from(&p.path)));
+        }
 
         let mut tools = ToolRegistry::new();
+        tools.set_sandbox_root(config.tools.sandbox_root.as_ref().map(std::path::PathBuf::from));
+        tools.set_max_glob_files(config.tools.max_glob_files);
+        tools.set_max_output_bytes(config.tools.max_output_bytes);
+        tools.set_read_only(config.tools.read_only);
+        tools.set_exec_policy(crate::tools::builtin::ExecPolicy::new(
+            &config.tools.exec.allow_patterns,
+            &config.tools.exec.deny_patterns,
+        )?);
+        tools.set_knowledge_enabled(config.knowledge.enabled);
         let tools_dir = crate::config::imp_home()?.join("tools");
         tools.load_from_directory(tools_dir).await?;
 
@@ -106,17 +163,16 @@ impl Agent {
         // Register tmux pane for this session (for TUI manager)
         let _ = crate::tmux::register_pane(&session_id);
 
-        let mut usage = UsageTracker::new();
+        let mut usage = UsageTracker::with_pricing(config.pricing.clone());
         usage.set_model(&config.llm.model);
 
-        // Disable embeddings if configured, otherwise start loading in background
-        if !config.knowledge.embeddings_enabled {
-            crate::embeddings::Embedder::disable();
-        } else if config.knowledge.enabled {
-            crate::embeddings::Embedder::init_background();
-        }
+        // Disable embeddings if configured, otherwise select the provider
+        // (the local model starts loading in the background)
+        crate::embeddings::Embedder::configure(&config.knowledge);
 
         let knowledge_enabled = config.knowledge.enabled;
+        let knowledge_writer = knowledge_enabled.then(|| KnowledgeWriter::spawn(config.knowledge.clone()));
+        let events = config.observability.events_file.as_deref().map(EventLog::open).transpose()?;
 
         Ok(Self {
             client,
@@ -127,13 +183,20 @@ impl Agent {
             project: project_info,
             session_start: std::time::Instant::now(),
             total_tool_calls: 0,
+            files_changed: BTreeMap::new(),
             usage,
             db,
             session_id,
             sub_agents: Vec::new(),
-            interrupt_flag: None,
+            queued_sub_agents: VecDeque::new(),
+            interrupt: None,
+            thinking_override: None,
             printer: None,
+            quiet: false,
+            deterministic: false,
             knowledge_enabled,
+            knowledge_writer,
+            events,
         })
     }
 
@@ -145,87 +208,108 @@ impl Agent {
         self.context.loaded_sections()
     }
 
+    /// The system prompt as assembled for the next turn, section by section.
+    pub fn system_prompt_sections(&self) -> Vec<(String, String)> {
+        self.context.prompt_sections()
+    }
+
+    /// Knowledge-graph context retrieved for the latest prompt, if any.
+    pub fn last_retrieved_context(&self) -> Option<&str> {
+        let prompt = self.messages.iter().rev().find(|m| is_user_prompt(m))?;
+        let blocks = prompt.content.as_array()?;
+        let text = blocks.first()?.get("text")?.as_str()?;
+        if blocks.len() > 1 && text.starts_with(RETRIEVED_CONTEXT_HEADER) {
+            Some(text)
+        } else {
+            None
+        }
+    }
+
+    /// Tokens taken up by the tool schemas sent with every request.
+    pub async fn tool_schema_tokens(&mut self) -> usize {
+        tokens::count_text(&self.tools.get_tool_schemas().await.to_string())
+    }
+
     /// The agent's display name, parsed from SOUL.md. Falls back to "Imp".
     pub fn display_name(&self) -> String {
         self.context.agent_name().unwrap_or_else(|| "Imp".to_string())
     }
 
-    /// Set the shared ExternalPrinter for readline-safe output.
+    /// Set the shared ExternalPrinter for readline-safe output, including
+    /// streamed response text.
     pub fn set_printer(&mut self, printer: SharedPrinter) {
+        self.client.set_printer(printer.clone());
         self.printer = Some(printer);
     }
 
+    /// Stop printing status output, for callers that present the result
+    /// themselves (`imp ask --json`).
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Tool calls made since the latest user prompt, in order.
+    pub fn last_turn_tool_calls(&self) -> Vec<TurnToolCall> {
+        let start = self.messages.iter().rposition(is_user_prompt).unwrap_or(0);
+        let blocks = || {
+            self.messages[start..]
+                .iter()
+                .filter_map(|m| m.content.as_array())
+                .flatten()
+        };
+        let failed: std::collections::HashSet<&str> = blocks()
+            .filter(|b| b["type"] == "tool_result" && b["is_error"] == true)
+            .filter_map(|b| b["tool_use_id"].as_str())
+            .collect();
+        blocks()
+            .filter(|b| b["type"] == "tool_use")
+            .map(|b| TurnToolCall {
+                name: b["name"].as_str().unwrap_or_default().to_string(),
+                input: b["input"].clone(),
+                is_error: b["id"].as_str().is_some_and(|id| failed.contains(id)),
+            })
+            .collect()
+    }
+
     pub async fn process_message(&mut self, user_message: &str, stream: bool) -> Result<String> {
         self.process_message_with_options(user_message, stream, false).await
     }
 
+    /// Process a message and render the reply as markdown. With `[display]
+    /// stream` on, the reply is streamed and rendered a block at a time.
     pub async fn process_message_with_markdown(&mut self, user_message: &str) -> Result<String> {
-        self.process_message_with_options(user_message, false, true).await
-    }
-
-    /// Repair orphaned tool_use blocks in the message history.
-    ///
-    /// When the agent is interrupted mid-tool-execution, the conversation history
-    /// may contain an assistant message with tool_use blocks but no corresponding
-    /// tool_result message. The Anthropic API requires every tool_use to have a
-    /// matching tool_result in the immediately following user message.
-    ///
-    /// This scans the last message and injects synthetic error tool_results
-    /// for any orphaned tool_use blocks.
-    fn repair_orphaned_tool_use(&mut self) {
-        let tool_use_ids: Vec<String> = match self.messages.last() {
-            Some(msg) if msg.role == "assistant" => {
-                match &msg.content {
-                    serde_json::Value::Array(blocks) => {
-                        blocks.iter()
-                            .filter_map(|block| {
-                                if block.get("type")?.as_str()? == "tool_use" {
-                                    block.get("id")?.as_str().map(|s| s.to_string())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect()
-                    }
-                    _ => return,
-                }
-            }
-            _ => return,
-        };
+        let stream = self.config.display.stream;
+        self.process_message_with_options(user_message, stream, true).await
+    }
 
-        if tool_use_ids.is_empty() {
+    /// Make the history API-valid again, e.g. after a turn was interrupted
+    /// mid-tool-execution (see `client::repair_history`). The repaired
+    /// messages are saved too, so a resumed session doesn't need repairing again.
+    fn repair_history(&mut self) {
+        let before = self.messages.clone();
+        let repairs = crate::client::repair_history(&mut self.messages);
+        if repairs.is_empty() {
             return;
         }
+        for repair in &repairs {
+            warn!(session = %self.session_id, "Repaired history: {}", repair);
+        }
 
-        let count = tool_use_ids.len();
-        let results: Vec<ToolResult> = tool_use_ids
-            .into_iter()
-            .map(|id| ToolResult {
-                tool_use_id: id,
-                content: "Tool execution was interrupted by the user.".to_string(),
-                is_error: Some(true),
-            })
-            .collect();
-
-        let tool_msg = Message::tool_results(results);
-
-        // Persist to DB so resumed sessions are also repaired
-        if let Err(e) = self.db.save_message(
+        // Compaction means the in-memory history may not start where the
+        // stored one does, so replace from the first change to the end
+        let first_change = before
+            .iter()
+            .zip(&self.messages)
+            .position(|(old, new)| old.role != new.role || old.content != new.content)
+            .unwrap_or(before.len().min(self.messages.len()));
+        if let Err(e) = self.db.replace_last_messages(
             &self.session_id,
-            "user",
-            &tool_msg.content,
-            0,
+            before.len() - first_change,
+            &self.messages[first_change..],
         ) {
             self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
         }
-
-        self.messages.push(tool_msg);
-        self.emit(
-            style(format!(
-                "🔧 Repaired {} interrupted tool call(s) from previous turn",
-                count
-            )).dim()
-        );
+        self.emit(style(format!("🔧 Repaired the session history: {}", repairs.join("; "))).dim());
     }
 
     async fn process_message_with_options(&mut self, user_message: &str, stream: bool, render_markdown: bool) -> Result<String> {
@@ -233,7 +317,7 @@ impl Agent {
         let _ = crate::tmux::set_status(&self.session_id, crate::tmux::AgentStatus::Working);
         
         // Repair any orphaned tool_use blocks from a previous interrupt
-        self.repair_orphaned_tool_use();
+        self.repair_history();
 
         // Before processing, check if any sub-agents have completed and enrich the message
         let completed = self.collect_completed_subagents().await;
@@ -257,11 +341,8 @@ impl Agent {
         };
 
         // Build user message with optional knowledge context as separate block
-        let knowledge_context = if self.knowledge_enabled {
-            crate::knowledge::KnowledgeGraph::open()
-                .ok()
-                .and_then(|kg| kg.retrieve_context(&effective_message, 5, 5).ok())
-                .filter(|ctx| !ctx.is_empty())
+        let knowledge_context = if self.knowledge_enabled && !self.deterministic {
+            self.retrieve_knowledge(&effective_message).await
         } else {
             None
         };
@@ -271,7 +352,7 @@ impl Agent {
             json!([
                 {
                     "type": "text",
-                    "text": format!("[Retrieved context]\n\n{}\n\n---", knowledge)
+                    "text": format!("{}\n\n{}\n\n---", RETRIEVED_CONTEXT_HEADER, knowledge)
                 },
                 {
                     "type": "text",
@@ -292,6 +373,14 @@ impl Agent {
         ) {
             self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
         }
+        if compaction::is_directive(user_message) {
+            self.keep_last_prompt();
+        }
+        self.record_event("turn_start", || json!({
+            "prompt_chars": user_message.chars().count(),
+            "knowledge_context": knowledge_context.is_some(),
+            "subagent_results": completed.len(),
+        }));
 
         let mut turn_tool_count: usize = 0;
 
@@ -305,30 +394,66 @@ impl Agent {
             let system_prompt = self.context.assemble_system_prompt();
             // Knowledge retrieval moved to user message blocks for better caching
             
-            let system_tokens = system_prompt.len() / 4;
+            let system_tokens = tokens::count_text(&system_prompt.stable) + tokens::count_text(&system_prompt.volatile);
             let tools = Some(self.tools.get_tool_schemas().await);
-            let tool_tokens = tools.as_ref().map_or(0, |t| t.to_string().len() / 4);
-            self.messages = compaction::compact_if_needed(&self.messages, system_tokens, tool_tokens);
+            let tool_tokens = tools.as_ref().map_or(0, |t| tokens::count_text(&t.to_string()));
+            let context_limit = self.config.llm.context_window();
+            if let Some(compacted) = compaction::compact_if_needed(
+                &self.messages,
+                system_tokens,
+                tool_tokens,
+                context_limit,
+                &self.config.compaction,
+            ) {
+                let before = tokens::count_messages(&self.messages);
+                self.messages = compacted;
+                let after = tokens::count_messages(&self.messages);
+                self.emit(
+                    style(format!("📦 Compacted the conversation: {} → {} tokens", before, after)).dim()
+                );
+                self.record_compaction("threshold", before, after);
+            }
+            self.usage.record_context(system_tokens + tool_tokens + tokens::count_messages(&self.messages));
+
+            self.client
+                .set_stream_markdown((stream && render_markdown).then(|| self.config.display.theme.clone()));
 
             // Show thinking indicator for non-streaming mode
-            let show_thinking = !stream && self.config.thinking.enabled;
+            let show_thinking = !stream && self.thinking_enabled();
             if show_thinking {
                 self.emit(style("💭 Thinking...").dim());
             }
 
+            let request_start = std::time::Instant::now();
             let response = match self
                 .client
-                .send_message(self.messages.clone(), Some(system_prompt.clone()), tools.clone(), stream)
+                .send_message_inner(
+                    self.messages.clone(),
+                    Some(system_prompt.clone()),
+                    tools.clone(),
+                    stream,
+                    None,
+                    self.thinking_override,
+                )
                 .await
             {
                 Ok(r) => r,
                 Err(ref e) if Self::is_context_overflow_error(e) => {
                     // Context too long — compact and retry
                     self.emit(style("⚠ Context too long — compacting and retrying...").yellow());
-                    self.messages = compaction::compact(&self.messages);
+                    let before = tokens::count_messages(&self.messages);
+                    self.messages = compaction::compact(&self.messages, self.config.compaction.strategy);
+                    self.record_compaction("overflow", before, tokens::count_messages(&self.messages));
                     let retry_tools = Some(self.tools.get_tool_schemas().await);
                     self.client
-                        .send_message(self.messages.clone(), Some(system_prompt), retry_tools, stream)
+                        .send_message_inner(
+                            self.messages.clone(),
+                            Some(system_prompt),
+                            retry_tools,
+                            stream,
+                            None,
+                            self.thinking_override,
+                        )
                         .await?
                 }
                 Err(e) => {
@@ -345,17 +470,33 @@ impl Agent {
             if let Some(ref usage) = response.usage {
                 self.usage.record(usage.input_tokens, usage.output_tokens);
                 self.usage.record_cache(usage.cache_creation_input_tokens, usage.cache_read_input_tokens);
-                self.emit(style(UsageTracker::format_response_usage(
+                let response_usage = self.usage.format_response_usage(
                     usage.input_tokens,
                     usage.output_tokens,
                     usage.cache_creation_input_tokens,
                     usage.cache_read_input_tokens,
-                    Some(&self.config.llm.model),
-                )).dim());
+                );
+                let gauge = self.usage.format_context_gauge(context_limit);
+                let gauge = match self.usage.context_percent(context_limit) {
+                    0..=59 => style(gauge).green(),
+                    60..=84 => style(gauge).yellow(),
+                    _ => style(gauge).red(),
+                };
+                self.emit(format!("{} · {}", style(response_usage).dim(), gauge.dim()));
             }
 
             let text_content = self.client.extract_text_content(&response);
             let tool_calls = self.client.extract_tool_calls(&response);
+            self.record_event("model_request", || json!({
+                "model": self.config.llm.model,
+                "duration_ms": request_start.elapsed().as_millis() as u64,
+                "input_tokens": response.usage.as_ref().map(|u| u.input_tokens),
+                "output_tokens": response.usage.as_ref().map(|u| u.output_tokens),
+                "cache_write_tokens": response.usage.as_ref().map(|u| u.cache_creation_input_tokens),
+                "cache_read_tokens": response.usage.as_ref().map(|u| u.cache_read_input_tokens),
+                "stop_reason": response.stop_reason,
+                "tool_calls": tool_calls.len(),
+            }));
 
             // CRITICAL: Preserve raw content blocks (text + tool_use) for proper protocol
             let content_blocks = self.client.extract_content_blocks(&response);
@@ -375,9 +516,9 @@ impl Agent {
 
             if tool_calls.is_empty() {
                 if render_markdown && !stream {
-                    let rendered = Self::render_markdown_to_string(&text_content, &self.config.display.theme);
+                    let rendered = highlight::render_markdown(&text_content, &self.config.display.theme);
                     if !rendered.is_empty() {
-                        self.emit(rendered.trim_end());
+                        emit_verbatim(&self.printer, rendered.trim_end());
                     }
                 }
                 // Auto-generate session title after the first exchange
@@ -408,6 +549,7 @@ impl Agent {
                 );
 
                 // Intercept tools that need Agent state (KG, sub-agents)
+                let tool_start = std::time::Instant::now();
                 let result = match tool_call.name.as_str() {
                     "spawn_agent" => self.handle_spawn_agent(&tool_call),
                     "check_agents" => {
@@ -441,24 +583,23 @@ impl Agent {
                     }
                 };
 
-                // Convert to proper ToolResult format for Anthropic
-                let anthropic_result = crate::client::ToolResult {
-                    tool_use_id: result.tool_use_id,
-                    content: if let Some(ref error) = result.error {
-                        error.clone()
-                    } else {
-                        result.content
-                    },
-                    is_error: result.error.is_some().then_some(true),
-                };
-
-                tool_results.push(anthropic_result);
+                self.record_event("tool_call", || json!({
+                    "name": tool_call.name,
+                    "input": crate::events::tool_input(&tool_call.input),
+                    "duration_ms": tool_start.elapsed().as_millis() as u64,
+                    "result_bytes": result.content.len(),
+                    "is_error": result.error.is_some(),
+                }));
 
                 if let Some(ref error) = result.error {
                     self.emit(style(format!("❌ Tool error: {}", error)).red());
                 } else {
                     self.emit(style("✅ Tool completed successfully").green());
+                    self.record_file_change(&tool_call.name, &tool_call.input);
                 }
+
+                // Convert to proper ToolResult format for Anthropic
+                tool_results.push(result.into_anthropic());
             }
 
             // CRITICAL: Send tool results as proper tool_result content blocks
@@ -497,19 +638,51 @@ impl Agent {
         self.messages.len()
     }
 
+    /// Keep the latest prompt verbatim through compaction (`/keep`). Returns
+    /// its text, or `None` if there's no prompt yet.
+    pub fn keep_last_prompt(&mut self) -> Option<String> {
+        let message = self.messages.iter_mut().rev().find(|m| is_user_prompt(m))?;
+        message.keep = true;
+        let text = prompt_text(message);
+        if let Err(e) = self.db.keep_last_prompt(&self.session_id) {
+            self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
+        }
+        text
+    }
+
     /// Manually trigger compaction (/compact). Always compacts.
-    /// Returns true if compaction was performed.
-    pub fn compact_now(&mut self) -> bool {
+    /// Returns the message tokens before and after, if compaction was performed.
+    pub fn compact_now(&mut self) -> Option<(usize, usize)> {
         let before = self.messages.len();
         if before <= 4 {
-            return false; // Nothing meaningful to compact
+            return None; // Nothing meaningful to compact
         }
-        self.messages = compaction::compact(&self.messages);
-        self.messages.len() < before
+        let before_tokens = tokens::count_messages(&self.messages);
+        self.messages = compaction::compact(&self.messages, self.config.compaction.strategy);
+        if self.messages.len() >= before {
+            return None;
+        }
+        let after_tokens = tokens::count_messages(&self.messages);
+        self.record_compaction("manual", before_tokens, after_tokens);
+        Some((before_tokens, after_tokens))
+    }
+
+    /// Record a compaction; `reason` is "threshold", "overflow" or "manual".
+    fn record_compaction(&self, reason: &str, before_tokens: usize, after_tokens: usize) {
+        self.record_event("compaction", || json!({
+            "reason": reason,
+            "strategy": self.config.compaction.strategy,
+            "before_tokens": before_tokens,
+            "after_tokens": after_tokens,
+        }));
     }
 
     /// Check if an error indicates the context/input is too long for the model.
     fn is_context_overflow_error(error: &ImpError) -> bool {
+        // Compacting won't help a request that timed out
+        if matches!(error, ImpError::Timeout(_)) {
+            return false;
+        }
         let msg = error.to_string().to_lowercase();
         msg.contains("too long")
             || msg.contains("too large")
@@ -527,17 +700,170 @@ impl Agent {
         result.content
     }
 
+    /// Connection status of every configured MCP server (for /mcp command).
+    pub async fn mcp_status(&mut self) -> Vec<McpServerStatus> {
+        self.tools.mcp_status().await
+    }
+
     /// Resume a previous session: load its messages from the database.
     pub fn resume(&mut self, session_id: &str) -> Result<()> {
         // Unregister old pane, register new one
         let _ = crate::tmux::unregister_pane(&self.session_id);
-        let messages = self.db.load_session_messages(session_id)?;
-        self.messages = messages;
+        self.messages = self.db.load_session_messages(session_id)?;
         self.session_id = session_id.to_string();
+        self.repair_history();
         let _ = crate::tmux::register_pane(&self.session_id);
+        self.relocate_if_project_missing();
+        self.restore_pins();
+        self.files_changed = self.db.load_session_files(session_id).unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to load the session's changed files");
+            BTreeMap::new()
+        });
         Ok(())
     }
 
+    /// The context in use was detected from the current directory. If the
+    /// resumed session came from a directory that's since gone, say so and
+    /// move the session to the current project (or to none).
+    fn relocate_if_project_missing(&mut self) {
+        let workdir = std::env::current_dir().ok().and_then(|p| p.to_str().map(String::from));
+        let project = self.project_name().map(String::from);
+        let old = match self.db.relocate_session_if_missing(&self.session_id, project.as_deref(), workdir.as_deref()) {
+            Ok(Some(old)) => old,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(error = %e, "Failed to check the resumed session's project");
+                return;
+            }
+        };
+        let started_in = match old.project {
+            Some(name) => format!("project {} ({})", name, old.workdir.unwrap_or_default()),
+            None => old.workdir.unwrap_or_default(),
+        };
+        let context = match project {
+            Some(name) => format!("the {} project detected here", name),
+            None => "global context only".to_string(),
+        };
+        self.emit(
+            style(format!("⚠ This session was started in {}, which no longer exists. Continuing with {}.", started_in, context))
+                .yellow()
+        );
+    }
+
+    /// Files written or edited this session, with the operations on each.
+    pub fn files_changed(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.files_changed
+    }
+
+    /// Note a successful `file_write` or `file_edit` and save the list.
+    /// Simulated ones (read-only mode) don't count.
+    fn record_file_change(&mut self, tool_name: &str, input: &serde_json::Value) {
+        if self.read_only() {
+            return;
+        }
+        let operation = match tool_name {
+            "file_write" => "write",
+            "file_edit" => "edit",
+            _ => return,
+        };
+        let Some(path) = input.get("path").and_then(|v| v.as_str()) else {
+            return;
+        };
+        // Absolute, so the daily memory file makes sense across projects
+        let path = fs::canonicalize(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        let operations = self.files_changed.entry(path).or_default();
+        if operations.iter().any(|op| op == operation) {
+            return;
+        }
+        operations.push(operation.to_string());
+        if let Err(e) = self.db.set_session_files(&self.session_id, &self.files_changed) {
+            self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
+        }
+    }
+
+    /// Pin a context file into the system prompt for this session.
+    pub fn pin_context_file(&mut self, file: &str) -> Result<PathBuf> {
+        let path = self.context.pin(file)?;
+        self.save_pins();
+        Ok(path)
+    }
+
+    /// Unpin a context file. Returns the path it unpinned, if any matched.
+    pub fn unpin_context_file(&mut self, file: &str) -> Option<PathBuf> {
+        let path = self.context.unpin(file)?;
+        self.save_pins();
+        Some(path)
+    }
+
+    /// Files pinned into the system prompt.
+    pub fn pinned_files(&self) -> &[PathBuf] {
+        self.context.pinned()
+    }
+
+    fn save_pins(&self) {
+        let pins: Vec<String> = self.context.pinned().iter().map(|p| p.display().to_string()).collect();
+        if let Err(e) = self.db.set_session_pins(&self.session_id, &pins) {
+            self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
+        }
+    }
+
+    /// Replace the current pins with the ones stored for this session.
+    fn restore_pins(&mut self) {
+        let current: Vec<String> = self.context.pinned().iter().map(|p| p.display().to_string()).collect();
+        for pin in current {
+            self.context.unpin(&pin);
+        }
+        let pins = match self.db.load_session_pins(&self.session_id) {
+            Ok(pins) => pins,
+            Err(e) => {
+                warn!(error = %e, "Failed to load pinned context files");
+                return;
+            }
+        };
+        for pin in pins {
+            if let Err(e) = self.context.pin(&pin) {
+                self.emit(style(format!("⚠ Could not re-pin {}: {}", pin, e)).dim());
+            }
+        }
+    }
+
+    /// Remove the latest exchange: the last user prompt and everything after
+    /// it (assistant replies, tool calls and their results), so tool_use and
+    /// tool_result blocks stay paired. The stored copies are marked superseded.
+    /// Returns `None` if there is no prompt left to remove — a compaction
+    /// summary counts as the start of the conversation.
+    pub fn undo_last_exchange(&mut self) -> Option<UndoneExchange> {
+        let index = self.messages.iter().rposition(is_user_prompt)?;
+        let prompt = prompt_text(&self.messages[index])?;
+        if prompt.starts_with(compaction::SUMMARY_PREFIX) {
+            return None;
+        }
+
+        let removed = self.messages.len() - index;
+        if let Err(e) = self.db.supersede_last_messages(&self.session_id, removed) {
+            self.emit(style(format!("⚠ DB write failed: {}", e)).dim());
+        }
+        self.messages.truncate(index);
+        Some(UndoneExchange { prompt, removed })
+    }
+
+    /// Roll back the latest exchange for `/retry`, returning the prompt's text
+    /// so it can be sent again.
+    pub fn rewind_last_turn(&mut self) -> Option<String> {
+        self.undo_last_exchange().map(|undone| undone.prompt)
+    }
+
+    /// Fork the current session: copy its first `from_message_index` stored
+    /// messages into a new session and switch to it. The original session is
+    /// left untouched and can be resumed later. Returns the new session ID.
+    pub fn fork_session(&mut self, from_message_index: usize) -> Result<String> {
+        let new_id = self.db.create_session_from(&self.session_id, from_message_index)?;
+        self.resume(&new_id)?;
+        Ok(new_id)
+    }
+
     /// Access the underlying database (for listing sessions, etc.).
     pub fn db(&self) -> &Database {
         &self.db
@@ -556,6 +882,63 @@ impl Agent {
         &self.config
     }
 
+    /// Switch to another model for the rest of the session. The name is
+    /// validated against the configured model list; returns the resolved name.
+    pub fn set_model(&mut self, name: &str) -> Result<String> {
+        let model = self.config.llm.resolve_model(name)?;
+        self.client.set_model(&model);
+        self.usage.set_model(&model);
+        self.config.llm.model = model.clone();
+        Ok(model)
+    }
+
+    /// Force extended thinking on or off for the rest of the session, or
+    /// `None` to go back to `[thinking] enabled`.
+    pub fn set_thinking_override(&mut self, enabled: Option<bool>) {
+        self.thinking_override = enabled;
+    }
+
+    /// Make replies as repeatable as the API allows (`--deterministic`):
+    /// temperature 0, no extended thinking and no retrieved knowledge in
+    /// prompts. An explicit `[llm] seed` is still sent. Sub-agents spawned
+    /// afterwards sample the same way.
+    pub fn set_deterministic(&mut self) {
+        self.client.set_sampling(0.0);
+        self.config.llm.temperature = Some(0.0);
+        self.config.thinking.enabled = false;
+        self.thinking_override = Some(false);
+        self.deterministic = true;
+    }
+
+    /// Turn read-only mode on or off for the rest of the session. Sub-agents
+    /// spawned afterwards inherit it.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.config.tools.read_only = read_only;
+        self.tools.set_read_only(read_only);
+    }
+
+    /// Whether writes and mutating commands are being simulated.
+    pub fn read_only(&self) -> bool {
+        self.tools.read_only()
+    }
+
+    /// Change the thinking budget for the rest of the session.
+    pub fn set_thinking_budget(&mut self, budget: u32) -> Result<()> {
+        self.client.set_thinking_budget(budget)?;
+        self.config.thinking.budget_tokens = budget;
+        Ok(())
+    }
+
+    /// Whether `set_deterministic` was called for this session.
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Whether requests are currently sent with extended thinking.
+    pub fn thinking_enabled(&self) -> bool {
+        self.thinking_override.unwrap_or(self.config.thinking.enabled)
+    }
+
     /// Distill structured insights from a conversation turn into the daily memory file.
     /// Only writes if the turn was substantive (had tool calls or a long response).
     /// NOTE: Knowledge extraction happens in `imp reflect`, NOT here.
@@ -605,133 +988,78 @@ impl Agent {
 
     // ── Knowledge graph tools ─────────────────────────────────────────
 
-    /// Handle `store_knowledge` — opens KG for this operation then releases lock.
-    fn handle_store_knowledge(&self, arguments: &serde_json::Value) -> crate::tools::ToolResult {
-        if !self.knowledge_enabled {
-            return crate::tools::ToolResult {
-                tool_use_id: String::new(),
-                content: String::new(),
-                error: Some("Knowledge graph is disabled".to_string()),
-            };
+    /// Look up knowledge-graph context for `message` on a blocking thread.
+    /// Gives up after `retrieval_timeout_ms` so a large graph can't hold up
+    /// the turn; the turn then goes ahead without retrieved context.
+    async fn retrieve_knowledge(&self, message: &str) -> Option<String> {
+        let settings = self.config.knowledge.clone();
+        let project = self.project_name().map(String::from);
+        let message = message.to_string();
+        let timeout = std::time::Duration::from_millis(settings.retrieval_timeout_ms);
+
+        let lookup = tokio::task::spawn_blocking(move || {
+            crate::knowledge::KnowledgeGraph::open()
+                .ok()?
+                .with_settings(&settings)
+                .with_project(project.as_deref())
+                .retrieve_context(&message, 5, 5)
+                .ok()
+        });
+
+        match tokio::time::timeout(timeout, lookup).await {
+            Ok(Ok(context)) => context.filter(|ctx| !ctx.is_empty()),
+            Ok(Err(e)) => {
+                warn!(error = %e, "Knowledge retrieval task failed");
+                None
+            }
+            Err(_) => {
+                warn!(timeout_ms = timeout.as_millis() as u64, "Knowledge retrieval timed out; skipped for this turn");
+                None
+            }
         }
-        
-        let kg = match crate::knowledge::KnowledgeGraph::open() {
-            Ok(kg) => kg,
-            Err(e) => {
+    }
+
+    /// Handle `store_knowledge` — queues the write on the background writer
+    /// so the turn carries on while it's applied.
+    fn handle_store_knowledge(&self, arguments: &serde_json::Value) -> crate::tools::ToolResult {
+        let writer = match &self.knowledge_writer {
+            Some(writer) if self.knowledge_enabled => writer,
+            _ => {
                 return crate::tools::ToolResult {
                     tool_use_id: String::new(),
                     content: String::new(),
-                    error: Some(format!("Failed to open knowledge graph: {}", e)),
+                    error: Some("Knowledge graph is disabled".to_string()),
                 };
             }
         };
 
-        let mut entities_added = 0usize;
-        let mut relationships_added = 0usize;
-        let mut chunks_stored = 0usize;
-
-        // Process entities
-        if let Some(entities) = arguments.get("entities").and_then(|v| v.as_array()) {
-            for entity_val in entities {
-                let name = match entity_val.get("name").and_then(|v| v.as_str()) {
-                    Some(n) => n,
-                    None => continue,
-                };
-                let entity_type = entity_val
-                    .get("type")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("concept");
-                let properties = entity_val
-                    .get("properties")
-                    .cloned()
-                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-
-                // Skip if entity already exists
-                if let Ok(Some(_)) = kg.find_entity_by_name(name) {
-                    continue;
-                }
-
-                let entity = crate::knowledge::Entity {
-                    id: String::new(),
-                    entity_type: entity_type.to_string(),
-                    name: name.to_string(),
-                    properties,
-                    aliases: Vec::new(),
-                    created_at: 0.0,
-                    updated_at: 0.0,
-                };
-
-                if kg.store_entity(entity).is_ok() {
-                    entities_added += 1;
-                }
-            }
-        }
-
-        // Process relationships
-        if let Some(rels) = arguments.get("relationships").and_then(|v| v.as_array()) {
-            for rel_val in rels {
-                let from_name = match rel_val.get("from").and_then(|v| v.as_str()) {
-                    Some(n) => n,
-                    None => continue,
-                };
-                let to_name = match rel_val.get("to").and_then(|v| v.as_str()) {
-                    Some(n) => n,
-                    None => continue,
-                };
-                let rel_type = match rel_val.get("rel").and_then(|v| v.as_str()) {
-                    Some(r) => r,
-                    None => continue,
-                };
-
-                let from = match kg.find_entity_by_name(from_name) {
-                    Ok(Some(e)) => e,
-                    _ => continue,
-                };
-                let to = match kg.find_entity_by_name(to_name) {
-                    Ok(Some(e)) => e,
-                    _ => continue,
-                };
-
-                let relationship = crate::knowledge::Relationship {
-                    id: String::new(),
-                    from_id: from.id,
-                    rel_type: rel_type.to_string(),
-                    to_id: to.id,
-                    properties: serde_json::Value::Object(serde_json::Map::new()),
-                    created_at: 0.0,
-                };
-
-                if kg.store_relationship(relationship).is_ok() {
-                    relationships_added += 1;
-                }
-            }
-        }
+        // Tag with the current project unless the facts hold everywhere
+        let global = arguments.get("global").and_then(|v| v.as_bool()).unwrap_or(false);
+        let project = if global { None } else { self.project_name() };
+        let request = crate::knowledge_writer::StoreRequest {
+            arguments: arguments.clone(),
+            project: project.map(String::from),
+            source: crate::knowledge::provenance(&self.session_id, chrono::Utc::now().timestamp() as f64),
+        };
 
-        // Process chunks
-        if let Some(chunks) = arguments.get("chunks").and_then(|v| v.as_array()) {
-            for chunk_val in chunks {
-                let content = match chunk_val.get("content").and_then(|v| v.as_str()) {
-                    Some(c) => c,
-                    None => continue,
-                };
-                let source = chunk_val
-                    .get("source")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("conversation");
+        let count = |key: &str| arguments.get(key).and_then(|v| v.as_array()).map_or(0, |a| a.len());
+        let (entities, relationships, chunks) = (count("entities"), count("relationships"), count("chunks"));
 
-                if kg.store_chunk(content, source, "live").is_ok() {
-                    chunks_stored += 1;
-                }
-            }
+        if !writer.send(request) {
+            return crate::tools::ToolResult {
+                tool_use_id: String::new(),
+                content: String::new(),
+                error: Some("Knowledge writer has stopped; nothing was stored".to_string()),
+            };
         }
 
-        let total = entities_added + relationships_added + chunks_stored;
-        let content = if total == 0 {
-            "No new knowledge stored (entities may already exist).".to_string()
+        let content = if entities + relationships + chunks == 0 {
+            "Nothing to store.".to_string()
         } else {
             format!(
-                "Stored: {} entities, {} relationships, {} chunks",
-                entities_added, relationships_added, chunks_stored
+                "Queued for storage: {} entities, {} relationships, {} chunks \
+                 (entities that already exist are skipped)",
+                entities, relationships, chunks
             )
         };
 
@@ -753,7 +1081,7 @@ impl Agent {
         }
         
         let kg = match crate::knowledge::KnowledgeGraph::open() {
-            Ok(kg) => kg,
+            Ok(kg) => kg.with_project(self.project_name()),
             Err(e) => {
                 return crate::tools::ToolResult {
                     tool_use_id: String::new(),
@@ -933,7 +1261,50 @@ impl Agent {
 
     // ── Sub-agent management ─────────────────────────────────────────
 
-    /// Handle the `spawn_agent` tool call: spawn a sub-agent and return immediately.
+    /// Start a sub-agent on `task`, or queue it when the concurrency limit is
+    /// reached. Returns its id and, if it was queued, how many are ahead of it.
+    /// Its result is picked up by `collect_completed_subagents` either way.
+    pub fn spawn_subagent(
+        &mut self,
+        task: String,
+        working_dir: Option<String>,
+        max_tokens: Option<u64>,
+        timeout_secs: Option<u64>,
+    ) -> (u64, Option<usize>) {
+        let subagent = SubAgent::new(task, working_dir, max_tokens, timeout_secs, self.config.clone());
+        let id = subagent.id();
+        let queued = self.sub_agents.len() >= self.max_concurrent_subagents();
+        self.record_event("subagent_spawn", || json!({
+            "id": id,
+            "task": subagent.task(),
+            "token_budget": max_tokens,
+            "queued": queued,
+        }));
+
+        if queued {
+            self.queued_sub_agents.push_back(subagent);
+            let ahead = self.queued_sub_agents.len() - 1;
+            self.emit(
+                style(format!(
+                    "⏳ Sub-agent #{} queued ({} running, {} ahead)",
+                    id,
+                    self.sub_agents.len(),
+                    ahead
+                ))
+                .yellow()
+            );
+            return (id, Some(ahead));
+        }
+
+        self.sub_agents.push(subagent.spawn());
+        self.emit(
+            style(format!("🚀 Sub-agent #{} spawned", id)).yellow()
+        );
+        (id, None)
+    }
+
+    /// Handle the `spawn_agent` tool call: spawn a sub-agent (or queue it when
+    /// `[subagent] max_concurrent` are already running) and return immediately.
     fn handle_spawn_agent(&mut self, tool_call: &crate::client::ToolCall) -> crate::tools::ToolResult {
         let task = match tool_call.input.get("task").and_then(|v| v.as_str()) {
             Some(t) => t.to_string(),
@@ -962,22 +1333,23 @@ impl Agent {
             .get("timeout_secs")
             .and_then(|v| v.as_u64());
 
-        let subagent = SubAgent::new(task, working_dir, max_tokens, timeout_secs, self.config.clone());
-        let handle = subagent.spawn();
-
-        let id = handle.id;
-        let task_preview = if handle.task.chars().count() > 100 {
-            let preview: String = handle.task.chars().take(100).collect();
-            format!("{}...", preview)
-        } else {
-            handle.task.clone()
-        };
-
-        self.emit(
-            style(format!("🚀 Sub-agent #{} spawned", id)).yellow()
-        );
+        let preview = task_preview(&task, 100);
+        let (id, queued_ahead) = self.spawn_subagent(task, working_dir, max_tokens, timeout_secs);
 
-        self.sub_agents.push(handle);
+        if let Some(ahead) = queued_ahead {
+            return crate::tools::ToolResult {
+                tool_use_id: tool_call.id.clone(),
+                content: format!(
+                    "Sub-agent #{} queued for: {}\n\
+                    {} sub-agents are already running (the limit), so this task has NOT started yet; \
+                    it will start automatically when a running sub-agent finishes ({} queued ahead of it). \
+                    Return to the user and let them know the task is queued. \
+                    Results will be automatically injected when they're ready.",
+                    id, preview, self.sub_agents.len(), ahead
+                ),
+                error: None,
+            };
+        }
 
         crate::tools::ToolResult {
             tool_use_id: tool_call.id.clone(),
@@ -986,7 +1358,7 @@ impl Agent {
                 The sub-agent is working in the background. Do NOT call check_agents immediately — \
                 it takes time to complete. Return to the user and let them know the task is running. \
                 Results will be automatically injected when they're ready (on the user's next message).",
-                id, task_preview
+                id, preview
             ),
             error: None,
         }
@@ -996,12 +1368,14 @@ impl Agent {
     async fn handle_check_agents(&mut self) -> crate::tools::ToolResult {
         // Dummy tool_use_id — will be overwritten by caller, but we need a placeholder
         // Actually the caller passes the real id. Let me return content only.
+        self.drain_subagent_progress();
         let completed = self.collect_completed_subagents().await;
         let running_count = self.sub_agents.len();
+        let queued_count = self.queued_sub_agents.len();
 
         let mut output = String::new();
 
-        if completed.is_empty() && running_count == 0 {
+        if completed.is_empty() && running_count == 0 && queued_count == 0 {
             output.push_str("No sub-agents (active or completed).");
         } else {
             if !completed.is_empty() {
@@ -1015,22 +1389,31 @@ impl Agent {
                 }
             }
 
-            if running_count > 0 {
+            if running_count > 0 || queued_count > 0 {
                 output.push_str(&format!(
-                    "\n=== {} Active Sub-Agent(s) ===\n",
-                    running_count
+                    "\n=== {} Active Sub-Agent(s): {} running, {} queued (max {} at once) ===\n",
+                    running_count + queued_count,
+                    running_count,
+                    queued_count,
+                    self.max_concurrent_subagents()
                 ));
                 for handle in &self.sub_agents {
                     let elapsed = handle.spawned_at.elapsed().as_secs();
-                    let task_preview = if handle.task.chars().count() > 80 {
-                        let preview: String = handle.task.chars().take(80).collect();
-                        format!("{}...", preview)
-                    } else {
-                        handle.task.clone()
-                    };
                     output.push_str(&format!(
                         "  #{} — running for {}s — {}\n",
-                        handle.id, elapsed, task_preview
+                        handle.id, elapsed, task_preview(&handle.task, 80)
+                    ));
+                    if let Some(ref progress) = handle.last_progress {
+                        output.push_str(&format!("      {}\n", progress.format_line()));
+                    }
+                }
+                for (position, subagent) in self.queued_sub_agents.iter().enumerate() {
+                    output.push_str(&format!(
+                        "  #{} — queued ({} of {}) — {}\n",
+                        subagent.id(),
+                        position + 1,
+                        queued_count,
+                        task_preview(subagent.task(), 80)
                     ));
                 }
                 output.push_str(
@@ -1051,7 +1434,51 @@ impl Agent {
         }
     }
 
-    /// Collect results from all finished sub-agents, removing them from the tracking list.
+    /// Take any progress reports waiting from running sub-agents, remembering
+    /// the latest per agent. Returns the newest report from each that sent one.
+    fn drain_subagent_progress(&mut self) -> Vec<SubAgentProgress> {
+        let mut latest = Vec::new();
+        for handle in &mut self.sub_agents {
+            let mut newest = None;
+            while let Ok(progress) = handle.progress.try_recv() {
+                newest = Some(progress);
+            }
+            if let Some(progress) = newest {
+                handle.last_progress = Some(progress.clone());
+                latest.push(progress);
+            }
+        }
+        latest
+    }
+
+    /// Show new sub-agent progress as dim lines.
+    fn emit_subagent_progress(&mut self) {
+        for progress in self.drain_subagent_progress() {
+            self.emit(style(progress.format_line()).dim());
+        }
+    }
+
+    /// Sub-agents allowed to run at once (at least one).
+    fn max_concurrent_subagents(&self) -> usize {
+        self.config.subagent.max_concurrent.max(1)
+    }
+
+    /// Start queued sub-agents while there are free slots.
+    fn start_queued_subagents(&mut self) {
+        while self.sub_agents.len() < self.max_concurrent_subagents() {
+            let Some(subagent) = self.queued_sub_agents.pop_front() else {
+                break;
+            };
+            let id = subagent.id();
+            self.sub_agents.push(subagent.spawn());
+            self.emit(
+                style(format!("🚀 Sub-agent #{} started from the queue", id)).yellow()
+            );
+        }
+    }
+
+    /// Collect results from all finished sub-agents, removing them from the
+    /// tracking list and starting queued sub-agents in the freed slots.
     pub async fn collect_completed_subagents(&mut self) -> Vec<SubAgentResult> {
         let mut completed = Vec::new();
         let mut remaining = Vec::new();
@@ -1063,10 +1490,14 @@ impl Agent {
                     Err(e) => completed.push(SubAgentResult {
                         id: handle.id,
                         task: handle.task,
+                        model: handle.model,
                         summary: String::new(),
+                        artifact_path: None,
                         files_changed: Vec::new(),
                         input_tokens_used: 0,
                         output_tokens_used: 0,
+                        cache_tokens_used: 0,
+                        token_budget: handle.token_budget,
                         success: false,
                         error: Some(format!("Sub-agent task panicked: {}", e)),
                     }),
@@ -1077,28 +1508,47 @@ impl Agent {
         }
 
         self.sub_agents = remaining;
+        self.start_queued_subagents();
+        for result in &completed {
+            self.record_event("subagent_complete", || json!({
+                "id": result.id,
+                "model": result.model,
+                "success": result.success,
+                "input_tokens": result.input_tokens_used,
+                "output_tokens": result.output_tokens_used,
+                "cache_tokens": result.cache_tokens_used,
+                "files_changed": result.files_changed.len(),
+                "error": result.error,
+            }));
+        }
         completed
     }
 
-    /// Whether there are any active (still running) sub-agents.
+    /// Whether there are any active (running or queued) sub-agents.
     pub fn has_active_subagents(&self) -> bool {
-        !self.sub_agents.is_empty()
+        !self.sub_agents.is_empty() || !self.queued_sub_agents.is_empty()
     }
 
-    /// Get IDs of active sub-agents (for notification display).
+    /// Get IDs of active (running or queued) sub-agents (for notification display).
     pub fn active_subagent_ids(&self) -> Vec<u64> {
-        self.sub_agents.iter().map(|h| h.id).collect()
+        self.sub_agents
+            .iter()
+            .map(|h| h.id)
+            .chain(self.queued_sub_agents.iter().map(|s| s.id()))
+            .collect()
     }
 
     /// Wait until at least one sub-agent completes. Returns the completed results.
     /// If no sub-agents are active, returns an empty vec immediately.
     pub async fn wait_for_subagent(&mut self) -> Vec<SubAgentResult> {
+        self.start_queued_subagents();
         if self.sub_agents.is_empty() {
             return Vec::new();
         }
 
-        // Poll every 500ms until something finishes
+        // Poll every 500ms until something finishes, showing progress meanwhile
         loop {
+            self.emit_subagent_progress();
             let any_finished = self.sub_agents.iter().any(|h| h.handle.is_finished());
             if any_finished {
                 return self.collect_completed_subagents().await;
@@ -1107,29 +1557,37 @@ impl Agent {
         }
     }
 
-    /// Set the interrupt flag (shared with Ctrl+C signal handler).
-    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
-        self.interrupt_flag = Some(flag);
+    /// Share the interrupt signal with the Ctrl+C handler. Model requests
+    /// are cancelled as soon as it's triggered; tools finish first.
+    pub fn set_interrupt(&mut self, interrupt: Arc<Interrupt>) {
+        self.client.set_interrupt(interrupt.clone());
+        self.interrupt = Some(interrupt);
     }
 
     /// Check if an interrupt has been requested.
     fn is_interrupted(&self) -> bool {
-        self.interrupt_flag
-            .as_ref()
-            .map(|f| f.load(Ordering::SeqCst))
-            .unwrap_or(false)
+        self.interrupt.as_ref().is_some_and(|i| i.is_set())
     }
 
-    /// Abort all running sub-agents. Called when the chat session ends.
-    /// Returns the number of sub-agents that were still running.
+    /// Abort all running sub-agents and drop queued ones. Called when the
+    /// chat session ends. Returns the number of sub-agents that were still active.
     pub fn abort_subagents(&mut self) -> usize {
-        let count = self.sub_agents.len();
+        let count = self.sub_agents.len() + self.queued_sub_agents.len();
+        self.queued_sub_agents.clear();
         for handle in self.sub_agents.drain(..) {
             handle.handle.abort();
         }
         count
     }
 
+    /// Persist this session's token usage and cost for `imp usage`.
+    /// Called when the session ends.
+    pub fn save_usage(&self) {
+        if let Err(e) = self.db.save_usage(&self.session_id, self.usage.by_model()) {
+            warn!(error = %e, "Failed to save session usage");
+        }
+    }
+
     /// Write a session summary to the daily memory file. Called when the chat ends.
     pub fn write_session_summary(&self) {
         let home = match imp_home() {
@@ -1145,13 +1603,19 @@ impl Agent {
 
         let duration = self.session_start.elapsed().as_secs() / 60;
 
-        let entry = format!(
+        let mut entry = format!(
             "\n## {} — Session End\n- Duration: {}m, {} messages, {} tool calls\n",
             time,
             duration,
             self.messages.len(),
             self.total_tool_calls,
         );
+        if !self.files_changed.is_empty() {
+            entry.push_str("- Files changed:\n");
+            for (path, operations) in &self.files_changed {
+                entry.push_str(&format!("  - {} ({})\n", path, operations.join(", ")));
+            }
+        }
 
         if let Err(e) = fs::OpenOptions::new()
             .create(true)
@@ -1164,9 +1628,53 @@ impl Agent {
     }
 }
 
+/// Whether a message is a prompt typed by the user, as opposed to a user-role
+/// message carrying tool results.
+fn is_user_prompt(message: &Message) -> bool {
+    if message.role != "user" {
+        return false;
+    }
+    match &message.content {
+        serde_json::Value::String(_) => true,
+        serde_json::Value::Array(blocks) => !blocks
+            .iter()
+            .any(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result")),
+        _ => false,
+    }
+}
+
+/// The text of a user prompt. Retrieved knowledge is sent as an earlier
+/// block, so the prompt itself is the last text block.
+fn prompt_text(message: &Message) -> Option<String> {
+    match &message.content {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .rev()
+            .find_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .map(String::from),
+        _ => None,
+    }
+}
+
+/// The first `max_chars` characters of a sub-agent task, with an ellipsis if cut.
+fn task_preview(task: &str, max_chars: usize) -> String {
+    if task.chars().count() > max_chars {
+        let preview: String = task.chars().take(max_chars).collect();
+        format!("{}...", preview)
+    } else {
+        task.to_string()
+    }
+}
+
 /// Emit a line of output through the ExternalPrinter (readline-safe) if
 /// available, otherwise fall back to stdout. Public so chat.rs can use it too.
 pub fn emit_line(printer: &Option<SharedPrinter>, msg: impl std::fmt::Display) {
+    emit_verbatim(printer, crate::output::clean(&msg.to_string()));
+}
+
+/// `emit_line` without cleaning for plain output, for the model's own text.
+fn emit_verbatim(printer: &Option<SharedPrinter>, msg: impl std::fmt::Display) {
     let text = format!("{}\n", msg);
     if let Some(ref p) = printer {
         if let Ok(mut guard) = p.lock() {
@@ -1215,7 +1723,7 @@ fn truncate(s: &str, max: usize) -> String {
 
 /// Format a tool call with its arguments for display.
 /// Keeps output compact: inline for simple calls, summarized for complex ones.
-fn format_tool_call(name: &str, input: &serde_json::Value) -> String {
+pub fn format_tool_call(name: &str, input: &serde_json::Value) -> String {
     let args = match input.as_object() {
         Some(map) if !map.is_empty() => map,
         _ => return format!("🔧 {name}"),
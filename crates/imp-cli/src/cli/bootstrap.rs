@@ -1,20 +1,27 @@
-use crate::config::{imp_home, AuthConfig, Config, LlmConfig};
+use crate::config::{imp_home, AuthConfig, AuthMethod, Config, LlmConfig};
 use crate::error::Result;
 use console::style;
 use dialoguer::{Confirm, Input, Password};
 use std::fs;
+use std::path::Path;
 
-pub async fn run() -> Result<()> {
+/// `imp bootstrap [--reset]`. On a fresh machine this runs the full setup.
+/// If `~/.imp` is already configured it edits the existing setup instead,
+/// unless `reset` asks to start over.
+pub async fn run(reset: bool) -> Result<()> {
     println!("{}", style("🚀 Welcome to Imp Bootstrap!").bold().blue());
-    println!("Let's get your AI agent configured.\n");
 
     let home = imp_home()?;
 
-    // Check if already initialised
     let config_path = home.join("config.toml");
     if config_path.exists() {
-        println!("{}", style("⚠️  Imp is already set up!").yellow());
+        if !reset {
+            return edit(&home);
+        }
+
+        println!("{}", style("⚠️  Resetting an existing setup!").yellow());
         println!("Found config at: {}", config_path.display());
+        println!("This overwrites config.toml, SOUL.md, USER.md and MEMORY.md.");
 
         let overwrite = Confirm::new()
             .with_prompt("Do you want to re-initialize?")
@@ -26,58 +33,11 @@ pub async fn run() -> Result<()> {
             return Ok(());
         }
     }
+    println!("Let's get your AI agent configured.\n");
 
     // ── 1. Authentication Method ─────────────────────────────────────
     println!("{}", style("1. Authentication Method").bold());
-    println!("To use imp, you need an Anthropic token.\n");
-    println!("{}", style("Getting your token:").bold());
-    println!("1. Install Claude Code CLI: https://claude.ai/code");
-    println!("2. Run: claude setup-token");
-    println!("3. Copy the token that appears");
-    println!("4. Paste it below\n");
-    
-    let mut custom_base_url: Option<String> = None;
-
-    let token = loop {
-        let input_token = Password::new()
-            .with_prompt("Enter your Anthropic token (from 'claude setup-token')")
-            .interact()?;
-        
-        if input_token.trim().is_empty() {
-            println!("{}", style("❌ Token cannot be empty").red());
-            continue;
-        }
-        
-        if !input_token.starts_with("sk-ant-") {
-            println!(
-                "{}",
-                style("⚠️  Token doesn't look like a standard Anthropic token.")
-                    .yellow()
-            );
-            println!("If you're using a proxy (e.g. LiteLLM), you'll need to provide the base URL.\n");
-            
-            let base_url: String = Input::new()
-                .with_prompt("API base URL (or press Enter to use api.anthropic.com)")
-                .default(String::new())
-                .interact()?;
-            
-            if !base_url.trim().is_empty() {
-                custom_base_url = Some(base_url.trim().to_string());
-            } else {
-                // No base URL and non-standard token — confirm they want to proceed
-                let continue_anyway = Confirm::new()
-                    .with_prompt("No custom URL set. Continue with this token for api.anthropic.com?")
-                    .default(false)
-                    .interact()?;
-                
-                if !continue_anyway {
-                    continue;
-                }
-            }
-        }
-        
-        break input_token;
-    };
+    let (token, custom_base_url) = prompt_token()?;
 
     // ── 2. Agent Identity ────────────────────────────────────────────
     println!("\n{}", style("2. Your Agent").bold());
@@ -117,11 +77,30 @@ pub async fn run() -> Result<()> {
             max_tokens: 16384,
             base_url: custom_base_url,
             api_key: None, // Legacy field - not used in new format
+            context_windows: Default::default(),
+            models: Default::default(),
+            request_timeout_secs: crate::config::default_request_timeout_secs(),
+            connect_timeout_secs: crate::config::default_connect_timeout_secs(),
+            stream_idle_timeout_secs: crate::config::default_stream_idle_timeout_secs(),
+            temperature: None,
+            seed: None,
+            prompt_caching: true,
         },
         auth: AuthConfig::default(),
         thinking: Default::default(),
         display: Default::default(),
         knowledge: Default::default(),
+        tools: Default::default(),
+        context: Default::default(),
+        reflect: Default::default(),
+        subagent: Default::default(),
+        compaction: Default::default(),
+        logging: Default::default(),
+        observability: Default::default(),
+        network: Default::default(),
+        pricing: Default::default(),
+        project_file: None,
+        env_overrides: Vec::new(),
     };
     
     // Auto-detect token type and configure auth
@@ -129,11 +108,7 @@ pub async fn run() -> Result<()> {
     config.save()?;
     println!("  ✅ config.toml");
 
-    // SOUL.md (identity + personality)
-    let soul_content = include_str!("../../../../templates/global/SOUL.md")
-        .replace("{{name}}", &agent_name)
-        .replace("{{persona}}", &persona);
-    fs::write(home.join("SOUL.md"), soul_content)?;
+    write_soul(&home, &agent_name, &persona)?;
     println!("  ✅ SOUL.md");
 
     // MEMORY.md
@@ -143,12 +118,26 @@ pub async fn run() -> Result<()> {
     )?;
     println!("  ✅ MEMORY.md");
 
-    // USER.md
-    let user_content = include_str!("../../../../templates/global/USER.md")
-        .replace("{{about_user}}", &about_user);
-    fs::write(home.join("USER.md"), user_content)?;
+    write_user(&home, &about_user)?;
     println!("  ✅ USER.md");
 
+    // The knowledge graph survives a reset unless it's explicitly cleared too
+    let knowledge_path = home.join("knowledge.cozo");
+    if reset && knowledge_path.exists() {
+        let clear_knowledge = Confirm::new()
+            .with_prompt("Also delete the knowledge graph (knowledge.cozo)? This cannot be undone")
+            .default(false)
+            .interact()?;
+        if clear_knowledge {
+            if knowledge_path.is_dir() {
+                fs::remove_dir_all(&knowledge_path)?;
+            } else {
+                fs::remove_file(&knowledge_path)?;
+            }
+            println!("  🗑️  Deleted knowledge.cozo");
+        }
+    }
+
     // ── 4. Optional engineering context ──────────────────────────────
     println!(
         "\n{}",
@@ -238,6 +227,200 @@ pub async fn run() -> Result<()> {
     Ok(())
 }
 
+/// Edit an existing setup: every step is opt-in and pre-filled with the
+/// current values. MEMORY.md, daily notes and the knowledge graph are never
+/// touched.
+fn edit(home: &Path) -> Result<()> {
+    println!("{}", style("Imp is already set up — editing your existing configuration.").yellow());
+    println!("Nothing changes unless you choose it. Memory and the knowledge graph are left alone.");
+    println!(
+        "{}\n",
+        style("(Run 'imp bootstrap --reset' to start over from scratch.)").dim()
+    );
+
+    let mut config = Config::load_global()?;
+
+    // ── 1. Authentication ────────────────────────────────────────────
+    println!("{}", style("1. Authentication").bold());
+    let method = match config.auth_method() {
+        AuthMethod::OAuth => "OAuth token",
+        AuthMethod::ApiKey => "API key",
+    };
+    match config.llm.base_url {
+        Some(ref url) => println!("  Current: {} via {}", method, url),
+        None => println!("  Current: {}", method),
+    }
+    let replace_token = Confirm::new()
+        .with_prompt("Replace your token?")
+        .default(false)
+        .interact()?;
+    if replace_token {
+        let (token, base_url) = prompt_token()?;
+        config.llm.base_url = base_url;
+        config.setup_token_auto_detect(token)?;
+    }
+
+    // ── 2. Model ─────────────────────────────────────────────────────
+    println!("\n{}", style("2. Model").bold());
+    let model: String = Input::new()
+        .with_prompt("Model")
+        .default(config.llm.model.clone())
+        .interact()?;
+    config.llm.model = model;
+    config.save()?;
+    println!("  ✅ config.toml");
+
+    // ── 3. Agent identity ────────────────────────────────────────────
+    println!("\n{}", style("3. Your Agent").bold());
+    let soul = fs::read_to_string(home.join("SOUL.md")).unwrap_or_default();
+    let rewrite_soul = Confirm::new()
+        .with_prompt("Rewrite SOUL.md with a new name or personality?")
+        .default(false)
+        .interact()?;
+    if rewrite_soul {
+        let agent_name: String = Input::new()
+            .with_prompt("Name your agent")
+            .default(soul_name(&soul).unwrap_or_else(|| "Imp".to_string()))
+            .interact()?;
+        let persona: String = Input::new()
+            .with_prompt("Personality")
+            .default(
+                markdown_section(&soul, "## Personality")
+                    .unwrap_or_else(|| "Direct and helpful. Learns and adapts over time.".to_string()),
+            )
+            .interact()?;
+        if confirm_overwrite("SOUL.md")? {
+            write_soul(home, &agent_name, &persona)?;
+            println!("  ✅ SOUL.md");
+        }
+    }
+
+    // ── 4. About you ─────────────────────────────────────────────────
+    println!("\n{}", style("4. About You").bold());
+    let user = fs::read_to_string(home.join("USER.md")).unwrap_or_default();
+    let rewrite_user = Confirm::new()
+        .with_prompt("Rewrite USER.md?")
+        .default(false)
+        .interact()?;
+    if rewrite_user {
+        let mut input = Input::<String>::new().with_prompt("About you");
+        if let Some(about) = markdown_section(&user, "# Your Human") {
+            input = input.default(about);
+        }
+        let about_user = input.interact()?;
+        if confirm_overwrite("USER.md")? {
+            write_user(home, &about_user)?;
+            println!("  ✅ USER.md");
+        }
+    }
+
+    println!("\n{}", style("✅ Configuration updated.").bold().green());
+    Ok(())
+}
+
+/// Walk the user through getting a token. Returns the token and, for
+/// non-Anthropic tokens, the proxy base URL to use with it.
+fn prompt_token() -> Result<(String, Option<String>)> {
+    println!("To use imp, you need an Anthropic token.\n");
+    println!("{}", style("Getting your token:").bold());
+    println!("1. Install Claude Code CLI: https://claude.ai/code");
+    println!("2. Run: claude setup-token");
+    println!("3. Copy the token that appears");
+    println!("4. Paste it below\n");
+
+    let mut custom_base_url: Option<String> = None;
+
+    let token = loop {
+        let input_token = Password::new()
+            .with_prompt("Enter your Anthropic token (from 'claude setup-token')")
+            .interact()?;
+        
+        if input_token.trim().is_empty() {
+            println!("{}", style("❌ Token cannot be empty").red());
+            continue;
+        }
+        
+        if !input_token.starts_with("sk-ant-") {
+            println!(
+                "{}",
+                style("⚠️  Token doesn't look like a standard Anthropic token.")
+                    .yellow()
+            );
+            println!("If you're using a proxy (e.g. LiteLLM), you'll need to provide the base URL.\n");
+            
+            let base_url: String = Input::new()
+                .with_prompt("API base URL (or press Enter to use api.anthropic.com)")
+                .default(String::new())
+                .interact()?;
+            
+            if !base_url.trim().is_empty() {
+                custom_base_url = Some(base_url.trim().to_string());
+            } else {
+                // No base URL and non-standard token — confirm they want to proceed
+                let continue_anyway = Confirm::new()
+                    .with_prompt("No custom URL set. Continue with this token for api.anthropic.com?")
+                    .default(false)
+                    .interact()?;
+                
+                if !continue_anyway {
+                    continue;
+                }
+            }
+        }
+        
+        break input_token;
+    };
+
+    Ok((token, custom_base_url))
+}
+
+/// Ask before replacing a core file, since the agent may have edited it.
+fn confirm_overwrite(file: &str) -> Result<bool> {
+    Ok(Confirm::new()
+        .with_prompt(format!("Overwrite {}? Edits made to it since setup will be lost", file))
+        .default(false)
+        .interact()?)
+}
+
+/// SOUL.md (identity + personality) from the template.
+fn write_soul(home: &Path, agent_name: &str, persona: &str) -> Result<()> {
+    let soul_content = include_str!("../../../../templates/global/SOUL.md")
+        .replace("{{name}}", agent_name)
+        .replace("{{persona}}", persona);
+    fs::write(home.join("SOUL.md"), soul_content)?;
+    Ok(())
+}
+
+/// USER.md from the template.
+fn write_user(home: &Path, about_user: &str) -> Result<()> {
+    let user_content = include_str!("../../../../templates/global/USER.md")
+        .replace("{{about_user}}", about_user);
+    fs::write(home.join("USER.md"), user_content)?;
+    Ok(())
+}
+
+/// The agent's name from a SOUL.md written by the template (`**Name**: Foo`).
+fn soul_name(soul: &str) -> Option<String> {
+    soul.lines()
+        .find_map(|l| l.trim().strip_prefix("**Name**:"))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The text under `heading`, up to the next heading or HTML comment, joined
+/// onto one line so it can pre-fill a prompt.
+fn markdown_section(content: &str, heading: &str) -> Option<String> {
+    let text: Vec<&str> = content
+        .lines()
+        .skip_while(|l| l.trim() != heading)
+        .skip(1)
+        .map(str::trim)
+        .take_while(|l| !l.starts_with('#') && !l.starts_with("<!--"))
+        .filter(|l| !l.is_empty())
+        .collect();
+    Some(text.join(" ")).filter(|t| !t.is_empty())
+}
+
 /// Install a cron job that runs `imp reflect` daily at 23:55.
 fn setup_reflection_cron() -> std::result::Result<(), String> {
     use std::process::Command;
@@ -1,12 +1,19 @@
 use crate::agent::{Agent, SharedPrinter, emit_line};
-use crate::error::Result;
+use crate::cli::completion::ChatHelper;
+use crate::config::imp_home;
+use crate::db::{Database, TranscriptFormat};
+use crate::error::{ImpError, Result};
+use crate::interrupt::Interrupt;
+use crate::tokens;
 use console::style;
 use dialoguer::Select;
 use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
 use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Commands sent from the main loop to the dedicated readline thread.
 enum InputCommand {
@@ -22,6 +29,23 @@ enum InputResult {
     Interrupted,
 }
 
+/// Entries kept in memory when `[display] history_size` is 0.
+const SESSION_HISTORY_SIZE: usize = 100;
+
+/// Line editor keeping up to `history_size` entries, skipping an entry that
+/// repeats the one before it, with Tab completion for commands and paths.
+fn new_editor(history_size: usize) -> Editor<ChatHelper, DefaultHistory> {
+    let max = if history_size == 0 { SESSION_HISTORY_SIZE } else { history_size };
+    let config = rustyline::Config::builder()
+        .max_history_size(max)
+        .and_then(|b| b.history_ignore_dups(true))
+        .map(|b| b.build())
+        .unwrap_or_default();
+    let mut editor = Editor::with_config(config).expect("Failed to initialize line editor");
+    editor.set_helper(Some(ChatHelper::new()));
+    editor
+}
+
 /// Build the prompt string, showing queue count when items are pending.
 fn make_prompt(queued: usize) -> String {
     if queued > 0 {
@@ -35,36 +59,22 @@ fn make_prompt(queued: usize) -> String {
     }
 }
 
-pub async fn run(resume: bool, continue_last: bool, session: Option<String>) -> Result<()> {
+pub async fn run(
+    resume: bool,
+    continue_last: bool,
+    session: Option<String>,
+    search: Option<String>,
+    thinking: Option<bool>,
+    deterministic: bool,
+) -> Result<()> {
     let mut agent = Agent::new().await?;
-
-    // --session <id>: resume a specific session
-    if let Some(ref sid) = session {
-        agent.resume(sid)?;
-        println!(
-            "{}",
-            style(format!("🔄 Resumed session: {}", &sid[..sid.len().min(8)])).yellow()
-        );
-    } else if continue_last {
-        let project = agent.project_name().map(|s| s.to_string());
-        if let Some(info) = agent.db().get_latest_session(project.as_deref())? {
-            agent.resume(&info.id)?;
-            println!(
-                "{}",
-                style(format!(
-                    "🔄 Continued session: {} ({} messages)",
-                    &info.id[..info.id.len().min(8)],
-                    info.message_count
-                ))
-                .yellow()
-            );
-        } else {
-            println!("{}", style("No previous session found — starting fresh.").dim());
-        }
-    } else if resume {
-        maybe_show_session_picker(&mut agent)?;
+    agent.set_thinking_override(thinking);
+    if deterministic {
+        agent.set_deterministic();
     }
 
+    open_session(&mut agent, resume, continue_last, session, search)?;
+
     println!(
         "{}",
         style(format!("🤖 {} - Interactive Chat", agent.display_name()))
@@ -91,16 +101,29 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
     println!();
 
     // Ctrl+C interrupt flag for agent work
-    let interrupted = Arc::new(AtomicBool::new(false));
-    agent.set_interrupt_flag(interrupted.clone());
+    let interrupted = Arc::new(Interrupt::default());
+    agent.set_interrupt(interrupted.clone());
 
     // ── Dedicated readline thread ────────────────────────────────────
     let (cmd_tx, cmd_rx) = std::sync::mpsc::channel::<InputCommand>();
     let (result_tx, mut result_rx) = tokio::sync::mpsc::unbounded_channel::<InputResult>();
     let (printer_tx, printer_rx) = std::sync::mpsc::sync_channel::<Option<Box<dyn rustyline::ExternalPrinter + Send>>>(1);
 
+    let history_size = agent.config().display.history_size;
     std::thread::spawn(move || {
-        let mut editor = DefaultEditor::new().expect("Failed to initialize line editor");
+        let mut editor = new_editor(history_size);
+        // Multi-line inputs are added joined, so they're one entry in the file
+        let history_path = match history_size {
+            0 => None,
+            _ => imp_home().ok().map(|home| home.join("history.txt")),
+        };
+        if let Some(ref path) = history_path {
+            if path.exists() {
+                if let Err(e) = editor.load_history(path) {
+                    tracing::warn!(error = %e, "Could not load chat history");
+                }
+            }
+        }
 
         // Create an ExternalPrinter and send it to the main thread.
         // This lets the main thread print output without garbling the prompt.
@@ -125,6 +148,13 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
                 }
                 InputCommand::AddHistory(entry) => {
                     let _ = editor.add_history_entry(&entry);
+                    // Appending as we go keeps the entry if imp exits without a
+                    // clean shutdown, and merges with other sessions' history.
+                    if let Some(ref path) = history_path {
+                        if let Err(e) = editor.append_history(path) {
+                            tracing::warn!(error = %e, "Could not save chat history");
+                        }
+                    }
                 }
                 InputCommand::Shutdown => break,
             }
@@ -135,6 +165,8 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
     let mut pending_queue: VecDeque<String> = VecDeque::new();
     let mut multiline_buffer = String::new();
     let mut readline_pending = false;
+    // Files from `/attach`, sent with the next message
+    let mut attachments = String::new();
 
     // ── External printer for readline-safe output ────────────────────
     let printer: Option<SharedPrinter> = printer_rx
@@ -144,6 +176,7 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
         .map(|p| Arc::new(Mutex::new(p)));
     if let Some(ref p) = printer {
         agent.set_printer(p.clone());
+        crate::logging::set_printer(p.clone());
     }
 
     // ── Main chat loop ───────────────────────────────────────────────
@@ -155,7 +188,7 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
         }
 
         // ── Phase 1: Get next input (from queue or readline) ─────────
-        let input: String = if let Some(queued) = pending_queue.pop_front() {
+        let mut input: String = if let Some(queued) = pending_queue.pop_front() {
             // Process queued input immediately, no prompting
             emit_line(
                 &printer,
@@ -262,86 +295,11 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
         };
 
         // ── Phase 2: Handle commands ─────────────────────────────────
-        match input.to_lowercase().as_str() {
-            "/quit" | "/exit" | "/q" | "quit" | "exit" | "bye" | "q" => {
-                let aborted = agent.abort_subagents();
-                if aborted > 0 {
-                    println!(
-                        "{}",
-                        style(format!("⚠️  Aborted {} running sub-agent(s)", aborted)).yellow()
-                    );
-                }
-                // Clear any remaining queued inputs
-                if !pending_queue.is_empty() {
-                    println!(
-                        "{}",
-                        style(format!("🗑️  Discarded {} queued input(s)", pending_queue.len())).dim()
-                    );
-                    pending_queue.clear();
-                }
-                agent.write_session_summary();
-                println!("{}", style(agent.usage().format_session_total()).dim());
-                println!("👋 Goodbye!");
-                break;
-            }
-            "/clear" | "clear" => {
-                agent.clear_conversation();
-                println!("🧹 Conversation cleared.");
-                continue;
-            }
-            "/help" | "help" => {
-                show_help();
-                continue;
-            }
-            "/compact" => {
-                let compacted = agent.compact_now();
-                if compacted {
-                    println!("{}", style("📦 Conversation compacted.").green());
-                } else {
-                    println!("{}", style("No compaction needed yet.").dim());
-                }
-                continue;
-            }
-            "/session" => {
-                show_session_info(&agent);
-                continue;
-            }
-            "/agents" => {
-                let status = agent.check_agents_status().await;
-                println!("{}", status);
-                continue;
-            }
-            "/cancel" => {
-                let count = pending_queue.len();
-                if count > 0 {
-                    pending_queue.clear();
-                    println!(
-                        "{}",
-                        style(format!("🗑️  Cleared {} queued input(s)", count)).yellow()
-                    );
-                } else {
-                    println!("{}", style("No queued inputs to cancel.").dim());
-                }
-                continue;
-            }
-            "/queue" => {
-                if pending_queue.is_empty() {
-                    println!("{}", style("Queue is empty.").dim());
-                } else {
-                    println!(
-                        "{}",
-                        style(format!("📋 {} queued input(s):", pending_queue.len())).bold()
-                    );
-                    for (i, q) in pending_queue.iter().enumerate() {
-                        let preview: String = q.chars().take(70).collect();
-                        let ellipsis = if q.chars().count() > 70 { "…" } else { "" };
-                        println!("  {}. {}{}", i + 1, preview, ellipsis);
-                    }
-                }
-                continue;
-            }
-            _ => {}
-        }
+        input = match handle_input(&mut agent, input, &mut attachments, &mut pending_queue).await {
+            InputAction::Send(prompt) => prompt,
+            InputAction::Handled => continue,
+            InputAction::Quit => break,
+        };
 
         // ── Phase 3: Process with agent ──────────────────────────────
         emit_line(
@@ -351,13 +309,13 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
         emit_line(&printer, style("─".repeat(20)).dim());
 
         // Clear interrupt flag before agent work
-        interrupted.store(false, Ordering::SeqCst);
+        interrupted.clear();
 
         // Ctrl+C handler for interrupting agent work
         let int_flag = interrupted.clone();
         let ctrlc_handler = tokio::spawn(async move {
             if let Ok(()) = tokio::signal::ctrl_c().await {
-                int_flag.store(true, Ordering::SeqCst);
+                int_flag.trigger();
             }
         });
 
@@ -394,7 +352,7 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
                             } else if trimmed.eq_ignore_ascii_case("/stop")
                                 || trimmed.eq_ignore_ascii_case("/interrupt")
                             {
-                                interrupted.store(true, Ordering::SeqCst);
+                                interrupted.trigger();
                                 emit_line(&printer, style("⚡ Interrupting…").yellow());
                             } else if !trimmed.is_empty() {
                                 pending_queue.push_back(trimmed.clone());
@@ -416,7 +374,7 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
                         }
                         Some(InputResult::Interrupted) => {
                             // Ctrl+C during processing — interrupt the agent
-                            interrupted.store(true, Ordering::SeqCst);
+                            interrupted.trigger();
                             let prompt = make_prompt(pending_queue.len());
                             if cmd_tx.send(InputCommand::Readline(prompt)).is_ok() {
                                 readline_pending = true;
@@ -455,10 +413,352 @@ pub async fn run(resume: bool, continue_last: bool, session: Option<String>) ->
 
     // Clean shutdown
     let _ = cmd_tx.send(InputCommand::Shutdown);
+    agent.save_usage();
     agent.cleanup();
     Ok(())
 }
 
+/// Pick the session to chat in: a search result, `--session`, the latest
+/// (`--continue`), one from the picker (`--resume`), or the new one.
+fn open_session(
+    agent: &mut Agent,
+    resume: bool,
+    continue_last: bool,
+    session: Option<String>,
+    search: Option<String>,
+) -> Result<()> {
+    // --search <query>: find a past session by content and resume it
+    if let Some(ref query) = search {
+        search_sessions(agent, query)?;
+    } else if let Some(ref sid) = session {
+        // --session <id>: resume a specific session (full ID or unique prefix)
+        let info = agent
+            .db()
+            .find_session_by_prefix(sid)?
+            .ok_or_else(|| ImpError::Database(format!("No session found matching '{}'", sid)))?;
+        agent.resume(&info.id)?;
+        println!(
+            "{}",
+            style(format!("🔄 Resumed session: {}", &info.id[..info.id.len().min(8)])).yellow()
+        );
+    } else if continue_last {
+        let project = agent.project_name().map(|s| s.to_string());
+        if let Some(info) = agent.db().get_latest_session(project.as_deref())? {
+            agent.resume(&info.id)?;
+            println!(
+                "{}",
+                style(format!(
+                    "🔄 Continued session: {} ({} messages)",
+                    &info.id[..info.id.len().min(8)],
+                    info.message_count
+                ))
+                .yellow()
+            );
+        } else {
+            println!("{}", style("No previous session found — starting fresh.").dim());
+        }
+    } else if resume {
+        maybe_show_session_picker(agent)?;
+    }
+    Ok(())
+}
+
+/// `imp chat --exec-file`: run the prompts in `path` (`-` for stdin) as
+/// chat turns in one session, printing each reply, then exit. Commands like
+/// `/clear` work as they do at the prompt; `/quit` ends the script early.
+pub async fn run_script(
+    path: &Path,
+    continue_last: bool,
+    session: Option<String>,
+    thinking: Option<bool>,
+    deterministic: bool,
+) -> Result<()> {
+    let script = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| ImpError::Config(format!("Can't read {}: {}", path.display(), e)))?
+    };
+    let prompts = parse_script(&script);
+
+    let mut agent = Agent::new().await?;
+    agent.set_thinking_override(thinking);
+    if deterministic {
+        agent.set_deterministic();
+    }
+    open_session(&mut agent, false, continue_last, session, None)?;
+
+    let interrupted = Arc::new(Interrupt::default());
+    agent.set_interrupt(interrupted.clone());
+    let int_flag = interrupted.clone();
+    let ctrlc_handler = tokio::spawn(async move {
+        if let Ok(()) = tokio::signal::ctrl_c().await {
+            int_flag.trigger();
+        }
+    });
+
+    let mut attachments = String::new();
+    let mut no_queue = VecDeque::new();
+    let mut result = Ok(());
+    let mut quit = false;
+    for (i, prompt) in prompts.into_iter().enumerate() {
+        println!("{} {}", style(format!("[{}] You:", i + 1)).bold().green(), prompt);
+        let input = match handle_input(&mut agent, prompt, &mut attachments, &mut no_queue).await {
+            InputAction::Send(input) => input,
+            InputAction::Handled => continue,
+            InputAction::Quit => {
+                quit = true;
+                break;
+            }
+        };
+
+        println!("\n{}", style(format!("{}:", agent.display_name())).bold().blue());
+        println!("{}", style("─".repeat(20)).dim());
+        if let Err(e) = agent.process_message_with_markdown(&input).await {
+            // Later prompts may depend on this one, so stop here
+            println!("{}", style(format!("❌ Error: {}", e)).red());
+            result = Err(e);
+            break;
+        }
+        println!("{}", style("─".repeat(50)).dim());
+        println!();
+    }
+
+    // Report sub-agents the script started before exiting
+    while result.is_ok() && !quit && agent.has_active_subagents() {
+        tokio::select! {
+            completed = agent.wait_for_subagent() => {
+                if !completed.is_empty() {
+                    auto_summarize_subagents(&mut agent, completed).await;
+                }
+            }
+            _ = interrupted.triggered() => break,
+        }
+    }
+
+    ctrlc_handler.abort();
+    if !quit {
+        agent.abort_subagents();
+        agent.write_session_summary();
+        println!("{}", style(agent.usage().format_session_total()).dim());
+    }
+    agent.save_usage();
+    agent.cleanup();
+    result
+}
+
+/// The prompts in an `--exec-file` script. If any line is just `---`, those
+/// lines separate multi-line prompts; otherwise each line is a prompt.
+/// Blank prompts are skipped.
+fn parse_script(script: &str) -> Vec<String> {
+    let prompts: Vec<String> = if script.lines().any(|l| l.trim() == "---") {
+        script
+            .lines()
+            .collect::<Vec<_>>()
+            .split(|l| l.trim() == "---")
+            .map(|lines| lines.join("\n"))
+            .collect()
+    } else {
+        script.lines().map(String::from).collect()
+    };
+    prompts
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// What the input loop does after `handle_input`.
+enum InputAction {
+    /// Send this prompt to the agent.
+    Send(String),
+    /// A command ran; read the next input.
+    Handled,
+    /// `/quit`: the session is wrapped up, stop reading input.
+    Quit,
+}
+
+/// Run `input` if it's a chat command. Otherwise, or for `/retry`, returns
+/// the prompt to send, with `@path` mentions and `/attach`ed files added.
+async fn handle_input(
+    agent: &mut Agent,
+    mut input: String,
+    attachments: &mut String,
+    pending_queue: &mut VecDeque<String>,
+) -> InputAction {
+    match input.to_lowercase().as_str() {
+        "/quit" | "/exit" | "/q" | "quit" | "exit" | "bye" | "q" => {
+            let aborted = agent.abort_subagents();
+            if aborted > 0 {
+                println!(
+                    "{}",
+                    style(format!("⚠️  Aborted {} running sub-agent(s)", aborted)).yellow()
+                );
+            }
+            // Clear any remaining queued inputs
+            if !pending_queue.is_empty() {
+                println!(
+                    "{}",
+                    style(format!("🗑️  Discarded {} queued input(s)", pending_queue.len())).dim()
+                );
+                pending_queue.clear();
+            }
+            agent.write_session_summary();
+            println!("{}", style(agent.usage().format_session_total()).dim());
+            println!("👋 Goodbye!");
+            return InputAction::Quit;
+        }
+        "/clear" | "clear" => {
+            agent.clear_conversation();
+            println!("🧹 Conversation cleared.");
+            return InputAction::Handled;
+        }
+        "/help" | "help" => {
+            show_help();
+            return InputAction::Handled;
+        }
+        "/compact" => {
+            match agent.compact_now() {
+                Some((before, after)) => println!(
+                    "{}",
+                    style(format!("📦 Conversation compacted: {} → {} tokens", before, after)).green()
+                ),
+                None => println!("{}", style("No compaction needed yet.").dim()),
+            }
+            return InputAction::Handled;
+        }
+        "/keep" => {
+            match agent.keep_last_prompt() {
+                Some(prompt) => {
+                    let preview: String = prompt.chars().take(70).collect();
+                    let ellipsis = if prompt.chars().count() > 70 { "…" } else { "" };
+                    println!(
+                        "{}",
+                        style(format!("📌 Kept through compaction: {}{}", preview, ellipsis)).green()
+                    );
+                }
+                None => println!("{}", style("Nothing to keep yet.").dim()),
+            }
+            return InputAction::Handled;
+        }
+        "/session" => {
+            show_session_info(agent);
+            return InputAction::Handled;
+        }
+        "/files" => {
+            show_files_changed(agent);
+            return InputAction::Handled;
+        }
+        "/context" => {
+            show_context(agent).await;
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/search" || cmd.starts_with("/search ") => {
+            let query = input["/search".len()..].trim();
+            if query.is_empty() {
+                println!("{}", style("Usage: /search <query>").yellow());
+            } else if let Err(e) = search_sessions(agent, query) {
+                println!("{}", style(format!("⚠ Search failed: {}", e)).red());
+            }
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/model" || cmd.starts_with("/model ") => {
+            switch_model(agent, cmd["/model".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/think" || cmd.starts_with("/think ") => {
+            set_thinking(agent, cmd["/think".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/readonly" || cmd.starts_with("/readonly ") => {
+            set_read_only(agent, cmd["/readonly".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/fork" || cmd.starts_with("/fork ") => {
+            fork_session(agent, cmd["/fork".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/pin" || cmd.starts_with("/pin ") => {
+            pin_file(agent, input["/pin".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/attach" || cmd.starts_with("/attach ") => {
+            attach_files(attachments, input["/attach".len()..].trim());
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/unpin" || cmd.starts_with("/unpin ") => {
+            unpin_file(agent, input["/unpin".len()..].trim());
+            return InputAction::Handled;
+        }
+        "/undo" => {
+            undo_exchange(agent);
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/retry" || cmd.starts_with("/retry ") => {
+            match retry_input(agent, input["/retry".len()..].trim()) {
+                Some(prompt) => input = prompt,
+                None => {
+                    println!("{}", style("Nothing to retry yet.").dim());
+                    return InputAction::Handled;
+                }
+            }
+        }
+        "/mcp" => {
+            let statuses = agent.mcp_status().await;
+            crate::cli::mcp_cmd::print_status(&statuses);
+            return InputAction::Handled;
+        }
+        cmd if cmd == "/spawn" || cmd.starts_with("/spawn ") => {
+            spawn_subagent(agent, input["/spawn".len()..].trim());
+            return InputAction::Handled;
+        }
+        "/agents" => {
+            let status = agent.check_agents_status().await;
+            println!("{}", status);
+            return InputAction::Handled;
+        }
+        "/cancel" => {
+            let count = pending_queue.len();
+            if count > 0 {
+                pending_queue.clear();
+                println!(
+                    "{}",
+                    style(format!("🗑️  Cleared {} queued input(s)", count)).yellow()
+                );
+            } else {
+                println!("{}", style("No queued inputs to cancel.").dim());
+            }
+            return InputAction::Handled;
+        }
+        "/queue" => {
+            if pending_queue.is_empty() {
+                println!("{}", style("Queue is empty.").dim());
+            } else {
+                println!(
+                    "{}",
+                    style(format!("📋 {} queued input(s):", pending_queue.len())).bold()
+                );
+                for (i, q) in pending_queue.iter().enumerate() {
+                    let preview: String = q.chars().take(70).collect();
+                    let ellipsis = if q.chars().count() > 70 { "…" } else { "" };
+                    println!("  {}. {}{}", i + 1, preview, ellipsis);
+                }
+            }
+            return InputAction::Handled;
+        }
+        _ => {}
+    }
+
+    if input.contains('@') {
+        attach_mentions(&mut input);
+    }
+    if !attachments.is_empty() {
+        input = format!("{}\n{}", std::mem::take(attachments), input);
+    }
+
+    InputAction::Send(input)
+}
+
 /// Auto-summarize completed sub-agent results with markdown rendering.
 async fn auto_summarize_subagents(agent: &mut Agent, completed: Vec<crate::subagent::SubAgentResult>) {
     let results_text = completed
@@ -525,9 +825,13 @@ fn maybe_show_session_picker(agent: &mut Agent) -> Result<()> {
             .as_deref()
             .filter(|t| !t.is_empty())
             .unwrap_or(short_id);
+        let fork_note = match s.parent_session_id.as_deref() {
+            Some(parent) => format!(" 🌿 fork of {}", &parent[..parent.len().min(8)]),
+            None => String::new(),
+        };
         items.push(format!(
-            "🔄 {} — {} msgs ({})",
-            display_name, s.message_count, age
+            "🔄 {} — {} msgs ({}){}",
+            display_name, s.message_count, age, fork_note
         ));
     }
     items.push("✨ Start new session".to_string());
@@ -561,6 +865,267 @@ fn maybe_show_session_picker(agent: &mut Agent) -> Result<()> {
     Ok(())
 }
 
+/// `imp chat --export <session-prefix>`: print a session transcript to stdout.
+pub fn export(session_prefix: &str, format: &str, include_thinking: bool) -> Result<()> {
+    let format: TranscriptFormat = format.parse()?;
+    let db = Database::open()?;
+    let info = db
+        .find_session_by_prefix(session_prefix)?
+        .ok_or_else(|| ImpError::Database(format!("No session found matching '{}'", session_prefix)))?;
+
+    let transcript = db.export_session(&info.id, format, include_thinking)?;
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    out.write_all(transcript.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Full-text search past sessions and offer to resume one of the matches.
+fn search_sessions(agent: &mut Agent, query: &str) -> Result<()> {
+    let current_session_id = agent.session_id().to_string();
+    let hits: Vec<_> = agent
+        .db()
+        .search_messages(query, 10)?
+        .into_iter()
+        .filter(|h| h.session.id != current_session_id)
+        .collect();
+
+    if hits.is_empty() {
+        println!("{}", style(format!("No sessions match \"{}\".", query)).dim());
+        return Ok(());
+    }
+
+    let mut items: Vec<String> = Vec::new();
+    for hit in &hits {
+        let s = &hit.session;
+        let short_id = &s.id[..s.id.len().min(8)];
+        let display_name = s
+            .title
+            .as_deref()
+            .filter(|t| !t.is_empty())
+            .unwrap_or(short_id);
+        let snippet: String = hit.snippet.chars().take(80).collect();
+        items.push(format!(
+            "🔄 {} ({}) — {}",
+            display_name,
+            format_relative_time(&s.updated_at),
+            snippet
+        ));
+    }
+    items.push("✨ Stay in this session".to_string());
+
+    let default = items.len() - 1;
+    let selection = Select::new()
+        .with_prompt(format!("Sessions matching \"{}\"", query))
+        .items(&items)
+        .default(0)
+        .interact_opt()
+        .unwrap_or(Some(default));
+
+    if let Some(idx) = selection.filter(|&idx| idx < hits.len()) {
+        let chosen = &hits[idx].session;
+        agent.resume(&chosen.id)?;
+        println!(
+            "{}",
+            style(format!(
+                "🔄 Resumed session: {} ({} messages)",
+                &chosen.id[..chosen.id.len().min(8)],
+                chosen.message_count
+            ))
+            .yellow()
+        );
+    }
+
+    Ok(())
+}
+
+/// Handle `/pin [file]`: list pinned files, or pin one into the system prompt.
+fn pin_file(agent: &mut Agent, file: &str) {
+    if file.is_empty() {
+        if agent.pinned_files().is_empty() {
+            println!("{}", style("No pinned files. Usage: /pin <file>").dim());
+        } else {
+            println!("{}", style("📌 Pinned files:").bold());
+            for path in agent.pinned_files() {
+                println!("  {}", path.display());
+            }
+        }
+        return;
+    }
+
+    match agent.pin_context_file(file) {
+        Ok(path) => println!(
+            "{}",
+            style(format!("📌 Pinned {} — it will be loaded every turn", path.display())).green()
+        ),
+        Err(e) => println!("{}", style(format!("⚠ {}", e)).red()),
+    }
+}
+
+/// Handle `/attach <path or glob...>`: read the files now and hold them for
+/// the next message.
+fn attach_files(attachments: &mut String, args: &str) {
+    if args.is_empty() {
+        println!("{}", style("Usage: /attach <file or glob> [...]").yellow());
+        return;
+    }
+
+    let patterns: Vec<String> = args.split_whitespace().map(String::from).collect();
+    match crate::tools::builtin::attach_files(&patterns) {
+        Ok(attached) => {
+            attachments.push_str(&attached.text);
+            println!(
+                "{}",
+                style(format!(
+                    "📎 Attached {} — sent with your next message",
+                    attached.files.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+                ))
+                .green()
+            );
+        }
+        Err(e) => println!("{}", style(format!("⚠ {}", e)).red()),
+    }
+}
+
+/// Append the files mentioned as `@path` to `input`, leaving the mentions in
+/// place. Mentions that don't name a file are sent as written.
+fn attach_mentions(input: &mut String) {
+    let mentions = crate::tools::builtin::attach_mentions(input);
+    for mention in &mentions.unresolved {
+        println!(
+            "{}",
+            style(format!("⚠ {} isn't a file imp can attach; sending it as written", mention)).yellow()
+        );
+    }
+    let attached = mentions.attachments;
+    if !attached.files.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "📎 Attached {}",
+                attached.files.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+            ))
+            .green()
+        );
+        input.push_str("\n\n");
+        input.push_str(&attached.text);
+    }
+}
+
+/// Handle `/unpin <file>`.
+fn unpin_file(agent: &mut Agent, file: &str) {
+    if file.is_empty() {
+        println!("{}", style("Usage: /unpin <file>").yellow());
+        return;
+    }
+    match agent.unpin_context_file(file) {
+        Some(path) => println!("{}", style(format!("Unpinned {}", path.display())).green()),
+        None => println!("{}", style(format!("No pinned file matches '{}'", file)).yellow()),
+    }
+}
+
+/// Handle `/undo`: drop the last exchange and say what went.
+fn undo_exchange(agent: &mut Agent) {
+    match agent.undo_last_exchange() {
+        Some(undone) => {
+            let preview: String = undone.prompt.chars().take(60).collect();
+            let ellipsis = if undone.prompt.chars().count() > 60 { "…" } else { "" };
+            println!(
+                "{}",
+                style(format!(
+                    "↩️  Removed {} message(s), starting from: \"{}{}\"",
+                    undone.removed, preview, ellipsis
+                ))
+                .yellow()
+            );
+        }
+        None => println!("{}", style("Nothing to undo.").dim()),
+    }
+}
+
+/// Handle `/retry [hint]`: drop the last exchange and return the prompt to
+/// send again, with the hint appended if one was given.
+fn retry_input(agent: &mut Agent, hint: &str) -> Option<String> {
+    let prompt = agent.rewind_last_turn()?;
+    println!("{}", style("🔁 Regenerating the last response...").dim());
+    if hint.is_empty() {
+        Some(prompt)
+    } else {
+        Some(format!("{}\n\n(Retry note: {})", prompt, hint))
+    }
+}
+
+/// Handle `/spawn [--budget TOKENS] [--timeout SECS] <task>`: start a
+/// sub-agent directly, as the `spawn_agent` tool would.
+fn spawn_subagent(agent: &mut Agent, args: &str) {
+    const USAGE: &str = "Usage: /spawn [--budget TOKENS] [--timeout SECS] <task>";
+    let mut budget = None;
+    let mut timeout = None;
+    let mut rest = args;
+    loop {
+        let (flag, value) = match rest.split_once(char::is_whitespace) {
+            Some((flag, after)) if flag == "--budget" || flag == "--timeout" => {
+                let after = after.trim_start();
+                let (value, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+                rest = after.trim_start();
+                (flag, value)
+            }
+            _ => break,
+        };
+        let Ok(value) = value.parse::<u64>() else {
+            println!("{}", style(format!("{} needs a number. {}", flag, USAGE)).yellow());
+            return;
+        };
+        if flag == "--budget" {
+            budget = Some(value);
+        } else {
+            timeout = Some(value);
+        }
+    }
+    if rest.is_empty() {
+        println!("{}", style(USAGE).yellow());
+        return;
+    }
+
+    // `spawn_subagent` reports the spawn; the result arrives like any other
+    agent.spawn_subagent(rest.to_string(), None, budget, timeout);
+}
+
+/// Handle `/fork [N]`: branch the session, keeping the first N stored
+/// messages (default: all of them), and switch to the new branch.
+fn fork_session(agent: &mut Agent, arg: &str) {
+    let upto = if arg.is_empty() {
+        usize::MAX
+    } else {
+        match arg.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                println!("{}", style("Usage: /fork [number of messages to keep]").yellow());
+                return;
+            }
+        }
+    };
+
+    let parent_id = agent.session_id().to_string();
+    match agent.fork_session(upto) {
+        Ok(new_id) => {
+            println!(
+                "{}",
+                style(format!(
+                    "🌿 Forked session {} → {} ({} messages). Resume the original with: imp chat --session {}",
+                    &parent_id[..parent_id.len().min(8)],
+                    &new_id[..new_id.len().min(8)],
+                    agent.message_count(),
+                    parent_id
+                ))
+                .green()
+            );
+        }
+        Err(e) => println!("{}", style(format!("⚠ Fork failed: {}", e)).red()),
+    }
+}
+
 fn format_relative_time(rfc3339: &str) -> String {
     let Ok(ts) = chrono::DateTime::parse_from_rfc3339(rfc3339) else {
         return rfc3339.to_string();
@@ -597,14 +1162,32 @@ fn show_help() {
     println!("  {}  — Exit the chat", style("/quit, /exit, /q").cyan());
     println!("  {}         — Clear conversation history", style("/clear").cyan());
     println!("  {}       — Manually compact conversation", style("/compact").cyan());
+    println!("  {}          — Keep your last message verbatim through compaction", style("/keep").cyan());
     println!("  {}       — Show session info", style("/session").cyan());
+    println!("  {}         — List files the agent wrote or edited this session", style("/files").cyan());
+    println!("  {}       — Show the assembled system prompt with token counts", style("/context").cyan());
+    println!("  {} — List models, or switch model for the next turn", style("/model [name]").cyan());
+    println!("  {} — Turn extended thinking on or off for this session", style("/think on|off").cyan());
+    println!("  {} — Set the thinking budget for this session", style("/think budget <n>").cyan());
+    println!("  {} — Simulate writes and mutating commands instead of running them", style("/readonly on|off").cyan());
+    println!("  {}   — Always load a context file (no argument: list pins)", style("/pin [file]").cyan());
+    println!("  {} — Stop always loading a pinned file", style("/unpin <file>").cyan());
+    println!("  {} — Send files (or a glob) with the next message", style("/attach <path>").cyan());
+    println!("  {}          — Remove the last exchange from the conversation", style("/undo").cyan());
+    println!("  {}  — Regenerate the last response, optionally with a hint", style("/retry [hint]").cyan());
+    println!("  {}      — Branch the session here (or keep first N messages)", style("/fork [N]").cyan());
+    println!("  {} — Search past sessions and resume one", style("/search <query>").cyan());
+    println!("  {}  — Start a sub-agent (--budget TOKENS, --timeout SECS)", style("/spawn <task>").cyan());
     println!("  {}        — Show sub-agent status", style("/agents").cyan());
+    println!("  {}           — Show MCP server status", style("/mcp").cyan());
     println!("  {}         — Show queued inputs", style("/queue").cyan());
     println!("  {}        — Cancel all queued inputs", style("/cancel").cyan());
     println!("  {}          — Show this help", style("/help").cyan());
     println!();
     println!("{}", style("Input:").bold());
     println!("  {}      — Multiline input (backslash at end of line)", style("line \\").cyan());
+    println!("  {}   — Attach a file (or lines 10-40 of it) to the message", style("@path[:10-40]").cyan());
+    println!("  {}           — Complete commands, and file paths after @ or /attach, /pin, /unpin", style("Tab").cyan());
     println!("  {}        — Interrupt current agent action", style("Ctrl+C").cyan());
     println!("  {}        — Exit chat", style("Ctrl+D").cyan());
     println!();
@@ -614,6 +1197,128 @@ fn show_help() {
     println!("  Use {} or {} during processing to interrupt.", style("/stop").cyan(), style("Ctrl+C").cyan());
 }
 
+/// Handle `/model [name]`: list the known models, or switch to one.
+fn switch_model(agent: &mut Agent, name: &str) {
+    if name.is_empty() {
+        let current = agent.config().llm.model.clone();
+        println!("{}", style("Models:").bold());
+        for model in agent.config().llm.known_models() {
+            if model == current {
+                println!("  {} {}", style("●").green(), style(&model).bold());
+            } else {
+                println!("    {}", model);
+            }
+        }
+        println!("{}", style("Switch with /model <name>").dim());
+        return;
+    }
+
+    match agent.set_model(name) {
+        Ok(model) => println!("{}", style(format!("🔀 Switched to {}", model)).green()),
+        Err(e) => println!("{}", style(format!("⚠ {}", e)).red()),
+    }
+}
+
+fn set_thinking(agent: &mut Agent, arg: &str) {
+    if let Some(budget) = arg.strip_prefix("budget") {
+        let Ok(budget) = budget.trim().parse::<u32>() else {
+            println!("{}", style("Usage: /think budget <tokens>").yellow());
+            return;
+        };
+        match agent.set_thinking_budget(budget) {
+            Ok(()) => println!("{}", style(format!("💭 Thinking budget set to {} tokens", budget)).green()),
+            Err(e) => println!("{}", style(format!("⚠ {}", e)).red()),
+        }
+        return;
+    }
+
+    match arg {
+        // Thinking can't be combined with the fixed temperature
+        "on" if agent.deterministic() => {
+            println!("{}", style("⚠ Thinking stays off in a --deterministic session").yellow());
+            return;
+        }
+        "on" => agent.set_thinking_override(Some(true)),
+        "off" => agent.set_thinking_override(Some(false)),
+        "default" => agent.set_thinking_override(None),
+        "" => {}
+        _ => {
+            println!("{}", style("Usage: /think on|off|default, or /think budget <tokens>").yellow());
+            return;
+        }
+    }
+    let state = if agent.thinking_enabled() { "on" } else { "off" };
+    println!("{}", style(format!("💭 Thinking is {}", state)).green());
+}
+
+/// Handle `/readonly [on|off]`.
+fn set_read_only(agent: &mut Agent, arg: &str) {
+    match arg {
+        "on" => agent.set_read_only(true),
+        "off" => agent.set_read_only(false),
+        "" => {}
+        _ => {
+            println!("{}", style("Usage: /readonly on|off").yellow());
+            return;
+        }
+    }
+    if agent.read_only() {
+        println!(
+            "{}",
+            style("🔒 Read-only mode is on: file writes, edits and mutating commands are simulated").green()
+        );
+    } else {
+        println!("{}", style("🔓 Read-only mode is off").green());
+    }
+}
+
+/// Handle `/context`: print the system prompt the next turn will send, with
+/// token counts per section, plus the knowledge retrieved for the last prompt.
+async fn show_context(agent: &mut Agent) {
+    let sections = agent.system_prompt_sections();
+    let mut system_tokens = 0;
+    for (name, text) in &sections {
+        let count = tokens::count_text(text);
+        system_tokens += count;
+        println!("{}", style(format!("── {} · {} tokens ──", name, count)).cyan().bold());
+        println!("{}\n", text);
+    }
+
+    let knowledge_tokens = match agent.last_retrieved_context() {
+        Some(knowledge) => {
+            let count = tokens::count_text(knowledge);
+            println!(
+                "{}",
+                style(format!("── Retrieved knowledge (last prompt) · {} tokens ──", count)).cyan().bold()
+            );
+            println!("{}\n", knowledge);
+            count
+        }
+        None => 0,
+    };
+
+    let tool_tokens = agent.tool_schema_tokens().await;
+    println!("{}", style("Totals:").bold());
+    println!("  System prompt: {} tokens ({} sections)", system_tokens, sections.len());
+    println!("  Tool schemas:  {} tokens", tool_tokens);
+    if knowledge_tokens > 0 {
+        println!("  Knowledge:     {} tokens", knowledge_tokens);
+    }
+}
+
+/// Handle `/files`: list the files the agent has written or edited.
+fn show_files_changed(agent: &Agent) {
+    let files = agent.files_changed();
+    if files.is_empty() {
+        println!("{}", style("No files changed in this session.").dim());
+        return;
+    }
+    println!("{}", style(format!("📝 Files changed ({}):", files.len())).bold());
+    for (path, operations) in files {
+        println!("  {} {}", path, style(format!("({})", operations.join(", "))).dim());
+    }
+}
+
 fn show_session_info(agent: &Agent) {
     let duration_secs = agent.session_start_elapsed().as_secs();
     let mins = duration_secs / 60;
@@ -622,10 +1327,45 @@ fn show_session_info(agent: &Agent) {
     let short_id = &agent.session_id()[..agent.session_id().len().min(8)];
     println!("{}", style("Session Info:").bold());
     println!("  ID:       {}", short_id);
+    if let Ok(Some(info)) = agent.db().get_session_by_id(agent.session_id()) {
+        if let Some(parent) = info.parent_session_id {
+            println!("  Fork of:  {}", &parent[..parent.len().min(8)]);
+        }
+    }
     if let Some(name) = agent.project_name() {
         println!("  Project:  {}", name);
     }
+    println!("  Model:    {}", agent.config().llm.model);
+    println!(
+        "  Thinking: {} (budget {} tokens)",
+        if agent.thinking_enabled() { "on" } else { "off" },
+        agent.config().thinking.budget_tokens
+    );
+    if agent.read_only() {
+        println!("  Mode:     read-only");
+    }
+    if !agent.pinned_files().is_empty() {
+        println!("  Pinned:   {} file(s)", agent.pinned_files().len());
+    }
     println!("  Messages: {}", agent.message_count());
     println!("  Duration: {}m {}s", mins, secs);
     println!("  {}", agent.usage().format_session_total());
+    println!("  {}", agent.usage().format_context(agent.config().llm.context_window()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        assert_eq!(
+            parse_script("Read src/main.rs\n\n/clear\nWhat does it do?\n"),
+            vec!["Read src/main.rs", "/clear", "What does it do?"]
+        );
+        assert_eq!(
+            parse_script("Here's the plan:\n1. parse\n2. test\n---\n\n/compact\n---\nGo\n---\n"),
+            vec!["Here's the plan:\n1. parse\n2. test", "/compact", "Go"]
+        );
+    }
 }
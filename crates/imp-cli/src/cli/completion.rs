@@ -0,0 +1,113 @@
+//! Tab completion for the chat prompt: slash commands at the start of a line,
+//! and file paths after `@` or as the argument of a file-taking command.
+
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Every chat command, for completion. Keep in step with `show_help`.
+const SLASH_COMMANDS: &[&str] = &[
+    "/agents", "/attach", "/cancel", "/clear", "/compact", "/context", "/exit", "/files", "/fork",
+    "/help", "/interrupt", "/keep", "/mcp", "/model", "/pin", "/queue", "/quit", "/readonly", "/retry",
+    "/search", "/session", "/spawn", "/stop", "/think", "/undo", "/unpin",
+];
+
+/// Commands whose arguments are file paths.
+const FILE_COMMANDS: &[&str] = &["/attach", "/pin", "/unpin"];
+
+pub struct ChatHelper {
+    files: FilenameCompleter,
+}
+
+impl ChatHelper {
+    pub fn new() -> Self {
+        Self { files: FilenameCompleter::new() }
+    }
+
+    /// Where the replacement starts in `line`, and the candidates, for the
+    /// text before `pos`.
+    fn complete_line(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let before = &line[..pos];
+
+        if before.starts_with('/') && !before.contains(char::is_whitespace) {
+            let commands = SLASH_COMMANDS
+                .iter()
+                .filter(|c| c.starts_with(before))
+                .map(|c| Pair { display: c.to_string(), replacement: c.to_string() })
+                .collect();
+            return (0, commands);
+        }
+
+        let word_start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        if before[word_start..].starts_with('@') {
+            let path_start = word_start + 1;
+            let path = &before[path_start..];
+            return match self.files.complete_path(path, path.len()) {
+                Ok((start, paths)) => (path_start + start, paths),
+                Err(_) => (pos, Vec::new()),
+            };
+        }
+
+        let command = before.split_whitespace().next().unwrap_or("");
+        if word_start > 0 && FILE_COMMANDS.contains(&command) {
+            return self.files.complete_path(line, pos).unwrap_or((pos, Vec::new()));
+        }
+
+        (pos, Vec::new())
+    }
+}
+
+impl Completer for ChatHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.complete_line(line, pos))
+    }
+}
+
+impl Hinter for ChatHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ChatHelper {}
+
+impl Validator for ChatHelper {}
+
+impl Helper for ChatHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacements(helper: &ChatHelper, line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = helper.complete_line(line, line.len());
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn test_complete_line() {
+        let dir = std::env::temp_dir().join(format!("imp-complete-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.md"), "").unwrap();
+        let prefix = format!("{}/no", dir.display());
+        let expected = format!("{}/notes.md", dir.display());
+        let helper = ChatHelper::new();
+
+        assert_eq!(replacements(&helper, "/comp"), (0, vec!["/compact".to_string()]));
+        assert_eq!(replacements(&helper, "/u").1, vec!["/undo", "/unpin"]);
+        // Only at the start of the line, and not once the command is typed
+        assert!(replacements(&helper, "see /comp").1.is_empty());
+        assert!(replacements(&helper, "/compact ").1.is_empty());
+
+        let line = format!("look at @{}", prefix);
+        assert_eq!(replacements(&helper, &line), ("look at @".len(), vec![expected.clone()]));
+
+        let line = format!("/attach {}", prefix);
+        assert_eq!(replacements(&helper, &line), ("/attach ".len(), vec![expected]));
+        assert!(replacements(&helper, &format!("/model {}", prefix)).1.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
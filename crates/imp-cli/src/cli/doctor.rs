@@ -0,0 +1,213 @@
+use crate::client::{ClaudeClient, Message};
+use crate::config::{AuthMethod, Config};
+use crate::embeddings::Embedder;
+use crate::error::Result;
+use crate::knowledge::KnowledgeGraph;
+use crate::tools::mcp::{self, McpConnectionState, McpRegistry};
+use console::style;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the local embedding model to finish loading.
+const EMBEDDING_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Why a check failed, and what to do about it.
+struct Problem {
+    message: String,
+    hint: String,
+}
+
+impl Problem {
+    fn new(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { message: message.into(), hint: hint.into() }
+    }
+}
+
+/// Running tally of check results.
+#[derive(Default)]
+struct Report {
+    critical_failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    /// Print one check's outcome. Critical failures make `imp doctor` exit
+    /// non-zero; other failures are warnings.
+    fn record(&mut self, name: &str, critical: bool, result: std::result::Result<String, Problem>) {
+        match result {
+            Ok(detail) => println!("  {} {:<16} {}", style("✓").green(), name, style(detail).dim()),
+            Err(problem) => {
+                if critical {
+                    self.critical_failures += 1;
+                    println!("  {} {:<16} {}", style("✗").red(), name, problem.message);
+                } else {
+                    self.warnings += 1;
+                    println!("  {} {:<16} {}", style("!").yellow(), name, problem.message);
+                }
+                println!("    {} {}", style("→").dim(), style(problem.hint).dim());
+            }
+        }
+    }
+}
+
+/// `imp doctor` — check config, auth, the model, the knowledge graph,
+/// embeddings, MCP servers and ripgrep. Every check runs even if an earlier
+/// one fails. Returns false if a critical check failed.
+pub async fn run() -> Result<bool> {
+    println!("{}", style("🩺 Checking your imp install...").bold());
+    let mut report = Report::default();
+
+    let config = match Config::load() {
+        Ok(config) => {
+            report.record("config", true, Ok(Config::config_path()?.display().to_string()));
+            Some(config)
+        }
+        Err(e) => {
+            report.record(
+                "config",
+                true,
+                Err(Problem::new(e.to_string(), "Run 'imp bootstrap' to create a config")),
+            );
+            None
+        }
+    };
+
+    match config {
+        Some(ref config) => {
+            report.record("auth", true, check_auth(config));
+            report.record("model", true, check_model(config).await);
+            report.record("knowledge graph", false, check_knowledge_graph(config));
+            report.record("embeddings", false, check_embeddings(config));
+        }
+        None => println!(
+            "  {}",
+            style("Skipped the auth, model, knowledge graph and embeddings checks").dim()
+        ),
+    }
+    check_mcp_servers(&mut report).await;
+    report.record("ripgrep", false, check_ripgrep());
+
+    println!();
+    if report.critical_failures > 0 {
+        println!(
+            "{}",
+            style(format!("✗ {} critical problem(s), {} warning(s)", report.critical_failures, report.warnings))
+                .red()
+                .bold()
+        );
+    } else if report.warnings > 0 {
+        println!("{}", style(format!("✓ Working, with {} warning(s)", report.warnings)).yellow().bold());
+    } else {
+        println!("{}", style("✓ Everything looks good").green().bold());
+    }
+    Ok(report.critical_failures == 0)
+}
+
+fn check_auth(config: &Config) -> std::result::Result<String, Problem> {
+    match config.auth_method() {
+        AuthMethod::ApiKey => match config.api_key() {
+            Some(key) if !key.trim().is_empty() => Ok("API key".to_string()),
+            _ => Err(Problem::new("No API key configured", "Run 'imp login' to add a token")),
+        },
+        AuthMethod::OAuth => match config.oauth_config() {
+            None => Err(Problem::new("OAuth selected but no token configured", "Run 'imp login' to add a token")),
+            Some(oauth) if oauth.is_expired() && oauth.refresh_token.is_empty() => Err(Problem::new(
+                "OAuth token has expired and can't be refreshed",
+                "Run 'imp login' to re-authenticate",
+            )),
+            Some(_) => Ok("OAuth token".to_string()),
+        },
+    }
+}
+
+/// Send a tiny request to the configured model.
+async fn check_model(config: &Config) -> std::result::Result<String, Problem> {
+    let hint = "Check the token with 'imp login', and [llm] model / base_url in config.toml";
+    let mut client = ClaudeClient::new(config.clone()).map_err(|e| Problem::new(e.to_string(), hint))?;
+    let messages = vec![Message::text("user", "Reply with the single word: ok")];
+    client
+        .send_message_inner(messages, None, None, false, Some(16), Some(false))
+        .await
+        .map(|_| format!("{} responded", config.llm.model))
+        .map_err(|e| Problem::new(e.to_string(), hint))
+}
+
+fn check_knowledge_graph(config: &Config) -> std::result::Result<String, Problem> {
+    if !config.knowledge.enabled {
+        return Ok("disabled in config".to_string());
+    }
+    let kg = KnowledgeGraph::open().map_err(|e| {
+        Problem::new(
+            e.to_string(),
+            "Another imp process may hold the database lock; close it and retry. Otherwise check ~/.imp/knowledge.cozo",
+        )
+    })?;
+    let stats = kg.stats().map_err(|e| Problem::new(e.to_string(), "The knowledge database may be corrupt"))?;
+    Ok(format!("{} entities, {} chunks", stats.entity_count, stats.chunk_count))
+}
+
+/// Wait for the local model to load (or call the remote provider) and embed
+/// a test string.
+fn check_embeddings(config: &Config) -> std::result::Result<String, Problem> {
+    if !config.knowledge.embeddings_enabled || !config.knowledge.enabled {
+        return Ok("disabled in config".to_string());
+    }
+    if !cfg!(feature = "embeddings") && config.knowledge.embedding_url.is_none() {
+        return Err(Problem::new(
+            "Built without the embeddings feature",
+            "Rebuild with default features, or set [knowledge] embedding_provider = \"remote\"",
+        ));
+    }
+
+    Embedder::configure(&config.knowledge);
+    let started = Instant::now();
+    while !Embedder::available() && started.elapsed() < EMBEDDING_LOAD_TIMEOUT {
+        std::thread::sleep(Duration::from_millis(250));
+    }
+    if Embedder::embed("imp doctor").is_some() {
+        Ok(Embedder::provider_name())
+    } else {
+        Err(Problem::new(
+            format!("{} unavailable — semantic search falls back to text", Embedder::provider_name()),
+            "The first run downloads the local model (~335MB); retry once it finishes. For a remote provider, check embedding_url and embedding_dim",
+        ))
+    }
+}
+
+/// One line per configured MCP server.
+async fn check_mcp_servers(report: &mut Report) {
+    let configs = match mcp::load_mcp_config() {
+        Ok(configs) => configs,
+        Err(e) => {
+            report.record("mcp", false, Err(Problem::new(e.to_string(), "Fix the JSON in ~/.imp/.mcp.json")));
+            return;
+        }
+    };
+    if configs.is_empty() {
+        report.record("mcp", false, Ok("no servers configured".to_string()));
+        return;
+    }
+
+    let mut registry = McpRegistry::new();
+    registry.load_from_config_background(&configs);
+    for status in registry.status().await {
+        let result = if status.state == McpConnectionState::Connected {
+            Ok(format!("{} tools via {}", status.tool_count, status.transport))
+        } else {
+            Err(Problem::new(
+                status.error.unwrap_or_else(|| status.state.to_string()),
+                "Check the server's command/url in ~/.imp/.mcp.json and run it by hand to see its output",
+            ))
+        };
+        report.record(&format!("mcp {}", status.name), false, result);
+    }
+}
+
+fn check_ripgrep() -> std::result::Result<String, Problem> {
+    let output = Command::new("rg")
+        .arg("--version")
+        .output()
+        .map_err(|_| Problem::new("rg not found on PATH", "Install ripgrep; search_code falls back to a slower built-in search without it"))?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    Ok(version.lines().next().unwrap_or("ripgrep").to_string())
+}
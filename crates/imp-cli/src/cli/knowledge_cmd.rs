@@ -1,38 +1,80 @@
 //! CLI commands for the knowledge graph.
 //!
-//! Provides `imp knowledge stats`, `imp knowledge schema`, and
-//! `imp knowledge query <name>` subcommands.
+//! Provides `imp knowledge stats`, `imp knowledge schema` (and its
+//! `add-type` / `add-rel` / `remove-type` / `remove-rel` subcommands),
+//! `imp knowledge query <name>`, `imp knowledge merge <a> <b>`,
+//! `imp knowledge delete <name>`, `imp knowledge prune`,
+//! `imp knowledge export`, and `imp knowledge import-mcp-resource <uri>`
+//! subcommands.
 
+use crate::config::{Config, KnowledgeConfig};
 use crate::embeddings::Embedder;
-use crate::error::Result;
-use crate::knowledge::KnowledgeGraph;
+use crate::error::{ImpError, Result};
+use crate::knowledge::{chunk_text, ExportFormat, KnowledgeGraph};
+use crate::tools::mcp::{self, McpRegistry};
 use console::style;
+use dialoguer::Select;
+use std::io::Write;
 
-/// Show entity/relationship/chunk counts.
-pub fn stats() -> Result<()> {
+/// Apply the `[knowledge]` embedding settings (provider, dimension) when a
+/// config exists, so commands that embed use the configured provider.
+/// Returns the settings in effect (defaults if there is no config).
+fn configure_embeddings() -> KnowledgeConfig {
+    match Config::load() {
+        Ok(config) => {
+            Embedder::configure(&config.knowledge);
+            config.knowledge
+        }
+        Err(_) => KnowledgeConfig::default(),
+    }
+}
+
+/// Show entity/relationship/chunk counts, for the whole graph or for the
+/// knowledge tagged with one project.
+pub fn stats(project: Option<&str>) -> Result<()> {
+    configure_embeddings();
     let kg = KnowledgeGraph::open()?;
-    let s = kg.stats()?;
 
-    // Get embedding-specific stats
-    let with_embeddings = kg.count_rows("?[count(id)] := *memory_chunk{id, has_embedding}, has_embedding == true")?;
-    let without_embeddings = kg.count_rows("?[count(id)] := *memory_chunk{id, has_embedding}, has_embedding == false")?;
+    let Some(project) = project else {
+        let s = kg.stats()?;
+
+        // Get embedding-specific stats
+        let with_embeddings = kg.count_rows("?[count(id)] := *memory_chunk{id, has_embedding}, has_embedding == true")?;
+        let without_embeddings = kg.count_rows("?[count(id)] := *memory_chunk{id, has_embedding}, has_embedding == false")?;
+        let global_chunks = kg.count_rows("?[count(id)] := *memory_chunk{id, project}, project == ''")?;
 
-    println!("{}", style("Knowledge Graph Stats").bold().cyan());
+        println!("{}", style("Knowledge Graph Stats").bold().cyan());
+        println!("  Entities:       {}", s.entity_count);
+        println!("  Relationships:  {}", s.relationship_count);
+        println!("  Memory chunks:  {} ({} with embeddings, {} without; {} global)",
+            s.chunk_count, with_embeddings, without_embeddings, global_chunks);
+        println!("  Deduplicated:   {} near-duplicate chunks not stored", s.duplicate_chunks);
+        println!("  Schema types:   {}", s.schema_type_count);
+        println!("  Schema rels:    {}", s.schema_rel_count);
+        print_embedding_status();
+        return Ok(());
+    };
+
+    let s = kg.project_stats(project)?;
+    println!("{}", style(format!("Knowledge Graph Stats — project {}", project)).bold().cyan());
     println!("  Entities:       {}", s.entity_count);
-    println!("  Relationships:  {}", s.relationship_count);
-    println!("  Memory chunks:  {} ({} with embeddings, {} without)", 
-        s.chunk_count, with_embeddings, without_embeddings);
-    println!("  Schema types:   {}", s.schema_type_count);
-    println!("  Schema rels:    {}", s.schema_rel_count);
-    
-    // Show embedding status
+    println!("  Relationships:  {} (from the project's entities)", s.relationship_count);
+    println!("  Memory chunks:  {}", s.chunk_count);
+    println!("  Deduplicated:   {} near-duplicate chunks not stored", s.duplicate_chunks);
+    println!("{}", style("  Untagged (global) knowledge is shared by every project and not counted here.").dim());
+    print_embedding_status();
+    Ok(())
+}
+
+fn print_embedding_status() {
     if Embedder::available() {
-        println!("  Embeddings:     {}", style("✓ Available (BGE-large-en-v1.5)").green());
+        println!(
+            "  Embeddings:     {}",
+            style(format!("✓ Available ({})", Embedder::provider_name())).green()
+        );
     } else {
         println!("  Embeddings:     {}", style("✗ Unavailable").red());
     }
-
-    Ok(())
 }
 
 /// Show current schema types and relationship types.
@@ -104,8 +146,98 @@ pub fn schema() -> Result<()> {
     Ok(())
 }
 
+/// `imp knowledge schema add-type` — add an entity type or update its description.
+pub fn schema_add_type(name: &str, description: &str) -> Result<()> {
+    let name = schema_name(name)?;
+    let kg = KnowledgeGraph::open()?;
+    let exists = kg.get_schema()?.types.iter().any(|t| t.type_name == name);
+    kg.put_schema_type(name, description)?;
+
+    let verb = if exists { "Updated" } else { "Added" };
+    println!("{}", style(format!("✓ {} entity type '{}'", verb, name)).green());
+    Ok(())
+}
+
+/// `imp knowledge schema add-rel` — add or replace a relationship type. Warns
+/// about from/to types that aren't in the schema but adds the type anyway.
+pub fn schema_add_rel(name: &str, from: &[String], to: &[String], description: &str, example: &str) -> Result<()> {
+    let name = schema_name(name)?;
+    let clean = |types: &[String]| -> Vec<String> {
+        types.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+    };
+    let (from, to) = (clean(from), clean(to));
+
+    let kg = KnowledgeGraph::open()?;
+    let schema = kg.get_schema()?;
+    for t in from.iter().chain(to.iter()) {
+        if !schema.types.iter().any(|known| &known.type_name == t) {
+            println!(
+                "{}",
+                style(format!("⚠ '{}' isn't a known entity type (add it with 'imp knowledge schema add-type')", t)).yellow()
+            );
+        }
+    }
+    let exists = schema.relationships.iter().any(|r| r.rel_name == name);
+    kg.put_schema_rel(name, description, &from, &to, example)?;
+
+    let verb = if exists { "Updated" } else { "Added" };
+    println!("{}", style(format!("✓ {} relationship type '{}'", verb, name)).green());
+    Ok(())
+}
+
+/// `imp knowledge schema remove-type` — remove an entity type. Entities that
+/// still use it are kept, with a warning.
+pub fn schema_remove_type(name: &str) -> Result<()> {
+    let name = name.trim();
+    let kg = KnowledgeGraph::open()?;
+    if !kg.remove_schema_type(name)? {
+        println!("{}", style(format!("No entity type named '{}'", name)).yellow());
+        return Ok(());
+    }
+
+    println!("{}", style(format!("✓ Removed entity type '{}'", name)).green());
+    let in_use = kg.count_entities_of_type(name)?;
+    if in_use > 0 {
+        println!(
+            "{}",
+            style(format!("⚠ {} entit{} still have this type and were left as they are", in_use, if in_use == 1 { "y" } else { "ies" })).yellow()
+        );
+    }
+    Ok(())
+}
+
+/// `imp knowledge schema remove-rel` — remove a relationship type.
+/// Relationships that still use it are kept, with a warning.
+pub fn schema_remove_rel(name: &str) -> Result<()> {
+    let name = name.trim();
+    let kg = KnowledgeGraph::open()?;
+    if !kg.remove_schema_rel(name)? {
+        println!("{}", style(format!("No relationship type named '{}'", name)).yellow());
+        return Ok(());
+    }
+
+    println!("{}", style(format!("✓ Removed relationship type '{}'", name)).green());
+    let in_use = kg.count_relationships_of_type(name)?;
+    if in_use > 0 {
+        println!(
+            "{}",
+            style(format!("⚠ {} relationship(s) still have this type and were left as they are", in_use)).yellow()
+        );
+    }
+    Ok(())
+}
+
+/// A schema type or relationship name, trimmed; rejects empty names.
+fn schema_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ImpError::Config("Schema names can't be empty".to_string()));
+    }
+    Ok(name)
+}
+
 /// Look up an entity by name and show its relationships.
-pub fn query(name: &str) -> Result<()> {
+pub fn query(name: &str, max_chunks: usize) -> Result<()> {
     let kg = KnowledgeGraph::open()?;
 
     match kg.find_entity_by_name(name)? {
@@ -123,6 +255,9 @@ pub fn query(name: &str) -> Result<()> {
             {
                 println!("  properties: {}", entity.properties);
             }
+            if let Some(origin) = describe_provenance(&entity.source, entity.confidence) {
+                println!("  from: {}", style(origin).dim());
+            }
 
             // Get relationships (up to 2 hops)
             let related = kg.get_related(&entity.name, 2)?;
@@ -152,6 +287,30 @@ pub fn query(name: &str) -> Result<()> {
                         arrow,
                         style(&r.entity.entity_type).dim()
                     );
+                    if let Some(origin) = describe_provenance(&r.source, r.confidence) {
+                        println!("      {}", style(format!("from {}", origin)).dim());
+                    }
+                }
+            }
+
+            let chunks = kg.chunks_for_entity(&entity, max_chunks)?;
+            if !chunks.is_empty() {
+                println!();
+                println!("  {}", style("Notes:").bold());
+                for chunk in &chunks {
+                    let preview: String = chunk.content.chars().take(200).collect();
+                    let ellipsis = if chunk.content.chars().count() > 200 { "..." } else { "" };
+                    println!("    • {}{}", preview.replace('\n', " "), ellipsis);
+                    println!(
+                        "      {}",
+                        style(format!(
+                            "{} on {} • accessed {} times",
+                            chunk.source_type,
+                            format_date(chunk.created_at),
+                            chunk.access_count
+                        ))
+                        .dim()
+                    );
                 }
             }
         }
@@ -166,10 +325,179 @@ pub fn query(name: &str) -> Result<()> {
     Ok(())
 }
 
-/// Search for memory chunks using semantic or text search.
+/// A Unix timestamp as a local YYYY-MM-DD date.
+fn format_date(timestamp: f64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "unknown date".to_string())
+}
+
+/// "session … on 2026-03-02, confidence 0.80", or None if nothing was recorded.
+fn describe_provenance(source: &str, confidence: Option<f64>) -> Option<String> {
+    match (source.is_empty(), confidence) {
+        (true, None) => None,
+        (true, Some(c)) => Some(format!("confidence {:.2}", c)),
+        (false, None) => Some(source.to_string()),
+        (false, Some(c)) => Some(format!("{}, confidence {:.2}", source, c)),
+    }
+}
+
+/// Merge two entities that refer to the same thing, prompting which to keep.
+pub fn merge(name1: &str, name2: &str) -> Result<()> {
+    let kg = KnowledgeGraph::open()?;
+
+    let first = match kg.find_entity_by_name(name1)? {
+        Some(e) => e,
+        None => {
+            println!("{}", style(format!("No entity found matching '{}'", name1)).yellow());
+            return Ok(());
+        }
+    };
+    let second = match kg.find_entity_by_name(name2)? {
+        Some(e) => e,
+        None => {
+            println!("{}", style(format!("No entity found matching '{}'", name2)).yellow());
+            return Ok(());
+        }
+    };
+
+    if first.id == second.id {
+        println!(
+            "{}",
+            style(format!("'{}' and '{}' are already the same entity", name1, name2)).yellow()
+        );
+        return Ok(());
+    }
+
+    let choices = [
+        format!("{} ({})", first.name, first.entity_type),
+        format!("{} ({})", second.name, second.entity_type),
+    ];
+    let selection = Select::new()
+        .with_prompt("Which entity should be kept?")
+        .items(&choices)
+        .default(0)
+        .interact()?;
+
+    let (keep, merged) = if selection == 0 { (first, second) } else { (second, first) };
+    let result = kg.merge_entities(&keep.id, &merged.id)?;
+
+    println!(
+        "{}",
+        style(format!("✓ Merged '{}' into '{}'", merged.name, result.name)).green()
+    );
+    if !result.aliases.is_empty() {
+        println!("  aliases: {}", style(result.aliases.join(", ")).dim());
+    }
+
+    Ok(())
+}
+
+/// Delete an entity and everything that references it.
+pub fn delete(name: &str) -> Result<()> {
+    let kg = KnowledgeGraph::open()?;
+
+    match kg.delete_entity(name)? {
+        Some(entity) => {
+            println!(
+                "{}",
+                style(format!("✓ Deleted '{}' ({})", entity.name, entity.entity_type)).green()
+            );
+        }
+        None => {
+            println!("{}", style(format!("No entity found matching '{}'", name)).yellow());
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove old chunks that have rarely (or never) been retrieved.
+pub fn prune(max_age_days: u64, min_access_count: i64, dry_run: bool) -> Result<()> {
+    let kg = KnowledgeGraph::open()?;
+
+    let chunks = if dry_run {
+        kg.prunable_chunks(max_age_days, min_access_count)?
+    } else {
+        kg.prune_chunks(max_age_days, min_access_count)?
+    };
+
+    if chunks.is_empty() {
+        println!("{}", style("✓ Nothing to prune").green());
+        return Ok(());
+    }
+
+    for chunk in &chunks {
+        let preview: String = chunk.content.chars().take(80).collect();
+        println!(
+            "  {} {} {}",
+            style(&chunk.source_type).green(),
+            style(format!("(accessed {}x)", chunk.access_count)).dim(),
+            preview.replace('\n', " ")
+        );
+    }
+
+    if dry_run {
+        println!(
+            "\n{}",
+            style(format!(
+                "Would delete {} chunk(s) older than {} days with fewer than {} accesses (dry run)",
+                chunks.len(), max_age_days, min_access_count
+            )).yellow()
+        );
+    } else {
+        println!(
+            "\n{}",
+            style(format!("✓ Deleted {} chunk(s)", chunks.len())).green()
+        );
+    }
+
+    Ok(())
+}
+
+/// Export the graph to stdout as JSON or GraphML.
+pub fn export(format: &str) -> Result<()> {
+    let format: ExportFormat = format.parse()?;
+    let kg = KnowledgeGraph::open()?;
+
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    kg.export(format, &mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Fuzzy-search entities by name, alias, and properties, then search memory
+/// chunks using semantic or text search.
 pub fn search(query: &str) -> Result<()> {
+    configure_embeddings();
     let kg = KnowledgeGraph::open()?;
 
+    let entities = kg.search_entities(query, 10)?;
+    if !entities.is_empty() {
+        println!("{}", style("Matching Entities").bold().cyan());
+        for (entity, score) in &entities {
+            println!(
+                "  {} ({}) {}",
+                style(&entity.name).bold().green(),
+                style(&entity.entity_type).cyan(),
+                style(format!("score {:.2}", score)).dim(),
+            );
+            let related = kg.get_related(&entity.name, 1).unwrap_or_default();
+            for r in related.iter().take(3) {
+                let arrow = if r.direction == "->" { "→" } else { "←" };
+                println!(
+                    "    {} {} {}",
+                    arrow,
+                    style(&r.rel_type).yellow(),
+                    r.entity.name
+                );
+            }
+        }
+        println!();
+    }
+
     // Warn if embeddings unavailable
     if !Embedder::available() {
         Embedder::warn_if_unavailable();
@@ -215,6 +543,7 @@ pub fn search(query: &str) -> Result<()> {
 
 /// Backfill embeddings for chunks that don't have them.
 pub fn backfill_embeddings() -> Result<()> {
+    let settings = configure_embeddings();
     let kg = KnowledgeGraph::open()?;
 
     if !Embedder::available() {
@@ -225,7 +554,9 @@ pub fn backfill_embeddings() -> Result<()> {
 
     println!("{}", style("Backfilling embeddings...").cyan());
 
-    let (processed, success) = kg.backfill_embeddings()?;
+    let (processed, success) = kg.backfill_embeddings(settings.embedding_batch_size, |done, total| {
+        println!("  {}", style(format!("… {}/{} chunks", done, total)).dim());
+    })?;
 
     if processed == 0 {
         println!("{}", style("✓ No chunks need embedding backfill").green());
@@ -245,3 +576,55 @@ pub fn backfill_embeddings() -> Result<()> {
 
     Ok(())
 }
+
+/// Read a resource from an MCP server and store it as knowledge chunks.
+/// Without `server`, the first connected server that lists `uri` is used.
+pub async fn import_mcp_resource(uri: &str, server: Option<&str>) -> Result<()> {
+    let settings = configure_embeddings();
+
+    let configs = mcp::load_mcp_config()?;
+    if configs.is_empty() {
+        println!("{}", style("No MCP servers configured in ~/.imp/.mcp.json").yellow());
+        return Ok(());
+    }
+
+    let mut registry = McpRegistry::new();
+    registry.load_from_config_background(&configs);
+
+    let server_name = match server {
+        Some(name) => name.to_string(),
+        None => registry.find_resource_server(uri).await?,
+    };
+    if !registry.supports_resources(&server_name).await? {
+        println!(
+            "{}",
+            style(format!("MCP server '{}' does not support resources.", server_name)).yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", style(format!("Reading {} from '{}'...", uri, server_name)).cyan());
+    let content = registry.read_resource(&server_name, uri).await?;
+
+    let kg = KnowledgeGraph::open()?.with_settings(&settings);
+    let chunks = chunk_text(&content, settings.chunk_size, settings.chunk_overlap);
+    let mut stored = 0;
+    for chunk in &chunks {
+        if !kg.store_chunk(chunk, "mcp_resource", uri)?.duplicate {
+            stored += 1;
+        }
+    }
+
+    println!(
+        "{}",
+        style(format!(
+            "✓ Imported {} chunks ({} already known) from {}",
+            stored,
+            chunks.len() - stored,
+            uri
+        ))
+        .green()
+    );
+
+    Ok(())
+}
@@ -1,11 +1,16 @@
 use crate::client::{ClaudeClient, Message};
 use crate::config::{imp_home, Config};
-use crate::error::Result;
+use crate::error::{ImpError, Result};
+use crate::extraction::{extract_knowledge_llm, process_extraction, ExtractionStats};
+use crate::knowledge::{chunk_text, KnowledgeGraph};
 use crate::project;
 use console::style;
 use dialoguer::Select;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+
+/// Largest piece of a file sent to the LLM in one extraction call.
+const MAX_EXTRACT_CHARS: usize = 12_000;
 
 pub async fn run() -> Result<()> {
     println!("{}", style("📚 Teaching Your Agent").bold().blue());
@@ -184,3 +189,90 @@ pub async fn run() -> Result<()> {
 
     Ok(())
 }
+
+/// Non-interactive teaching: extract knowledge from a file (or stdin when
+/// `path` is `None` or `-`) straight into the knowledge graph.
+pub async fn from_file(path: Option<&str>) -> Result<()> {
+    let (content, label) = match path {
+        Some(p) if p != "-" => {
+            let content = fs::read_to_string(p)
+                .map_err(|e| ImpError::Config(format!("Could not read {}: {}", p, e)))?;
+            (content, format!("file {}", p))
+        }
+        _ => {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            (content, "stdin".to_string())
+        }
+    };
+
+    if content.trim().is_empty() {
+        return Err(ImpError::Config(format!("Nothing to learn: {} is empty", label)));
+    }
+
+    let config = Config::load()?;
+    if !config.knowledge.enabled {
+        return Err(ImpError::Config(
+            "The knowledge graph is disabled — set [knowledge] enabled = true to use imp learn --file".to_string(),
+        ));
+    }
+    crate::embeddings::Embedder::configure(&config.knowledge);
+
+    let mut client = ClaudeClient::new(config.clone())?;
+    let kg = KnowledgeGraph::open()?.with_settings(&config.knowledge);
+    let schema = kg.get_schema()?;
+    let source = format!("{} on {}", label, chrono::Local::now().format("%Y-%m-%d"));
+
+    let pieces = chunk_text(&content, MAX_EXTRACT_CHARS, 0);
+    let mut total = ExtractionStats::default();
+    let mut failed = 0;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        if pieces.len() > 1 {
+            eprintln!("{}", style(format!("Extracting part {}/{}...", i + 1, pieces.len())).dim());
+        }
+        // Refresh per piece so later parts link to entities found in earlier ones
+        let existing = super::reflect::get_entity_names(&kg);
+        let result = match extract_knowledge_llm(piece, &schema, &existing, &mut client).await {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("⚠️ Knowledge extraction failed: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+        match process_extraction(&kg, &result, &source, &config.knowledge) {
+            Ok(stats) => total.add(&stats),
+            Err(e) => {
+                eprintln!("⚠️ Failed to process extraction: {}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed == pieces.len() {
+        return Err(ImpError::Agent(format!("Could not extract any knowledge from {}", label)));
+    }
+
+    println!(
+        "Learned from {}: {} entities, {} relationships, {} chunks",
+        label, total.entities_added, total.relationships_added, total.chunks_stored
+    );
+    if total.new_types_added > 0 {
+        println!("  {} new entity types", total.new_types_added);
+    }
+    if total.types_coerced > 0 || total.entities_rejected > 0 {
+        println!(
+            "  {} types mapped to the schema, {} entities sent to review",
+            total.types_coerced, total.entities_rejected
+        );
+    }
+    if total.low_confidence_held > 0 {
+        println!("  {} low-confidence items held for review", total.low_confidence_held);
+    }
+    if failed > 0 {
+        println!("  {} of {} parts failed to extract", failed, pieces.len());
+    }
+
+    Ok(())
+}
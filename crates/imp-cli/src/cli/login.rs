@@ -8,7 +8,7 @@ pub async fn run() -> Result<()> {
     println!("Set up authentication for imp.\n");
 
     // Check if already configured
-    if let Ok(config) = Config::load() {
+    if let Ok(config) = Config::load_global() {
         match config.auth_method() {
             AuthMethod::OAuth => {
                 println!("{}", style("ℹ️  You're currently using OAuth authentication.").cyan());
@@ -70,18 +70,37 @@ pub async fn run() -> Result<()> {
     };
     
     // Load or create config
-    let mut config = Config::load().unwrap_or_else(|_| Config {
+    let mut config = Config::load_global().unwrap_or_else(|_| Config {
         llm: crate::config::LlmConfig {
             provider: "anthropic".to_string(),
             model: "claude-opus-4-5-20251101".to_string(),
             max_tokens: 16384,
             base_url: None,
             api_key: None,
+            context_windows: Default::default(),
+            models: Default::default(),
+            request_timeout_secs: crate::config::default_request_timeout_secs(),
+            connect_timeout_secs: crate::config::default_connect_timeout_secs(),
+            stream_idle_timeout_secs: crate::config::default_stream_idle_timeout_secs(),
+            temperature: None,
+            seed: None,
+            prompt_caching: true,
         },
         auth: Default::default(),
         thinking: Default::default(),
         display: Default::default(),
         knowledge: Default::default(),
+        tools: Default::default(),
+        context: Default::default(),
+        reflect: Default::default(),
+        subagent: Default::default(),
+        compaction: Default::default(),
+        logging: Default::default(),
+        observability: Default::default(),
+        network: Default::default(),
+        pricing: Default::default(),
+        project_file: None,
+        env_overrides: Vec::new(),
     });
     
     // Auto-detect token type and configure
@@ -0,0 +1,52 @@
+use crate::error::Result;
+use crate::tools::mcp::{self, McpConnectionState, McpRegistry, McpServerStatus};
+use console::style;
+
+/// `imp mcp status`
+pub async fn status() -> Result<()> {
+    let configs = mcp::load_mcp_config()?;
+    let mut registry = McpRegistry::new();
+    registry.load_from_config_background(&configs);
+    print_status(&registry.status().await);
+    Ok(())
+}
+
+/// Print one line per MCP server, with the init error under failed ones.
+/// Shared by `imp mcp status` and the `/mcp` chat command.
+pub fn print_status(statuses: &[McpServerStatus]) {
+    if statuses.is_empty() {
+        println!("{}", style("No MCP servers configured in ~/.imp/.mcp.json").dim());
+        return;
+    }
+
+    println!("{}", style("MCP servers:").bold());
+    for s in statuses {
+        let state = match s.state {
+            McpConnectionState::Connected => style(s.state.to_string()).green(),
+            McpConnectionState::Connecting | McpConnectionState::Disconnected => {
+                style(s.state.to_string()).yellow()
+            }
+            McpConnectionState::Failed => style(s.state.to_string()).red(),
+        };
+        let enabled = if s.enabled { " [enabled]" } else { "" };
+        println!(
+            "  {:<20} {:<6} {:<13} {:>3} tools{}",
+            s.name,
+            s.transport,
+            state,
+            s.tool_count,
+            enabled
+        );
+        if let Some(ref error) = s.error {
+            println!("    {}", style(error).dim());
+        }
+        if s.attempts > 0 {
+            let retry = match s.next_retry {
+                Some(d) => format!(", retrying in {}s", d.as_secs()),
+                None if s.state == McpConnectionState::Connecting => ", retrying now".to_string(),
+                None => String::new(),
+            };
+            println!("    {}", style(format!("{} failed attempt(s){}", s.attempts, retry)).dim());
+        }
+    }
+}
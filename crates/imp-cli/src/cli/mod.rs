@@ -1,9 +1,13 @@
 pub mod bootstrap;
 pub mod chat;
+pub mod completion;
+pub mod doctor;
 pub mod knowledge_cmd;
 pub mod learn;
 pub mod login;
+pub mod mcp_cmd;
 pub mod oneshot;
 pub mod project_cmd;
 pub mod reflect;
 pub mod tui;
+pub mod usage_cmd;
@@ -1,30 +1,130 @@
 use crate::agent::Agent;
 use crate::error::Result;
+use crate::output;
 use console::style;
+use serde_json::json;
+use std::io::{IsTerminal, Read};
 
-pub async fn run(message: &str) -> Result<()> {
+/// Most piped input `imp ask` includes in the prompt.
+pub const MAX_STDIN_BYTES: usize = 100_000;
+
+/// `thinking` overrides `[thinking] enabled` for this question (`--think`/`--no-think`).
+/// `read_only` turns on read-only mode whatever `[tools] read_only` says.
+/// `deterministic` makes the reply repeatable (see `Agent::set_deterministic`).
+/// With `json`, only a single JSON result is printed (see `run_json`).
+pub async fn run(message: &str, thinking: Option<bool>, json: bool, read_only: bool, deterministic: bool) -> Result<()> {
     let mut agent = Agent::new().await?;
+    agent.set_thinking_override(thinking);
+    if read_only {
+        agent.set_read_only(true);
+    }
+    if deterministic {
+        agent.set_deterministic();
+    }
+    if json {
+        return run_json(&mut agent, message).await;
+    }
 
-    println!("{}", style(format!("🤖 {}", agent.display_name())).bold().blue());
-    println!("{}", style("─".repeat(50)).dim());
+    output::line(style(format!("🤖 {}", agent.display_name())).bold().blue());
+    output::line(style("─".repeat(50)).dim());
 
     if let Some(name) = agent.project_name() {
-        println!("{}", style(format!("📂 Project: {}", name)).dim());
+        output::line(style(format!("📂 Project: {}", name)).dim());
+    }
+    if agent.read_only() {
+        output::line(style("🔒 Read-only: writes and mutating commands are simulated").dim());
     }
 
     let sections = agent.loaded_sections();
     if !sections.is_empty() {
-        println!(
-            "{}",
-            style(format!("📚 Context: {}", sections.join(", "))).dim()
-        );
+        output::line(style(format!("📚 Context: {}", sections.join(", "))).dim());
     }
     println!();
 
-    let _response = agent.process_message_with_markdown(message).await?;
+    let result = agent.process_message_with_markdown(message).await;
+    agent.save_usage();
+    result?;
 
-    println!("{}", style(agent.usage().format_session_total()).dim());
-    println!("\n{}", style("─".repeat(50)).dim());
+    output::line(style(agent.usage().format_session_total()).dim());
+    println!();
+    output::line(style("─".repeat(50)).dim());
 
     Ok(())
 }
+
+/// The prompt for `imp ask`. Stdin is only read when there are no message
+/// arguments (it is then the whole prompt) or the first one is `-` (it is
+/// attached to the rest as a fenced block), so a question asked from a
+/// script never waits on or swallows the script's input. Input past
+/// `MAX_STDIN_BYTES` is cut off with a note. `None` if there's nothing to ask.
+pub fn prompt_with_stdin(args: &[String]) -> Result<Option<String>> {
+    let (read_stdin, message) = match args.split_first() {
+        None => (true, String::new()),
+        Some((first, rest)) if first == "-" => (true, rest.join(" ")),
+        Some(_) => (false, args.join(" ")),
+    };
+    let stdin = std::io::stdin();
+    let mut input = Vec::new();
+    if read_stdin && !stdin.is_terminal() {
+        stdin.lock().take(MAX_STDIN_BYTES as u64 + 1).read_to_end(&mut input)?;
+    }
+    let truncated = input.len() > MAX_STDIN_BYTES;
+    let mut input = String::from_utf8_lossy(&input).into_owned();
+    if truncated {
+        let mut end = MAX_STDIN_BYTES;
+        while !input.is_char_boundary(end) {
+            end -= 1;
+        }
+        input.truncate(end);
+    }
+
+    match (message.trim().is_empty(), input.trim().is_empty()) {
+        (true, true) => Ok(None),
+        (false, true) => Ok(Some(message)),
+        (true, false) if !truncated => Ok(Some(input)),
+        _ => {
+            // A fence longer than any run of backticks in the input
+            let longest_run = input.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+            let fence = "`".repeat(longest_run.max(2) + 1);
+            let mut prompt = format!("{}\n\nPiped input:\n{}\n{}", message.trim(), fence, input.trim_end());
+            prompt.push_str(&format!("\n{}", fence));
+            if truncated {
+                prompt.push_str(&format!(
+                    "\n[Input truncated: only the first {} KB is included]",
+                    MAX_STDIN_BYTES / 1000
+                ));
+            }
+            Ok(Some(prompt.trim_start().to_string()))
+        }
+    }
+}
+
+/// `imp ask --json`: answer quietly, then print one JSON object with the
+/// reply, the tool calls made, token usage and the session ID. On failure the
+/// object carries `error` instead of `text` and the command still fails.
+async fn run_json(agent: &mut Agent, message: &str) -> Result<()> {
+    agent.set_quiet(true);
+    let result = agent.process_message(message, false).await;
+    agent.save_usage();
+
+    let usage = agent.usage();
+    let mut output = json!({
+        "session_id": agent.session_id(),
+        "model": agent.config().llm.model,
+        "tool_calls": agent.last_turn_tool_calls(),
+        "usage": {
+            "input_tokens": usage.total_input_tokens,
+            "output_tokens": usage.total_output_tokens,
+            "cache_creation_tokens": usage.total_cache_creation_tokens,
+            "cache_read_tokens": usage.total_cache_read_tokens,
+            "requests": usage.request_count,
+            "cost_usd": usage.total_cost(),
+        },
+    });
+    match result {
+        Ok(ref text) => output["text"] = json!(text),
+        Err(ref e) => output["error"] = json!(e.to_string()),
+    }
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    result.map(|_| ())
+}
@@ -1,3 +1,4 @@
+use crate::config::Config;
 use crate::context::ContextManager;
 use crate::error::Result;
 use crate::project::{detect_project, ProjectRegistry};
@@ -97,5 +98,18 @@ pub fn context() -> Result<()> {
         }
     }
 
+    println!("\n{}", style("Config in effect (later ones win):").bold());
+    match Config::load() {
+        Ok(config) => {
+            for file in config.files() {
+                println!("  • {}", file.display());
+            }
+            if !config.env_overrides.is_empty() {
+                println!("  • environment: {}", config.env_overrides.join(", "));
+            }
+        }
+        Err(e) => println!("  {}", style(e.to_string()).red()),
+    }
+
     Ok(())
 }
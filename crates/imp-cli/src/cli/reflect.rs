@@ -1,123 +1,228 @@
 use anyhow::Result;
 use console::style;
+use dialoguer::{Confirm, Select};
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::client::{ClaudeClient, Message};
-use crate::config::{imp_home, Config};
+use crate::config::{imp_home, Config, KnowledgeConfig};
 use crate::db::Database;
 use crate::extraction::{extract_knowledge_llm, process_extraction, ExtractionStats};
-use crate::knowledge::{KnowledgeGraph, read_queue, clear_queue, append_to_queue};
+use crate::knowledge::{KnowledgeGraph, QueueEntry, chunk_text, provenance, read_queue, clear_queue, append_to_queue, dedup_queue};
+
+/// How reflect applies the file rewrites it proposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WriteMode {
+    /// Write every update (the default).
+    Write,
+    /// Show a diff for each update; write nothing and leave the knowledge queue alone.
+    DryRun,
+    /// Show a diff for each update and ask before writing it.
+    Interactive,
+}
 
-pub async fn run(date: Option<String>) -> Result<()> {
-    let config = Config::load()?;
-    let mut client = ClaudeClient::new(config.clone())?;
-    let home = imp_home()?;
+/// Core files reflect may rewrite, and therefore backs up.
+const CORE_FILES: [&str; 6] = [
+    "MEMORY.md",
+    "USER.md",
+    "SOUL.md",
+    "STACK.md",
+    "ARCHITECTURE.md",
+    "PRINCIPLES.md",
+];
+
+/// Timestamped copies of core files in `~/.imp/backups/`, named
+/// `<NAME>.<YYYYmmdd-HHMMSS>-<NN>.md`, keeping the newest `keep` per file.
+/// `NN` tells apart backups made within the same second.
+struct Backups {
+    dir: PathBuf,
+    keep: usize,
+}
 
-    let target_date =
-        date.unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+impl Backups {
+    fn new(home: &Path, keep: usize) -> Self {
+        Self { dir: home.join("backups"), keep }
+    }
 
-    println!("🧠 Reflecting on {}...\n", target_date);
+    /// Copy `path` into the backup directory (if it exists and isn't empty),
+    /// then prune old backups of the same file.
+    fn backup(&self, path: &Path, file_name: &str) -> Result<Option<PathBuf>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) if !content.trim().is_empty() => {}
+            _ => return Ok(None),
+        }
 
-    // ══════════════════════════════════════════════════════════════════
-    // PHASE 1: PULL CONVERSATIONS FROM DB
-    // ══════════════════════════════════════════════════════════════════
+        std::fs::create_dir_all(&self.dir)?;
+        // Numbered after any from the same second, so the newest sorts last
+        let prefix = format!("{}.{}-", file_stem(file_name), chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let next = self
+            .list(file_name)
+            .iter()
+            .filter_map(|p| p.file_name()?.to_str()?.strip_prefix(&prefix)?.strip_suffix(".md")?.parse::<u32>().ok())
+            .max()
+            .map_or(0, |n| n + 1);
+        let backup_path = self.dir.join(format!("{}{:02}.md", prefix, next));
+        std::fs::copy(path, &backup_path)?;
+
+        for old in self.list(file_name).into_iter().skip(self.keep.max(1)) {
+            let _ = std::fs::remove_file(old);
+        }
+        Ok(Some(backup_path))
+    }
 
-    let db = Database::open()?;
-    let conversations = db.load_conversations_for_date(&target_date)?;
+    /// Backups of `file_name`, newest first.
+    fn list(&self, file_name: &str) -> Vec<PathBuf> {
+        let prefix = format!("{}.", file_stem(file_name));
+        let mut backups: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".md"))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Timestamps sort lexicographically
+        backups.sort();
+        backups.reverse();
+        backups
+    }
 
-    let has_conversations = !conversations.is_empty();
-    let conversation_text = if has_conversations {
-        let total_sessions = conversations.len();
-        println!(
-            "{}",
-            style(format!("📂 Found {} conversation session(s) in database", total_sessions)).dim()
-        );
+    /// Replace `target` with the contents of `backup`, backing up the current
+    /// version first. The backup is read before pruning can remove it.
+    fn restore(&self, backup: &Path, target: &Path, file_name: &str) -> Result<()> {
+        let content = std::fs::read(backup)?;
+        self.backup(target, file_name)?;
+        std::fs::write(target, content)?;
+        Ok(())
+    }
+}
 
-        let mut combined = String::new();
-        for (title, text) in &conversations {
-            combined.push_str(&format!("### Session: {}\n\n{}\n---\n\n", title, text));
-        }
-        combined
+fn file_stem(file_name: &str) -> &str {
+    file_name.strip_suffix(".md").unwrap_or(file_name)
+}
+
+/// `imp reflect --restore <name>`: pick one of a core file's backups and
+/// restore it. The current version is backed up first, so a restore can
+/// itself be undone.
+pub fn restore(name: &str) -> Result<()> {
+    let home = imp_home()?;
+    let max_backups = Config::load().map(|c| c.reflect.max_backups).unwrap_or(10);
+    let backups = Backups::new(&home, max_backups);
+
+    let wanted = file_stem(name).to_uppercase();
+    let file_name = match CORE_FILES.iter().find(|f| file_stem(f) == wanted) {
+        Some(f) => *f,
+        None => anyhow::bail!(
+            "Unknown file '{}'. Backups exist for: {}",
+            name,
+            CORE_FILES.map(file_stem).join(", ")
+        ),
+    };
+
+    let available = backups.list(file_name);
+    if available.is_empty() {
+        println!("{}", style(format!("No backups of {} found.", file_name)).yellow());
+        return Ok(());
+    }
+
+    let labels: Vec<String> = available
+        .iter()
+        .map(|p| {
+            let size = std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+            format!(
+                "{}  ({} bytes)",
+                p.file_name().and_then(|n| n.to_str()).unwrap_or("?"),
+                size
+            )
+        })
+        .collect();
+
+    let selection = Select::new()
+        .with_prompt(format!("Restore which backup of {}?", file_name))
+        .items(&labels)
+        .default(0)
+        .interact_opt()?;
+
+    let Some(index) = selection else {
+        println!("Cancelled.");
+        return Ok(());
+    };
+
+    backups.restore(&available[index], &home.join(file_name), file_name)?;
+    println!(
+        "{}",
+        style(format!("✅ Restored {} from {}", file_name, labels[index])).green()
+    );
+    Ok(())
+}
+
+pub async fn run(
+    date: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    days: Option<u32>,
+    dry_run: bool,
+    interactive: bool,
+    force: bool,
+) -> Result<()> {
+    let mode = if dry_run {
+        WriteMode::DryRun
+    } else if interactive {
+        WriteMode::Interactive
     } else {
-        println!("{}", style("📂 No conversations found in database for this date").dim());
-        String::new()
+        WriteMode::Write
     };
 
-    // ══════════════════════════════════════════════════════════════════
-    // PHASE 2: SUMMARIZE CONVERSATIONS → DAILY MEMORY FILE
-    // ══════════════════════════════════════════════════════════════════
+    let config = Config::load()?;
+    let mut client = ClaudeClient::new(config.clone())?;
+    let home = imp_home()?;
+    crate::embeddings::Embedder::configure(&config.knowledge);
 
-    let daily_file = home.join("memory").join(format!("{}.md", target_date));
-    let existing_daily_content = if daily_file.exists() {
-        std::fs::read_to_string(&daily_file)?
+    let dates = resolve_dates(date, from, to, days)?;
+    let is_range = dates.len() > 1;
+    let label = if is_range {
+        format!("{}..{}", dates[0], dates[dates.len() - 1])
     } else {
-        String::new()
+        dates[0].clone()
     };
 
-    // If no daily content and no conversations, nothing to reflect on
-    if existing_daily_content.trim().is_empty() && !has_conversations {
-        println!("Nothing to reflect on for {} — no memory file and no conversations.", target_date);
-        return Ok(());
+    if mode == WriteMode::DryRun {
+        println!("{}", style("Dry run — proposed changes are shown as diffs, nothing is written.").yellow());
     }
-
-    // Summarize conversations and rewrite the daily file as a single consolidated document.
-    // The LLM sees both the existing notes and full conversations, producing the definitive
-    // daily record. This is idempotent — running reflect multiple times yields the same result.
-    println!("{}", style("📝 Consolidating daily memory file...").dim());
-    {
-        let summary_prompt = format!(
-            "You are writing the definitive daily memory file for a personal AI agent.\n\n\
-            You have two sources:\n\
-            1. Existing daily notes (may contain auto-generated session markers, previous reflect output, or manual notes)\n\
-            2. Full conversation transcripts from the database\n\n\
-            Produce a single, clean, consolidated markdown document that captures EVERYTHING \
-            important from the day. This REPLACES the entire daily file.\n\n\
-            Structure it as:\n\
-            - `# YYYY-MM-DD` header\n\
-            - Sections for major topics/sessions\n\
-            - Key decisions, accomplishments, technical details\n\
-            - Open threads or follow-ups\n\n\
-            Rules:\n\
-            - Be thorough — this is the only record of the day\n\
-            - Be concise — capture substance, skip noise (token counts, tool call counts, timestamps)\n\
-            - Preserve any important information from the existing notes\n\
-            - Don't invent information not present in the sources\n\n\
-            Existing daily notes:\n---\n{}\n---\n\n\
-            Today's conversations:\n---\n{}\n---\n\n\
-            Write the complete daily memory file now (markdown, no JSON wrapping):",
-            if existing_daily_content.is_empty() { "(none)" } else { &existing_daily_content },
-            if has_conversations { &conversation_text } else { "(no conversations recorded)" }
+    if is_range {
+        println!(
+            "🧠 Reflecting on {} to {} ({} days)...\n",
+            dates[0],
+            dates[dates.len() - 1],
+            dates.len()
         );
+    } else {
+        println!("🧠 Reflecting on {}...\n", label);
+    }
 
-        let messages = vec![Message::text("user", &summary_prompt)];
-        let response = client
-            .send_message_with_options(messages, None, None, false, Some(64_000))
-            .await?;
-        let daily_summary = client.extract_text_content(&response);
+    // ══════════════════════════════════════════════════════════════════
+    // PHASES 1–2: CONSOLIDATE EACH DAY'S CONVERSATIONS INTO ITS MEMORY FILE
+    // ══════════════════════════════════════════════════════════════════
 
-        if let Some(ref usage) = response.usage {
-            println!(
-                "{}",
-                style(format!(
-                    "  daily file: {} in / {} out | stop: {}",
-                    usage.input_tokens, usage.output_tokens,
-                    response.stop_reason.as_deref().unwrap_or("?")
-                ))
-                .dim()
-            );
+    let db = Database::open()?;
+    let mut daily_notes: Vec<(String, String)> = Vec::new();
+    for (i, day) in dates.iter().enumerate() {
+        if is_range {
+            println!("{}", style(format!("📅 {} ({}/{})", day, i + 1, dates.len())).bold());
+        }
+        if let Some(content) = consolidate_day(&mut client, &db, &home, day, mode, force).await? {
+            daily_notes.push((day.clone(), content));
         }
-
-        let memory_dir = home.join("memory");
-        let _ = std::fs::create_dir_all(&memory_dir);
-        std::fs::write(&daily_file, &daily_summary)?;
-        println!("{}", style("  ✅ Daily memory file rewritten").green());
     }
 
-    // Reload daily content (now the consolidated version)
-    let daily_content = if daily_file.exists() {
-        std::fs::read_to_string(&daily_file)?
-    } else {
-        String::new()
-    };
+    if daily_notes.is_empty() {
+        return Ok(());
+    }
 
     // ══════════════════════════════════════════════════════════════════
     // PHASE 3: REFLECT ON FILES (MEMORY.md, USER.md, SOUL.md, etc.)
@@ -213,15 +318,27 @@ Rules:
         user_message.push_str(&format!("## Current PRINCIPLES.md\n\n{}\n\n---\n\n", content));
     }
 
-    user_message.push_str(&format!(
-        "## Today's Notes ({})\n\n{}\n\n---\n\n",
-        target_date, daily_content
-    ));
+    for (day, content) in &daily_notes {
+        let heading = if is_range { "Notes" } else { "Today's Notes" };
+        user_message.push_str(&format!(
+            "## {} ({})\n\n{}\n\n---\n\n",
+            heading, day, content
+        ));
+    }
 
-    user_message.push_str(
-        "Reflect on today's interactions and produce the JSON response. \
-         Include knowledge_entries for anything worth storing in the knowledge graph."
-    );
+    if is_range {
+        user_message.push_str(&format!(
+            "Reflect on the interactions across these {} days — look for threads that span days — \
+             and produce the JSON response. \
+             Include knowledge_entries for anything worth storing in the knowledge graph.",
+            daily_notes.len()
+        ));
+    } else {
+        user_message.push_str(
+            "Reflect on today's interactions and produce the JSON response. \
+             Include knowledge_entries for anything worth storing in the knowledge graph."
+        );
+    }
 
     println!("{}", style("🔍 Reflecting on files and extracting knowledge...").dim());
 
@@ -274,73 +391,40 @@ Rules:
     }
 
     let mut updates = 0;
+    let backups = Backups::new(&home, config.reflect.max_backups);
 
     // Apply file updates
-    if let Some(content) = parsed.get("memory_update").and_then(|v| v.as_str()) {
-        let content = content.trim();
-        if !content.is_empty() {
-            std::fs::write(home.join("MEMORY.md"), content)?;
-            println!("{}", style("  ✅ MEMORY.md updated").green());
-            updates += 1;
-        }
-    }
-
-    if let Some(content) = parsed.get("user_update").and_then(|v| v.as_str()) {
-        let content = content.trim();
-        if !content.is_empty() {
-            std::fs::write(home.join("USER.md"), content)?;
-            println!("{}", style("  ✅ USER.md updated").green());
-            updates += 1;
-        }
-    }
-
-    if let Some(content) = parsed.get("soul_update").and_then(|v| v.as_str()) {
-        let content = content.trim();
-        if !content.is_empty() {
-            std::fs::write(home.join("SOUL.md"), content)?;
-            println!("{}", style("  ✅ SOUL.md updated").green());
-            updates += 1;
-        }
-    }
-
-    if stack_content.is_some() {
-        if let Some(content) = parsed.get("stack_update").and_then(|v| v.as_str()) {
-            let content = content.trim();
-            if !content.is_empty() {
-                std::fs::write(home.join("STACK.md"), content)?;
-                println!("{}", style("  ✅ STACK.md updated").green());
-                updates += 1;
-            }
-        }
-    }
-
-    if arch_content.is_some() {
-        if let Some(content) = parsed.get("architecture_update").and_then(|v| v.as_str()) {
-            let content = content.trim();
-            if !content.is_empty() {
-                std::fs::write(home.join("ARCHITECTURE.md"), content)?;
-                println!("{}", style("  ✅ ARCHITECTURE.md updated").green());
-                updates += 1;
-            }
+    let file_updates = [
+        ("memory_update", "MEMORY.md", true),
+        ("user_update", "USER.md", true),
+        ("soul_update", "SOUL.md", true),
+        ("stack_update", "STACK.md", stack_content.is_some()),
+        ("architecture_update", "ARCHITECTURE.md", arch_content.is_some()),
+        ("principles_update", "PRINCIPLES.md", principles_content.is_some()),
+    ];
+
+    for (field, file_name, allowed) in file_updates {
+        if !allowed {
+            continue;
         }
-    }
-
-    if principles_content.is_some() {
-        if let Some(content) = parsed.get("principles_update").and_then(|v| v.as_str()) {
+        if let Some(content) = parsed.get(field).and_then(|v| v.as_str()) {
             let content = content.trim();
             if !content.is_empty() {
-                std::fs::write(home.join("PRINCIPLES.md"), content)?;
-                println!("{}", style("  ✅ PRINCIPLES.md updated").green());
-                updates += 1;
+                if apply_file_update(&home.join(file_name), file_name, content, mode, Some(&backups))? {
+                    println!("{}", style(format!("  ✅ {} updated", file_name)).green());
+                    updates += 1;
+                }
             }
         }
     }
 
     if updates == 0 {
-        println!(
-            "{}",
-            style("  No file updates needed — nothing new to persist.").dim()
-        );
+        let note = match mode {
+            WriteMode::Write => "  No file updates needed — nothing new to persist.",
+            WriteMode::Interactive => "  No file updates applied.",
+            WriteMode::DryRun => "  No files written (dry run).",
+        };
+        println!("{}", style(note).dim());
     }
 
     // ══════════════════════════════════════════════════════════════════
@@ -361,7 +445,13 @@ Rules:
                 .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
                 .unwrap_or_default();
 
-            let session_id = format!("reflect-{}", target_date);
+            if mode == WriteMode::DryRun {
+                println!("{}", style(format!("  would queue: {}", content)).dim());
+                knowledge_queued += 1;
+                continue;
+            }
+
+            let session_id = format!("reflect-{}", label);
             if let Err(e) = append_to_queue(content, &session_id, entities) {
                 eprintln!("⚠️ Failed to queue knowledge entry: {}", e);
             } else {
@@ -369,7 +459,7 @@ Rules:
             }
         }
 
-        if knowledge_queued > 0 {
+        if knowledge_queued > 0 && mode != WriteMode::DryRun {
             println!(
                 "{}",
                 style(format!("  ✅ Queued {} knowledge entries for graph processing", knowledge_queued)).green()
@@ -377,11 +467,16 @@ Rules:
         }
     }
 
+    if mode == WriteMode::DryRun {
+        println!("\n{}", style("✨ Dry run complete — nothing was written.").bold().green());
+        return Ok(());
+    }
+
     // ══════════════════════════════════════════════════════════════════
     // PHASE 5: PROCESS KNOWLEDGE GRAPH QUEUE
     // ══════════════════════════════════════════════════════════════════
 
-    match process_knowledge_queue(&mut client).await {
+    match process_knowledge_queue(&mut client, &config.knowledge).await {
         Ok(stats) => {
             if stats.entities_added > 0 || stats.relationships_added > 0 || stats.chunks_stored > 0 {
                 println!(
@@ -395,6 +490,26 @@ Rules:
             } else {
                 println!("{}", style("  Knowledge graph: no new entries to process.").dim());
             }
+            if stats.types_coerced > 0 || stats.entities_rejected > 0 {
+                println!(
+                    "{}",
+                    style(format!(
+                        "  Out-of-schema types: {} mapped to an existing type, {} held for review in ~/.imp/knowledge_review.jsonl",
+                        stats.types_coerced, stats.entities_rejected
+                    ))
+                    .yellow()
+                );
+            }
+            if stats.low_confidence_held > 0 {
+                println!(
+                    "{}",
+                    style(format!(
+                        "  Low confidence: {} item(s) held for review in ~/.imp/knowledge_review.jsonl",
+                        stats.low_confidence_held
+                    ))
+                    .yellow()
+                );
+            }
         }
         Err(e) => {
             eprintln!("⚠️ Knowledge graph processing failed: {}", e);
@@ -408,24 +523,27 @@ Rules:
     if config.knowledge.enabled {
         match KnowledgeGraph::open() {
             Ok(kg) => {
-                // Reload daily content (now includes conversation summary)
-                let full_daily = if daily_file.exists() {
-                    std::fs::read_to_string(&daily_file)?
-                } else {
-                    daily_content.clone()
-                };
-
-                let chunks = chunk_text(&full_daily, 400);
+                let kg = kg.with_settings(&config.knowledge);
                 let mut chunks_stored = 0;
 
-                for chunk_txt in &chunks {
-                    if chunk_txt.trim().len() < 50 {
-                        continue;
-                    }
-                    if let Ok(false) = kg.has_similar_chunk(chunk_txt, 0.9) {
-                        if let Ok(chunk_id) = kg.store_chunk(chunk_txt, "daily_note", &target_date) {
-                            link_chunk_to_entities(&kg, &chunk_id, chunk_txt);
-                            chunks_stored += 1;
+                for (day, content) in &daily_notes {
+                    // Reload daily content (now includes conversation summary)
+                    let daily_file = home.join("memory").join(format!("{}.md", day));
+                    let full_daily = if daily_file.exists() {
+                        std::fs::read_to_string(&daily_file)?
+                    } else {
+                        content.clone()
+                    };
+
+                    for chunk_txt in &chunk_text(&full_daily, config.knowledge.chunk_size, config.knowledge.chunk_overlap) {
+                        if chunk_txt.trim().len() < 50 {
+                            continue;
+                        }
+                        if let Ok(stored) = kg.store_chunk(chunk_txt, "daily_note", day) {
+                            if !stored.duplicate {
+                                link_chunk_to_entities(&kg, &stored.id, chunk_txt);
+                                chunks_stored += 1;
+                            }
                         }
                     }
                 }
@@ -438,7 +556,13 @@ Rules:
                 }
 
                 // Backfill missing embeddings
-                match kg.backfill_embeddings() {
+                let backfill = kg.backfill_embeddings(
+                    config.knowledge.embedding_batch_size,
+                    |done, total| {
+                        println!("{}", style(format!("  … embedded {}/{} chunks", done, total)).dim());
+                    },
+                );
+                match backfill {
                     Ok((processed, success)) if processed > 0 => {
                         println!(
                             "{}",
@@ -472,17 +596,279 @@ Rules:
     Ok(())
 }
 
+/// Phases 1–2 for one day: pull that day's conversations from the database
+/// and have the LLM rewrite `memory/<date>.md` as a consolidated record.
+/// Once a day has been reflected on, later runs only read messages added
+/// since and merge them into the existing file; `force` starts over from
+/// every conversation. Returns the consolidated content, or `None` if there
+/// was nothing (new) to reflect on.
+async fn consolidate_day(
+    client: &mut ClaudeClient,
+    db: &Database,
+    home: &Path,
+    target_date: &str,
+    mode: WriteMode,
+    force: bool,
+) -> Result<Option<String>> {
+    // ══════════════════════════════════════════════════════════════════
+    // PHASE 1: PULL CONVERSATIONS FROM DB
+    // ══════════════════════════════════════════════════════════════════
+
+    let daily_file = home.join("memory").join(format!("{}.md", target_date));
+    let existing_daily_content = if daily_file.exists() {
+        std::fs::read_to_string(&daily_file)?
+    } else {
+        String::new()
+    };
+
+    // Without the file there's nothing to merge into, so start over
+    let reflected = if force || existing_daily_content.trim().is_empty() {
+        HashMap::new()
+    } else {
+        db.reflected_sessions(target_date)?
+    };
+    let incremental = !reflected.is_empty();
+    let conversations = db.load_conversations_for_date(target_date, &reflected)?;
+
+    if incremental && conversations.is_empty() {
+        println!(
+            "Already reflected on {} and there are no new conversations since (use --force to redo it).",
+            target_date
+        );
+        return Ok(None);
+    }
+
+    let has_conversations = !conversations.is_empty();
+    let conversation_text = if has_conversations {
+        let total_sessions = conversations.len();
+        let found = if incremental {
+            format!("📂 Found new messages in {} session(s) since the last reflect", total_sessions)
+        } else {
+            format!("📂 Found {} conversation session(s) in database", total_sessions)
+        };
+        println!("{}", style(found).dim());
+
+        let mut combined = String::new();
+        for conversation in &conversations {
+            let continued = if conversation.continued { " (continued)" } else { "" };
+            combined.push_str(&format!(
+                "### Session: {}{}\n\n{}\n---\n\n",
+                conversation.title, continued, conversation.text
+            ));
+        }
+        combined
+    } else {
+        println!("{}", style("📂 No conversations found in database for this date").dim());
+        String::new()
+    };
+
+    // ══════════════════════════════════════════════════════════════════
+    // PHASE 2: SUMMARIZE CONVERSATIONS → DAILY MEMORY FILE
+    // ══════════════════════════════════════════════════════════════════
+
+    // If no daily content and no conversations, nothing to reflect on
+    if existing_daily_content.trim().is_empty() && !has_conversations {
+        println!("Nothing to reflect on for {} — no memory file and no conversations.", target_date);
+        return Ok(None);
+    }
+
+    // Summarize conversations and rewrite the daily file as a single consolidated document.
+    // The first time, the LLM sees the existing notes and full conversations and produces the
+    // definitive daily record. After that it only sees what's new, and merges it in rather than
+    // regenerating, so re-running reflect doesn't churn the file.
+    println!("{}", style("📝 Consolidating daily memory file...").dim());
+    let daily_content = {
+        let summary_prompt = if incremental {
+            format!(
+                "You are updating the daily memory file for a personal AI agent.\n\n\
+                The existing file already covers the day's earlier conversations. Below are only the \
+                conversations (or parts of conversations, marked \"continued\") that happened since it was written.\n\n\
+                Merge the new material into the existing file and return the complete updated file:\n\
+                - Keep the existing content and structure; don't rephrase or reorder what's already there\n\
+                - Add new sections, or extend existing ones when a conversation continues a topic\n\
+                - Update open threads or follow-ups that the new conversations resolved\n\
+                - Be concise — capture substance, skip noise (token counts, tool call counts, timestamps)\n\
+                - Don't invent information not present in the sources\n\n\
+                Existing daily file:\n---\n{}\n---\n\n\
+                New conversations:\n---\n{}\n---\n\n\
+                Write the complete updated daily memory file now (markdown, no JSON wrapping):",
+                existing_daily_content, conversation_text
+            )
+        } else {
+            format!(
+                "You are writing the definitive daily memory file for a personal AI agent.\n\n\
+                You have two sources:\n\
+                1. Existing daily notes (may contain auto-generated session markers, previous reflect output, or manual notes)\n\
+                2. Full conversation transcripts from the database\n\n\
+                Produce a single, clean, consolidated markdown document that captures EVERYTHING \
+                important from the day. This REPLACES the entire daily file.\n\n\
+                Structure it as:\n\
+                - `# YYYY-MM-DD` header\n\
+                - Sections for major topics/sessions\n\
+                - Key decisions, accomplishments, technical details\n\
+                - Open threads or follow-ups\n\n\
+                Rules:\n\
+                - Be thorough — this is the only record of the day\n\
+                - Be concise — capture substance, skip noise (token counts, tool call counts, timestamps)\n\
+                - Preserve any important information from the existing notes\n\
+                - Don't invent information not present in the sources\n\n\
+                Existing daily notes:\n---\n{}\n---\n\n\
+                Today's conversations:\n---\n{}\n---\n\n\
+                Write the complete daily memory file now (markdown, no JSON wrapping):",
+                if existing_daily_content.is_empty() { "(none)" } else { &existing_daily_content },
+                if has_conversations { &conversation_text } else { "(no conversations recorded)" }
+            )
+        };
+
+        let messages = vec![Message::text("user", &summary_prompt)];
+        let response = client
+            .send_message_with_options(messages, None, None, false, Some(64_000))
+            .await?;
+        let daily_summary = client.extract_text_content(&response);
+
+        if let Some(ref usage) = response.usage {
+            println!(
+                "{}",
+                style(format!(
+                    "  daily file: {} in / {} out | stop: {}",
+                    usage.input_tokens, usage.output_tokens,
+                    response.stop_reason.as_deref().unwrap_or("?")
+                ))
+                .dim()
+            );
+        }
+
+        let daily_name = format!("memory/{}.md", target_date);
+        if apply_file_update(&daily_file, &daily_name, &daily_summary, mode, None)? {
+            println!("{}", style("  ✅ Daily memory file rewritten").green());
+        }
+        // Processed, even if the file didn't change; a dry run only previews
+        if mode != WriteMode::DryRun {
+            db.mark_reflected(target_date, &conversations)?;
+        }
+
+        // Later phases work from the consolidated version even when it wasn't written
+        daily_summary
+    };
+
+    Ok(Some(daily_content))
+}
+
+/// Write `content` to `path` according to `mode`: straight away, never (dry
+/// run — just show the diff), or after showing the diff and asking. With
+/// `backups`, the current file is backed up before it's overwritten.
+/// Returns whether the file was written.
+fn apply_file_update(
+    path: &Path,
+    name: &str,
+    content: &str,
+    mode: WriteMode,
+    backups: Option<&Backups>,
+) -> Result<bool> {
+    if mode != WriteMode::Write {
+        let current = std::fs::read_to_string(path).unwrap_or_default();
+        if current.trim() == content.trim() {
+            println!("{}", style(format!("  {}: no changes", name)).dim());
+            return Ok(false);
+        }
+        print_diff(name, &current, content);
+    }
+
+    let write = match mode {
+        WriteMode::Write => true,
+        WriteMode::DryRun => false,
+        WriteMode::Interactive => Confirm::new()
+            .with_prompt(format!("Apply update to {}?", name))
+            .default(true)
+            .interact()?,
+    };
+
+    if write {
+        if let Some(backups) = backups {
+            backups.backup(path, name)?;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    } else if mode == WriteMode::Interactive {
+        println!("{}", style(format!("  Skipped {}", name)).dim());
+    }
+    Ok(write)
+}
+
+/// Print a coloured unified diff of a proposed file rewrite.
+fn print_diff(name: &str, old: &str, new: &str) {
+    let diff = TextDiff::from_lines(old, new);
+    println!("\n{}", style(format!("--- a/{}\n+++ b/{}", name, name)).bold());
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        println!("{}", style(hunk.header()).cyan());
+        for change in hunk.iter_changes() {
+            let line = format!("{}{}", change.tag(), change.value().trim_end_matches('\n'));
+            match change.tag() {
+                ChangeTag::Delete => println!("{}", style(line).red()),
+                ChangeTag::Insert => println!("{}", style(line).green()),
+                ChangeTag::Equal => println!("{}", style(line).dim()),
+            }
+        }
+    }
+    println!();
+}
+
+/// Work out which days to reflect on. `--days N` means the last N days ending
+/// today; `--from`/`--to` is an inclusive range (`--to` defaults to today);
+/// otherwise the single `--date` (default today).
+fn resolve_dates(
+    date: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    days: Option<u32>,
+) -> Result<Vec<String>> {
+    use chrono::{Duration, Local, NaiveDate};
+
+    let parse = |s: &str| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("Invalid date '{}' (expected YYYY-MM-DD)", s))
+    };
+    let today = Local::now().date_naive();
+
+    let (start, end) = match (days, from) {
+        (Some(0), _) => anyhow::bail!("--days must be at least 1"),
+        (Some(n), _) => (today - Duration::days(n as i64 - 1), today),
+        (None, Some(from)) => {
+            let end = match to {
+                Some(to) => parse(&to)?,
+                None => today,
+            };
+            (parse(&from)?, end)
+        }
+        (None, None) => {
+            let day = match date {
+                Some(date) => parse(&date)?,
+                None => today,
+            };
+            (day, day)
+        }
+    };
+
+    if start > end {
+        anyhow::bail!("--from {} is after --to {}", start, end);
+    }
+
+    Ok(start
+        .iter_days()
+        .take_while(|day| *day <= end)
+        .map(|day| day.format("%Y-%m-%d").to_string())
+        .collect())
+}
+
 /// Process the knowledge queue using LLM extraction.
-async fn process_knowledge_queue(client: &mut ClaudeClient) -> Result<ExtractionStats> {
-    let queue_entries = read_queue()?;
+async fn process_knowledge_queue(client: &mut ClaudeClient, settings: &KnowledgeConfig) -> Result<ExtractionStats> {
+    // The same fact is often flagged in several turns — only extract it once
+    let queue_entries = dedup_queue(read_queue()?);
 
     if queue_entries.is_empty() {
-        return Ok(ExtractionStats {
-            entities_added: 0,
-            relationships_added: 0,
-            chunks_stored: 0,
-            new_types_added: 0,
-        });
+        return Ok(ExtractionStats::default());
     }
 
     println!(
@@ -490,40 +876,44 @@ async fn process_knowledge_queue(client: &mut ClaudeClient) -> Result<Extraction
         style(format!("  Processing {} knowledge queue entries...", queue_entries.len())).dim()
     );
 
-    let kg = KnowledgeGraph::open()?;
+    let kg = KnowledgeGraph::open()?.with_settings(settings);
     let schema = kg.get_schema()?;
-    let existing_entities = get_entity_names(&kg);
 
-    let mut total_stats = ExtractionStats {
-        entities_added: 0,
-        relationships_added: 0,
-        chunks_stored: 0,
-        new_types_added: 0,
-    };
+    let mut total_stats = ExtractionStats::default();
 
-    // Batch queue entries to avoid excessive LLM calls — combine related entries
-    let batched_content = queue_entries
-        .iter()
-        .map(|e| e.content.clone())
-        .collect::<Vec<_>>()
-        .join("\n\n---\n\n");
-
-    match extract_knowledge_llm(&batched_content, &schema, &existing_entities, client).await {
-        Ok(extraction_result) => {
-            match process_extraction(&kg, &extraction_result) {
-                Ok(stats) => {
-                    total_stats.entities_added += stats.entities_added;
-                    total_stats.relationships_added += stats.relationships_added;
-                    total_stats.chunks_stored += stats.chunks_stored;
-                    total_stats.new_types_added += stats.new_types_added;
-                }
-                Err(e) => {
-                    eprintln!("⚠️ Failed to process extraction: {}", e);
+    // One extraction per session, so everything stored can be traced back to
+    // the conversation it came from. Within a session, entries are batched.
+    let mut sessions: Vec<(String, Vec<&QueueEntry>)> = Vec::new();
+    for entry in &queue_entries {
+        match sessions.iter_mut().find(|(id, _)| *id == entry.session_id) {
+            Some((_, entries)) => entries.push(entry),
+            None => sessions.push((entry.session_id.clone(), vec![entry])),
+        }
+    }
+
+    for (session_id, entries) in sessions {
+        let started = entries.iter().map(|e| e.timestamp).fold(f64::INFINITY, f64::min);
+        let source = provenance(&session_id, started);
+        let batched_content = entries
+            .iter()
+            .map(|e| e.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n");
+        // Refreshed per session so entities from earlier sessions aren't duplicated
+        let existing_entities = get_entity_names(&kg);
+
+        match extract_knowledge_llm(&batched_content, &schema, &existing_entities, client).await {
+            Ok(extraction_result) => {
+                match process_extraction(&kg, &extraction_result, &source, settings) {
+                    Ok(stats) => total_stats.add(&stats),
+                    Err(e) => {
+                        eprintln!("⚠️ Failed to process extraction: {}", e);
+                    }
                 }
             }
-        }
-        Err(e) => {
-            eprintln!("⚠️ Failed to extract knowledge: {}", e);
+            Err(e) => {
+                eprintln!("⚠️ Failed to extract knowledge: {}", e);
+            }
         }
     }
 
@@ -534,7 +924,7 @@ async fn process_knowledge_queue(client: &mut ClaudeClient) -> Result<Extraction
 }
 
 /// Get all entity names from the knowledge graph (for dedup context in LLM prompts).
-fn get_entity_names(kg: &KnowledgeGraph) -> Vec<String> {
+pub(crate) fn get_entity_names(kg: &KnowledgeGraph) -> Vec<String> {
     let params = std::collections::BTreeMap::new();
     match kg.run_query(
         "?[name, entity_type] := *entity{name, entity_type}",
@@ -561,35 +951,6 @@ fn get_entity_names(kg: &KnowledgeGraph) -> Vec<String> {
     }
 }
 
-/// Split text into chunks at paragraph boundaries, targeting ~max_chars per chunk.
-fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut current = String::new();
-
-    for paragraph in text.split("\n\n") {
-        let trimmed = paragraph.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-
-        if current.len() + trimmed.len() + 2 > max_chars && !current.is_empty() {
-            chunks.push(current.clone());
-            current.clear();
-        }
-
-        if !current.is_empty() {
-            current.push_str("\n\n");
-        }
-        current.push_str(trimmed);
-    }
-
-    if !current.is_empty() {
-        chunks.push(current);
-    }
-
-    chunks
-}
-
 /// Link a chunk to any entities whose names appear in the chunk text.
 fn link_chunk_to_entities(kg: &KnowledgeGraph, chunk_id: &str, text: &str) {
     let chunk_lower = text.to_lowercase();
@@ -643,3 +1004,33 @@ fn extract_json_block(text: &str) -> &str {
 
     trimmed
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_oldest_backup_at_the_limit() {
+        let home = std::env::temp_dir().join(format!("imp-reflect-backups-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        let target = home.join("MEMORY.md");
+        let backups = Backups::new(&home, 2);
+
+        for version in ["one", "two", "three"] {
+            std::fs::write(&target, version).unwrap();
+            backups.backup(&target, "MEMORY.md").unwrap();
+        }
+        // Same-second backups don't overwrite each other
+        let available = backups.list("MEMORY.md");
+        assert_eq!(available.len(), 2);
+        assert_eq!(std::fs::read_to_string(&available[1]).unwrap(), "two");
+
+        std::fs::write(&target, "current").unwrap();
+        backups.restore(&available[1], &target, "MEMORY.md").unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "two");
+        assert_eq!(std::fs::read_to_string(&backups.list("MEMORY.md")[0]).unwrap(), "current");
+
+        let _ = std::fs::remove_dir_all(&home);
+    }
+}
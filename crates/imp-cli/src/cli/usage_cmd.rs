@@ -0,0 +1,143 @@
+use crate::db::{Database, UsageRecord};
+use crate::error::{ImpError, Result};
+use crate::usage::ModelUsage;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use console::style;
+use std::collections::{BTreeMap, HashSet};
+
+/// Width of the longest bar in the daily spend chart.
+const BAR_WIDTH: usize = 30;
+
+/// `imp usage [--since DATE | --today] [--project NAME]`
+pub fn run(since: Option<String>, today: bool, project: Option<String>) -> Result<()> {
+    let since_date = match (since, today) {
+        (_, true) => Some(Local::now().date_naive()),
+        (Some(s), false) => Some(NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(|_| {
+            ImpError::Config(format!("Invalid --since date '{}', expected YYYY-MM-DD", s))
+        })?),
+        (None, false) => None,
+    };
+
+    let db = Database::open()?;
+    let records = db.load_usage(since_date.map(start_of_day).as_deref(), project.as_deref())?;
+
+    let mut heading = match since_date {
+        Some(d) => format!("Usage since {}", d.format("%Y-%m-%d")),
+        None => "Usage (all time)".to_string(),
+    };
+    if let Some(ref p) = project {
+        heading.push_str(&format!(" · project {}", p));
+    }
+    println!("{}", style(format!("💰 {}", heading)).bold());
+
+    if records.is_empty() {
+        println!("{}", style("No usage recorded yet.").dim());
+        return Ok(());
+    }
+
+    // Totals
+    let totals = sum_usage(records.iter().map(|r| &r.usage));
+    let sessions: HashSet<&str> = records.iter().map(|r| r.session_id.as_str()).collect();
+    println!();
+    println!("  Sessions: {} · Requests: {}", sessions.len(), totals.requests);
+    println!(
+        "  Tokens:   {} (in: {}, out: {}, cache: +{}w/{}r)",
+        totals.total_tokens(),
+        totals.input_tokens,
+        totals.output_tokens,
+        totals.cache_creation_tokens,
+        totals.cache_read_tokens
+    );
+    println!("  Cost:     {}", format_cost(totals.cost));
+
+    // Per-model breakdown, most expensive first
+    let mut by_model: BTreeMap<&str, Vec<&ModelUsage>> = BTreeMap::new();
+    for r in &records {
+        by_model.entry(r.usage.model.as_str()).or_default().push(&r.usage);
+    }
+    let mut models: Vec<ModelUsage> = by_model
+        .into_iter()
+        .map(|(model, usages)| ModelUsage { model: model.to_string(), ..sum_usage(usages) })
+        .collect();
+    models.sort_by(|a, b| b.cost.unwrap_or(0.0).total_cmp(&a.cost.unwrap_or(0.0)));
+
+    println!("\n{}", style("By model:").bold());
+    for m in &models {
+        println!(
+            "  {:<30} {:>12} tokens  {:>5} requests  {}",
+            m.model,
+            m.total_tokens(),
+            m.requests,
+            format_cost(m.cost)
+        );
+    }
+
+    // Daily spend for the last week (respecting --project, not --since)
+    let week_start = Local::now().date_naive() - Duration::days(6);
+    let week = db.load_usage(Some(&start_of_day(week_start)), project.as_deref())?;
+    println!("\n{}", style("Last 7 days:").bold());
+    print_daily_chart(&week, week_start);
+
+    Ok(())
+}
+
+/// Local midnight of `date`, as an RFC 3339 UTC timestamp comparable with `recorded_at`.
+fn start_of_day(date: NaiveDate) -> String {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+        .to_rfc3339()
+}
+
+/// Add up usage rows. The cost is `None` only if none of the rows were priced.
+fn sum_usage<'a>(usages: impl IntoIterator<Item = &'a ModelUsage>) -> ModelUsage {
+    let mut total = ModelUsage::default();
+    for u in usages {
+        total.input_tokens += u.input_tokens;
+        total.output_tokens += u.output_tokens;
+        total.cache_creation_tokens += u.cache_creation_tokens;
+        total.cache_read_tokens += u.cache_read_tokens;
+        total.requests += u.requests;
+        if let Some(cost) = u.cost {
+            *total.cost.get_or_insert(0.0) += cost;
+        }
+    }
+    total
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(c) => format!("${:.4}", c),
+        None => "(no pricing)".to_string(),
+    }
+}
+
+/// One row per day from `start` to today with a bar proportional to spend.
+fn print_daily_chart(records: &[UsageRecord], start: NaiveDate) {
+    let mut daily = [0.0f64; 7];
+    for r in records {
+        let Ok(at) = DateTime::parse_from_rfc3339(&r.recorded_at) else {
+            continue;
+        };
+        let day = (at.with_timezone(&Local).date_naive() - start).num_days();
+        if (0..7).contains(&day) {
+            daily[day as usize] += r.usage.cost.unwrap_or(0.0);
+        }
+    }
+
+    let max = daily.iter().cloned().fold(0.0, f64::max);
+    for (i, cost) in daily.iter().enumerate() {
+        let date = start + Duration::days(i as i64);
+        let width = if max > 0.0 { (cost / max * BAR_WIDTH as f64).round() as usize } else { 0 };
+        println!(
+            "  {}  {}{}  ${:.2}",
+            date.format("%a %m-%d"),
+            style("█".repeat(width)).cyan(),
+            " ".repeat(BAR_WIDTH - width),
+            cost
+        );
+    }
+}
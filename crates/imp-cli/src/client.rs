@@ -1,15 +1,70 @@
+use crate::agent::SharedPrinter;
 use crate::config::{AuthMethod, Config};
 use crate::error::{ImpError, Result};
+use crate::highlight::MarkdownStream;
+use crate::interrupt::Interrupt;
+use crate::redact::{self, redact};
 use futures::stream::StreamExt;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Tokens of `max_tokens` kept for the response when thinking is on.
+const THINKING_HEADROOM_TOKENS: u32 = 4096;
+/// Smallest thinking budget the API accepts.
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+/// First system block for OAuth tokens, which require this identity.
+const OAUTH_IDENTITY: &str = "You are Claude Code, Anthropic's official CLI for Claude.";
+
+/// A system prompt split at its cache breakpoint: `stable` text that stays
+/// the same from turn to turn, and `volatile` text that may not (pinned
+/// files, git status). Only the stable part is marked for caching, so a
+/// change in the volatile part doesn't throw the cached prefix away.
+#[derive(Debug, Clone, Default)]
+pub struct SystemPrompt {
+    pub stable: String,
+    pub volatile: String,
+}
+
+impl From<String> for SystemPrompt {
+    fn from(stable: String) -> Self {
+        Self { stable, volatile: String::new() }
+    }
+}
+
+/// The request's `system` blocks, with the cache breakpoint after the
+/// stable prefix when `cache` is on.
+fn system_blocks(system: Option<SystemPrompt>, oauth: bool, cache: bool) -> Vec<Value> {
+    let system = system.unwrap_or_default();
+    let mut blocks = Vec::new();
+    if oauth {
+        blocks.push(json!({ "type": "text", "text": OAUTH_IDENTITY }));
+    }
+    if !system.stable.is_empty() {
+        blocks.push(json!({ "type": "text", "text": system.stable }));
+    }
+    if cache {
+        if let Some(Value::Object(last)) = blocks.last_mut() {
+            last.insert("cache_control".to_string(), json!({ "type": "ephemeral" }));
+        }
+    }
+    if !system.volatile.is_empty() {
+        blocks.push(json!({ "type": "text", "text": system.volatile }));
+    }
+    blocks
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: Value,
+    /// Kept verbatim through compaction (`/keep`, or an instruction like
+    /// "never touch the auth module"). Stored in the database, never sent.
+    #[serde(skip)]
+    pub keep: bool,
 }
 
 impl Message {
@@ -18,6 +73,7 @@ impl Message {
         Self {
             role: role.to_string(),
             content: Value::String(text.to_string()),
+            keep: false,
         }
     }
 
@@ -26,6 +82,7 @@ impl Message {
         Self {
             role: role.to_string(),
             content,
+            keep: false,
         }
     }
 
@@ -49,6 +106,7 @@ impl Message {
         Self {
             role: "user".to_string(),
             content: Value::Array(content_blocks),
+            keep: false,
         }
     }
 
@@ -74,6 +132,85 @@ impl Message {
     }
 }
 
+/// Result recorded for a tool call that never got one.
+const MISSING_TOOL_RESULT: &str = "Tool execution was interrupted before it returned a result.";
+
+/// Make a stored history API-valid: every `tool_use` needs a `tool_result`
+/// in the next message, and every `tool_result` a `tool_use` in the message
+/// before it. Missing results are added as errors; stray ones are dropped.
+/// Returns a description of each repair.
+pub fn repair_history(messages: &mut Vec<Message>) -> Vec<String> {
+    let mut repairs = Vec::new();
+    let mut i = 0;
+    while i < messages.len() {
+        if messages[i].role == "user" {
+            let expected = match i.checked_sub(1).map(|p| &messages[p]) {
+                Some(prev) if prev.role == "assistant" => block_ids(&prev.content, "tool_use", "id"),
+                _ => Vec::new(),
+            };
+            if let Value::Array(blocks) = &mut messages[i].content {
+                let before = blocks.len();
+                blocks.retain(|block| {
+                    block.get("type").and_then(|t| t.as_str()) != Some("tool_result")
+                        || block
+                            .get("tool_use_id")
+                            .and_then(|id| id.as_str())
+                            .is_some_and(|id| expected.iter().any(|e| e == id))
+                });
+                let dropped = before - blocks.len();
+                if dropped > 0 {
+                    repairs.push(format!("dropped {} tool result(s) with no matching tool call", dropped));
+                }
+                if blocks.is_empty() {
+                    messages.remove(i);
+                    continue;
+                }
+            }
+        }
+
+        if messages[i].role == "assistant" {
+            let answered = match messages.get(i + 1) {
+                Some(next) if next.role == "user" => block_ids(&next.content, "tool_result", "tool_use_id"),
+                _ => Vec::new(),
+            };
+            let missing: Vec<Value> = block_ids(&messages[i].content, "tool_use", "id")
+                .into_iter()
+                .filter(|id| !answered.contains(id))
+                .map(|id| json!({ "type": "tool_result", "tool_use_id": id, "content": MISSING_TOOL_RESULT, "is_error": true }))
+                .collect();
+            if !missing.is_empty() {
+                repairs.push(format!("added results for {} tool call(s) that never completed", missing.len()));
+                match messages.get_mut(i + 1) {
+                    // Results go first, ahead of anything the user wrote
+                    Some(next) if next.role == "user" => {
+                        let rest = match std::mem::take(&mut next.content) {
+                            Value::Array(blocks) => blocks,
+                            Value::String(text) if !text.is_empty() => vec![json!({ "type": "text", "text": text })],
+                            _ => Vec::new(),
+                        };
+                        next.content = Value::Array(missing.into_iter().chain(rest).collect());
+                    }
+                    _ => messages.insert(i + 1, Message::with_content("user", Value::Array(missing))),
+                }
+            }
+        }
+        i += 1;
+    }
+    repairs
+}
+
+/// The `key` of each `block_type` block in `content`.
+fn block_ids(content: &Value, block_type: &str, key: &str) -> Vec<String> {
+    let Value::Array(blocks) = content else {
+        return Vec::new();
+    };
+    blocks
+        .iter()
+        .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some(block_type))
+        .filter_map(|block| block.get(key).and_then(|id| id.as_str()).map(String::from))
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
@@ -97,6 +234,39 @@ pub struct AnthropicResponse {
     pub usage: Option<Usage>,
 }
 
+impl AnthropicResponse {
+    /// The reply's text blocks, joined.
+    pub fn text_content(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } => Some(ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    input: input.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Raw content blocks (preserves tool_use and thinking blocks)
+    pub fn content_blocks(&self) -> Vec<Value> {
+        self.content.iter().map(ContentBlock::to_json).collect()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum ContentBlock {
@@ -110,6 +280,42 @@ enum ContentBlock {
     },
     #[serde(rename = "thinking")]
     Thinking { thinking: String, signature: Option<String> },
+    /// Thinking the API has encrypted; `data` must be sent back untouched.
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+}
+
+impl ContentBlock {
+    /// The block as the API expects it back in an assistant message. Thinking
+    /// blocks keep their signature exactly, or the API rejects the conversation.
+    fn to_json(&self) -> Value {
+        match self {
+            ContentBlock::Text { text } => json!({
+                "type": "text",
+                "text": text
+            }),
+            ContentBlock::ToolUse { id, name, input } => json!({
+                "type": "tool_use",
+                "id": id,
+                "name": name,
+                "input": input
+            }),
+            ContentBlock::Thinking { thinking, signature } => {
+                let mut block = json!({
+                    "type": "thinking",
+                    "thinking": thinking
+                });
+                if let Some(sig) = signature {
+                    block["signature"] = json!(sig);
+                }
+                block
+            }
+            ContentBlock::RedactedThinking { data } => json!({
+                "type": "redacted_thinking",
+                "data": data
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -147,34 +353,238 @@ pub struct ClaudeClient {
     model: String,
     base_url: String,
     config: Config,
+    /// Where streamed text goes; stdout when unset.
+    printer: Option<SharedPrinter>,
+    /// Streamed text after the last newline, held back from the printer,
+    /// which ends everything it prints with a newline.
+    printer_line: std::sync::Mutex<String>,
+    /// Theme to render streamed text as markdown with; raw text when unset.
+    stream_markdown_theme: Option<String>,
+    /// Cancels an in-flight request when triggered.
+    interrupt: Option<Arc<Interrupt>>,
 }
 
 impl ClaudeClient {
     pub fn new(config: Config) -> Result<Self> {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(300))
+        // No total timeout on the client: streams can run as long as they keep
+        // sending. Limits are applied per request in `send_request`.
+        let client = crate::http::client_builder()?
+            .connect_timeout(Duration::from_secs(config.llm.connect_timeout_secs))
             .build()
-            .unwrap();
+            .map_err(|e| ImpError::Config(format!("Failed to set up HTTP client: {}", e)))?;
 
         let base_url = config.llm.base_url.clone()
             .unwrap_or_else(|| "https://api.anthropic.com".to_string())
             .trim_end_matches('/')
             .to_string();
 
+        // Make sure credentials never show up in logs or error messages
+        if let Some(key) = config.api_key() {
+            redact::register_secret(key);
+        }
+        if let Some(oauth) = config.oauth_config() {
+            redact::register_secret(&oauth.access_token);
+            redact::register_secret(&oauth.refresh_token);
+        }
+
         Ok(Self {
             client,
             model: config.llm.model.clone(),
             base_url,
             config,
+            printer: None,
+            printer_line: Default::default(),
+            stream_markdown_theme: None,
+            interrupt: None,
         })
     }
 
-    /// Ensure we have a valid token (setup-tokens are long-lived, no refresh needed)
+    /// Stream response text through a readline-safe printer instead of stdout.
+    pub fn set_printer(&mut self, printer: SharedPrinter) {
+        self.printer = Some(printer);
+    }
+
+    /// Render streamed text as markdown with `theme`, a block at a time, or
+    /// print it raw as it arrives (`None`).
+    pub fn set_stream_markdown(&mut self, theme: Option<String>) {
+        self.stream_markdown_theme = theme;
+    }
+
+    /// Write a piece of streamed output as-is, without adding a newline.
+    /// Through the printer, only whole lines go out; the rest waits in
+    /// `printer_line` for more text or `flush_stream_line`. `to_stderr`
+    /// only applies when there is no printer.
+    fn stream_print(&self, text: &str, to_stderr: bool) {
+        if let Some(ref p) = self.printer {
+            if let (Ok(mut line), Ok(mut printer)) = (self.printer_line.lock(), p.lock()) {
+                line.push_str(text);
+                if let Some(end) = line.rfind('\n') {
+                    let rest = line.split_off(end + 1);
+                    let _ = printer.print(std::mem::replace(&mut *line, rest));
+                }
+                return;
+            }
+        }
+        if to_stderr {
+            eprint!("{}", crate::output::clean(text));
+        } else {
+            print!("{}", text);
+            let _ = std::io::stdout().flush();
+        }
+    }
+
+    /// Print any partial line `stream_print` is holding back.
+    fn flush_stream_line(&self) {
+        if let (Some(p), Ok(mut line)) = (&self.printer, self.printer_line.lock()) {
+            if !line.is_empty() {
+                if let Ok(mut printer) = p.lock() {
+                    let _ = printer.print(std::mem::take(&mut *line));
+                }
+            }
+        }
+    }
+
+    /// Switch the model used for subsequent requests.
+    pub fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+    }
+
+    /// Sample with `temperature` from now on, whatever `[llm]` says.
+    pub fn set_sampling(&mut self, temperature: f64) {
+        self.config.llm.temperature = Some(temperature);
+    }
+
+    /// Change the thinking budget for subsequent requests. It must leave
+    /// `THINKING_HEADROOM_TOKENS` of `max_tokens` for the response itself.
+    pub fn set_thinking_budget(&mut self, budget: u32) -> Result<()> {
+        let max_budget = self.config.llm.max_tokens.saturating_sub(THINKING_HEADROOM_TOKENS);
+        if budget < MIN_THINKING_BUDGET {
+            return Err(ImpError::Config(format!(
+                "Thinking budget must be at least {} tokens",
+                MIN_THINKING_BUDGET
+            )));
+        }
+        if budget > max_budget {
+            return Err(ImpError::Config(format!(
+                "Thinking budget must be at most {} tokens (max_tokens {} minus {} for the response). Raise [llm] max_tokens to allow more.",
+                max_budget, self.config.llm.max_tokens, THINKING_HEADROOM_TOKENS
+            )));
+        }
+        self.config.thinking.budget_tokens = budget;
+        Ok(())
+    }
+
+    /// Refresh the OAuth access token ahead of time if it has expired.
+    /// API-key auth needs no refresh.
     async fn ensure_valid_token(&mut self) -> Result<()> {
-        // Setup-tokens from `claude setup-token` are long-lived and don't need refresh
+        if self.config.auth_method() != &AuthMethod::OAuth {
+            return Ok(());
+        }
+        match self.config.oauth_config() {
+            Some(oauth) if oauth.is_expired() => self.refresh_token().await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Abandon requests in flight when `interrupt` is triggered; they then
+    /// fail with an "interrupted" error.
+    pub fn set_interrupt(&mut self, interrupt: Arc<Interrupt>) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Exchange the refresh token for a new access token and use it from now on.
+    async fn refresh_token(&mut self) -> Result<()> {
+        let stale = self
+            .config
+            .oauth_config()
+            .map(|o| o.access_token.clone())
+            .unwrap_or_default();
+        let fresh = crate::oauth::refresh(
+            &self.client,
+            &stale,
+            Duration::from_secs(self.config.llm.request_timeout_secs),
+        )
+        .await
+        .map_err(|e| match e {
+            ImpError::Http(error) => self.request_error(error, false),
+            other => other,
+        })?;
+        self.config.auth.oauth = Some(fresh);
         Ok(())
     }
 
+    /// POST a request body to the Messages API. If an OAuth token is rejected
+    /// with 401, refresh it and retry once.
+    async fn post_messages(&mut self, request_body: &Value, stream: bool) -> Result<reqwest::Response> {
+        let url = format!("{}/v1/messages", self.base_url);
+        let response = self.send_request(&url, request_body, stream).await?;
+
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED
+            || self.config.auth_method() != &AuthMethod::OAuth
+        {
+            return Ok(response);
+        }
+
+        self.refresh_token().await?;
+        self.send_request(&url, request_body, stream).await
+    }
+
+    /// Send one request. A non-streaming request must finish within
+    /// `request_timeout_secs`; a streaming one only has to start within
+    /// `stream_idle_timeout_secs` (the same limit then applies between chunks).
+    async fn send_request(&self, url: &str, request_body: &Value, stream: bool) -> Result<reqwest::Response> {
+        let request = self
+            .client
+            .post(url)
+            .headers(self.prepare_auth_headers()?)
+            .json(request_body);
+
+        if !stream {
+            return request
+                .timeout(Duration::from_secs(self.config.llm.request_timeout_secs))
+                .send()
+                .await
+                .map_err(|e| self.request_error(e, false));
+        }
+
+        match tokio::time::timeout(self.stream_idle_timeout(), request.send()).await {
+            Ok(sent) => sent.map_err(|e| self.request_error(e, true)),
+            Err(_) => Err(self.stream_stalled()),
+        }
+    }
+
+    fn stream_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.llm.stream_idle_timeout_secs)
+    }
+
+    fn stream_stalled(&self) -> ImpError {
+        ImpError::Timeout(format!(
+            "the API sent nothing for {}s. Try again, or raise [llm] stream_idle_timeout_secs.",
+            self.config.llm.stream_idle_timeout_secs
+        ))
+    }
+
+    /// `ImpError::Timeout` naming the `[llm]` setting that ran out, for
+    /// timeouts; `ImpError::Http` for anything else.
+    fn request_error(&self, error: reqwest::Error, stream: bool) -> ImpError {
+        let llm = &self.config.llm;
+        if !error.is_timeout() {
+            ImpError::Http(error)
+        } else if error.is_connect() {
+            ImpError::Timeout(format!(
+                "couldn't connect to the API within {}s. Check your network, or raise [llm] connect_timeout_secs.",
+                llm.connect_timeout_secs
+            ))
+        } else if stream {
+            self.stream_stalled()
+        } else {
+            ImpError::Timeout(format!(
+                "the API didn't respond within {}s. Try again, or raise [llm] request_timeout_secs.",
+                llm.request_timeout_secs
+            ))
+        }
+    }
+
     /// Prepare authorization headers based on the current auth method
     fn prepare_auth_headers(&self) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
@@ -238,15 +648,20 @@ impl ClaudeClient {
         stream: bool,
         max_tokens_override: Option<u32>,
     ) -> Result<AnthropicResponse> {
+        let system_prompt = system_prompt.map(SystemPrompt::from);
         self.send_message_inner(messages, system_prompt, tools, stream, max_tokens_override, None).await
     }
 
     /// Full-control message send with all overrides.
     /// `thinking_override`: Some(true) = force on, Some(false) = force off, None = use config.
+    /// With thinking on, `max_tokens` is raised to leave room above the thinking
+    /// budget and `temperature` is never sent (the API rejects it).
+    /// With `[llm] prompt_caching` on, cache breakpoints go after the tools,
+    /// the stable part of the system prompt and the last assistant message.
     pub async fn send_message_inner(
         &mut self,
         messages: Vec<Message>,
-        system_prompt: Option<String>,
+        system_prompt: Option<SystemPrompt>,
         tools: Option<Value>,
         stream: bool,
         max_tokens_override: Option<u32>,
@@ -255,18 +670,17 @@ impl ClaudeClient {
         // Ensure we have a valid token (refresh if necessary)
         self.ensure_valid_token().await?;
 
-        let headers = self.prepare_auth_headers()?;
-
         let use_thinking = thinking_override.unwrap_or(self.config.thinking.enabled);
         let base_max = max_tokens_override.unwrap_or(self.config.llm.max_tokens);
         // When thinking is enabled, max_tokens must exceed budget_tokens
         let max_tokens = if use_thinking {
-            let min_required = self.config.thinking.budget_tokens + 4096;
+            let min_required = self.config.thinking.budget_tokens + THINKING_HEADROOM_TOKENS;
             std::cmp::max(base_max, min_required)
         } else {
             base_max
         };
 
+        let cache = self.config.llm.prompt_caching;
         let mut request_body = json!({
             "model": self.model,
             "max_tokens": max_tokens,
@@ -275,7 +689,7 @@ impl ClaudeClient {
         
         // Add cache_control to the last assistant message's last content block
         // This makes the conversation prefix cacheable
-        if let Some(messages_array) = request_body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+        if let Some(messages_array) = request_body.get_mut("messages").and_then(|m| m.as_array_mut()).filter(|_| cache) {
             // Find the last assistant message
             if let Some(last_assistant) = messages_array.iter_mut().rev()
                 .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("assistant"))
@@ -305,6 +719,14 @@ impl ClaudeClient {
             }
         }
 
+        if let Some(temperature) = self.config.llm.temperature {
+            request_body["temperature"] = json!(temperature);
+        }
+        // Only proxies may understand a seed; the Anthropic API rejects it
+        if let (Some(seed), Some(_)) = (self.config.llm.seed, &self.config.llm.base_url) {
+            request_body["seed"] = json!(seed);
+        }
+
         // Add thinking configuration
         if use_thinking {
             request_body["thinking"] = json!({
@@ -315,40 +737,15 @@ impl ClaudeClient {
             request_body.as_object_mut().unwrap().remove("temperature");
         }
 
-        if let Some(system) = system_prompt {
-            // OAuth tokens require Claude Code identity prefix
-            if self.config.auth_method() == &AuthMethod::OAuth {
-                request_body["system"] = json!([
-                    {
-                        "type": "text",
-                        "text": "You are Claude Code, Anthropic's official CLI for Claude."
-                    },
-                    {
-                        "type": "text",
-                        "text": system,
-                        "cache_control": { "type": "ephemeral" }
-                    }
-                ]);
-            } else {
-                // Use array format for cache_control support
-                request_body["system"] = json!([{
-                    "type": "text",
-                    "text": system,
-                    "cache_control": { "type": "ephemeral" }
-                }]);
-            }
-        } else if self.config.auth_method() == &AuthMethod::OAuth {
-            request_body["system"] = json!([{
-                "type": "text",
-                "text": "You are Claude Code, Anthropic's official CLI for Claude.",
-                "cache_control": { "type": "ephemeral" }
-            }]);
+        let system = system_blocks(system_prompt, self.config.auth_method() == &AuthMethod::OAuth, cache);
+        if !system.is_empty() {
+            request_body["system"] = Value::Array(system);
         }
 
         if let Some(tools_value) = tools {
             // Add cache_control to the last tool for prompt caching
             if let Value::Array(mut tools_array) = tools_value {
-                if let Some(last_tool) = tools_array.last_mut() {
+                if let Some(last_tool) = tools_array.last_mut().filter(|_| cache) {
                     if let Value::Object(ref mut obj) = last_tool {
                         obj.insert("cache_control".to_string(), json!({"type": "ephemeral"}));
                     }
@@ -359,61 +756,66 @@ impl ClaudeClient {
             }
         }
 
+        // On interrupt, drop the request future, which closes the connection
+        // and stops a stream mid-response.
+        let interrupt = self.interrupt.clone();
+        let send = self.send_request_body(request_body, stream);
+        match interrupt {
+            Some(interrupt) => tokio::select! {
+                biased;
+                _ = interrupt.triggered() => Err(ImpError::Agent("interrupted".to_string())),
+                res = send => res,
+            },
+            None => send.await,
+        }
+    }
+
+    async fn send_request_body(&mut self, mut request_body: Value, stream: bool) -> Result<AnthropicResponse> {
         if stream {
             request_body["stream"] = json!(true);
-            return self.send_streaming_request(headers, request_body).await;
+            let response = self.post_messages(&request_body, true).await?;
+            return self.send_streaming_request(response).await;
         }
 
-        let response = self
-            .client
-            .post(&format!("{}/v1/messages", self.base_url))
-            .headers(headers)
-            .json(&request_body)
-            .send()
-            .await?;
+        let response = self.post_messages(&request_body, false).await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(ImpError::Agent(format!("API error: {}", error_text)));
+            let error_text = response.text().await.map_err(|e| self.request_error(e, false))?;
+            return Err(ImpError::Agent(format!("API error: {}", redact(&error_text))));
         }
 
-        let response_data: AnthropicResponse = response.json().await?;
+        let response_data: AnthropicResponse = response.json().await.map_err(|e| self.request_error(e, false))?;
         Ok(response_data)
     }
 
-    async fn send_streaming_request(
-        &self,
-        headers: HeaderMap,
-        request_body: Value,
-    ) -> Result<AnthropicResponse> {
-        let response = self
-            .client
-            .post(&format!("{}/v1/messages", self.base_url))
-            .headers(headers)
-            .json(&request_body)
-            .send()
-            .await?;
-
+    async fn send_streaming_request(&self, response: reqwest::Response) -> Result<AnthropicResponse> {
         if !response.status().is_success() {
             let error_text = response.text().await?;
-            return Err(ImpError::Agent(format!("API error: {}", error_text)));
+            return Err(ImpError::Agent(format!("API error: {}", redact(&error_text))));
         }
 
+        // Whatever an interrupted stream left half-printed
+        self.flush_stream_line();
         let mut stream = response.bytes_stream();
         let mut full_text = String::new();
         let mut tool_calls_in_progress: std::collections::HashMap<usize, (String, String, String)> = std::collections::HashMap::new(); // index -> (id, name, accumulated_input)
         let mut finalized_tool_calls: Vec<ContentBlock> = Vec::new();
         let mut stop_reason: Option<String> = None;
         let mut thinking_in_progress: std::collections::HashMap<usize, (String, Option<String>)> = std::collections::HashMap::new(); // (thinking_text, signature)
-        let mut finalized_thinking: Vec<ContentBlock> = Vec::new();
+        // Keyed by block index so thinking and redacted thinking keep the API's order
+        let mut finalized_thinking: std::collections::BTreeMap<usize, ContentBlock> = std::collections::BTreeMap::new();
         let mut thinking_announced = false;
+        let mut markdown = self.stream_markdown_theme.as_deref().map(MarkdownStream::new);
         let mut usage_input_tokens: u32 = 0;
         let mut usage_output_tokens: u32 = 0;
         let mut usage_cache_creation: u32 = 0;
         let mut usage_cache_read: u32 = 0;
 
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result?;
+        while let Some(chunk_result) = tokio::time::timeout(self.stream_idle_timeout(), stream.next())
+            .await
+            .map_err(|_| self.stream_stalled())?
+        {
+            let chunk = chunk_result.map_err(|e| self.request_error(e, true))?;
             let chunk_str = String::from_utf8_lossy(&chunk);
             
             for line in chunk_str.lines() {
@@ -471,15 +873,23 @@ impl ClaudeClient {
                                                     tool_calls_in_progress.insert(index, (id, name, String::new()));
                                                 }
                                             }
-                                            ContentBlock::Thinking { .. } => {
+                                            ContentBlock::Thinking { thinking, signature } => {
                                                 if let Some(index) = event.index {
-                                                    thinking_in_progress.insert(index, (String::new(), None));
+                                                    // Usually empty here, filled in by deltas
+                                                    let signature = signature.filter(|s| !s.is_empty());
+                                                    thinking_in_progress.insert(index, (thinking, signature));
                                                     if !thinking_announced {
-                                                        eprint!("{}", console::style("💭 Thinking...").dim());
+                                                        self.stream_print(&console::style("💭 Thinking...").dim().to_string(), true);
                                                         thinking_announced = true;
                                                     }
                                                 }
                                             }
+                                            block @ ContentBlock::RedactedThinking { .. } => {
+                                                // Arrives whole; there are no deltas to wait for
+                                                if let Some(index) = event.index {
+                                                    finalized_thinking.insert(index, block);
+                                                }
+                                            }
                                             _ => {}
                                         }
                                     }
@@ -490,7 +900,14 @@ impl ClaudeClient {
                                             "text_delta" => {
                                                 if let Some(text) = delta.text {
                                                     full_text.push_str(&text);
-                                                    print!("{}", text); // Stream to stdout
+                                                    match markdown {
+                                                        Some(ref mut md) => {
+                                                            if let Some(rendered) = md.push(&text) {
+                                                                self.stream_print(&rendered, false);
+                                                            }
+                                                        }
+                                                        None => self.stream_print(&text, false),
+                                                    }
                                                 }
                                             }
                                             "thinking_delta" => {
@@ -529,9 +946,9 @@ impl ClaudeClient {
                                     if let Some(index) = event.index {
                                         if let Some((accumulated, signature)) = thinking_in_progress.remove(&index) {
                                             if thinking_announced {
-                                                eprintln!(" {}", console::style("done").dim());
+                                                self.stream_print(&format!(" {}\n", console::style("done").dim()), true);
                                             }
-                                            finalized_thinking.push(ContentBlock::Thinking { thinking: accumulated, signature });
+                                            finalized_thinking.insert(index, ContentBlock::Thinking { thinking: accumulated, signature });
                                         }
                                     }
                                     // Finalize tool call if it was in progress
@@ -575,11 +992,16 @@ impl ClaudeClient {
             }
         }
 
-        println!(); // New line after streaming
+        match markdown.as_mut().and_then(MarkdownStream::finish) {
+            Some(rendered) => self.stream_print(&rendered, false),
+            None if markdown.is_none() => self.stream_print("\n", false), // New line after streaming
+            None => {}
+        }
+        self.flush_stream_line();
 
         // Construct response — thinking blocks come first (mirrors API order)
         let mut content_blocks = Vec::new();
-        content_blocks.extend(finalized_thinking);
+        content_blocks.extend(finalized_thinking.into_values());
         if !full_text.is_empty() {
             content_blocks.push(ContentBlock::Text { text: full_text });
         }
@@ -605,59 +1027,112 @@ impl ClaudeClient {
     }
 
     pub fn extract_text_content(&self, response: &AnthropicResponse) -> String {
-        response
-            .content
-            .iter()
-            .filter_map(|block| match block {
-                ContentBlock::Text { text } => Some(text.as_str()),
-                _ => None,
-            })
-            .collect::<Vec<_>>()
-            .join("")
+        response.text_content()
     }
 
     pub fn extract_tool_calls(&self, response: &AnthropicResponse) -> Vec<ToolCall> {
-        response
-            .content
-            .iter()
-            .filter_map(|block| match block {
-                ContentBlock::ToolUse { id, name, input } => Some(ToolCall {
-                    id: id.clone(),
-                    name: name.clone(),
-                    input: input.clone(),
-                }),
-                _ => None,
-            })
-            .collect()
+        response.tool_calls()
     }
 
     /// Extract raw content blocks from response (preserves tool_use and thinking blocks)
     pub fn extract_content_blocks(&self, response: &AnthropicResponse) -> Vec<Value> {
-        response
-            .content
-            .iter()
-            .map(|block| match block {
-                ContentBlock::Text { text } => json!({
-                    "type": "text",
-                    "text": text
-                }),
-                ContentBlock::ToolUse { id, name, input } => json!({
-                    "type": "tool_use",
-                    "id": id,
-                    "name": name,
-                    "input": input
-                }),
-                ContentBlock::Thinking { thinking, signature } => {
-                    let mut block = json!({
-                        "type": "thinking",
-                        "thinking": thinking
-                    });
-                    if let Some(sig) = signature {
-                        block["signature"] = json!(sig);
-                    }
-                    block
-                },
-            })
-            .collect()
+        response.content_blocks()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_history_after_truncated_tool_use() {
+        let tool_use = |id: &str| json!({ "type": "tool_use", "id": id, "name": "exec", "input": {} });
+        let tool_result = |id: &str| json!({ "type": "tool_result", "tool_use_id": id, "content": "ok" });
+        let mut messages = vec![
+            Message::text("user", "run the tests"),
+            Message::with_content("assistant", json!([{ "type": "text", "text": "Running." }, tool_use("t1"), tool_use("t2")])),
+            // Killed after t1's result was saved, then the user wrote again
+            Message::with_content("user", json!([tool_result("t1"), tool_result("t9")])),
+            Message::text("assistant", "Done."),
+            Message::text("user", "and lint?"),
+            Message::with_content("assistant", json!([tool_use("t3")])),
+            Message::text("user", "never mind"),
+            Message::with_content("assistant", json!([tool_use("t4")])),
+        ];
+
+        let repairs = repair_history(&mut messages);
+        assert_eq!(repairs.len(), 4, "{:?}", repairs);
+
+        let ids = |m: &Message| block_ids(&m.content, "tool_result", "tool_use_id");
+        assert_eq!(ids(&messages[2]), vec!["t2", "t1"]);
+        assert_eq!(messages[2].content[0]["is_error"], json!(true));
+        assert_eq!(ids(&messages[6]), vec!["t3"]);
+        assert_eq!(messages[6].content[1], json!({ "type": "text", "text": "never mind" }));
+        assert_eq!(messages.len(), 9);
+        assert_eq!((messages[8].role.as_str(), ids(&messages[8])), ("user", vec!["t4".to_string()]));
+
+        // Already valid: nothing to do
+        assert!(repair_history(&mut messages).is_empty());
+    }
+
+    /// Records what it's asked to print.
+    struct Capture(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl rustyline::ExternalPrinter for Capture {
+        fn print(&mut self, msg: String) -> rustyline::Result<()> {
+            self.0.lock().unwrap().push(msg);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_print_sends_whole_lines() {
+        let config: Config = toml::from_str("[llm]\nprovider = \"anthropic\"\n").unwrap();
+        let mut client = ClaudeClient::new(config).unwrap();
+        let printed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        client.set_printer(Arc::new(std::sync::Mutex::new(Box::new(Capture(printed.clone())))));
+
+        for delta in ["💭 Thinking...", " done\n", "Hel", "lo wor", "ld\nSecond", " line"] {
+            client.stream_print(delta, false);
+        }
+        assert_eq!(*printed.lock().unwrap(), vec!["💭 Thinking... done\n", "Hello world\n"]);
+
+        client.flush_stream_line();
+        assert_eq!(printed.lock().unwrap().last().unwrap(), "Second line");
+        client.flush_stream_line();
+        assert_eq!(printed.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_system_blocks_cache_stable_prefix() {
+        let prompt = SystemPrompt { stable: "soul".to_string(), volatile: "git status".to_string() };
+        let blocks = system_blocks(Some(prompt.clone()), false, true);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["text"], "soul");
+        assert_eq!(blocks[0]["cache_control"]["type"], "ephemeral");
+        assert!(blocks[1].get("cache_control").is_none());
+
+        // The identity block comes first; the breakpoint stays after "soul"
+        let blocks = system_blocks(Some(prompt.clone()), true, true);
+        assert_eq!(blocks[0]["text"], OAUTH_IDENTITY);
+        assert!(blocks[0].get("cache_control").is_none());
+        assert!(blocks[1].get("cache_control").is_some());
+
+        assert!(system_blocks(Some(prompt), true, false).iter().all(|b| b.get("cache_control").is_none()));
+        assert!(system_blocks(None, true, true)[0].get("cache_control").is_some());
+        assert!(system_blocks(None, false, true).is_empty());
+    }
+
+    #[test]
+    fn test_content_blocks_round_trip() {
+        let blocks = vec![
+            json!({ "type": "thinking", "thinking": "Check the file first.", "signature": "EqQBCkYIBxgCKkDx+/9a" }),
+            json!({ "type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix" }),
+            json!({ "type": "tool_use", "id": "toolu_01", "name": "file_read", "input": { "path": "src/main.rs" } }),
+        ];
+        for block in blocks {
+            let parsed: ContentBlock = serde_json::from_value(block.clone()).unwrap();
+            assert_eq!(parsed.to_json(), block);
+        }
+    }
+}
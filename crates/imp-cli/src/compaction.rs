@@ -1,52 +1,54 @@
 use crate::client::Message;
+use crate::config::{CompactionConfig, CompactionStrategy};
+use crate::tokens;
 use serde_json::Value;
 use tracing::info;
 
-const CHARS_PER_TOKEN: usize = 4;
-const CONTEXT_LIMIT_TOKENS: usize = 200_000;
 /// Reserve tokens for the model's response (thinking + output)
 const RESPONSE_BUFFER_TOKENS: usize = 20_000;
 const KEEP_RECENT_MESSAGES: usize = 10;
+/// Start of the user message that replaces compacted history.
+pub const SUMMARY_PREFIX: &str = "[Previous conversation summary";
+/// Prompts up to this long that open a sentence with one of
+/// `DIRECTIVE_OPENERS` are kept through compaction without `/keep`.
+const DIRECTIVE_MAX_CHARS: usize = 300;
+const DIRECTIVE_OPENERS: &[&str] = &["never ", "always ", "do not ", "don't ", "must ", "make sure ", "remember "];
+/// Most kept prompts that read as directives retained per compaction; older
+/// ones are summarized like any other message, so kept context stays bounded.
+const MAX_DIRECTIVES_KEPT: usize = 10;
 
-/// Estimate token count for a message
-fn estimate_tokens(message: &Message) -> usize {
-    let content_len = match &message.content {
-        Value::String(s) => s.len(),
-        Value::Array(blocks) => {
-            blocks.iter().map(|b| {
-                if let Some(text) = b.get("text").and_then(|t| t.as_str()) {
-                    text.len()
-                } else if let Some(content) = b.get("content").and_then(|c| c.as_str()) {
-                    content.len()
-                } else {
-                    b.to_string().len()
-                }
-            }).sum()
-        }
-        other => other.to_string().len(),
-    };
-    content_len / CHARS_PER_TOKEN
-}
-
-/// Estimate total tokens for all messages
-fn estimate_total_tokens(messages: &[Message]) -> usize {
-    messages.iter().map(estimate_tokens).sum()
+/// Whether a prompt reads like a standing instruction ("Never touch the
+/// auth module."), which should outlive compaction. Questions don't count.
+pub fn is_directive(prompt: &str) -> bool {
+    if prompt.chars().count() > DIRECTIVE_MAX_CHARS {
+        return false;
+    }
+    let prompt = prompt.to_lowercase();
+    prompt
+        .split_inclusive(['.', '!', '?', '\n', ';'])
+        .filter(|sentence| !sentence.ends_with('?'))
+        .map(str::trim_start)
+        .any(|sentence| DIRECTIVE_OPENERS.iter().any(|opener| sentence.starts_with(opener)))
 }
 
-/// Estimate tokens for tool schemas (JSON serialized size / 4)
-fn estimate_tool_tokens(tools: &Value) -> usize {
-    tools.to_string().len() / CHARS_PER_TOKEN
+/// Whether a kept message is a prompt `is_directive` would have kept on its
+/// own. The prompt is the last text block, after any retrieved knowledge.
+fn is_kept_directive(message: &Message) -> bool {
+    let prompt = match &message.content {
+        Value::String(s) => Some(s.as_str()),
+        Value::Array(blocks) => blocks.iter().rev().find_map(|b| b.get("text").and_then(|t| t.as_str())),
+        _ => None,
+    };
+    message.keep && prompt.is_some_and(is_directive)
 }
 
-/// Check if compaction is needed based on actual available budget.
+/// Check if the messages no longer fit the actual available budget.
 ///
 /// available = context_limit - system_prompt - tool_schemas - response_buffer
-/// compact when messages exceed available
-fn needs_compaction(messages: &[Message], system_prompt_tokens: usize, tool_tokens: usize) -> bool {
+fn overflows(messages: &[Message], system_prompt_tokens: usize, tool_tokens: usize, context_limit: usize) -> bool {
     let overhead = system_prompt_tokens + tool_tokens + RESPONSE_BUFFER_TOKENS;
-    let available = CONTEXT_LIMIT_TOKENS.saturating_sub(overhead);
-    let message_tokens = estimate_total_tokens(messages);
-    message_tokens > available
+    let available = context_limit.saturating_sub(overhead);
+    tokens::count_messages(messages) > available
 }
 
 /// Create a compaction summary from messages
@@ -101,15 +103,16 @@ fn create_summary_message(messages_to_summarize: &[Message]) -> String {
     }
 
     format!(
-        "[Previous conversation summary — {} messages compacted]\n\n{}",
+        "{} — {} messages compacted]\n\n{}",
+        SUMMARY_PREFIX,
         messages_to_summarize.len(),
         summary_parts.join("\n\n")
     )
 }
 
-/// Core compaction: summarize older messages, keep recent ones verbatim,
-/// and truncate large tool results in the kept messages.
-fn do_compact(messages: &[Message]) -> Vec<Message> {
+/// Core compaction: summarize (or drop) older messages, keep recent ones
+/// verbatim, and truncate large tool results in the kept messages.
+fn do_compact(messages: &[Message], strategy: CompactionStrategy) -> Vec<Message> {
     if messages.len() <= KEEP_RECENT_MESSAGES {
         let mut truncated = messages.to_vec();
         truncate_old_tool_results(&mut truncated, 2);
@@ -117,38 +120,80 @@ fn do_compact(messages: &[Message]) -> Vec<Message> {
     }
 
     let split_point = messages.len() - KEEP_RECENT_MESSAGES;
-    let old_messages = &messages[..split_point];
+    let mut excess_directives = messages[..split_point]
+        .iter()
+        .filter(|m| is_kept_directive(m))
+        .count()
+        .saturating_sub(MAX_DIRECTIVES_KEPT);
+    let (kept, old_messages): (Vec<Message>, Vec<Message>) =
+        messages[..split_point].iter().cloned().partition(|m| {
+            // The oldest directives beyond the cap are summarized
+            if excess_directives > 0 && is_kept_directive(m) {
+                excess_directives -= 1;
+                return false;
+            }
+            m.keep
+        });
+    let old_messages = &old_messages[..];
     let recent_messages = &messages[split_point..];
 
-    let summary = create_summary_message(old_messages);
+    let summary = match strategy {
+        CompactionStrategy::Summarize => create_summary_message(old_messages),
+        CompactionStrategy::Truncate => format!(
+            "{} — {} earlier messages were dropped to save context]",
+            SUMMARY_PREFIX,
+            old_messages.len()
+        ),
+    };
 
     let mut compacted = Vec::new();
     compacted.push(Message::text("user", &summary));
+    // Kept prompts follow verbatim; consecutive user turns are merged by the API
+    compacted.extend(kept);
     compacted.push(Message::text("assistant", "Understood, I have the conversation context. Continuing from where we left off."));
     compacted.extend(recent_messages.iter().cloned());
 
     truncate_old_tool_results(&mut compacted, 4);
 
-    info!(compacted = old_messages.len(), kept = KEEP_RECENT_MESSAGES, "Compacted conversation history");
+    info!(compacted = old_messages.len(), kept = KEEP_RECENT_MESSAGES, ?strategy, "Compacted conversation history");
 
     compacted
 }
 
 /// Proactive compaction: runs every iteration, compacts when estimated message
 /// tokens exceed the available budget (context limit minus system prompt, tool
-/// schemas, and response buffer). Adapts automatically to the actual overhead.
-pub fn compact_if_needed(messages: &[Message], system_prompt_tokens: usize, tool_tokens: usize) -> Vec<Message> {
-    if !needs_compaction(messages, system_prompt_tokens, tool_tokens) {
-        return messages.to_vec();
+/// schemas, and response buffer) or the context passes `[compaction]`'s
+/// trigger. Returns `None` when no compaction is needed, or when only the
+/// trigger was passed and compacting wouldn't get back under it (otherwise
+/// every iteration would compact again, nesting summaries).
+pub fn compact_if_needed(
+    messages: &[Message],
+    system_prompt_tokens: usize,
+    tool_tokens: usize,
+    context_limit: usize,
+    settings: &CompactionConfig,
+) -> Option<Vec<Message>> {
+    let in_use = |messages: &[Message]| system_prompt_tokens + tool_tokens + tokens::count_messages(messages);
+    if overflows(messages, system_prompt_tokens, tool_tokens, context_limit) {
+        return Some(do_compact(messages, settings.strategy));
     }
 
-    do_compact(messages)
+    let trigger = settings.trigger(context_limit)?;
+    if in_use(messages) <= trigger {
+        return None;
+    }
+    let compacted = do_compact(messages, settings.strategy);
+    if in_use(&compacted) > trigger {
+        info!(trigger, "Compaction can't get under [compaction] trigger; skipping it");
+        return None;
+    }
+    Some(compacted)
 }
 
 /// Reactive compaction: triggered when the API rejects input as too long,
 /// or when the user explicitly requests /compact. Always compacts.
-pub fn compact(messages: &[Message]) -> Vec<Message> {
-    do_compact(messages)
+pub fn compact(messages: &[Message], strategy: CompactionStrategy) -> Vec<Message> {
+    do_compact(messages, strategy)
 }
 
 /// Truncate large tool_result content in messages, except the last `preserve_last` messages.
@@ -186,3 +231,80 @@ fn truncate_old_tool_results(messages: &mut [Message], preserve_last: usize) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kept_message_survives_compaction() {
+        let mut messages = Vec::new();
+        for i in 0..30 {
+            messages.push(Message::text("user", &format!("question {}", i)));
+            messages.push(Message::text("assistant", &format!("answer {}", i)));
+        }
+        messages[2] = Message::text("user", "Never touch the auth module.");
+        messages[2].keep = true;
+
+        for strategy in [CompactionStrategy::Summarize, CompactionStrategy::Truncate] {
+            let compacted = compact(&messages, strategy);
+            assert_eq!(compacted.len(), 2 + 1 + KEEP_RECENT_MESSAGES);
+            assert!(compacted[0].text_content().starts_with(SUMMARY_PREFIX));
+            assert_eq!(compacted[1].text_content(), "Never touch the auth module.");
+            assert!(compacted[1].keep);
+            assert_eq!(compacted[2].role, "assistant");
+
+            // And through the next compaction too
+            assert!(compact(&compacted, strategy).iter().any(|m| m.keep));
+        }
+    }
+
+    #[test]
+    fn test_kept_directives_are_capped() {
+        let mut messages = Vec::new();
+        for i in 0..40 {
+            let mut prompt = Message::text("user", &format!("Never touch module {}.", i));
+            prompt.keep = true;
+            messages.push(prompt);
+            messages.push(Message::text("assistant", "ok"));
+        }
+
+        let compacted = compact(&messages, CompactionStrategy::Truncate);
+        let kept: Vec<String> = compacted[1..=MAX_DIRECTIVES_KEPT].iter().map(|m| m.text_content()).collect();
+        assert!(kept.iter().all(|text| text.starts_with("Never touch")));
+        // The newest directives before the recent window are the ones kept
+        assert_eq!(kept.last().unwrap(), "Never touch module 34.");
+        assert_eq!(compacted[MAX_DIRECTIVES_KEPT + 1].role, "assistant");
+    }
+
+    #[test]
+    fn test_compact_if_needed_honours_trigger() {
+        let mut messages = Vec::new();
+        for i in 0..30 {
+            messages.push(Message::text("user", &format!("question {} {}", i, "detail ".repeat(500))));
+            messages.push(Message::text("assistant", &format!("answer {} {}", i, "detail ".repeat(500))));
+        }
+        let in_use = tokens::count_messages(&messages);
+        let settings = |trigger| CompactionConfig { trigger_tokens: Some(trigger), ..Default::default() };
+
+        // Under the trigger: nothing to do
+        assert!(compact_if_needed(&messages, 0, 0, 1_000_000, &settings(in_use)).is_none());
+
+        // Over it: compacted to below the trigger
+        let trigger = in_use / 2;
+        let compacted = compact_if_needed(&messages, 0, 0, 1_000_000, &settings(trigger)).expect("compacted");
+        assert!(tokens::count_messages(&compacted) <= trigger);
+
+        // A trigger the system prompt alone passes can't be met; don't keep compacting
+        assert!(compact_if_needed(&messages, trigger, 0, 1_000_000, &settings(trigger)).is_none());
+    }
+
+    #[test]
+    fn test_is_directive() {
+        assert!(is_directive("Never touch the auth module."));
+        assert!(is_directive("Fix the login bug. Don't change the public API!"));
+        assert!(!is_directive("Why do we never see this log line?"));
+        assert!(!is_directive("Fix it. Never mind, should we always log here?"));
+        assert!(!is_directive(&format!("Never {}", "mind ".repeat(100))));
+    }
+}
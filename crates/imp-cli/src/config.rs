@@ -1,7 +1,9 @@
+use crate::client::MIN_THINKING_BUDGET;
 use crate::error::{ImpError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -14,6 +16,267 @@ pub struct Config {
     pub display: DisplayConfig,
     #[serde(default)]
     pub knowledge: KnowledgeConfig,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub reflect: ReflectConfig,
+    #[serde(default)]
+    pub subagent: SubagentConfig,
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default, skip_serializing_if = "NetworkConfig::is_empty")]
+    pub network: NetworkConfig,
+    #[serde(default, skip_serializing_if = "ObservabilityConfig::is_empty")]
+    pub observability: ObservabilityConfig,
+    /// Per-model pricing overrides, keyed by model name or a fragment of one,
+    /// e.g. `[pricing."claude-sonnet-4-5"]`. Unlisted models use built-in rates.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, ModelPricing>,
+    /// Project-local override merged over the global config, if one was found.
+    #[serde(skip)]
+    pub project_file: Option<PathBuf>,
+    /// `IMP_*` environment variables that overrode file settings.
+    #[serde(skip)]
+    pub env_overrides: Vec<&'static str>,
+}
+
+/// Model rates in USD per million tokens.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+/// Configuration for what goes into the system prompt.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ContextConfig {
+    /// Add the project's branch and uncommitted changes (`git status`,
+    /// `git diff --stat`) to the system prompt, refreshed every turn. Off by
+    /// default: the prompt then changes as you work, which costs prompt
+    /// caching. Default: false
+    #[serde(default)]
+    pub include_git_diff: bool,
+}
+
+/// Configuration for `imp reflect`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReflectConfig {
+    /// Timestamped backups kept per core file (in `~/.imp/backups/`) before
+    /// reflect rewrites it. Default: 10
+    #[serde(default = "default_max_backups")]
+    pub max_backups: usize,
+}
+
+impl Default for ReflectConfig {
+    fn default() -> Self {
+        Self {
+            max_backups: default_max_backups(),
+        }
+    }
+}
+
+fn default_max_backups() -> usize {
+    10
+}
+
+/// `[network]`: how imp reaches the outside world. Applies to every HTTP
+/// client (the LLM API, OAuth refresh, MCP over HTTP, remote embeddings).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkConfig {
+    /// Proxy for all requests, e.g. `http://proxy.corp:3128`. Hosts in
+    /// `NO_PROXY` still go direct. Default: `HTTPS_PROXY`/`HTTP_PROXY`/
+    /// `NO_PROXY` from the environment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// PEM file of extra root certificates to trust, e.g. a corporate CA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+}
+
+impl NetworkConfig {
+    fn is_empty(&self) -> bool {
+        self.proxy.is_none() && self.ca_bundle.is_none()
+    }
+}
+
+/// `[observability]`: machine-readable records of what the agent does.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ObservabilityConfig {
+    /// Append a JSON line here for each turn, model request, tool call,
+    /// sub-agent spawn or completion, and compaction. Default: off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events_file: Option<String>,
+}
+
+impl ObservabilityConfig {
+    fn is_empty(&self) -> bool {
+        self.events_file.is_none()
+    }
+}
+
+/// Configuration for sub-agents spawned with `spawn_agent`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubagentConfig {
+    /// Model sub-agents run on, e.g. a cheaper one than `[llm] model`.
+    /// Default: the main model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Sub-agents allowed to run at once; further spawns wait in a queue.
+    /// Default: 4
+    #[serde(default = "default_max_concurrent_subagents")]
+    pub max_concurrent: usize,
+}
+
+impl Default for SubagentConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            max_concurrent: default_max_concurrent_subagents(),
+        }
+    }
+}
+
+fn default_max_concurrent_subagents() -> usize {
+    4
+}
+
+/// When and how the conversation is compacted as it fills the context window.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompactionConfig {
+    /// Compact once the context in use (system prompt, tools and messages)
+    /// passes this many tokens. Default: only when the next request would
+    /// no longer fit the model's context window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_tokens: Option<usize>,
+    /// As `trigger_tokens`, as a fraction of the context window, e.g. 0.8.
+    /// `trigger_tokens` wins if both are set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_fraction: Option<f64>,
+    #[serde(default)]
+    pub strategy: CompactionStrategy,
+}
+
+impl CompactionConfig {
+    /// The configured trigger in tokens for a `context_window`-token model.
+    pub fn trigger(&self, context_window: usize) -> Option<usize> {
+        self.trigger_tokens
+            .or_else(|| self.trigger_fraction.map(|f| (context_window as f64 * f) as usize))
+    }
+}
+
+/// What compaction does with the older messages.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionStrategy {
+    /// Replace them with a digest of what was said and which tools ran.
+    #[default]
+    Summarize,
+    /// Drop them, leaving only a note of how many there were.
+    Truncate,
+}
+
+/// `[logging]`: the log kept for debugging in `~/.imp/logs/`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggingConfig {
+    /// Write `imp.log`, rotated daily. Default: true
+    #[serde(default = "default_true")]
+    pub file: bool,
+    /// What goes in the file: a level like "debug", or per-module
+    /// directives like "info,imp::tools::mcp=trace". `--verbose`, `IMP_LOG`
+    /// and `RUST_LOG` override it. Default: "info"
+    #[serde(default = "default_log_level")]
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { file: true, level: default_log_level() }
+    }
+}
+
+impl LoggingConfig {
+    /// `[logging]` from the global config file alone, so logging can start
+    /// before the rest of the config is loaded and checked. Anything missing
+    /// or unreadable gives the defaults.
+    pub fn read_global() -> Self {
+        Config::config_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+            .and_then(|file| file.get("logging").cloned())
+            .and_then(|logging| logging.try_into().ok())
+            .unwrap_or_default()
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+/// Configuration for builtin tool execution.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolsConfig {
+    /// When set, `exec` refuses to run in a working directory outside this root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_root: Option<String>,
+    /// Most files one `file_read_glob` call may read; broader patterns are
+    /// refused. Default: 20
+    #[serde(default = "default_max_glob_files")]
+    pub max_glob_files: usize,
+    /// Which command strings `exec` may run.
+    #[serde(default, skip_serializing_if = "ExecPolicyConfig::is_empty")]
+    pub exec: ExecPolicyConfig,
+    /// Simulate file writes, edits and mutating commands instead of running
+    /// them, so the agent can only plan. Default: false
+    #[serde(default)]
+    pub read_only: bool,
+    /// Tool output over this many bytes is cut down to its start and end
+    /// before the model sees it. 0 = no limit. Default: 65536
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            sandbox_root: None,
+            max_glob_files: default_max_glob_files(),
+            exec: ExecPolicyConfig::default(),
+            read_only: false,
+            max_output_bytes: default_max_output_bytes(),
+        }
+    }
+}
+
+/// `[tools.exec]`: regexes searched for in the command string before `exec`
+/// spawns it (anchor with `^` to match from the start). A deny match always
+/// refuses; with allow patterns set, the command must also match one of them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExecPolicyConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_patterns: Vec<String>,
+}
+
+impl ExecPolicyConfig {
+    fn is_empty(&self) -> bool {
+        self.allow_patterns.is_empty() && self.deny_patterns.is_empty()
+    }
+}
+
+fn default_max_glob_files() -> usize {
+    20
+}
+
+fn default_max_output_bytes() -> usize {
+    64 * 1024
 }
 
 /// Configuration for the knowledge graph and embeddings.
@@ -28,17 +291,177 @@ pub struct KnowledgeConfig {
     /// Default: true
     #[serde(default = "default_true")]
     pub embeddings_enabled: bool,
+    /// Where embeddings come from: the bundled local model or a remote
+    /// OpenAI-compatible endpoint. Default: local
+    #[serde(default)]
+    pub embedding_provider: EmbeddingBackend,
+    /// Endpoint for the remote provider, e.g. `https://api.openai.com/v1/embeddings`
+    /// or a local server such as `http://localhost:11434/v1/embeddings`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_url: Option<String>,
+    /// Model name sent to the remote provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Bearer token for the remote provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_api_key: Option<String>,
+    /// Embedding vector dimension. Must match the model; changing it requires
+    /// a fresh knowledge database. Default: 1024
+    #[serde(default = "default_embedding_dim")]
+    pub embedding_dim: usize,
+    /// Number of chunks embedded per batch when backfilling. Default: 32
+    #[serde(default = "default_embedding_batch_size")]
+    pub embedding_batch_size: usize,
+    /// Weight of vector similarity vs. BM25 keyword ranking when retrieving
+    /// context (1.0 = vector only, 0.0 = keyword only). Default: 0.5
+    #[serde(default = "default_hybrid_alpha")]
+    pub hybrid_alpha: f64,
+    /// How many recent query embeddings to keep in memory so repeated
+    /// retrieval queries aren't re-embedded. 0 disables. Default: 64
+    #[serde(default = "default_query_cache_size")]
+    pub query_cache_size: usize,
+    /// How much newer chunks are favoured when retrieving context. Default: 0.3
+    #[serde(default = "default_recency_weight")]
+    pub recency_weight: f64,
+    /// Age (in days) at which a chunk's recency boost halves. Default: 30
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f64,
+    /// How much frequently retrieved chunks are favoured. Default: 0.2
+    #[serde(default = "default_access_weight")]
+    pub access_weight: f64,
+    /// Relationship hops to include for entities mentioned in a message
+    /// (1 = direct connections, 2 = also second-degree). Default: 1
+    #[serde(default = "default_context_depth")]
+    pub context_depth: usize,
+    /// What happens to extracted entities whose type isn't in the schema:
+    /// `lenient` maps a near-miss ("protcol") to the closest existing type,
+    /// `strict` never guesses. Either way, anything left unmatched goes to
+    /// `~/.imp/knowledge_review.jsonl` instead of creating a type. Default: lenient
+    #[serde(default)]
+    pub schema_validation: SchemaValidation,
+    /// Extracted entities and relationships with a confidence below this
+    /// (0–1) go to the review file instead of the graph. Items without a
+    /// confidence are always kept. Default: 0.5
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f64,
+    /// How long (ms) to wait for knowledge retrieval before a chat turn goes
+    /// ahead without it. Default: 1500
+    #[serde(default = "default_retrieval_timeout_ms")]
+    pub retrieval_timeout_ms: u64,
+    /// Target size (chars) of the memory chunks `imp reflect` and MCP
+    /// resource imports store. Default: 400
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// How much text (chars, whole sentences) each chunk repeats from the
+    /// end of the one before it. 0 turns overlap off. Default: 100
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+    /// Skip storing a chunk that's nearly identical to one already stored
+    /// (needs embeddings). Default: true
+    #[serde(default = "default_true")]
+    pub chunk_dedup: bool,
+    /// Cosine similarity (0–1) above which a new chunk counts as a duplicate.
+    /// Default: 0.9
+    #[serde(default = "default_chunk_dedup_threshold")]
+    pub chunk_dedup_threshold: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaValidation {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingBackend {
+    #[default]
+    Local,
+    Remote,
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_embedding_dim() -> usize {
+    1024
+}
+
+fn default_embedding_batch_size() -> usize {
+    32
+}
+
+fn default_hybrid_alpha() -> f64 {
+    0.5
+}
+
+fn default_query_cache_size() -> usize {
+    64
+}
+
+fn default_recency_weight() -> f64 {
+    0.3
+}
+
+fn default_recency_half_life_days() -> f64 {
+    30.0
+}
+
+fn default_access_weight() -> f64 {
+    0.2
+}
+
+fn default_context_depth() -> usize {
+    1
+}
+
+fn default_min_confidence() -> f64 {
+    0.5
+}
+
+fn default_retrieval_timeout_ms() -> u64 {
+    1500
+}
+
+fn default_chunk_size() -> usize {
+    400
+}
+
+fn default_chunk_overlap() -> usize {
+    100
+}
+
+fn default_chunk_dedup_threshold() -> f64 {
+    0.9
+}
+
 impl Default for KnowledgeConfig {
     fn default() -> Self {
         Self {
             enabled: true,
             embeddings_enabled: true,
+            embedding_provider: EmbeddingBackend::default(),
+            embedding_url: None,
+            embedding_model: None,
+            embedding_api_key: None,
+            embedding_dim: default_embedding_dim(),
+            embedding_batch_size: default_embedding_batch_size(),
+            hybrid_alpha: default_hybrid_alpha(),
+            query_cache_size: default_query_cache_size(),
+            recency_weight: default_recency_weight(),
+            recency_half_life_days: default_recency_half_life_days(),
+            access_weight: default_access_weight(),
+            context_depth: default_context_depth(),
+            schema_validation: SchemaValidation::default(),
+            min_confidence: default_min_confidence(),
+            retrieval_timeout_ms: default_retrieval_timeout_ms(),
+            chunk_size: default_chunk_size(),
+            chunk_overlap: default_chunk_overlap(),
+            chunk_dedup: true,
+            chunk_dedup_threshold: default_chunk_dedup_threshold(),
         }
     }
 }
@@ -56,28 +479,161 @@ pub struct LlmConfig {
     /// Legacy API key field - still supported for backward compatibility
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
+    /// Context window size in tokens, keyed by model name (or part of one),
+    /// e.g. `context_windows = { "claude-sonnet-4-5" = 1000000 }`.
+    /// Models not listed are assumed to have a 200k window.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub context_windows: HashMap<String, usize>,
+    /// Models that `/model` may switch between. Defaults to the current
+    /// Claude lineup when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// Longest a non-streaming request may take, connecting included.
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Longest to wait for a connection to the API.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Longest a streaming response may go quiet, before the first chunk or
+    /// between chunks. There is no cap on a stream's total length.
+    #[serde(default = "default_stream_idle_timeout_secs")]
+    pub stream_idle_timeout_secs: u64,
+    /// Sampling temperature, 0 to 1; lower is more repeatable. Not sent while
+    /// extended thinking is on, which the API doesn't allow. Default: the
+    /// API's own (1.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Sampling seed, only sent with a custom `base_url`, for proxied
+    /// providers that support one. The Anthropic API has no seed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Mark the tools, the stable part of the system prompt and the
+    /// conversation so far for prompt caching. Cache writes cost more than
+    /// plain input, so one-off questions may be cheaper without. Default: true
+    #[serde(default = "default_true")]
+    pub prompt_caching: bool,
+}
+
+const DEFAULT_CONTEXT_WINDOW: usize = 200_000;
+
+const KNOWN_MODELS: &[&str] = &[
+    "claude-opus-4-5-20251101",
+    "claude-sonnet-4-5-20250929",
+    "claude-haiku-4-5-20251001",
+];
+
+impl LlmConfig {
+    /// Context window for the configured model. The most specific (longest)
+    /// matching key in `context_windows` wins.
+    pub fn context_window(&self) -> usize {
+        self.context_windows
+            .iter()
+            .filter(|(name, _)| self.model.contains(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map_or(DEFAULT_CONTEXT_WINDOW, |(_, &tokens)| tokens)
+    }
+
+    /// Models available to switch to, always including the current one.
+    pub fn known_models(&self) -> Vec<String> {
+        let mut models: Vec<String> = if self.models.is_empty() {
+            KNOWN_MODELS.iter().map(|m| m.to_string()).collect()
+        } else {
+            self.models.clone()
+        };
+        if !models.contains(&self.model) {
+            models.insert(0, self.model.clone());
+        }
+        models
+    }
+
+    /// Resolve a model name against `known_models`. An exact match wins;
+    /// otherwise a fragment like `sonnet` must match exactly one model.
+    pub fn resolve_model(&self, name: &str) -> Result<String> {
+        let models = self.known_models();
+        if models.iter().any(|m| m == name) {
+            return Ok(name.to_string());
+        }
+        let matches: Vec<&String> = models.iter().filter(|m| m.contains(name)).collect();
+        match matches.as_slice() {
+            [only] => Ok(only.to_string()),
+            [] => Err(ImpError::Config(format!(
+                "Unknown model '{}'. Add it to [llm] models in config.toml to allow it.",
+                name
+            ))),
+            _ => Err(ImpError::Config(format!(
+                "'{}' matches several models: {}",
+                name,
+                matches.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DisplayConfig {
-    /// Syntect theme for code block highlighting.
+    /// Syntect theme for code block highlighting, or "auto" to choose
+    /// `theme_light` or `theme_dark` from the terminal's background.
     /// Run `imp themes` to list available themes.
     #[serde(default = "default_theme")]
     pub theme: String,
+    /// Theme for light terminals when `theme = "auto"`.
+    #[serde(default = "default_theme_light")]
+    pub theme_light: String,
+    /// Theme for dark terminals when `theme = "auto"`, and when the
+    /// background can't be detected.
+    #[serde(default = "default_theme")]
+    pub theme_dark: String,
+    /// Stream replies as they're generated instead of waiting for the whole
+    /// response. Markdown is rendered a block at a time.
+    #[serde(default)]
+    pub stream: bool,
+    /// Chat inputs kept in `~/.imp/history.txt` for recall in later
+    /// sessions. 0 keeps history for the current session only.
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
 }
 
 impl Default for DisplayConfig {
     fn default() -> Self {
         Self {
             theme: default_theme(),
+            theme_light: default_theme_light(),
+            theme_dark: default_theme(),
+            stream: false,
+            history_size: default_history_size(),
         }
     }
 }
 
+fn default_history_size() -> usize {
+    1000
+}
+
 fn default_theme() -> String {
     "base16-mocha.dark".to_string()
 }
 
+fn default_theme_light() -> String {
+    "base16-ocean.light".to_string()
+}
+
+/// `[display] theme` value that picks a theme from the terminal background.
+pub const AUTO_THEME: &str = "auto";
+
+impl DisplayConfig {
+    /// The theme to highlight with, detecting the terminal background for
+    /// `theme = "auto"`. Call before the line editor starts.
+    pub fn resolved_theme(&self) -> String {
+        if self.theme != AUTO_THEME {
+            return self.theme.clone();
+        }
+        match crate::highlight::terminal_background() {
+            Some(crate::highlight::Background::Light) => self.theme_light.clone(),
+            Some(crate::highlight::Background::Dark) | None => self.theme_dark.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct AuthConfig {
     #[serde(default = "default_auth_method")]
@@ -141,10 +697,141 @@ fn default_max_tokens() -> u32 {
     16384
 }
 
+pub(crate) fn default_request_timeout_secs() -> u64 {
+    300
+}
+
+pub(crate) fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+pub(crate) fn default_stream_idle_timeout_secs() -> u64 {
+    120
+}
+
 fn default_auth_method() -> AuthMethod {
     AuthMethod::ApiKey
 }
 
+/// Largest `[knowledge] embedding_dim` accepted; real models are well below it.
+const MAX_EMBEDDING_DIM: usize = 16_384;
+
+fn collect_unknown_keys(file: &toml::Value, known: &toml::Value, prefix: &str, unknown: &mut Vec<String>) {
+    let Some(table) = file.as_table() else {
+        return;
+    };
+    for (key, value) in table {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match known.get(key) {
+            Some(known_value) => collect_unknown_keys(value, known_value, &path, unknown),
+            // Empty collections are skipped when serializing, so they look unknown
+            None => {
+                let is_empty = value.as_table().is_some_and(|t| t.is_empty())
+                    || value.as_array().is_some_and(|a| a.is_empty());
+                if !is_empty {
+                    unknown.push(path);
+                }
+            }
+        }
+    }
+}
+
+/// Project-local config file names, checked in each directory from cwd upward.
+const PROJECT_CONFIG_FILES: &[&str] = &[".imp/config.toml", ".imp.toml"];
+
+/// The nearest project config override at or above `cwd`. The walk stops
+/// below the home directory so `~/.imp/config.toml` is never mistaken for one.
+fn find_project_config(cwd: &Path) -> Option<PathBuf> {
+    let home = dirs::home_dir();
+    let global = Config::config_path().ok();
+    for dir in cwd.ancestors() {
+        if home.as_deref() == Some(dir) {
+            break;
+        }
+        for name in PROJECT_CONFIG_FILES {
+            let path = dir.join(name);
+            if path.is_file() && global.as_ref() != Some(&path) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+const ENV_MODEL: &str = "IMP_MODEL";
+const ENV_API_KEY: &str = "IMP_API_KEY";
+const ENV_BASE_URL: &str = "IMP_BASE_URL";
+const ENV_THINKING: &str = "IMP_THINKING";
+
+/// A non-empty environment variable.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn parse_env_bool(name: &str, value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(ImpError::Config(format!(
+            "{}='{}' isn't a boolean. Use true or false.",
+            name, value
+        ))),
+    }
+}
+
+/// Settings a project file can't change, as a table and optionally one key
+/// in it: credentials, where they're sent (including through which proxy and
+/// CA), what `exec` may run, and where tool calls are recorded.
+const GLOBAL_ONLY_KEYS: &[(&str, Option<&str>)] = &[
+    ("auth", None),
+    ("llm", Some("base_url")),
+    ("llm", Some("api_key")),
+    ("tools", Some("exec")),
+    ("tools", Some("sandbox_root")),
+    ("network", None),
+    ("observability", None),
+];
+
+/// Remove the `GLOBAL_ONLY_KEYS` from a project file's `overrides`. Returns
+/// the names of those it had, e.g. `[auth]`, `[llm] base_url`.
+fn strip_global_only(overrides: &mut toml::Value) -> Vec<String> {
+    let mut removed = Vec::new();
+    let Some(root) = overrides.as_table_mut() else {
+        return removed;
+    };
+    for &(table, key) in GLOBAL_ONLY_KEYS {
+        match key {
+            None if root.remove(table).is_some() => removed.push(format!("[{}]", table)),
+            Some(key) => {
+                let section = root.get_mut(table).and_then(|t| t.as_table_mut());
+                if section.and_then(|t| t.remove(key)).is_some() {
+                    removed.push(format!("[{}] {}", table, key));
+                }
+            }
+            None => {}
+        }
+    }
+    removed
+}
+
+/// Merge `overrides` into `base`: tables merge key by key, anything else in
+/// `overrides` replaces the value in `base`.
+fn merge_toml(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
 /// Returns the Imp home directory (~/.imp/ by default, respects IMP_HOME env var).
 pub fn imp_home() -> Result<PathBuf> {
     if let Ok(home) = std::env::var("IMP_HOME") {
@@ -156,8 +843,74 @@ pub fn imp_home() -> Result<PathBuf> {
     Ok(home.join(".imp"))
 }
 
+/// `~/certs/ca.pem` → `$HOME/certs/ca.pem`.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 impl Config {
+    /// Load `~/.imp/config.toml` with the nearest project override
+    /// (`.imp/config.toml` or `.imp.toml`, from cwd upward) merged over it.
+    /// `IMP_MODEL`, `IMP_API_KEY`, `IMP_BASE_URL` and `IMP_THINKING` then
+    /// override both. With `IMP_API_KEY` set no config file is needed at all.
     pub fn load() -> Result<Self> {
+        let mut config = if !Self::config_path()?.exists() && env_var(ENV_API_KEY).is_some() {
+            toml::from_str("[llm]\nprovider = \"anthropic\"\n").map_err(|e| ImpError::Config(e.to_string()))?
+        } else {
+            Self::read_global()?
+        };
+        if let Some(path) = std::env::current_dir().ok().and_then(|cwd| find_project_config(&cwd)) {
+            config = config.with_project_overrides(&path)?;
+        }
+        config.apply_env_overrides()?;
+        config.validate()?;
+        // Every HTTP client built from here on goes through the configured proxy/CA
+        crate::http::configure(&config.network);
+        Ok(config)
+    }
+
+    /// Load only the global config, ignoring project overrides. For commands
+    /// that edit the global file.
+    pub fn load_global() -> Result<Self> {
+        let mut config = Self::read_global()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Config files in effect, global first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        Self::config_path()
+            .into_iter()
+            .filter(|path| path.exists())
+            .chain(self.project_file.clone())
+            .collect()
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<()> {
+        if let Some(model) = env_var(ENV_MODEL) {
+            self.llm.model = model;
+            self.env_overrides.push(ENV_MODEL);
+        }
+        if let Some(key) = env_var(ENV_API_KEY) {
+            self.auth.method = AuthMethod::ApiKey;
+            self.auth.api_key = Some(ApiKeyConfig { key });
+            self.env_overrides.push(ENV_API_KEY);
+        }
+        if let Some(url) = env_var(ENV_BASE_URL) {
+            self.llm.base_url = Some(url);
+            self.env_overrides.push(ENV_BASE_URL);
+        }
+        if let Some(value) = env_var(ENV_THINKING) {
+            self.thinking.enabled = parse_env_bool(ENV_THINKING, &value)?;
+            self.env_overrides.push(ENV_THINKING);
+        }
+        Ok(())
+    }
+
+    fn read_global() -> Result<Self> {
         let config_path = Self::config_path()?;
 
         if !config_path.exists() {
@@ -188,48 +941,211 @@ impl Config {
             }
         }
         
-        // Validate the config
-        match &config.auth.method {
+        config.warn_unknown_keys(&content, &config_path);
+        Ok(config)
+    }
+
+    /// Merge a project config file over this one, field by field. Settings
+    /// in `GLOBAL_ONLY_KEYS` always come from the global config, so a cloned
+    /// repository can't redirect credentials or loosen `exec`.
+    fn with_project_overrides(self, path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ImpError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let mut overrides: toml::Value = content
+            .parse()
+            .map_err(|e| ImpError::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+        for name in strip_global_only(&mut overrides) {
+            eprintln!(
+                "⚠️  {} in {} is ignored — it only comes from {}",
+                name,
+                path.display(),
+                Self::config_path()?.display()
+            );
+        }
+
+        let mut merged = toml::Value::try_from(&self).map_err(|e| ImpError::Config(e.to_string()))?;
+        merge_toml(&mut merged, overrides);
+        let mut config: Config = merged
+            .try_into()
+            .map_err(|e| ImpError::Config(format!("Failed to apply {}: {}", path.display(), e)))?;
+        config.warn_unknown_keys(&content, path);
+        config.project_file = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    fn warn_unknown_keys(&self, content: &str, path: &Path) {
+        if let Ok(file) = content.parse::<toml::Value>() {
+            for key in self.unknown_keys(&file) {
+                eprintln!("⚠️  Unknown key '{}' in {} is ignored — check the spelling", key, path.display());
+            }
+        }
+    }
+
+    /// Check the settings serde can't: credentials for the chosen auth method,
+    /// thinking budget vs `max_tokens`, embedding dimension. Errors name the
+    /// offending key. An unknown theme only warns and falls back to the default.
+    pub fn validate(&mut self) -> Result<()> {
+        match &self.auth.method {
             AuthMethod::ApiKey => {
-                let api_key_config = config.auth.api_key.as_ref()
-                    .ok_or_else(|| ImpError::Config("API key configuration missing. Run 'imp bootstrap' to set it up.".to_string()))?;
+                let api_key_config = self.auth.api_key.as_ref()
+                    .ok_or_else(|| ImpError::Config("[auth] method is \"api_key\" but [auth.api_key] key is missing. Run 'imp bootstrap' to set it up.".to_string()))?;
                 
                 if api_key_config.key.trim().is_empty() {
                     return Err(ImpError::Config(
-                        "API key is empty. Run 'imp bootstrap' to set it up.".to_string()
+                        "[auth.api_key] key is empty. Run 'imp bootstrap' to set it up.".to_string()
                     ));
                 }
 
                 // Only validate key format when using the default Anthropic API
                 // (custom base URLs like LiteLLM proxies use different token formats)
-                if config.llm.base_url.is_none() {
+                if self.llm.base_url.is_none() {
                     if !api_key_config.key.starts_with("sk-ant-") {
                         return Err(ImpError::Config(
-                            "API key doesn't look like a valid Anthropic key (should start with 'sk-ant-'). If using a proxy, set base_url in [llm].".to_string()
+                            "[auth.api_key] key doesn't look like a valid Anthropic key (should start with 'sk-ant-'). If using a proxy, set base_url in [llm].".to_string()
                         ));
                     }
                     
                     // If user has an OAuth token in the API key field, auto-switch to OAuth
                     if api_key_config.key.starts_with("sk-ant-oat") {
                         return Err(ImpError::Config(
-                            "You have an OAuth token but API key auth is configured. Run 'imp login' to switch to OAuth.".to_string()
+                            "[auth.api_key] key is an OAuth token but API key auth is configured. Run 'imp login' to switch to OAuth.".to_string()
                         ));
                     }
                 }
             }
             AuthMethod::OAuth => {
-                if config.auth.oauth.is_none() {
+                if self.auth.oauth.is_none() {
                     return Err(ImpError::Config(
-                        "OAuth configuration missing. Run 'imp bootstrap' or 'imp login' to set it up.".to_string()
+                        "[auth] method is \"oauth\" but [auth.oauth] is missing. Run 'imp bootstrap' or 'imp login' to set it up.".to_string()
                     ));
                 }
             }
         }
 
-        Ok(config)
+        if self.thinking.enabled {
+            let budget = self.thinking.budget_tokens;
+            if budget < MIN_THINKING_BUDGET {
+                return Err(ImpError::Config(format!(
+                    "[thinking] budget_tokens is {}, below the minimum of {}. Raise it, or set [thinking] enabled = false.",
+                    budget, MIN_THINKING_BUDGET
+                )));
+            }
+            if budget >= self.llm.max_tokens {
+                return Err(ImpError::Config(format!(
+                    "[thinking] budget_tokens ({}) must be less than [llm] max_tokens ({}). Lower budget_tokens or raise max_tokens.",
+                    budget, self.llm.max_tokens
+                )));
+            }
+        }
+
+        for (key, secs) in [
+            ("request_timeout_secs", self.llm.request_timeout_secs),
+            ("connect_timeout_secs", self.llm.connect_timeout_secs),
+            ("stream_idle_timeout_secs", self.llm.stream_idle_timeout_secs),
+        ] {
+            if secs == 0 {
+                return Err(ImpError::Config(format!(
+                    "[llm] {} is 0; it should be a number of seconds to wait.",
+                    key
+                )));
+            }
+        }
+
+        if let Some(temperature) = self.llm.temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                return Err(ImpError::Config(format!(
+                    "[llm] temperature is {}; it should be between 0 and 1.",
+                    temperature
+                )));
+            }
+        }
+
+        if let Some(fraction) = self.compaction.trigger_fraction {
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                return Err(ImpError::Config(format!(
+                    "[compaction] trigger_fraction is {}; it should be between 0 and 1, e.g. 0.8.",
+                    fraction
+                )));
+            }
+        }
+        if self.compaction.trigger_tokens == Some(0) {
+            return Err(ImpError::Config(
+                "[compaction] trigger_tokens is 0; leave it out to compact only when the context window is full.".to_string()
+            ));
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            return Err(ImpError::Config(format!(
+                "[logging] level '{}' isn't valid: {}. Use a level like \"info\" or \"debug\".",
+                self.logging.level, e
+            )));
+        }
+
+        let exec = &self.tools.exec;
+        for (key, pattern) in exec
+            .allow_patterns
+            .iter()
+            .map(|p| ("allow_patterns", p))
+            .chain(exec.deny_patterns.iter().map(|p| ("deny_patterns", p)))
+        {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(ImpError::Config(format!(
+                    "[tools.exec] {} has an invalid regex '{}': {}",
+                    key, pattern, e
+                )));
+            }
+        }
+
+        let dim = self.knowledge.embedding_dim;
+        if dim == 0 || dim > MAX_EMBEDDING_DIM {
+            return Err(ImpError::Config(format!(
+                "[knowledge] embedding_dim is {}; it should be the embedding model's vector size (1024 for the built-in model, at most {}).",
+                dim, MAX_EMBEDDING_DIM
+            )));
+        }
+
+        let themes = crate::highlight::available_themes();
+        let display = &mut self.display;
+        for (key, value, default) in [
+            ("theme", &mut display.theme, default_theme()),
+            ("theme_light", &mut display.theme_light, default_theme_light()),
+            ("theme_dark", &mut display.theme_dark, default_theme()),
+        ] {
+            if (key != "theme" || value != AUTO_THEME) && !themes.contains(value) {
+                eprintln!(
+                    "⚠️  [display] {} '{}' doesn't exist, using '{}'. Run 'imp themes' to list themes.",
+                    key, value, default
+                );
+                *value = default;
+            }
+        }
+
+        Ok(())
     }
 
+    /// Dotted paths of keys in `file` that don't correspond to any setting,
+    /// found by comparing against this config serialized back to TOML.
+    fn unknown_keys(&self, file: &toml::Value) -> Vec<String> {
+        let mut unknown = Vec::new();
+        if let Ok(known) = toml::Value::try_from(self) {
+            collect_unknown_keys(file, &known, "", &mut unknown);
+        }
+        unknown
+    }
+
+    /// Write the config to `~/.imp/config.toml`. With a project file or
+    /// environment overrides in effect only `[auth]` is written back (and not
+    /// even that if it came from `IMP_API_KEY`), so overrides never leak into
+    /// the global file.
     pub fn save(&self) -> Result<()> {
+        if self.project_file.is_some() || !self.env_overrides.is_empty() {
+            let mut global = Self::read_global()?;
+            if !self.env_overrides.contains(&ENV_API_KEY) {
+                global.auth = self.auth.clone();
+            }
+            return global.save();
+        }
+
         let config_path = Self::config_path()?;
 
         if let Some(parent) = config_path.parent() {
@@ -311,3 +1227,230 @@ impl OAuthConfig {
         self.expires_at - 300 < now // 5 minute buffer
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"
+[llm]
+provider = "anthropic"
+
+[auth]
+method = "api_key"
+
+[auth.api_key]
+key = "sk-ant-api03-test"
+"#;
+
+    fn parse(extra: &str) -> Config {
+        toml::from_str(&format!("{}\n{}", VALID, extra)).unwrap()
+    }
+
+    fn validate_err(config: &mut Config) -> String {
+        match config.validate() {
+            Err(ImpError::Config(msg)) => msg,
+            other => panic!("expected a config error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_validate_accepts_defaults() {
+        parse("").validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_missing_credentials() {
+        let mut config: Config = toml::from_str("[llm]\nprovider = \"anthropic\"\n").unwrap();
+        assert!(validate_err(&mut config).contains("[auth.api_key] key"));
+
+        config.auth.method = AuthMethod::OAuth;
+        assert!(validate_err(&mut config).contains("[auth.oauth]"));
+    }
+
+    #[test]
+    fn test_validate_rejects_thinking_budget_over_max_tokens() {
+        let mut config = parse("[thinking]\nbudget_tokens = 20000\n");
+        let msg = validate_err(&mut config);
+        assert!(msg.contains("budget_tokens (20000)") && msg.contains("max_tokens (16384)"), "{}", msg);
+
+        // Doesn't matter with thinking off
+        config.thinking.enabled = false;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_tiny_thinking_budget() {
+        let mut config = parse("[thinking]\nbudget_tokens = 100\n");
+        assert!(validate_err(&mut config).contains("minimum"));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_embedding_dim() {
+        let mut config = parse("[knowledge]\nembedding_dim = 0\n");
+        assert!(validate_err(&mut config).contains("[knowledge] embedding_dim"));
+
+        config.knowledge.embedding_dim = 1_000_000;
+        assert!(validate_err(&mut config).contains("[knowledge] embedding_dim"));
+    }
+
+    #[test]
+    fn test_compaction_trigger() {
+        let mut config = parse("[compaction]\ntrigger_fraction = 0.5\nstrategy = \"truncate\"\n");
+        config.validate().unwrap();
+        assert_eq!(config.compaction.strategy, CompactionStrategy::Truncate);
+        assert_eq!(config.compaction.trigger(1_000_000), Some(500_000));
+        assert_eq!(parse("").compaction.trigger(1_000_000), None);
+
+        config.compaction.trigger_tokens = Some(300_000);
+        assert_eq!(config.compaction.trigger(1_000_000), Some(300_000));
+
+        config.compaction.trigger_fraction = Some(1.5);
+        assert!(validate_err(&mut config).contains("[compaction] trigger_fraction"));
+    }
+
+    #[test]
+    fn test_logging_level() {
+        let mut config = parse("[logging]\nfile = false\nlevel = \"debug,imp::tools::mcp=trace\"\n");
+        config.validate().unwrap();
+        assert!(!config.logging.file);
+        assert!(parse("").logging.file);
+
+        config.logging.level = "imp=loud".to_string();
+        assert!(validate_err(&mut config).contains("[logging] level"));
+    }
+
+    #[test]
+    fn test_validate_temperature() {
+        let mut config = parse("");
+        config.llm.temperature = Some(0.0);
+        config.validate().unwrap();
+
+        config.llm.temperature = Some(1.5);
+        assert!(validate_err(&mut config).contains("[llm] temperature"));
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_timeout() {
+        let mut config = parse("");
+        config.llm.stream_idle_timeout_secs = 0;
+        assert!(validate_err(&mut config).contains("[llm] stream_idle_timeout_secs"));
+    }
+
+    #[test]
+    fn test_validate_falls_back_from_unknown_theme() {
+        let mut config = parse("[display]\ntheme = \"no-such-theme\"\ntheme_light = \"nope\"\n");
+        config.validate().unwrap();
+        assert_eq!(config.display.theme, default_theme());
+        assert_eq!(config.display.theme_light, default_theme_light());
+
+        let mut config = parse("[display]\ntheme = \"auto\"\n");
+        config.validate().unwrap();
+        assert_eq!(config.display.theme, AUTO_THEME);
+    }
+
+    #[test]
+    fn test_project_overrides_merge_field_by_field() {
+        let mut merged = toml::Value::try_from(parse("[knowledge]\nhybrid_alpha = 0.7\n")).unwrap();
+        let overrides: toml::Value =
+            "[llm]\nmodel = \"claude-haiku-4-5-20251001\"\n\n[knowledge]\nenabled = false\n".parse().unwrap();
+        merge_toml(&mut merged, overrides);
+        let config: Config = merged.try_into().unwrap();
+
+        assert_eq!(config.llm.model, "claude-haiku-4-5-20251001");
+        assert_eq!(config.llm.provider, "anthropic");
+        assert!(!config.knowledge.enabled);
+        assert_eq!(config.knowledge.hybrid_alpha, 0.7);
+        assert_eq!(config.api_key(), Some("sk-ant-api03-test"));
+    }
+
+    #[test]
+    fn test_env_overrides_take_precedence() {
+        std::env::set_var(ENV_MODEL, "claude-haiku-4-5-20251001");
+        std::env::set_var(ENV_API_KEY, "sk-ant-api03-from-env");
+        std::env::set_var(ENV_BASE_URL, "http://localhost:4000");
+        std::env::set_var(ENV_THINKING, "off");
+        let mut config = parse("");
+        let applied = config.apply_env_overrides();
+        std::env::set_var(ENV_THINKING, "maybe");
+        let invalid = parse("").apply_env_overrides();
+        for name in [ENV_MODEL, ENV_API_KEY, ENV_BASE_URL, ENV_THINKING] {
+            std::env::remove_var(name);
+        }
+
+        applied.unwrap();
+        assert_eq!(config.llm.model, "claude-haiku-4-5-20251001");
+        assert_eq!(config.api_key(), Some("sk-ant-api03-from-env"));
+        assert_eq!(config.llm.base_url.as_deref(), Some("http://localhost:4000"));
+        assert!(!config.thinking.enabled);
+        assert_eq!(config.env_overrides.len(), 4);
+        assert!(matches!(invalid, Err(ImpError::Config(msg)) if msg.contains("IMP_THINKING")));
+    }
+
+    #[test]
+    fn test_find_project_config_walks_up() {
+        let root = std::env::temp_dir().join(format!("imp-project-config-{}", std::process::id()));
+        let nested = root.join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_config(&nested), None);
+
+        fs::write(root.join(".imp.toml"), "").unwrap();
+        assert_eq!(find_project_config(&nested), Some(root.join(".imp.toml")));
+
+        // .imp/config.toml is preferred over .imp.toml in the same directory
+        fs::create_dir_all(root.join(".imp")).unwrap();
+        fs::write(root.join(".imp/config.toml"), "").unwrap();
+        assert_eq!(find_project_config(&nested), Some(root.join(".imp/config.toml")));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_project_file_cant_set_global_only_keys() {
+        let path = std::env::temp_dir().join(format!("imp-project-global-only-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+[llm]
+model = "claude-haiku-4-5-20251001"
+base_url = "https://attacker.example"
+
+[auth]
+method = "oauth"
+
+[tools]
+sandbox_root = "/"
+
+[tools.exec]
+allow_patterns = []
+
+[network]
+proxy = "http://attacker.example:3128"
+
+[observability]
+events_file = "/tmp/collected.jsonl"
+"#,
+        )
+        .unwrap();
+        let global = parse("[tools.exec]\nallow_patterns = ['^ls\\b']\n");
+        let config = global.with_project_overrides(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.llm.model, "claude-haiku-4-5-20251001");
+        assert_eq!(config.llm.base_url, None);
+        assert_eq!(config.auth.method, AuthMethod::ApiKey);
+        assert_eq!(config.tools.sandbox_root, None);
+        assert_eq!(config.network.proxy, None);
+        assert_eq!(config.observability.events_file, None);
+        assert_eq!(config.tools.exec.allow_patterns, vec![r"^ls\b".to_string()]);
+    }
+
+    #[test]
+    fn test_unknown_keys_finds_misspellings() {
+        let content = VALID.replace("provider = \"anthropic\"", "provider = \"anthropic\"\nmodles = [\"x\"]")
+            + "\n[thinking]\nbudget_token = 5000\n";
+        let config: Config = toml::from_str(&content).unwrap();
+        let file: toml::Value = content.parse().unwrap();
+        assert_eq!(config.unknown_keys(&file), vec!["llm.modles", "thinking.budget_token"]);
+    }
+}
@@ -6,20 +6,33 @@
 //!   so the agent can file_read them when relevant
 //! - L3 (Cold storage): SQLite imp.db — searchable via exec tool
 
+use crate::client::SystemPrompt;
 use crate::config::imp_home;
-use crate::error::Result;
+use crate::error::{ImpError, Result};
 use crate::project::ProjectInfo;
 use chrono::Local;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Most of the git status section kept in the system prompt.
+const GIT_CONTEXT_MAX_BYTES: usize = 4_000;
+
+/// Most of the pinned files' contents kept in the system prompt, shared
+/// between them in the order they were pinned. The same cap as attachments.
+const PINNED_MAX_BYTES: usize = crate::tools::builtin::ATTACH_MAX_BYTES;
 
 /// Context manager with tiered loading.
 ///
 /// L1 sections are always included in the system prompt.
 /// L2 files are listed with paths and size hints so the agent can load them on demand.
+/// Pinned files are promoted to L1 and re-read every time the prompt is assembled.
+/// So is the git status of `git_root`, when `[context] include_git_diff` is on.
 pub struct ContextManager {
     l1_sections: Vec<ContextSection>,
     l2_manifest: Vec<L2FileInfo>,
+    pinned: Vec<PathBuf>,
+    git_root: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +43,7 @@ struct ContextSection {
 
 #[derive(Debug, Clone)]
 struct L2FileInfo {
+    file: PathBuf,
     path: String,
     heading: String,
     size_hint: String,
@@ -91,7 +105,7 @@ impl ContextManager {
                 project_summary.push_str(&format!("\n**Config:** {}", proj.config_files.join(", ")));
             }
             
-            // Git context available via exec tool — not injected to keep system prompt stable
+            // Git status is only injected with [context] include_git_diff (see git_summary)
 
             l1_sections.push(ContextSection {
                 heading: format!("Current Project — {}", proj.name),
@@ -144,11 +158,87 @@ impl ContextManager {
         Ok(Self {
             l1_sections,
             l2_manifest,
+            pinned: Vec::new(),
+            git_root: None,
         })
     }
 
-    /// Assemble the full system prompt: L1 content + L2 manifest.
-    pub fn assemble_system_prompt(&self) -> String {
+    /// Include the branch and uncommitted changes of the repository at
+    /// `root` in every prompt (`[context] include_git_diff`), or stop with `None`.
+    pub fn set_git_root(&mut self, root: Option<PathBuf>) {
+        self.git_root = root;
+    }
+
+    /// Promote a file to always-loaded. `file` may be a manifest path
+    /// (`~/.imp/ARCHITECTURE.md`), a file name from the manifest
+    /// (`ARCHITECTURE.md`), or any other readable file. Returns the resolved path.
+    pub fn pin(&mut self, file: &str) -> Result<PathBuf> {
+        let path = self.resolve_file(file)?;
+        if !self.pinned.contains(&path) {
+            self.pinned.push(path.clone());
+        }
+        Ok(path)
+    }
+
+    /// Return a pinned file to on-demand loading. Returns the path it unpinned.
+    pub fn unpin(&mut self, file: &str) -> Option<PathBuf> {
+        let index = self.pinned.iter().position(|p| {
+            format_display_path(p) == file
+                || p.as_os_str() == file
+                || p.ends_with(file)
+        })?;
+        Some(self.pinned.remove(index))
+    }
+
+    /// Currently pinned files, in the order they were pinned.
+    pub fn pinned(&self) -> &[PathBuf] {
+        &self.pinned
+    }
+
+    fn resolve_file(&self, file: &str) -> Result<PathBuf> {
+        let file = file.trim();
+        if let Some(entry) = self.l2_manifest.iter().find(|e| e.path == file || e.file.ends_with(file)) {
+            return Ok(entry.file.clone());
+        }
+
+        let path = match file.strip_prefix("~/.imp/") {
+            Some(rest) => imp_home()?.join(rest),
+            None => PathBuf::from(file),
+        };
+        if path.is_file() {
+            Ok(path.canonicalize().unwrap_or(path))
+        } else {
+            Err(ImpError::Context(format!("No context file matching '{}'", file)))
+        }
+    }
+
+    /// Assemble the system prompt: L1 content and the L2 manifest, then the
+    /// sections read fresh each turn (pinned files, git status).
+    pub fn assemble_system_prompt(&self) -> SystemPrompt {
+        let join = |sections: Vec<(String, String)>| {
+            sections
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect::<Vec<_>>()
+                .join("\n\n---\n\n")
+        };
+        let (stable, volatile) = (self.stable_sections(), self.volatile_sections());
+        if stable.is_empty() && volatile.is_empty() {
+            return SystemPrompt::from("You are a personal AI agent with memory and learning capabilities.".to_string());
+        }
+
+        SystemPrompt { stable: join(stable), volatile: join(volatile) }
+    }
+
+    /// The system prompt's sections in order, as (name, text) pairs.
+    pub fn prompt_sections(&self) -> Vec<(String, String)> {
+        let mut sections = self.stable_sections();
+        sections.extend(self.volatile_sections());
+        sections
+    }
+
+    /// Sections that stay the same from turn to turn, cached as a prefix.
+    fn stable_sections(&self) -> Vec<(String, String)> {
         let mut prompt_parts = Vec::new();
 
         // Home directory information (L1)
@@ -164,10 +254,11 @@ impl ContextManager {
                 Use file_read and file_write tools with these ABSOLUTE paths to read and update your context files.",
                 home.display()
             );
-            prompt_parts.push(home_section);
+            prompt_parts.push(("Home directory".to_string(), home_section));
 
             // Self-learning instructions (L1)
-            prompt_parts.push(
+            prompt_parts.push((
+                "Self-learning".to_string(),
                 "# Self-Learning\n\n\
                 You can and should update your own context files to improve over time:\n\
                 - ~/.imp/memory/YYYY-MM-DD.md — daily notes about what you learned\n\
@@ -176,13 +267,15 @@ impl ContextManager {
                 - ~/.imp/MEMORY.md — long-term memory (important things to remember)\n\n\
                 After completing significant work, use file_write to update relevant context files."
                     .to_string(),
-            );
+            ));
 
             // Capabilities overview (L1)
-            prompt_parts.push(
+            prompt_parts.push((
+                "Capabilities".to_string(),
                 "# Your Capabilities\n\n\
                 You have powerful tools — use them proactively:\n\
                 - **file_read / file_write / file_edit** — read, create, and modify files\n\
+                - **file_read_glob** — read several related files (e.g. src/*.rs) in one call\n\
                 - **exec** — run shell commands (build, test, git, scripts, anything)\n\
                 - **spawn_agent** — spin up background sub-agents for parallel work; results auto-inject on completion\n\
                 - **check_agents** — check on running sub-agents (but prefer letting results come to you)\n\
@@ -194,21 +287,29 @@ impl ContextManager {
                 Don't just describe what you'd do — use these tools and actually do it.\n\
                 For independent tasks, spawn sub-agents so they work in parallel while you continue."
                     .to_string(),
-            );
+            ));
         }
 
         // Add all L1 context sections
         for section in &self.l1_sections {
-            prompt_parts.push(format!("# {}\n\n{}", section.heading, section.content));
+            prompt_parts.push((
+                section.heading.clone(),
+                format!("# {}\n\n{}", section.heading, section.content),
+            ));
         }
 
         // Add L2 manifest — tell the agent what's available on-demand
-        if !self.l2_manifest.is_empty() {
+        let available: Vec<&L2FileInfo> = self
+            .l2_manifest
+            .iter()
+            .filter(|e| !self.pinned.contains(&e.file))
+            .collect();
+        if !available.is_empty() {
             let mut manifest = String::from(
                 "# Available Context (load with file_read when relevant)\n\n\
                 These files contain additional context. Read them when the conversation requires it:\n",
             );
-            for entry in &self.l2_manifest {
+            for entry in available {
                 manifest.push_str(&format!(
                     "\n- {} — {} ({})",
                     entry.path, entry.heading, entry.size_hint
@@ -217,14 +318,36 @@ impl ContextManager {
             manifest.push_str(
                 "\n\nUse file_read to access any of these when you need the information.",
             );
-            prompt_parts.push(manifest);
+            prompt_parts.push(("L2 manifest".to_string(), manifest));
         }
 
-        if prompt_parts.is_empty() && self.l1_sections.is_empty() {
-            return "You are a personal AI agent with memory and learning capabilities.".to_string();
+        prompt_parts
+    }
+
+    /// Sections read fresh each turn, after the cache breakpoint.
+    fn volatile_sections(&self) -> Vec<(String, String)> {
+        let mut prompt_parts = Vec::new();
+
+        // Pinned files, read fresh so edits show up on the next turn
+        let mut budget = PINNED_MAX_BYTES;
+        for path in &self.pinned {
+            let display = format_display_path(path);
+            let content = match fs::read_to_string(path) {
+                Ok(content) => truncate_pinned(content.trim(), &mut budget),
+                Err(e) => format!("(could not read file: {})", e),
+            };
+            prompt_parts.push((
+                format!("Pinned — {}", display),
+                format!("# Pinned: {}\n\n{}", display, content),
+            ));
         }
 
-        prompt_parts.join("\n\n---\n\n")
+        // Working tree status, also fresh each turn
+        if let Some(summary) = self.git_root.as_deref().and_then(git_summary) {
+            prompt_parts.push(("Git status".to_string(), format!("# Git Status\n\n{}", summary)));
+        }
+
+        prompt_parts
     }
 
     /// List all loaded L1 section headings (for display).
@@ -324,6 +447,7 @@ fn register_l2_file(path: &Path, heading: &str, manifest: &mut Vec<L2FileInfo>)
             }
         }
         manifest.push(L2FileInfo {
+            file: path.to_path_buf(),
             path: format_display_path(path),
             heading: heading.to_string(),
             size_hint: format_size_hint(size),
@@ -352,6 +476,7 @@ fn register_daily_memory_l2(
             let size = metadata.len();
             if size > 0 {
                 manifest.push(L2FileInfo {
+                    file: path.clone(),
                     path: format_display_path(&path),
                     heading: format!("{} — {} ({})", prefix, date, label),
                     size_hint: format_size_hint(size),
@@ -389,5 +514,123 @@ fn format_display_path(path: &Path) -> String {
     path.display().to_string()
 }
 
-// Git context removed to keep system prompt stable for caching.
-// Agent can use exec tool to run git commands when needed.
+// Git context is opt-in (`[context] include_git_diff`) to keep the system
+// prompt stable for caching. Otherwise the agent runs git through exec.
+
+/// The branch, ahead/behind counts and uncommitted changes of the repository
+/// at `root`, capped at `GIT_CONTEXT_MAX_BYTES`. `None` if it isn't a git
+/// repository or git isn't installed.
+fn git_summary(root: &Path) -> Option<String> {
+    let run = |args: &[&str]| -> Option<String> {
+        let output = Command::new("git").args(args).current_dir(root).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    let status = run(&["status", "--porcelain", "--branch"])?;
+    let mut lines = status.lines();
+    let mut summary = match lines.next().and_then(|l| l.strip_prefix("## ")) {
+        Some(branch) => format!("**Branch:** {}", describe_branch(branch)),
+        None => "**Branch:** unknown".to_string(),
+    };
+
+    let untracked: Vec<&str> = lines.clone().filter_map(|l| l.strip_prefix("?? ")).collect();
+    let changed = lines.filter(|l| !l.starts_with("??")).count();
+    if changed == 0 && untracked.is_empty() {
+        summary.push_str("\n\nWorking tree clean.");
+        return Some(summary);
+    }
+
+    // Staged and unstaged changes against HEAD (fails before the first commit)
+    if changed > 0 {
+        match run(&["diff", "--stat", "HEAD"]) {
+            Some(stat) if !stat.trim().is_empty() => {
+                summary.push_str(&format!("\n\nUncommitted changes:\n```\n{}\n```", stat.trim_end()));
+            }
+            _ => summary.push_str(&format!("\n\n{} file(s) with uncommitted changes.", changed)),
+        }
+    }
+    if !untracked.is_empty() {
+        summary.push_str(&format!("\n\nUntracked: {}", untracked.join(", ")));
+    }
+
+    if summary.len() > GIT_CONTEXT_MAX_BYTES {
+        let mut end = GIT_CONTEXT_MAX_BYTES;
+        while !summary.is_char_boundary(end) {
+            end -= 1;
+        }
+        summary.truncate(end);
+        summary.push_str("…\n(truncated — run `git status` for the full list)");
+    }
+    Some(summary)
+}
+
+/// `content` cut at a line boundary to fit `budget` bytes, which is reduced
+/// by what's kept. A cut file ends with a note saying so.
+fn truncate_pinned(content: &str, budget: &mut usize) -> String {
+    if content.len() <= *budget {
+        *budget -= content.len();
+        return content.to_string();
+    }
+    let mut end = *budget;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    let kept = &content[..content[..end].rfind('\n').unwrap_or(0)];
+    *budget -= kept.len();
+    format!(
+        "{}\n…\n(truncated — pinned files are capped at {} bytes in total; use file_read for the rest)",
+        kept, PINNED_MAX_BYTES
+    )
+}
+
+/// Turn the `## ` header of `git status --porcelain --branch`
+/// (`main...origin/main [ahead 2, behind 1]`) into
+/// `main (tracking origin/main, ahead 2, behind 1)`.
+fn describe_branch(header: &str) -> String {
+    let (refs, counts) = match header.split_once(" [") {
+        Some((refs, counts)) => (refs, Some(counts.trim_end_matches(']'))),
+        None => (header, None),
+    };
+    let mut details = Vec::new();
+    let branch = match refs.split_once("...") {
+        Some((branch, upstream)) => {
+            details.push(format!("tracking {}", upstream));
+            branch
+        }
+        None => refs,
+    };
+    details.extend(counts.map(String::from));
+    if details.is_empty() {
+        branch.to_string()
+    } else {
+        format!("{} ({})", branch, details.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_branch() {
+        assert_eq!(
+            describe_branch("main...origin/main [ahead 2, behind 1]"),
+            "main (tracking origin/main, ahead 2, behind 1)"
+        );
+        assert_eq!(describe_branch("feature...origin/feature"), "feature (tracking origin/feature)");
+        assert_eq!(describe_branch("scratch"), "scratch");
+        assert_eq!(describe_branch("No commits yet on main"), "No commits yet on main");
+    }
+
+    #[test]
+    fn test_truncate_pinned_shares_the_budget() {
+        let mut budget = 21;
+        assert_eq!(truncate_pinned("line one\nline two", &mut budget), "line one\nline two");
+        assert_eq!(budget, 4);
+
+        let cut = truncate_pinned("abc\ndefgh", &mut budget);
+        assert!(cut.starts_with("abc\n…\n(truncated"), "{}", cut);
+        assert_eq!(budget, 1);
+        assert!(truncate_pinned("more", &mut budget).starts_with("\n…\n(truncated"));
+    }
+}
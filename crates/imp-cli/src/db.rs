@@ -1,7 +1,9 @@
 use crate::client::Message;
 use crate::error::{ImpError, Result};
-use rusqlite::{params, Connection};
+use crate::usage::ModelUsage;
+use rusqlite::{params, Connection, OptionalExtension};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 
 /// Extract human-readable text from a message's JSON content.
 /// Handles both plain string content and structured content blocks,
@@ -38,6 +40,180 @@ pub struct SessionInfo {
     pub updated_at: String,
     pub title: Option<String>,
     pub message_count: i64,
+    /// The session this one was forked from, if any.
+    pub parent_session_id: Option<String>,
+}
+
+/// Columns selected for every `SessionInfo` query, in `session_from_row` order.
+const SESSION_COLUMNS: &str =
+    "id, project, workdir, created_at, updated_at, title, message_count, parent_session_id";
+
+fn session_from_row(row: &rusqlite::Row) -> rusqlite::Result<SessionInfo> {
+    Ok(SessionInfo {
+        id: row.get(0)?,
+        project: row.get(1)?,
+        workdir: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        title: row.get(5)?,
+        message_count: row.get(6)?,
+        parent_session_id: row.get(7)?,
+    })
+}
+
+/// A persisted per-session, per-model usage row.
+pub struct UsageRecord {
+    pub session_id: String,
+    pub usage: ModelUsage,
+    /// RFC 3339 timestamp (UTC) of when the session's usage was saved.
+    pub recorded_at: String,
+}
+
+/// One session's readable conversation on a given day, for reflect.
+pub struct DayConversation {
+    pub session_id: String,
+    pub title: String,
+    pub text: String,
+    /// Id of the session's last message that day; reflect records it so the
+    /// next run only reads what came after.
+    pub last_message_id: i64,
+    /// True when earlier messages from that day were already reflected on
+    /// and `text` only holds the new ones.
+    pub continued: bool,
+}
+
+/// A session matching a full-text search, with its best-matching snippet.
+pub struct SearchHit {
+    pub session: SessionInfo,
+    pub snippet: String,
+}
+
+/// Turn free text into an FTS5 query: each word becomes a quoted phrase
+/// (so punctuation and operators are taken literally), all required.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Output format for `Database::export_session`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Markdown,
+    Json,
+}
+
+impl std::str::FromStr for TranscriptFormat {
+    type Err = ImpError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "md" | "markdown" => Ok(TranscriptFormat::Markdown),
+            "json" => Ok(TranscriptFormat::Json),
+            other => Err(ImpError::Config(format!(
+                "Unknown transcript format '{}' (expected 'md' or 'json')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Render one stored message as Markdown. Text is kept as-is, tool calls
+/// show their name and JSON input, tool results are fenced, and thinking
+/// goes in a collapsible `<details>` block (or is dropped).
+fn message_to_markdown(role: &str, content: &Value, include_thinking: bool) -> String {
+    let blocks = match content {
+        Value::String(text) => return format_turn(role_heading(role, false), text),
+        Value::Array(blocks) => blocks,
+        _ => return String::new(),
+    };
+
+    let only_tool_results = !blocks.is_empty()
+        && blocks
+            .iter()
+            .all(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"));
+
+    let mut parts: Vec<String> = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                    if !text.trim().is_empty() {
+                        parts.push(text.trim().to_string());
+                    }
+                }
+            }
+            Some("tool_use") => {
+                let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+                let input = block
+                    .get("input")
+                    .map(|i| serde_json::to_string_pretty(i).unwrap_or_default())
+                    .unwrap_or_default();
+                parts.push(format!("**Tool call:** `{}`\n\n```json\n{}\n```", name, input));
+            }
+            Some("tool_result") => {
+                let is_error = block.get("is_error").and_then(|e| e.as_bool()).unwrap_or(false);
+                let text = match block.get("content") {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Array(items)) => items
+                        .iter()
+                        .filter_map(|i| i.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => String::new(),
+                };
+                let label = if is_error { "**Tool result (error):**" } else { "**Tool result:**" };
+                parts.push(format!("{}\n\n```\n{}\n```", label, text.trim_end()));
+            }
+            Some("thinking") if include_thinking => {
+                if let Some(thinking) = block.get("thinking").and_then(|t| t.as_str()) {
+                    parts.push(format!(
+                        "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>",
+                        thinking.trim()
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if parts.is_empty() {
+        return String::new();
+    }
+    format_turn(role_heading(role, only_tool_results), &parts.join("\n\n"))
+}
+
+fn role_heading(role: &str, only_tool_results: bool) -> &'static str {
+    match (role, only_tool_results) {
+        (_, true) => "Tool results",
+        ("user", _) => "User",
+        ("assistant", _) => "Assistant",
+        _ => "System",
+    }
+}
+
+fn format_turn(heading: &str, body: &str) -> String {
+    format!("### {}\n\n{}\n\n", heading, body)
+}
+
+/// Drop thinking blocks from a message's content (for JSON exports).
+fn strip_thinking(content: Value) -> Value {
+    match content {
+        Value::Array(blocks) => Value::Array(
+            blocks
+                .into_iter()
+                .filter(|b| {
+                    !matches!(
+                        b.get("type").and_then(|t| t.as_str()),
+                        Some("thinking") | Some("redacted_thinking")
+                    )
+                })
+                .collect(),
+        ),
+        other => other,
+    }
 }
 
 pub struct Database {
@@ -53,7 +229,17 @@ impl Database {
 
         let conn =
             Connection::open(&db_path).map_err(|e| ImpError::Database(e.to_string()))?;
+        Self::with_connection(conn)
+    }
+
+    /// A throwaway database, for tests outside this module.
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(|e| ImpError::Database(e.to_string()))?)
+    }
 
+    /// Run migrations on `conn` and wrap it.
+    fn with_connection(conn: Connection) -> Result<Self> {
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS sessions (
                 id TEXT PRIMARY KEY,
@@ -72,14 +258,142 @@ impl Database {
                 created_at TEXT NOT NULL,
                 tool_calls INTEGER DEFAULT 0
             );
-            CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);",
+            CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
+            CREATE TABLE IF NOT EXISTS usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL REFERENCES sessions(id),
+                model TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                cache_creation_tokens INTEGER NOT NULL,
+                cache_read_tokens INTEGER NOT NULL,
+                requests INTEGER NOT NULL,
+                cost REAL,
+                recorded_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_usage_recorded ON usage(recorded_at);
+            CREATE TABLE IF NOT EXISTS reflected_sessions (
+                date TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                last_message_id INTEGER NOT NULL,
+                reflected_at TEXT NOT NULL,
+                PRIMARY KEY (date, session_id)
+            );",
         )
         .map_err(|e| ImpError::Database(e.to_string()))?;
 
         // Migration: add workdir column to existing databases
         let _ = conn.execute("ALTER TABLE sessions ADD COLUMN workdir TEXT", []);
+        // Migration: track which session a fork was branched from
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN parent_session_id TEXT", []);
+        // Migration: context files pinned into the system prompt (JSON array of paths)
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN pinned_files TEXT", []);
+        // Migration: files the agent wrote or edited (JSON object of path → operations)
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN files_changed TEXT", []);
+        // Migration: messages replaced by /retry are kept but no longer loaded
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN superseded INTEGER NOT NULL DEFAULT 0", []);
+        // Migration: messages kept verbatim through compaction
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN kept INTEGER NOT NULL DEFAULT 0", []);
+
+        // Full-text index over the readable text of messages (tool calls,
+        // tool results and thinking are left out so searches hit conversation)
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                text,
+                session_id UNINDEXED,
+                message_id UNINDEXED
+            );",
+        )
+        .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        let db = Self { conn };
+        db.backfill_search_index()?;
+        Ok(db)
+    }
+
+    /// Index messages stored before the search index existed. Runs once:
+    /// after that the index is kept in sync by `save_message`.
+    fn backfill_search_index(&self) -> Result<()> {
+        let indexed: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM messages_fts", [], |row| row.get(0))
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        if indexed > 0 {
+            return Ok(());
+        }
+        self.index_messages("SELECT id, session_id, content FROM messages", params![])
+    }
+
+    /// Add the messages selected by `query` (id, session_id, content) to the
+    /// search index.
+    fn index_messages(&self, query: &str, query_params: impl rusqlite::Params) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare(query)
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        let rows: Vec<(i64, String, String)> = stmt
+            .query_map(query_params, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
 
-        Ok(Self { conn })
+        for (message_id, session_id, content_json) in rows {
+            self.index_message(message_id, &session_id, &content_json)?;
+        }
+        Ok(())
+    }
+
+    fn index_message(&self, message_id: i64, session_id: &str, content_json: &str) -> Result<()> {
+        let text = extract_readable_text(content_json);
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        self.conn
+            .execute(
+                "INSERT INTO messages_fts (text, session_id, message_id) VALUES (?1, ?2, ?3)",
+                params![text, session_id, message_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Full-text search over all stored sessions. Returns up to `limit`
+    /// sessions, best match first, each with a snippet of its best message.
+    pub fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let fts = fts_query(query);
+        if fts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT session_id, snippet(messages_fts, 0, '[', ']', '…', 12) \
+                 FROM messages_fts WHERE messages_fts MATCH ?1 \
+                 ORDER BY rank LIMIT ?2",
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        // Over-fetch messages, since several may come from the same session
+        let matches: Vec<(String, String)> = stmt
+            .query_map(params![fts, (limit * 20) as i64], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for (session_id, snippet) in matches {
+            if hits.len() >= limit {
+                break;
+            }
+            if hits.iter().any(|h| h.session.id == session_id) {
+                continue;
+            }
+            if let Some(session) = self.get_session_by_id(&session_id)? {
+                hits.push(SearchHit { session, snippet: snippet.replace('\n', " ") });
+            }
+        }
+        Ok(hits)
     }
 
     /// Create a new session row and return its UUID.
@@ -95,6 +409,49 @@ impl Database {
         Ok(id)
     }
 
+    /// Fork `parent_id`: create a new session with the same project, workdir
+    /// and title, copying the parent's first `upto` messages into it.
+    /// Returns the new session's UUID.
+    pub fn create_session_from(&self, parent_id: &str, upto: usize) -> Result<String> {
+        let parent = self
+            .get_session_by_id(parent_id)?
+            .ok_or_else(|| ImpError::Database(format!("Session not found: {}", parent_id)))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn
+            .execute(
+                "INSERT INTO sessions (id, project, workdir, created_at, updated_at, title, parent_session_id, pinned_files, files_changed) \
+                 SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, pinned_files, files_changed FROM sessions WHERE id = ?7",
+                params![id, parent.project, parent.workdir, now, now, parent.title, parent_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        let copied = self
+            .conn
+            .execute(
+                "INSERT INTO messages (session_id, role, content, created_at, tool_calls, kept) \
+                 SELECT ?1, role, content, created_at, tool_calls, kept FROM messages \
+                 WHERE session_id = ?2 AND superseded = 0 ORDER BY id ASC LIMIT ?3",
+                params![id, parent_id, i64::try_from(upto).unwrap_or(i64::MAX)],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        self.conn
+            .execute(
+                "UPDATE sessions SET message_count = ?1 WHERE id = ?2",
+                params![copied as i64, id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        self.index_messages(
+            "SELECT id, session_id, content FROM messages WHERE session_id = ?1",
+            params![id],
+        )?;
+
+        Ok(id)
+    }
+
     /// Persist a single message (user or assistant) into the database.
     pub fn save_message(
         &self,
@@ -112,6 +469,7 @@ impl Database {
                 params![session_id, role, content_json, now, tool_calls as i64],
             )
             .map_err(|e| ImpError::Database(e.to_string()))?;
+        self.index_message(self.conn.last_insert_rowid(), session_id, &content_json)?;
 
         self.conn
             .execute(
@@ -123,52 +481,133 @@ impl Database {
         Ok(())
     }
 
+    /// Mark a session's last `count` live messages as superseded (by `/retry`).
+    /// They stay in the database but are no longer loaded, exported or searched.
+    pub fn supersede_last_messages(&self, session_id: &str, count: usize) -> Result<()> {
+        let ids: Vec<i64> = {
+            let mut stmt = self
+                .conn
+                .prepare(
+                    "SELECT id FROM messages WHERE session_id = ?1 AND superseded = 0 \
+                     ORDER BY id DESC LIMIT ?2",
+                )
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![session_id, count as i64], |row| row.get(0))
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+            rows.filter_map(|r| r.ok()).collect()
+        };
+
+        for id in &ids {
+            self.conn
+                .execute("UPDATE messages SET superseded = 1 WHERE id = ?1", params![id])
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+            self.conn
+                .execute("DELETE FROM messages_fts WHERE message_id = ?1", params![id])
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+        }
+        self.conn
+            .execute(
+                "UPDATE sessions SET message_count = max(message_count - ?1, 0) WHERE id = ?2",
+                params![ids.len() as i64, session_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Replace a session's last `count` live messages with `messages`, e.g. to
+    /// save a repaired history. The old rows are superseded, as for `/retry`.
+    pub fn replace_last_messages(&self, session_id: &str, count: usize, messages: &[Message]) -> Result<()> {
+        self.supersede_last_messages(session_id, count)?;
+        for message in messages {
+            let tool_calls = message
+                .content
+                .as_array()
+                .map_or(0, |blocks| blocks.iter().filter(|b| b["type"] == "tool_use").count());
+            self.save_message(session_id, &message.role, &message.content, tool_calls)?;
+            if message.keep {
+                self.conn
+                    .execute(
+                        "UPDATE messages SET kept = 1 WHERE id = (SELECT max(id) FROM messages WHERE session_id = ?1)",
+                        params![session_id],
+                    )
+                    .map_err(|e| ImpError::Database(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
     /// Reload every message for a session, ordered by insertion.
     pub fn load_session_messages(&self, session_id: &str) -> Result<Vec<Message>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT role, content FROM messages WHERE session_id = ?1 ORDER BY id ASC")
+            .prepare("SELECT role, content, kept FROM messages WHERE session_id = ?1 AND superseded = 0 ORDER BY id ASC")
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let rows = stmt
             .query_map(params![session_id], |row| {
                 let role: String = row.get(0)?;
                 let content_json: String = row.get(1)?;
-                Ok((role, content_json))
+                let kept: bool = row.get(2)?;
+                Ok((role, content_json, kept))
             })
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let mut result = Vec::new();
         for row in rows {
-            let (role, content_json) = row.map_err(|e| ImpError::Database(e.to_string()))?;
+            let (role, content_json, kept) = row.map_err(|e| ImpError::Database(e.to_string()))?;
             let content: Value = serde_json::from_str(&content_json)?;
-            result.push(Message::with_content(&role, content));
+            let mut message = Message::with_content(&role, content);
+            message.keep = kept;
+            result.push(message);
         }
         Ok(result)
     }
 
+    /// Mark the session's latest user prompt (not a tool result) to be kept
+    /// through compaction. Returns false if there is no prompt yet.
+    pub fn keep_last_prompt(&self, session_id: &str) -> Result<bool> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, content FROM messages WHERE session_id = ?1 AND role = 'user' AND superseded = 0 \
+                 ORDER BY id DESC",
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![session_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        for row in rows {
+            let (id, content_json) = row.map_err(|e| ImpError::Database(e.to_string()))?;
+            let content: Value = serde_json::from_str(&content_json)?;
+            let is_tool_result = content
+                .as_array()
+                .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"));
+            if !is_tool_result {
+                self.conn
+                    .execute("UPDATE messages SET kept = 1 WHERE id = ?1", params![id])
+                    .map_err(|e| ImpError::Database(e.to_string()))?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// List the most recent sessions, newest first.
     pub fn list_sessions(&self, limit: usize) -> Result<Vec<SessionInfo>> {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT id, project, workdir, created_at, updated_at, title, message_count \
-                 FROM sessions ORDER BY updated_at DESC LIMIT ?1",
+                &format!(
+                    "SELECT {} FROM sessions ORDER BY updated_at DESC LIMIT ?1",
+                    SESSION_COLUMNS
+                ),
             )
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map(params![limit as i64], |row| {
-                Ok(SessionInfo {
-                    id: row.get(0)?,
-                    project: row.get(1)?,
-                    workdir: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                    title: row.get(5)?,
-                    message_count: row.get(6)?,
-                })
-            })
+            .query_map(params![limit as i64], session_from_row)
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let mut result = Vec::new();
@@ -185,25 +624,17 @@ impl Database {
                 let mut stmt = self
                     .conn
                     .prepare(
-                        "SELECT id, project, workdir, created_at, updated_at, title, message_count \
-                         FROM sessions WHERE project = ?1 \
-                         AND project NOT LIKE 'subagent-%' \
-                         ORDER BY updated_at DESC LIMIT 1",
+                        &format!(
+                            "SELECT {} FROM sessions WHERE project = ?1 \
+                             AND project NOT LIKE 'subagent-%' \
+                             ORDER BY updated_at DESC LIMIT 1",
+                            SESSION_COLUMNS
+                        ),
                     )
                     .map_err(|e| ImpError::Database(e.to_string()))?;
 
                 let mut rows = stmt
-                    .query_map(params![p], |row| {
-                        Ok(SessionInfo {
-                            id: row.get(0)?,
-                            project: row.get(1)?,
-                            workdir: row.get(2)?,
-                            created_at: row.get(3)?,
-                            updated_at: row.get(4)?,
-                            title: row.get(5)?,
-                            message_count: row.get(6)?,
-                        })
-                    })
+                    .query_map(params![p], session_from_row)
                     .map_err(|e| ImpError::Database(e.to_string()))?;
 
                 match rows.next() {
@@ -215,25 +646,17 @@ impl Database {
                 let mut stmt = self
                     .conn
                     .prepare(
-                        "SELECT id, project, workdir, created_at, updated_at, title, message_count \
-                         FROM sessions WHERE project IS NULL \
-                         OR project NOT LIKE 'subagent-%' \
-                         ORDER BY updated_at DESC LIMIT 1",
+                        &format!(
+                            "SELECT {} FROM sessions WHERE project IS NULL \
+                             OR project NOT LIKE 'subagent-%' \
+                             ORDER BY updated_at DESC LIMIT 1",
+                            SESSION_COLUMNS
+                        ),
                     )
                     .map_err(|e| ImpError::Database(e.to_string()))?;
 
                 let mut rows = stmt
-                    .query_map([], |row| {
-                        Ok(SessionInfo {
-                            id: row.get(0)?,
-                            project: row.get(1)?,
-                            workdir: row.get(2)?,
-                            created_at: row.get(3)?,
-                            updated_at: row.get(4)?,
-                            title: row.get(5)?,
-                            message_count: row.get(6)?,
-                        })
-                    })
+                    .query_map([], session_from_row)
                     .map_err(|e| ImpError::Database(e.to_string()))?;
 
                 match rows.next() {
@@ -255,24 +678,16 @@ impl Database {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT id, project, workdir, created_at, updated_at, title, message_count \
-                 FROM sessions WHERE project = ?1 AND id != ?2 AND message_count > 0 \
-                 ORDER BY updated_at DESC LIMIT ?3",
+                &format!(
+                    "SELECT {} FROM sessions WHERE project = ?1 AND id != ?2 AND message_count > 0 \
+                     ORDER BY updated_at DESC LIMIT ?3",
+                    SESSION_COLUMNS
+                ),
             )
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map(params![project, exclude_id, limit as i64], |row| {
-                Ok(SessionInfo {
-                    id: row.get(0)?,
-                    project: row.get(1)?,
-                    workdir: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                    title: row.get(5)?,
-                    message_count: row.get(6)?,
-                })
-            })
+            .query_map(params![project, exclude_id, limit as i64], session_from_row)
             .map_err(|e| ImpError::Database(e.to_string()))?;
 
         let mut result = Vec::new();
@@ -283,10 +698,14 @@ impl Database {
     }
 
     /// Load all conversations for a given date, formatted as readable text.
-    /// Returns a vec of (session_title, conversation_text) pairs.
     /// Extracts only human-readable content (user text + assistant text),
-    /// skipping tool_use, tool_result, and thinking blocks.
-    pub fn load_conversations_for_date(&self, date: &str) -> Result<Vec<(String, String)>> {
+    /// skipping tool_use, tool_result, and thinking blocks. For sessions in
+    /// `after` (session id → message id), only later messages are loaded.
+    pub fn load_conversations_for_date(
+        &self,
+        date: &str,
+        after: &HashMap<String, i64>,
+    ) -> Result<Vec<DayConversation>> {
         // Find sessions active on this date
         let mut session_stmt = self.conn.prepare(
             "SELECT id, title FROM sessions \
@@ -310,28 +729,29 @@ impl Database {
                 continue;
             }
 
+            let seen_up_to = after.get(&session_id).copied();
             let mut msg_stmt = self.conn.prepare(
-                "SELECT role, content FROM messages \
-                 WHERE session_id = ?1 AND date(created_at) = ?2 \
+                "SELECT id, role, content FROM messages \
+                 WHERE session_id = ?1 AND date(created_at) = ?2 AND superseded = 0 AND id > ?3 \
                  ORDER BY id ASC"
             ).map_err(|e| ImpError::Database(e.to_string()))?;
 
-            let messages: Vec<(String, String)> = msg_stmt
-                .query_map(params![session_id, date], |row| {
-                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            let messages: Vec<(i64, String, String)> = msg_stmt
+                .query_map(params![session_id, date, seen_up_to.unwrap_or(0)], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
                 })
                 .map_err(|e| ImpError::Database(e.to_string()))?
                 .filter_map(|r| r.ok())
                 .collect();
 
-            if messages.is_empty() {
+            let Some(&(last_message_id, _, _)) = messages.last() else {
                 continue;
-            }
+            };
 
             let display_title = title.unwrap_or_else(|| session_id[..8.min(session_id.len())].to_string());
             let mut conv_text = String::new();
 
-            for (role, content_json) in &messages {
+            for (_, role, content_json) in &messages {
                 let text = extract_readable_text(content_json);
                 if text.is_empty() {
                     continue;
@@ -341,13 +761,53 @@ impl Database {
             }
 
             if !conv_text.is_empty() {
-                conversations.push((display_title, conv_text));
+                conversations.push(DayConversation {
+                    session_id,
+                    title: display_title,
+                    text: conv_text,
+                    last_message_id,
+                    continued: seen_up_to.is_some(),
+                });
             }
         }
 
         Ok(conversations)
     }
 
+    /// Sessions already reflected on for `date`: session id → the last
+    /// message id that was included.
+    pub fn reflected_sessions(&self, date: &str) -> Result<HashMap<String, i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT session_id, last_message_id FROM reflected_sessions WHERE date = ?1")
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![date], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        let mut reflected = HashMap::new();
+        for row in rows {
+            let (session_id, last_message_id) = row.map_err(|e| ImpError::Database(e.to_string()))?;
+            reflected.insert(session_id, last_message_id);
+        }
+        Ok(reflected)
+    }
+
+    /// Record that these conversations are now part of `date`'s memory file.
+    pub fn mark_reflected(&self, date: &str, conversations: &[DayConversation]) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        for conversation in conversations {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO reflected_sessions (date, session_id, last_message_id, reflected_at) \
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![date, conversation.session_id, conversation.last_message_id, now],
+                )
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+        }
+        Ok(())
+    }
+
     /// Set a human-readable title on a session.
     pub fn update_session_title(&self, session_id: &str, title: &str) -> Result<()> {
         self.conn
@@ -359,6 +819,155 @@ impl Database {
         Ok(())
     }
 
+    /// Save the files pinned into a session's system prompt.
+    pub fn set_session_pins(&self, session_id: &str, pins: &[String]) -> Result<()> {
+        let pins_json = serde_json::to_string(pins)?;
+        self.conn
+            .execute(
+                "UPDATE sessions SET pinned_files = ?1 WHERE id = ?2",
+                params![pins_json, session_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load the files pinned into a session's system prompt.
+    pub fn load_session_pins(&self, session_id: &str) -> Result<Vec<String>> {
+        let pins_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT pinned_files FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .flatten();
+        match pins_json {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Save the files the agent changed in a session, with the operations
+    /// (`write`, `edit`) done to each.
+    pub fn set_session_files(&self, session_id: &str, files: &BTreeMap<String, Vec<String>>) -> Result<()> {
+        let files_json = serde_json::to_string(files)?;
+        self.conn
+            .execute(
+                "UPDATE sessions SET files_changed = ?1 WHERE id = ?2",
+                params![files_json, session_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// If the directory `session_id` was started in no longer exists (its
+    /// project was moved, renamed or deleted), re-tag the session with
+    /// `project` and `workdir` so it's found from there from now on.
+    /// Returns the session as it was, if it was re-tagged.
+    pub fn relocate_session_if_missing(
+        &self,
+        session_id: &str,
+        project: Option<&str>,
+        workdir: Option<&str>,
+    ) -> Result<Option<SessionInfo>> {
+        let Some(session) = self.get_session_by_id(session_id)? else {
+            return Ok(None);
+        };
+        match session.workdir.as_deref() {
+            Some(dir) if !std::path::Path::new(dir).is_dir() => {}
+            _ => return Ok(None),
+        }
+        self.conn
+            .execute(
+                "UPDATE sessions SET project = ?1, workdir = ?2 WHERE id = ?3",
+                params![project, workdir, session_id],
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        Ok(Some(session))
+    }
+
+    /// Load the files the agent changed in a session.
+    pub fn load_session_files(&self, session_id: &str) -> Result<BTreeMap<String, Vec<String>>> {
+        let files_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT files_changed FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .flatten();
+        match files_json {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Persist a session's usage, one row per model used.
+    pub fn save_usage(&self, session_id: &str, usage: &[ModelUsage]) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        for u in usage.iter().filter(|u| u.requests > 0) {
+            self.conn
+                .execute(
+                    "INSERT INTO usage (session_id, model, input_tokens, output_tokens, \
+                     cache_creation_tokens, cache_read_tokens, requests, cost, recorded_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        session_id,
+                        u.model,
+                        u.input_tokens as i64,
+                        u.output_tokens as i64,
+                        u.cache_creation_tokens as i64,
+                        u.cache_read_tokens as i64,
+                        u.requests,
+                        u.cost,
+                        now
+                    ],
+                )
+                .map_err(|e| ImpError::Database(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Load usage rows recorded at or after `since` (RFC 3339), optionally
+    /// limited to sessions of one project. Oldest first.
+    pub fn load_usage(&self, since: Option<&str>, project: Option<&str>) -> Result<Vec<UsageRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT u.session_id, u.model, u.input_tokens, u.output_tokens, \
+             u.cache_creation_tokens, u.cache_read_tokens, u.requests, u.cost, u.recorded_at \
+             FROM usage u LEFT JOIN sessions s ON s.id = u.session_id \
+             WHERE (?1 IS NULL OR u.recorded_at >= ?1) AND (?2 IS NULL OR s.project = ?2) \
+             ORDER BY u.recorded_at"
+        ).map_err(|e| ImpError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![since, project], |row| {
+                Ok(UsageRecord {
+                    session_id: row.get(0)?,
+                    usage: ModelUsage {
+                        model: row.get(1)?,
+                        input_tokens: row.get::<_, i64>(2)? as u64,
+                        output_tokens: row.get::<_, i64>(3)? as u64,
+                        cache_creation_tokens: row.get::<_, i64>(4)? as u64,
+                        cache_read_tokens: row.get::<_, i64>(5)? as u64,
+                        requests: row.get(6)?,
+                        cost: row.get(7)?,
+                    },
+                    recorded_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| ImpError::Database(e.to_string()))?);
+        }
+        Ok(result)
+    }
+
     /// List sessions created/updated on a specific date.
     /// Returns (session_id, project, workdir, created_at) tuples.
     pub fn list_sessions_for_date(&self, date: &str) -> Result<Vec<(String, Option<String>, Option<String>, String)>> {
@@ -386,24 +995,121 @@ impl Database {
         Ok(result)
     }
 
+    /// Find a session by full ID or unique ID prefix. Errors if the prefix
+    /// matches more than one session.
+    pub fn find_session_by_prefix(&self, prefix: &str) -> Result<Option<SessionInfo>> {
+        if let Some(info) = self.get_session_by_id(prefix)? {
+            return Ok(Some(info));
+        }
+
+        let mut stmt = self.conn.prepare(
+            &format!(
+                "SELECT {} FROM sessions WHERE id LIKE ?1 || '%' ESCAPE '\\' ORDER BY updated_at DESC LIMIT 2",
+                SESSION_COLUMNS
+            )
+        ).map_err(|e| ImpError::Database(e.to_string()))?;
+
+        // `%` and `_` in the prefix are literal characters, not wildcards
+        let pattern = prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let mut matches: Vec<SessionInfo> = stmt
+            .query_map(params![pattern], session_from_row)
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches.pop()),
+            _ => Err(ImpError::Database(format!(
+                "Session prefix '{}' is ambiguous — use more characters",
+                prefix
+            ))),
+        }
+    }
+
+    /// Reconstruct a session as a readable transcript (Markdown) or as
+    /// structured JSON. Thinking blocks are folded (Markdown) or kept (JSON)
+    /// unless `include_thinking` is false, in which case they're omitted.
+    pub fn export_session(
+        &self,
+        session_id: &str,
+        format: TranscriptFormat,
+        include_thinking: bool,
+    ) -> Result<String> {
+        let session = self
+            .get_session_by_id(session_id)?
+            .ok_or_else(|| ImpError::Database(format!("Session not found: {}", session_id)))?;
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT role, content, created_at FROM messages \
+                 WHERE session_id = ?1 AND superseded = 0 ORDER BY id ASC",
+            )
+            .map_err(|e| ImpError::Database(e.to_string()))?;
+        let rows: Vec<(String, String, String)> = stmt
+            .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| ImpError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        match format {
+            TranscriptFormat::Json => {
+                let messages: Vec<Value> = rows
+                    .into_iter()
+                    .map(|(role, content_json, created_at)| {
+                        let content: Value = serde_json::from_str(&content_json).unwrap_or(Value::Null);
+                        let content = if include_thinking { content } else { strip_thinking(content) };
+                        serde_json::json!({
+                            "role": role,
+                            "created_at": created_at,
+                            "content": content,
+                        })
+                    })
+                    .collect();
+
+                let transcript = serde_json::json!({
+                    "session": {
+                        "id": session.id,
+                        "title": session.title,
+                        "project": session.project,
+                        "workdir": session.workdir,
+                        "created_at": session.created_at,
+                        "updated_at": session.updated_at,
+                        "parent_session_id": session.parent_session_id,
+                    },
+                    "messages": messages,
+                });
+                Ok(serde_json::to_string_pretty(&transcript)?)
+            }
+            TranscriptFormat::Markdown => {
+                let title = session.title.as_deref().filter(|t| !t.is_empty()).unwrap_or("Untitled session");
+                let mut out = format!("# {}\n\n", title);
+                out.push_str(&format!("- Session: `{}`\n", session.id));
+                if let Some(ref project) = session.project {
+                    out.push_str(&format!("- Project: {}\n", project));
+                }
+                if let Some(ref parent) = session.parent_session_id {
+                    out.push_str(&format!("- Forked from: `{}`\n", parent));
+                }
+                out.push_str(&format!("- Started: {}\n\n---\n\n", session.created_at));
+
+                for (role, content_json, _) in rows {
+                    let content: Value = serde_json::from_str(&content_json).unwrap_or(Value::Null);
+                    out.push_str(&message_to_markdown(&role, &content, include_thinking));
+                }
+                Ok(out)
+            }
+        }
+    }
+
     /// Get a session by its ID.
     pub fn get_session_by_id(&self, session_id: &str) -> Result<Option<SessionInfo>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, project, workdir, created_at, updated_at, title, message_count \
-             FROM sessions WHERE id = ?1"
+            &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS)
         ).map_err(|e| ImpError::Database(e.to_string()))?;
 
-        let result = stmt.query_row(params![session_id], |row| {
-            Ok(SessionInfo {
-                id: row.get(0)?,
-                project: row.get(1)?,
-                workdir: row.get(2)?,
-                created_at: row.get(3)?,
-                updated_at: row.get(4)?,
-                title: row.get(5)?,
-                message_count: row.get(6)?,
-            })
-        });
+        let result = stmt.query_row(params![session_id], session_from_row);
 
         match result {
             Ok(info) => Ok(Some(info)),
@@ -430,3 +1136,174 @@ impl Database {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_thinking_blocks_survive_reload() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+
+        let content = json!([
+            {
+                "type": "thinking",
+                "thinking": "The user wants the tests run.\nUse exec — \"cargo test\".",
+                "signature": "EqQBCkYIBxgCKkDx+/9aZ3Q==\\n/tail"
+            },
+            { "type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix/LafPsn4a" },
+            { "type": "text", "text": "Running the tests." },
+            { "type": "tool_use", "id": "toolu_01", "name": "exec", "input": { "command": "cargo test" } }
+        ]);
+        db.save_message(&session, "assistant", &content, 1).unwrap();
+
+        let messages = db.load_session_messages(&session).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "assistant");
+        assert_eq!(messages[0].content, content);
+        assert_eq!(
+            serde_json::to_string(&messages[0].content).unwrap(),
+            serde_json::to_string(&content).unwrap()
+        );
+        assert_eq!(
+            messages[0].content[0]["signature"].as_str().unwrap(),
+            "EqQBCkYIBxgCKkDx+/9aZ3Q==\\n/tail"
+        );
+    }
+
+    #[test]
+    fn test_kept_prompt_survives_reload() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+        assert!(!db.keep_last_prompt(&session).unwrap());
+
+        db.save_message(&session, "user", &json!("never touch the auth module"), 1).unwrap();
+        db.save_message(&session, "assistant", &json!([{ "type": "tool_use", "id": "t1", "name": "exec", "input": {} }]), 1).unwrap();
+        db.save_message(&session, "user", &json!([{ "type": "tool_result", "tool_use_id": "t1", "content": "ok" }]), 1).unwrap();
+        assert!(db.keep_last_prompt(&session).unwrap());
+
+        let kept: Vec<bool> = db.load_session_messages(&session).unwrap().iter().map(|m| m.keep).collect();
+        assert_eq!(kept, vec![true, false, false]);
+    }
+
+    #[test]
+    fn test_superseded_messages_are_not_reloaded() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+
+        db.save_message(&session, "user", &json!("first question"), 1).unwrap();
+        db.save_message(&session, "assistant", &json!("first answer"), 1).unwrap();
+        db.save_message(&session, "user", &json!("second question"), 1).unwrap();
+        db.save_message(&session, "assistant", &json!("bad answer"), 1).unwrap();
+
+        db.supersede_last_messages(&session, 2).unwrap();
+        db.save_message(&session, "user", &json!("second question"), 1).unwrap();
+        db.save_message(&session, "assistant", &json!("better answer"), 1).unwrap();
+
+        let contents: Vec<Value> = db
+            .load_session_messages(&session)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(
+            contents,
+            vec![json!("first question"), json!("first answer"), json!("second question"), json!("better answer")]
+        );
+        assert!(db.search_messages("bad", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_session_prefix_wildcards_are_literal() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+
+        assert_eq!(db.find_session_by_prefix(&session[..4]).unwrap().unwrap().id, session);
+        assert!(db.find_session_by_prefix("%").unwrap().is_none());
+        assert!(db.find_session_by_prefix(&format!("{}_", &session[..3])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_repaired_history_is_saved() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+        db.save_message(&session, "user", &json!("run the tests"), 0).unwrap();
+        db.save_message(&session, "assistant", &json!([{ "type": "tool_use", "id": "t1", "name": "exec", "input": {} }]), 1).unwrap();
+        db.save_message(&session, "user", &json!("never mind"), 0).unwrap();
+
+        let mut messages = db.load_session_messages(&session).unwrap();
+        messages[0].keep = true;
+        assert!(!crate::client::repair_history(&mut messages).is_empty());
+        db.replace_last_messages(&session, 3, &messages).unwrap();
+
+        let mut reloaded = db.load_session_messages(&session).unwrap();
+        assert_eq!(reloaded.len(), 3);
+        assert!(reloaded[0].keep);
+        assert_eq!(reloaded[2].content[0]["tool_use_id"], json!("t1"));
+        assert!(crate::client::repair_history(&mut reloaded).is_empty());
+    }
+
+    #[test]
+    fn test_session_files_survive_reload_and_fork() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let session = db.create_session(None, None).unwrap();
+        assert!(db.load_session_files(&session).unwrap().is_empty());
+
+        let mut files = BTreeMap::new();
+        files.insert("/src/main.rs".to_string(), vec!["write".to_string(), "edit".to_string()]);
+        files.insert("/README.md".to_string(), vec!["edit".to_string()]);
+        db.set_session_files(&session, &files).unwrap();
+        assert_eq!(db.load_session_files(&session).unwrap(), files);
+
+        let fork = db.create_session_from(&session, usize::MAX).unwrap();
+        assert_eq!(db.load_session_files(&fork).unwrap(), files);
+    }
+
+    #[test]
+    fn test_resume_relocates_session_from_removed_project() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let dir = std::env::temp_dir().join(format!("imp-gone-project-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let session = db.create_session(Some("gone"), dir.to_str()).unwrap();
+        db.save_message(&session, "user", &json!("hello"), 1).unwrap();
+
+        // Still there: left alone
+        assert!(db.relocate_session_if_missing(&session, Some("here"), Some("/here")).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let old = db.relocate_session_if_missing(&session, None, Some("/here")).unwrap().unwrap();
+        assert_eq!(old.project.as_deref(), Some("gone"));
+
+        let info = db.get_session_by_id(&session).unwrap().unwrap();
+        assert_eq!((info.project, info.workdir.as_deref()), (None, Some("/here")));
+        assert_eq!(db.load_session_messages(&session).unwrap().len(), 1);
+        assert!(db.get_latest_session(Some("gone")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reflect_only_loads_new_messages() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let session = db.create_session(None, None).unwrap();
+        db.save_message(&session, "user", &json!("first question"), 0).unwrap();
+        db.save_message(&session, "assistant", &json!("first answer"), 0).unwrap();
+
+        let first = db.load_conversations_for_date(&today, &db.reflected_sessions(&today).unwrap()).unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(!first[0].continued);
+        db.mark_reflected(&today, &first).unwrap();
+
+        // Nothing new since the last reflect
+        let reflected = db.reflected_sessions(&today).unwrap();
+        assert!(db.load_conversations_for_date(&today, &reflected).unwrap().is_empty());
+
+        db.save_message(&session, "user", &json!("second question"), 0).unwrap();
+        let delta = db.load_conversations_for_date(&today, &reflected).unwrap();
+        assert_eq!(delta.len(), 1);
+        assert!(delta[0].continued);
+        assert!(delta[0].text.contains("second question"));
+        assert!(!delta[0].text.contains("first question"));
+    }
+}
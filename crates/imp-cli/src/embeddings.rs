@@ -7,13 +7,21 @@
 //! Without the `embeddings` feature, all methods are no-ops that return `None`,
 //! so the knowledge graph still works with text-based search fallback.
 //!
+//! Alternatively, `[knowledge] embedding_provider = "remote"` sends texts to an
+//! OpenAI-compatible embeddings endpoint (`embedding_url`) instead. Remote
+//! failures return `None`, so callers fall back to text search just as they do
+//! when the local model is missing.
+//!
 //! Can also be disabled at runtime via `[knowledge] embeddings_enabled = false`
 //! in config.toml.
 
-use std::sync::atomic::{AtomicBool, Ordering};
-
-#[cfg(feature = "embeddings")]
-use std::sync::OnceLock;
+use crate::config::{EmbeddingBackend, KnowledgeConfig};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
 
 #[cfg(feature = "embeddings")]
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
@@ -28,6 +36,199 @@ static EMBEDDINGS_WARNED: AtomicBool = AtomicBool::new(false);
 /// Set to true to globally disable embedding attempts (via config).
 static EMBEDDINGS_DISABLED: AtomicBool = AtomicBool::new(false);
 
+/// The configured provider. Unset means the local model.
+static PROVIDER: OnceLock<EmbeddingProvider> = OnceLock::new();
+
+/// Expected embedding dimension (`[knowledge] embedding_dim`).
+static DIMENSION: AtomicUsize = AtomicUsize::new(1024);
+
+/// Embeddings of recent retrieval queries (`[knowledge] query_cache_size`).
+static QUERY_CACHE: Mutex<QueryCache> = Mutex::new(QueryCache::new(64));
+
+/// Bounded LRU of query embeddings, keyed on a hash of the query text.
+/// Most recently used entries are at the front.
+pub struct QueryCache {
+    capacity: usize,
+    entries: VecDeque<(u64, Vec<f32>)>,
+}
+
+impl QueryCache {
+    pub const fn new(capacity: usize) -> Self {
+        Self { capacity, entries: VecDeque::new() }
+    }
+
+    /// Change the bound, evicting the least recently used entries if needed.
+    /// A capacity of 0 disables caching.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.entries.truncate(capacity);
+    }
+
+    fn key(text: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Look up a query, marking it most recently used on a hit.
+    fn get(&mut self, text: &str) -> Option<Vec<f32>> {
+        let key = Self::key(text);
+        let pos = self.entries.iter().position(|(k, _)| *k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let vector = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(vector)
+    }
+
+    fn insert(&mut self, text: &str, vector: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = Self::key(text);
+        self.entries.retain(|(k, _)| *k != key);
+        self.entries.push_front((key, vector));
+        self.entries.truncate(self.capacity);
+    }
+}
+
+/// Serve `text` from `cache`, calling `embed` only on a miss. The lock isn't
+/// held while embedding, so a slow model or HTTP call doesn't block others.
+fn cached_embed(
+    cache: &Mutex<QueryCache>,
+    text: &str,
+    embed: impl FnOnce(&str) -> Option<Vec<f32>>,
+) -> Option<Vec<f32>> {
+    if let Some(hit) = cache.lock().ok()?.get(text) {
+        return Some(hit);
+    }
+    let vector = embed(text)?;
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(text, vector.clone());
+    }
+    Some(vector)
+}
+
+/// Where embeddings come from.
+pub enum EmbeddingProvider {
+    /// The bundled fastembed model.
+    Local,
+    /// An OpenAI-compatible HTTP endpoint.
+    Remote(RemoteProvider),
+}
+
+/// Client for an OpenAI-compatible `/embeddings` endpoint.
+pub struct RemoteProvider {
+    url: String,
+    model: Option<String>,
+    api_key: Option<String>,
+    /// Built on first use and kept for the rest of the process.
+    transport: OnceLock<Option<RemoteTransport>>,
+}
+
+/// The HTTP client and the runtime its requests run on.
+struct RemoteTransport {
+    runtime: tokio::runtime::Runtime,
+    client: reqwest::Client,
+}
+
+impl RemoteTransport {
+    fn new() -> crate::error::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("imp-embeddings")
+            .enable_all()
+            .build()?;
+        let client = crate::http::client_builder()?
+            .timeout(std::time::Duration::from_secs(60))
+            .build()
+            .map_err(|e| crate::error::ImpError::Config(format!("Failed to set up HTTP client: {}", e)))?;
+        Ok(Self { runtime, client })
+    }
+}
+
+impl RemoteProvider {
+    fn new(url: String, model: Option<String>, api_key: Option<String>) -> Self {
+        Self { url, model, api_key, transport: OnceLock::new() }
+    }
+
+    /// Callers are synchronous and often already inside the tokio runtime,
+    /// where blocking on a future isn't allowed, so requests run on a runtime
+    /// of their own. `None` if the client can't be set up.
+    fn transport(&self) -> Option<&RemoteTransport> {
+        self.transport
+            .get_or_init(|| {
+                RemoteTransport::new()
+                    .map_err(|e| tracing::warn!(error = %e, "Embedding requests disabled"))
+                    .ok()
+            })
+            .as_ref()
+    }
+
+    /// POST the batch and parse the returned vectors. Returns `None` on any
+    /// HTTP or parse failure.
+    fn embed_batch(&self, texts: &[&str]) -> Option<Vec<Vec<f32>>> {
+        let mut body = json!({ "input": texts });
+        if let Some(ref model) = self.model {
+            body["model"] = json!(model);
+        }
+
+        let transport = self.transport()?;
+        let mut request = transport.client.post(&self.url).json(&body);
+        if let Some(ref key) = self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let url = self.url.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+        transport.runtime.spawn(async move {
+            let response = match request.send().await {
+                Ok(resp) if resp.status().is_success() => resp.json::<Value>().await.ok(),
+                Ok(resp) => {
+                    tracing::warn!(status = %resp.status(), url = %url, "Embedding request failed");
+                    None
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, url = %url, "Embedding request failed");
+                    None
+                }
+            };
+            let _ = tx.send(response);
+        });
+        let response = rx.recv().ok().flatten()?;
+
+        let vectors = parse_embedding_response(&response)?;
+        if vectors.len() != texts.len() {
+            tracing::warn!(expected = texts.len(), got = vectors.len(), "Embedding response size mismatch");
+            return None;
+        }
+        Some(vectors)
+    }
+}
+
+/// Parse the vectors out of an embeddings response. Accepts the OpenAI shape
+/// (`{"data": [{"embedding": [...], "index": 0}]}`), the Ollama shape
+/// (`{"embeddings": [[...]]}`), and a bare array of vectors.
+fn parse_embedding_response(response: &Value) -> Option<Vec<Vec<f32>>> {
+    let to_vec = |v: &Value| -> Option<Vec<f32>> {
+        v.as_array()?.iter().map(|x| x.as_f64().map(|f| f as f32)).collect()
+    };
+
+    if let Some(data) = response.get("data").and_then(|d| d.as_array()) {
+        let mut items: Vec<(u64, Vec<f32>)> = data
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let index = item.get("index").and_then(|v| v.as_u64()).unwrap_or(i as u64);
+                Some((index, to_vec(item.get("embedding")?)?))
+            })
+            .collect::<Option<_>>()?;
+        items.sort_by_key(|(index, _)| *index);
+        return Some(items.into_iter().map(|(_, v)| v).collect());
+    }
+
+    let list = response.get("embeddings").unwrap_or(response).as_array()?;
+    list.iter().map(to_vec).collect()
+}
+
 pub struct Embedder;
 
 impl Embedder {
@@ -37,6 +238,63 @@ impl Embedder {
         EMBEDDINGS_DISABLED.store(true, Ordering::Relaxed);
     }
 
+    /// Apply `[knowledge]` settings: disable embeddings, or select the
+    /// provider (starting the local model load in the background if local).
+    pub fn configure(config: &KnowledgeConfig) {
+        // Vectors are sized by this even when none are computed
+        DIMENSION.store(config.embedding_dim, Ordering::Relaxed);
+        if !config.embeddings_enabled {
+            Self::disable();
+            return;
+        }
+        if !config.enabled {
+            return;
+        }
+
+        if let Ok(mut cache) = QUERY_CACHE.lock() {
+            cache.set_capacity(config.query_cache_size);
+        }
+
+        let provider = match (&config.embedding_provider, &config.embedding_url) {
+            (EmbeddingBackend::Remote, Some(url)) => EmbeddingProvider::Remote(RemoteProvider::new(
+                url.clone(),
+                config.embedding_model.clone(),
+                config.embedding_api_key.clone(),
+            )),
+            (EmbeddingBackend::Remote, None) => {
+                tracing::warn!("embedding_provider = \"remote\" but no embedding_url set — using local model");
+                EmbeddingProvider::Local
+            }
+            (EmbeddingBackend::Local, _) => EmbeddingProvider::Local,
+        };
+
+        let is_local = matches!(provider, EmbeddingProvider::Local);
+        let _ = PROVIDER.set(provider);
+        if is_local {
+            Self::init_background();
+        }
+    }
+
+    /// The configured embedding dimension (default 1024).
+    pub fn dimension() -> usize {
+        DIMENSION.load(Ordering::Relaxed)
+    }
+
+    /// Short description of the active provider, for status output.
+    pub fn provider_name() -> String {
+        match Self::remote() {
+            Some(remote) => format!("remote: {}", remote.url),
+            None => "BGE-large-en-v1.5".to_string(),
+        }
+    }
+
+    fn remote() -> Option<&'static RemoteProvider> {
+        match PROVIDER.get()? {
+            EmbeddingProvider::Remote(remote) => Some(remote),
+            EmbeddingProvider::Local => None,
+        }
+    }
+
     /// Begin loading the embedding model in a background thread.
     /// Call once at startup. The model becomes available when loading completes;
     /// until then, `embed()` / `available()` gracefully return `None` / `false`
@@ -85,24 +343,50 @@ impl Embedder {
     }
 
     /// Embed a single piece of text. Returns `None` when the model is
-    /// unavailable, disabled, or not compiled in.
+    /// unavailable, disabled, or not compiled in, or the remote call fails.
     pub fn embed(text: &str) -> Option<Vec<f32>> {
-        #[cfg(feature = "embeddings")]
-        {
-            let model = Self::try_model()?;
-            model.embed(vec![text], None).ok().and_then(|mut v| v.pop())
-        }
-        #[cfg(not(feature = "embeddings"))]
-        {
-            let _ = text;
-            None
+        Self::embed_batch(&[text]).pop().flatten()
+    }
+
+    /// Embed a retrieval query, reusing the vector if the same query was
+    /// embedded recently in this process.
+    pub fn embed_query(text: &str) -> Option<Vec<f32>> {
+        cached_embed(&QUERY_CACHE, text, Self::embed)
+    }
+
+    /// Embed multiple texts in one model call / HTTP request (much faster than
+    /// repeated single calls). Always returns one entry per input: `None` where
+    /// embedding failed or the vector has the wrong dimension, and all `None`
+    /// when embeddings are unavailable.
+    pub fn embed_batch(texts: &[&str]) -> Vec<Option<Vec<f32>>> {
+        if texts.is_empty() || EMBEDDINGS_DISABLED.load(Ordering::Relaxed) {
+            return vec![None; texts.len()];
         }
+
+        let vectors = match Self::remote() {
+            Some(remote) => remote.embed_batch(texts),
+            None => Self::embed_batch_local(texts.to_vec()),
+        };
+        let vectors = match vectors {
+            Some(v) if v.len() == texts.len() => v,
+            _ => return vec![None; texts.len()],
+        };
+
+        let dim = Self::dimension();
+        vectors
+            .into_iter()
+            .map(|v| {
+                if v.len() == dim {
+                    Some(v)
+                } else {
+                    tracing::warn!(expected = dim, got = v.len(), "Embedding dimension mismatch — check [knowledge] embedding_dim");
+                    None
+                }
+            })
+            .collect()
     }
 
-    /// Embed multiple texts in one batch (more efficient than repeated single
-    /// calls). Returns `None` when the model is unavailable, disabled, or not
-    /// compiled in.
-    pub fn embed_batch(texts: Vec<&str>) -> Option<Vec<Vec<f32>>> {
+    fn embed_batch_local(texts: Vec<&str>) -> Option<Vec<Vec<f32>>> {
         #[cfg(feature = "embeddings")]
         {
             let model = Self::try_model()?;
@@ -115,9 +399,13 @@ impl Embedder {
         }
     }
 
-    /// Whether the embedding model is loaded and ready.
+    /// Whether embeddings can be requested: the local model is loaded and
+    /// ready, or a remote provider is configured.
     pub fn available() -> bool {
-        Self::try_model().is_some()
+        if EMBEDDINGS_DISABLED.load(Ordering::Relaxed) {
+            return false;
+        }
+        Self::remote().is_some() || Self::try_model().is_some()
     }
 
     /// Print a one-time warning if embeddings aren't available.
@@ -133,3 +421,31 @@ impl Embedder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_query_cache_hit_skips_embedding() {
+        let cache = Mutex::new(QueryCache::new(2));
+        let calls = Cell::new(0);
+        let mock = |text: &str| {
+            calls.set(calls.get() + 1);
+            Some(vec![text.len() as f32])
+        };
+
+        assert_eq!(cached_embed(&cache, "rust", mock), Some(vec![4.0]));
+        assert_eq!(cached_embed(&cache, "rust", mock), Some(vec![4.0]));
+        assert_eq!(calls.get(), 1);
+
+        // Least recently used entry is evicted once the bound is exceeded
+        cached_embed(&cache, "tokio", mock);
+        cached_embed(&cache, "rust", mock);
+        cached_embed(&cache, "serde", mock);
+        assert_eq!(calls.get(), 3);
+        cached_embed(&cache, "tokio", mock);
+        assert_eq!(calls.get(), 4);
+    }
+}
@@ -34,6 +34,11 @@ pub enum ImpError {
 
     #[error("Database error: {0}")]
     Database(String),
+
+    /// A request that gave up waiting: connecting, for the whole response,
+    /// or between streamed chunks. The message names the setting to raise.
+    #[error("Timed out: {0}")]
+    Timeout(String),
 }
 
 impl From<toml::de::Error> for ImpError {
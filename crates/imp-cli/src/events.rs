@@ -0,0 +1,112 @@
+//! Structured telemetry for dashboards. With `[observability] events_file`
+//! set, the agent appends one JSON object per line for each thing it does:
+//! turns, model requests, tool calls, sub-agents and compaction. This is
+//! separate from the human-readable log in `logging`.
+
+use crate::config::expand_home;
+use crate::error::{ImpError, Result};
+use crate::redact::redact;
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use tracing::warn;
+
+/// Most of a tool call's input recorded in a `tool_call` event.
+const MAX_INPUT_BYTES: usize = 2_000;
+
+pub struct EventLog {
+    file: File,
+}
+
+impl EventLog {
+    /// Open `path` for appending, creating it and its directory if needed.
+    pub fn open(path: &str) -> Result<Self> {
+        let path = expand_home(path);
+        let open = || {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            OpenOptions::new().create(true).append(true).open(&path)
+        };
+        let file = open().map_err(|e| {
+            ImpError::Config(format!(
+                "Can't open [observability] events_file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(Self { file })
+    }
+
+    /// Append `{"ts", "session", "event", ...fields}` as one line. Failures
+    /// are logged, never passed on: telemetry mustn't break a turn.
+    pub fn record(&self, session_id: &str, event: &str, fields: Value) {
+        let mut record = json!({
+            "ts": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            "session": session_id,
+            "event": event,
+        });
+        if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+            record.extend(fields);
+        }
+        let mut line = record.to_string();
+        line.push('\n');
+        // One write per line, so sessions sharing the file don't interleave
+        if let Err(e) = (&self.file).write_all(line.as_bytes()) {
+            warn!("Failed to write event '{}': {}", event, e);
+        }
+    }
+}
+
+/// A tool call's input as recorded in an event: serialized, with secrets
+/// redacted, and cut to `MAX_INPUT_BYTES` (file contents can be large).
+pub fn tool_input(input: &Value) -> String {
+    let mut text = redact(&input.to_string());
+    if text.len() > MAX_INPUT_BYTES {
+        let total = text.len();
+        let mut end = MAX_INPUT_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str(&format!("… [truncated — {} bytes total]", total));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_appends_json_lines() {
+        let dir = std::env::temp_dir().join(format!("imp-events-{}", std::process::id()));
+        let path = dir.join("nested/events.jsonl");
+        let log = EventLog::open(path.to_str().unwrap()).unwrap();
+        log.record("s1", "turn_start", json!({"prompt_chars": 5}));
+        log.record("s1", "tool_call", json!({"name": "exec", "duration_ms": 12}));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "turn_start");
+        assert_eq!(events[0]["session"], "s1");
+        assert_eq!(events[0]["prompt_chars"], 5);
+        assert_eq!(events[1]["name"], "exec");
+        assert!(events[1]["ts"].is_string());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tool_input_is_redacted_and_capped() {
+        let input = json!({"command": "curl -H 'x-api-key: sk-ant-api03-SECRET_123' https://api.example"});
+        let recorded = tool_input(&input);
+        assert!(!recorded.contains("SECRET_123"), "{}", recorded);
+        assert!(recorded.contains("curl"));
+
+        let recorded = tool_input(&json!({"content": "x".repeat(10_000)}));
+        assert!(recorded.len() < MAX_INPUT_BYTES + 50);
+        assert!(recorded.ends_with("bytes total]"));
+    }
+}
@@ -4,12 +4,20 @@
 //! and stores them in the knowledge graph. Uses lightweight pattern matching
 //! and queue processing to avoid blocking conversations.
 
+use crate::config::{imp_home, KnowledgeConfig, SchemaValidation};
 use crate::error::{ImpError, Result};
-use crate::knowledge::{Entity, Relationship, SchemaInfo, QueueEntry};
+use crate::knowledge::{levenshtein, Entity, Relationship, SchemaInfo, QueueEntry};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Largest edit distance at which lenient validation maps an unknown entity
+/// type onto an existing one.
+const MAX_TYPE_EDIT_DISTANCE: usize = 2;
+
 // ────────────────────────────────────────────────────────────────────
 // Types
 // ────────────────────────────────────────────────────────────────────
@@ -27,6 +35,8 @@ pub struct ExtractedEntity {
     pub name: String,
     pub properties: JsonValue,
     pub type_description: Option<String>, // only for NEW types
+    #[serde(default)]
+    pub confidence: Option<f64>, // 0–1, if the LLM gave one
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +45,8 @@ pub struct ExtractedRelationship {
     pub rel_type: String,
     pub to_name: String,
     pub rel_description: Option<String>, // only for NEW types
+    #[serde(default)]
+    pub confidence: Option<f64>, // 0–1, if the LLM gave one
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,12 +55,61 @@ pub struct ExtractedChunk {
     pub mentions: Vec<String>, // entity names
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtractionStats {
     pub entities_added: usize,
     pub relationships_added: usize,
     pub chunks_stored: usize,
     pub new_types_added: usize,
+    /// Entities whose out-of-schema type was mapped to the closest known type.
+    pub types_coerced: usize,
+    /// Entities with an out-of-schema type that went to the review file.
+    pub entities_rejected: usize,
+    /// Entities and relationships below `min_confidence` that went to the review file.
+    pub low_confidence_held: usize,
+}
+
+impl ExtractionStats {
+    /// Add another batch's counts to these.
+    pub fn add(&mut self, other: &ExtractionStats) {
+        self.entities_added += other.entities_added;
+        self.relationships_added += other.relationships_added;
+        self.chunks_stored += other.chunks_stored;
+        self.new_types_added += other.new_types_added;
+        self.types_coerced += other.types_coerced;
+        self.entities_rejected += other.entities_rejected;
+        self.low_confidence_held += other.low_confidence_held;
+    }
+}
+
+/// An extracted item held back from the graph for a person to look at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewEntry {
+    #[serde(flatten)]
+    pub item: ReviewItem,
+    pub reason: String,
+    pub timestamp: f64,
+    /// Provenance of the extraction it came from.
+    #[serde(default)]
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewItem {
+    Entity(ExtractedEntity),
+    Relationship(ExtractedRelationship),
+}
+
+/// Result of checking an extracted entity type against the schema.
+#[derive(Debug, PartialEq)]
+enum TypeCheck {
+    /// The type is in the schema (returned with the schema's spelling).
+    Known(String),
+    /// Not in the schema, but close enough to this known type.
+    Coerced(String),
+    /// Not in the schema and no close match (or strict mode).
+    Rejected,
 }
 
 // ────────────────────────────────────────────────────────────────────
@@ -128,6 +189,7 @@ pub fn process_knowledge_queue(
                 name: entity_name.clone(),
                 properties: JsonValue::Object(serde_json::Map::new()),
                 type_description: None,
+                confidence: None,
             });
         }
 
@@ -141,6 +203,7 @@ pub fn process_knowledge_queue(
                         rel_type: "related_to".to_string(),
                         to_name: entry.suggested_entities[j].clone(),
                         rel_description: None,
+                        confidence: None,
                     });
                 }
             }
@@ -215,6 +278,8 @@ impl From<ExtractedEntity> for Entity {
             aliases: Vec::new(), // Aliases can be added later
             created_at: now,
             updated_at: now,
+            source: String::new(),
+            confidence: extracted.confidence,
         }
     }
 }
@@ -234,6 +299,8 @@ impl From<ExtractedRelationship> for Relationship {
             to_id: String::new(), // Will be resolved by caller
             properties: JsonValue::Object(serde_json::Map::new()),
             created_at: now,
+            source: String::new(),
+            confidence: extracted.confidence,
         }
     }
 }
@@ -275,12 +342,12 @@ Extract from this content:
 Return JSON:
 {{
   "entities": [
-    {{"type": "existing_type", "name": "...", "properties": {{}}}},
-    {{"type": "NEW:suggested_type", "name": "...", "properties": {{}}, "type_description": "..."}}
+    {{"type": "existing_type", "name": "...", "properties": {{}}, "confidence": 0.9}},
+    {{"type": "NEW:suggested_type", "name": "...", "properties": {{}}, "type_description": "...", "confidence": 0.7}}
   ],
   "relationships": [
-    {{"from": "entity_name", "rel": "existing_rel", "to": "entity_name"}},
-    {{"from": "entity_name", "rel": "NEW:suggested_rel", "to": "entity_name", "rel_description": "..."}}
+    {{"from": "entity_name", "rel": "existing_rel", "to": "entity_name", "confidence": 0.9}},
+    {{"from": "entity_name", "rel": "NEW:suggested_rel", "to": "entity_name", "rel_description": "...", "confidence": 0.6}}
   ],
   "chunks": [
     {{"content": "verbatim useful text", "mentions": ["entity_name", "entity_name"]}}
@@ -292,6 +359,7 @@ Rules:
 - If new information applies to an existing entity, include it in the entities array with the same name — its properties will be merged.
 - Use existing types/relationships when possible.
 - For NEW: prefixed items, provide clear descriptions.
+- Give each entity and relationship a confidence from 0 to 1: 1 when the content states it outright, lower when you are inferring it.
 - Extract only substantive, factual content — skip greetings, filler, and routine operations.
 - Chunks should be self-contained useful information.
 - Entity names should be consistent and canonical (e.g., "NASDAQ" not "nasdaq", "Casey" not "casey").
@@ -325,6 +393,7 @@ pub fn parse_extraction_response(response: &str) -> Result<ExtractionResult> {
                     name: name.to_string(),
                     properties: entity_val.get("properties").cloned().unwrap_or(JsonValue::Object(serde_json::Map::new())),
                     type_description: entity_val.get("type_description").and_then(|v| v.as_str()).map(String::from),
+                    confidence: parse_confidence(entity_val),
                 });
             }
         }
@@ -344,6 +413,7 @@ pub fn parse_extraction_response(response: &str) -> Result<ExtractionResult> {
                     rel_type: rel_type.to_string(),
                     to_name: to.to_string(),
                     rel_description: rel_val.get("rel_description").and_then(|v| v.as_str()).map(String::from),
+                    confidence: parse_confidence(rel_val),
                 });
             }
         }
@@ -375,22 +445,31 @@ pub fn parse_extraction_response(response: &str) -> Result<ExtractionResult> {
 }
 
 /// Process extraction results and store them in the knowledge graph.
-/// Handles deduplication and NEW type creation.
+/// Handles deduplication and NEW type creation. New entities whose type isn't
+/// in the schema (and isn't `NEW:`) are coerced or held for review according
+/// to `settings.schema_validation`, as are items below `settings.min_confidence`.
+/// Stored entities and relationships are tagged with `source` (see
+/// `knowledge::provenance`).
 pub fn process_extraction(
     kg: &crate::knowledge::KnowledgeGraph,
     result: &ExtractionResult,
+    source: &str,
+    settings: &KnowledgeConfig,
 ) -> Result<ExtractionStats> {
     use crate::knowledge::{Entity, Relationship};
     
-    let mut stats = ExtractionStats {
-        entities_added: 0,
-        relationships_added: 0,
-        chunks_stored: 0,
-        new_types_added: 0,
-    };
+    let mut stats = ExtractionStats::default();
+    let mut known_types: Vec<String> = kg.get_schema()?.types.into_iter().map(|t| t.type_name).collect();
 
     // Process entities
     for extracted_entity in &result.entities {
+        if is_low_confidence(extracted_entity.confidence, settings.min_confidence) {
+            let item = ReviewItem::Entity(extracted_entity.clone());
+            append_to_review(item, &low_confidence_reason(extracted_entity.confidence), source)?;
+            stats.low_confidence_held += 1;
+            continue;
+        }
+
         let mut entity_type = extracted_entity.entity_type.clone();
         
         // Handle NEW: prefixed types
@@ -419,8 +498,10 @@ pub fn process_extraction(
                 let _ = kg.run_mutating(schema_add_script, params);
                 
                 stats.new_types_added += 1;
-                entity_type = new_type.to_string();
+                known_types.push(new_type.to_string());
             }
+            // Without a description no type was created, so it must already exist
+            entity_type = new_type.to_string();
         }
         
         // Check if entity already exists — merge properties if so
@@ -446,6 +527,8 @@ pub fn process_extraction(
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs_f64(),
+                        source: existing.source,
+                        confidence: existing.confidence,
                     };
                     let _ = kg.store_entity(updated);
                 }
@@ -453,6 +536,28 @@ pub fn process_extraction(
             continue;
         }
         
+        let entity_type = match check_entity_type(&entity_type, &known_types, settings.schema_validation) {
+            TypeCheck::Known(known) => known,
+            TypeCheck::Coerced(known) => {
+                tracing::info!(
+                    "Mapped out-of-schema type '{}' to '{}' for entity '{}'",
+                    entity_type, known, extracted_entity.name
+                );
+                stats.types_coerced += 1;
+                known
+            }
+            TypeCheck::Rejected => {
+                tracing::info!(
+                    "Entity '{}' has out-of-schema type '{}'; holding it for review",
+                    extracted_entity.name, entity_type
+                );
+                let item = ReviewItem::Entity(extracted_entity.clone());
+                append_to_review(item, &format!("type '{}' is not in the schema", entity_type), source)?;
+                stats.entities_rejected += 1;
+                continue;
+            }
+        };
+
         // Create new entity
         let entity = Entity {
             id: String::new(),
@@ -462,6 +567,8 @@ pub fn process_extraction(
             aliases: Vec::new(),
             created_at: 0.0, // Will be set by store_entity
             updated_at: 0.0, // Will be set by store_entity
+            source: source.to_string(),
+            confidence: extracted_entity.confidence,
         };
         
         kg.store_entity(entity)?;
@@ -470,6 +577,13 @@ pub fn process_extraction(
 
     // Process relationships
     for extracted_rel in &result.relationships {
+        if is_low_confidence(extracted_rel.confidence, settings.min_confidence) {
+            let item = ReviewItem::Relationship(extracted_rel.clone());
+            append_to_review(item, &low_confidence_reason(extracted_rel.confidence), source)?;
+            stats.low_confidence_held += 1;
+            continue;
+        }
+
         // Find entity IDs
         let from_entity = kg.find_entity_by_name(&extracted_rel.from_name)?;
         let to_entity = kg.find_entity_by_name(&extracted_rel.to_name)?;
@@ -500,6 +614,8 @@ pub fn process_extraction(
                 to_id: to.id,
                 properties: JsonValue::Object(serde_json::Map::new()),
                 created_at: 0.0, // Will be set by store_relationship
+                source: source.to_string(),
+                confidence: extracted_rel.confidence,
             };
             
             kg.store_relationship(relationship)?;
@@ -510,7 +626,9 @@ pub fn process_extraction(
     // Process chunks
     for extracted_chunk in &result.chunks {
         // Store chunk with "conversation" source type
-        let chunk_id = kg.store_chunk(&extracted_chunk.content, "conversation", "reflect")?;
+        // A near-duplicate returns the existing chunk, which still gets the links
+        let stored = kg.store_chunk(&extracted_chunk.content, "conversation", "reflect")?;
+        let chunk_id = stored.id.clone();
         
         // Link chunk to mentioned entities
         for entity_name in &extracted_chunk.mentions {
@@ -528,12 +646,77 @@ pub fn process_extraction(
             }
         }
         
-        stats.chunks_stored += 1;
+        if !stored.duplicate {
+            stats.chunks_stored += 1;
+        }
     }
 
     Ok(stats)
 }
 
+/// Check an extracted entity type against the schema's types. Case is
+/// ignored; in lenient mode a type within a small edit distance of a known
+/// type (e.g. "protcol") is mapped onto it.
+fn check_entity_type(entity_type: &str, known_types: &[String], validation: SchemaValidation) -> TypeCheck {
+    let wanted = entity_type.trim().to_lowercase();
+    if let Some(known) = known_types.iter().find(|t| t.to_lowercase() == wanted) {
+        return TypeCheck::Known(known.clone());
+    }
+    if validation == SchemaValidation::Strict {
+        return TypeCheck::Rejected;
+    }
+
+    known_types
+        .iter()
+        .map(|t| (levenshtein(&wanted, &t.to_lowercase()), t))
+        // Short names need a proportionally closer match: "tol" → "tool", not "x" → "tool"
+        .filter(|(distance, t)| *distance <= MAX_TYPE_EDIT_DISTANCE && distance * 4 <= t.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, t)| TypeCheck::Coerced(t.clone()))
+        .unwrap_or(TypeCheck::Rejected)
+}
+
+/// A `confidence` field clamped to 0–1; absent or non-numeric is `None`.
+fn parse_confidence(value: &JsonValue) -> Option<f64> {
+    value.get("confidence").and_then(|v| v.as_f64()).map(|c| c.clamp(0.0, 1.0))
+}
+
+/// Items without a confidence are trusted.
+fn is_low_confidence(confidence: Option<f64>, min_confidence: f64) -> bool {
+    confidence.is_some_and(|c| c < min_confidence)
+}
+
+fn low_confidence_reason(confidence: Option<f64>) -> String {
+    format!("confidence {:.2} is below min_confidence", confidence.unwrap_or_default())
+}
+
+fn review_path() -> Result<PathBuf> {
+    Ok(imp_home()?.join("knowledge_review.jsonl"))
+}
+
+/// Append an item to the needs-review file instead of storing it.
+fn append_to_review(item: ReviewItem, reason: &str, source: &str) -> Result<()> {
+    let entry = ReviewEntry {
+        item,
+        reason: reason.to_string(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64(),
+        source: source.to_string(),
+    };
+
+    let path = review_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let json = serde_json::to_string(&entry)
+        .map_err(|e| ImpError::Database(format!("Failed to serialize review entry: {}", e)))?;
+    writeln!(file, "{}", json)?;
+    Ok(())
+}
+
 /// LLM-based extraction for reflect command.
 pub async fn extract_knowledge_llm(
     content: &str, 
@@ -614,6 +797,39 @@ mod tests {
         assert_eq!(infer_entity_type("retry logic"), "concept");
     }
 
+    #[test]
+    fn test_check_entity_type() {
+        let known: Vec<String> = ["person", "protocol", "tool"].iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(check_entity_type("Protocol", &known, SchemaValidation::Strict), TypeCheck::Known("protocol".into()));
+        assert_eq!(check_entity_type("protcol", &known, SchemaValidation::Lenient), TypeCheck::Coerced("protocol".into()));
+        assert_eq!(check_entity_type("protcol", &known, SchemaValidation::Strict), TypeCheck::Rejected);
+        // Too far from anything known, even when lenient
+        assert_eq!(check_entity_type("library", &known, SchemaValidation::Lenient), TypeCheck::Rejected);
+        assert_eq!(check_entity_type("x", &known, SchemaValidation::Lenient), TypeCheck::Rejected);
+    }
+
+    #[test]
+    fn test_parse_confidence() {
+        let response = r#"{
+            "entities": [
+                {"type": "tool", "name": "cozo", "confidence": 0.3},
+                {"type": "tool", "name": "tokio"},
+                {"type": "tool", "name": "serde", "confidence": 7}
+            ],
+            "relationships": [{"from": "imp", "rel": "uses", "to": "cozo", "confidence": 0.95}]
+        }"#;
+        let result = parse_extraction_response(response).unwrap();
+        let confidences: Vec<Option<f64>> = result.entities.iter().map(|e| e.confidence).collect();
+        assert_eq!(confidences, vec![Some(0.3), None, Some(1.0)]);
+        assert_eq!(result.relationships[0].confidence, Some(0.95));
+
+        // Only a stated confidence below the threshold holds an item back
+        assert!(is_low_confidence(Some(0.3), 0.5));
+        assert!(!is_low_confidence(None, 0.5));
+        assert!(!is_low_confidence(Some(0.5), 0.5));
+    }
+
     #[test]
     fn test_process_knowledge_queue() {
         let schema = SchemaInfo {
@@ -2,13 +2,16 @@
 //!
 //! Pre-processes markdown text: fenced code blocks with language tags get
 //! replaced with ANSI-highlighted output before termimad renders the rest.
+//! `MarkdownStream` does the same for streamed text, a block at a time.
+//! `terminal_background` lets `theme = "auto"` pick a light or dark theme.
 
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
+use std::time::Duration;
 
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
 static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
@@ -18,6 +21,181 @@ pub fn available_themes() -> Vec<String> {
     THEME_SET.themes.keys().cloned().collect()
 }
 
+/// Terminal background brightness, for picking a light or dark theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// How long to wait for the terminal to answer a background colour query.
+const BACKGROUND_QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The terminal's background, from `COLORFGBG` or else by asking the terminal
+/// (OSC 11). `None` if neither works. Detected once per process; the first
+/// call must happen before a line editor starts reading stdin.
+pub fn terminal_background() -> Option<Background> {
+    static DETECTED: OnceLock<Option<Background>> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        std::env::var("COLORFGBG")
+            .ok()
+            .and_then(|value| background_from_colorfgbg(&value))
+            .or_else(query_background)
+    })
+}
+
+/// `COLORFGBG` is "fg;bg" (sometimes "fg;default;bg") in ANSI colour numbers.
+fn background_from_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.trim().parse().ok()?;
+    Some(match bg {
+        7 | 9..=15 => Background::Light,
+        _ => Background::Dark,
+    })
+}
+
+/// Parse an OSC 11 reply like `ESC ]11;rgb:ffff/ffff/ffff BEL`.
+fn background_from_osc11(reply: &str) -> Option<Background> {
+    let rgb = reply.split("rgb:").nth(1)?;
+    let rgb = rgb.trim_end_matches(['\x07', '\\']).trim_end_matches('\x1b');
+    let channels: Vec<f64> = rgb
+        .split('/')
+        .map(|hex| {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            let max = 16u32.checked_pow(hex.len() as u32)? - 1;
+            Some(value as f64 / max as f64)
+        })
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
+
+#[cfg(unix)]
+fn query_background() -> Option<Background> {
+    use std::io::{IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    crossterm::terminal::enable_raw_mode().ok()?;
+    let mut stdout = std::io::stdout();
+    let reply = stdout
+        .write_all(b"\x1b]11;?\x07")
+        .and_then(|_| stdout.flush())
+        .ok()
+        .and_then(|_| read_osc_reply());
+    let _ = crossterm::terminal::disable_raw_mode();
+    background_from_osc11(&reply?)
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<Background> {
+    None
+}
+
+/// Read a terminal reply ending in BEL or ST from stdin, giving up after
+/// `BACKGROUND_QUERY_TIMEOUT` (terminals that don't support the query say nothing).
+#[cfg(unix)]
+fn read_osc_reply() -> Option<String> {
+    use nix::poll::{poll, PollFd, PollFlags};
+    use std::os::fd::{AsFd, AsRawFd};
+    use std::time::Instant;
+
+    let stdin = std::io::stdin();
+    let deadline = Instant::now() + BACKGROUND_QUERY_TIMEOUT;
+    let mut reply = Vec::new();
+    while !(reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\")) {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+        if poll(&mut fds, remaining.as_millis() as u16).ok()? == 0 {
+            return None;
+        }
+        // Read the fd directly: Stdin's buffer would keep bytes the line editor needs
+        let mut buf = [0u8; 64];
+        let n = nix::unistd::read(stdin.as_raw_fd(), &mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+    Some(String::from_utf8_lossy(&reply).into_owned())
+}
+
+/// Render markdown for the terminal, highlighting fenced code blocks.
+/// Plain output gets the markdown source as-is.
+pub fn render_markdown(text: &str, theme: &str) -> String {
+    if text.trim().is_empty() {
+        return String::new();
+    }
+    if crate::output::is_plain() {
+        return text.to_string();
+    }
+    let highlighted = highlight_code_blocks(text, theme);
+    let skin = termimad::MadSkin::default();
+    format!("{}", skin.term_text(&highlighted))
+}
+
+/// Renders streamed markdown a block at a time. Text is held back until a
+/// paragraph ends or a fenced code block closes, so a code block is always
+/// highlighted whole.
+pub struct MarkdownStream {
+    theme: String,
+    buffer: String,
+    /// Bytes of `buffer` already checked for block boundaries.
+    scanned: usize,
+    in_fence: bool,
+}
+
+impl MarkdownStream {
+    pub fn new(theme: &str) -> Self {
+        Self { theme: theme.to_string(), buffer: String::new(), scanned: 0, in_fence: false }
+    }
+
+    /// Add streamed text. Returns the rendered blocks it completed, if any.
+    pub fn push(&mut self, text: &str) -> Option<String> {
+        self.buffer.push_str(text);
+        let block = self.take_complete_blocks()?;
+        Some(render_markdown(&block, &self.theme)).filter(|r| !r.is_empty())
+    }
+
+    /// Render whatever is left, complete or not, at the end of the stream.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        self.scanned = 0;
+        self.in_fence = false;
+        Some(render_markdown(&rest, &self.theme)).filter(|r| !r.is_empty())
+    }
+
+    /// Remove and return the buffered text up to the last block boundary:
+    /// a blank line outside a code block, a closing fence, or the line
+    /// before an opening fence.
+    fn take_complete_blocks(&mut self) -> Option<String> {
+        let mut boundary = None;
+        while let Some(len) = self.buffer[self.scanned..].find('\n') {
+            let start = self.scanned;
+            let end = start + len + 1;
+            self.scanned = end;
+            let line = &self.buffer[start..end];
+            if line.starts_with("```") {
+                if self.in_fence {
+                    boundary = Some(end);
+                } else if !self.buffer[..start].trim().is_empty() {
+                    boundary = Some(start);
+                }
+                self.in_fence = !self.in_fence;
+            } else if !self.in_fence && line.trim().is_empty() {
+                boundary = Some(end);
+            }
+        }
+
+        let boundary = boundary?;
+        self.scanned -= boundary;
+        Some(self.buffer.drain(..boundary).collect())
+    }
+}
+
 /// Process markdown text: find fenced code blocks, syntax-highlight them,
 /// and return the text with highlighted blocks replaced.
 ///
@@ -124,3 +302,40 @@ fn try_highlight(lang: &str, code: &str, theme_name: &str) -> Option<String> {
 
     Some(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_detection_parsing() {
+        assert_eq!(background_from_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(background_from_colorfgbg("0;default;15"), Some(Background::Light));
+        assert_eq!(background_from_colorfgbg("default"), None);
+
+        assert_eq!(background_from_osc11("\x1b]11;rgb:ffff/ffff/ffff\x07"), Some(Background::Light));
+        assert_eq!(background_from_osc11("\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\"), Some(Background::Dark));
+        assert_eq!(background_from_osc11("\x1b]11;rgb:fd/f6/e3\x07"), Some(Background::Light));
+        assert_eq!(background_from_osc11("garbage"), None);
+    }
+
+    #[test]
+    fn test_markdown_stream_waits_for_block_boundaries() {
+        let mut stream = MarkdownStream::new("base16-mocha.dark");
+        let mut push = |text: &str| {
+            stream.buffer.push_str(text);
+            stream.take_complete_blocks()
+        };
+
+        assert_eq!(push("Here is a fix"), None);
+        assert_eq!(push(":\n```rust\nfn main() {\n"), Some("Here is a fix:\n".to_string()));
+        // A blank line inside a code block isn't a boundary
+        assert_eq!(push("\n    println!(\"hi\");\n}\n"), None);
+        assert_eq!(
+            push("```\nDone"),
+            Some("```rust\nfn main() {\n\n    println!(\"hi\");\n}\n```\n".to_string())
+        );
+        assert_eq!(push(".\n\nNext"), Some("Done.\n\n".to_string()));
+        assert_eq!(stream.buffer, "Next");
+    }
+}
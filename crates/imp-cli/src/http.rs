@@ -0,0 +1,77 @@
+//! Shared setup for HTTP clients, so the `[network]` proxy and CA bundle
+//! apply to every request imp makes (LLM API, OAuth refresh, MCP over HTTP,
+//! remote embeddings).
+
+use crate::config::{expand_home, NetworkConfig};
+use crate::error::{ImpError, Result};
+use std::sync::{OnceLock, RwLock};
+
+fn settings() -> &'static RwLock<NetworkConfig> {
+    static SETTINGS: OnceLock<RwLock<NetworkConfig>> = OnceLock::new();
+    SETTINGS.get_or_init(|| RwLock::new(NetworkConfig::default()))
+}
+
+/// Use `network` for clients built from now on. `Config::load` calls this.
+pub fn configure(network: &NetworkConfig) {
+    if let Ok(mut current) = settings().write() {
+        *current = network.clone();
+    }
+}
+
+/// A client builder with the configured proxy and extra root certificates.
+/// Without `[network] proxy`, reqwest picks up `HTTPS_PROXY`/`HTTP_PROXY`/
+/// `NO_PROXY` from the environment itself.
+pub fn client_builder() -> Result<reqwest::ClientBuilder> {
+    let network = settings().read().map(|n| n.clone()).unwrap_or_default();
+    builder_for(&network)
+}
+
+/// `client_builder` for the given settings rather than the configured ones.
+fn builder_for(network: &NetworkConfig) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(ref url) = network.proxy {
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| ImpError::Config(format!("Invalid [network] proxy '{}': {}", url, e)))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(ref path) = network.ca_bundle {
+        let pem = std::fs::read(expand_home(path)).map_err(|e| {
+            ImpError::Config(format!("Can't read [network] ca_bundle '{}': {}", path, e))
+        })?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| {
+            ImpError::Config(format!("[network] ca_bundle '{}' isn't a valid PEM bundle: {}", path, e))
+        })?;
+        if certs.is_empty() {
+            return Err(ImpError::Config(format!(
+                "[network] ca_bundle '{}' contains no certificates",
+                path
+            )));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_builder_reports_bad_network_settings() {
+        // Not through `configure`: other tests build clients from the shared settings
+        let missing_ca = NetworkConfig { proxy: None, ca_bundle: Some("/nonexistent/ca.pem".to_string()) };
+        let err = builder_for(&missing_ca).err().expect("missing CA file").to_string();
+        assert!(err.contains("Can't read [network] ca_bundle '/nonexistent/ca.pem'"), "{}", err);
+
+        let proxy = NetworkConfig { proxy: Some("http://proxy.corp:3128".to_string()), ca_bundle: None };
+        assert!(builder_for(&proxy).is_ok());
+
+        assert!(builder_for(&NetworkConfig::default()).is_ok());
+    }
+}
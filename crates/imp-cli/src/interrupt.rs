@@ -0,0 +1,62 @@
+//! The Ctrl+C / `/stop` signal shared by the chat loop, the agent and its
+//! model client.
+
+use tokio::sync::watch;
+
+/// Set when the user interrupts, cleared before each turn. Code between
+/// steps checks `is_set()`; code awaiting something slow (a model request)
+/// races it against `triggered()` and drops it when that resolves.
+pub struct Interrupt(watch::Sender<bool>);
+
+impl Default for Interrupt {
+    fn default() -> Self {
+        Self(watch::Sender::new(false))
+    }
+}
+
+impl Interrupt {
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn clear(&self) {
+        self.0.send_replace(false);
+    }
+
+    pub fn is_set(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once `trigger()` is called, or immediately if it already was.
+    pub async fn triggered(&self) {
+        let mut rx = self.0.subscribe();
+        // The sender lives as long as `self`, so this only returns once set
+        let _ = rx.wait_for(|&set| set).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_triggered_wakes_waiter() {
+        let interrupt = Arc::new(Interrupt::default());
+        assert!(!interrupt.is_set());
+
+        let waiter = tokio::spawn({
+            let interrupt = interrupt.clone();
+            async move { interrupt.triggered().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        interrupt.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+
+        // Already set: resolves straight away until cleared
+        tokio::time::timeout(Duration::from_millis(10), interrupt.triggered()).await.unwrap();
+        interrupt.clear();
+        assert!(tokio::time::timeout(Duration::from_millis(10), interrupt.triggered()).await.is_err());
+    }
+}
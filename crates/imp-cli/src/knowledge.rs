@@ -7,7 +7,7 @@
 //! Also provides a JSONL-based knowledge queue for flagging content during
 //! conversations for later processing.
 
-use crate::config::imp_home;
+use crate::config::{imp_home, KnowledgeConfig};
 use crate::embeddings::Embedder;
 use crate::error::{ImpError, Result};
 use cozo::{DataValue, DbInstance, NamedRows, ScriptMutability};
@@ -16,8 +16,12 @@ use serde_json::Value as JsonValue;
 use std::collections::BTreeMap;
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long `KnowledgeGraph::open` keeps retrying while another process
+/// holds the RocksDB lock. Other sessions only hold it for one operation.
+const OPEN_LOCK_WAIT: Duration = Duration::from_secs(3);
 
 // ────────────────────────────────────────────────────────────────────
 // Types
@@ -32,6 +36,13 @@ pub struct Entity {
     pub aliases: Vec<String>, // Loaded from entity_alias table
     pub created_at: f64,
     pub updated_at: f64,
+    /// Where the entity came from, e.g. "session 4f1c… on 2026-03-02" (see
+    /// `provenance`). Empty when unknown.
+    #[serde(default)]
+    pub source: String,
+    /// How sure the extraction was (0–1), if it said.
+    #[serde(default)]
+    pub confidence: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +53,12 @@ pub struct Relationship {
     pub to_id: String,
     pub properties: JsonValue,
     pub created_at: f64,
+    /// Where the relationship came from; empty when unknown.
+    #[serde(default)]
+    pub source: String,
+    /// How sure the extraction was (0–1), if it said.
+    #[serde(default)]
+    pub confidence: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,6 +90,8 @@ pub struct KnowledgeStats {
     pub entity_count: usize,
     pub relationship_count: usize,
     pub chunk_count: usize,
+    /// Chunks not stored because a near-identical one already was.
+    pub duplicate_chunks: usize,
     pub schema_type_count: usize,
     pub schema_rel_count: usize,
 }
@@ -82,6 +101,42 @@ pub struct RelatedEntity {
     pub entity: Entity,
     pub rel_type: String,
     pub direction: String, // "->" or "<-"
+    /// For 2-hop results, the name of the intermediate entity; `rel_type` and
+    /// `direction` then describe the link between it and `entity`.
+    pub via: Option<String>,
+    /// Provenance of the relationship itself (see `Relationship::source`).
+    pub source: String,
+    pub confidence: Option<f64>,
+}
+
+/// Result of `KnowledgeGraph::store_chunk`.
+#[derive(Debug, Clone)]
+pub struct StoredChunk {
+    /// The new chunk, or the existing near-duplicate that was kept instead.
+    pub id: String,
+    pub duplicate: bool,
+}
+
+/// Output format for `KnowledgeGraph::export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    GraphMl,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = ImpError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "graphml" | "xml" => Ok(ExportFormat::GraphMl),
+            other => Err(ImpError::Config(format!(
+                "Unknown export format '{}' (expected 'json' or 'graphml')",
+                other
+            ))),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,6 +149,9 @@ pub struct MemoryChunk {
     pub has_embedding: bool,
     pub access_count: i64,
     pub last_accessed: f64,
+    /// The project the chunk was stored in; empty for global knowledge.
+    #[serde(default)]
+    pub project: String,
 }
 
 // ────────────────────────────────────────────────────────────────────
@@ -114,14 +172,22 @@ pub struct QueueEntry {
 
 pub struct KnowledgeGraph {
     db: DbInstance,
+    settings: KnowledgeConfig,
+    /// Active project: new knowledge is tagged with it, and retrieval skips
+    /// other projects' knowledge (untagged knowledge is global).
+    project: Option<String>,
 }
 
 impl KnowledgeGraph {
     /// Open or create the knowledge graph database.
     /// Uses RocksDB storage at `~/.imp/knowledge.cozo`.
     pub fn open() -> Result<Self> {
-        let path = Self::db_path()?;
+        Self::open_at(&Self::db_path()?, OPEN_LOCK_WAIT)
+    }
 
+    /// Open the database at `path`. If another process holds the RocksDB
+    /// lock, retry with backoff for up to `wait` before giving up.
+    pub(crate) fn open_at(path: &Path, wait: Duration) -> Result<Self> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
@@ -131,15 +197,57 @@ impl KnowledgeGraph {
             ImpError::Database("Invalid path for knowledge database".to_string())
         })?;
 
-        let db = DbInstance::new("rocksdb", path_str, Default::default()).map_err(|e| {
-            ImpError::Database(format!("Failed to open knowledge database: {}", e))
-        })?;
+        let started = Instant::now();
+        let mut delay = Duration::from_millis(50);
+        let db = loop {
+            match DbInstance::new("rocksdb", path_str, Default::default()) {
+                Ok(db) => break db,
+                Err(e) => {
+                    let message = e.to_string();
+                    if !is_lock_error(&message) {
+                        return Err(ImpError::Database(format!(
+                            "Failed to open knowledge database: {}",
+                            message
+                        )));
+                    }
+                    if started.elapsed() >= wait {
+                        return Err(ImpError::Database(format!(
+                            "The knowledge database at {} is in use by another imp process. \
+                             Wait for it to finish (e.g. a running `imp reflect`) and try again.",
+                            path.display()
+                        )));
+                    }
+                    std::thread::sleep(delay);
+                    delay = (delay * 2).min(Duration::from_millis(500));
+                }
+            }
+        };
 
-        let kg = Self { db };
+        let kg = Self { db, settings: KnowledgeConfig::default(), project: None };
         kg.ensure_schema()?;
         Ok(kg)
     }
 
+    /// Use the given `[knowledge]` settings for retrieval tuning
+    /// (e.g. `hybrid_alpha`) instead of the defaults.
+    pub fn with_settings(mut self, settings: &KnowledgeConfig) -> Self {
+        self.settings = settings.clone();
+        self
+    }
+
+    /// Scope to `project`: entities and chunks stored from now on are tagged
+    /// with it, and searches only return its knowledge plus global knowledge.
+    /// `None` stores global knowledge and searches everything.
+    pub fn with_project(mut self, project: Option<&str>) -> Self {
+        self.project = project.map(String::from);
+        self
+    }
+
+    /// Whether knowledge tagged `project` is visible from the active project.
+    fn in_scope(&self, project: &str) -> bool {
+        project.is_empty() || self.project.as_deref().map_or(true, |active| active == project)
+    }
+
     /// Path to the CozoDB database directory.
     fn db_path() -> Result<PathBuf> {
         Ok(imp_home()?.join("knowledge.cozo"))
@@ -147,6 +255,36 @@ impl KnowledgeGraph {
 
     /// Create all required relations if they don't already exist.
     fn ensure_schema(&self) -> Result<()> {
+        let memory_chunk = format!(
+            r#":create memory_chunk {{
+                id: String,
+                =>
+                content: String,
+                source_type: String,
+                source_id: String,
+                created_at: Float,
+                embedding: <F32; {}>,
+                has_embedding: Bool default false,
+                access_count: Int default 0,
+                last_accessed: Float default 0,
+                project: String default "",
+                dupe_count: Int default 0
+            }}"#,
+            Embedder::dimension()
+        );
+        let hnsw_index = format!(
+            r#"::hnsw create memory_chunk:embedding_index {{
+                dim: {},
+                m: 16,
+                dtype: F32,
+                fields: [embedding],
+                distance: Cosine,
+                ef_construction: 200,
+                filter: has_embedding
+            }}"#,
+            Embedder::dimension()
+        );
+
         // Create each relation, ignoring "already exists" errors
         let relations = vec![
             r#":create entity {
@@ -157,7 +295,10 @@ impl KnowledgeGraph {
                 name_lower: String default "",
                 properties: Json,
                 created_at: Float,
-                updated_at: Float
+                updated_at: Float,
+                project: String default "",
+                source: String default "",
+                confidence: Float? default null
             }"#,
             // Separate table for aliases with indexed case-insensitive search
             r#":create entity_alias {
@@ -173,20 +314,11 @@ impl KnowledgeGraph {
                 to_id: String,
                 =>
                 properties: Json,
-                created_at: Float
-            }"#,
-            r#":create memory_chunk {
-                id: String,
-                =>
-                content: String,
-                source_type: String,
-                source_id: String,
                 created_at: Float,
-                embedding: <F32; 1024>,
-                has_embedding: Bool default false,
-                access_count: Int default 0,
-                last_accessed: Float default 0
+                source: String default "",
+                confidence: Float? default null
             }"#,
+            memory_chunk.as_str(),
             r#":create chunk_entity {
                 chunk_id: String,
                 entity_id: String
@@ -228,18 +360,7 @@ impl KnowledgeGraph {
 
         // Create HNSW vector index on memory_chunk embeddings.
         // Ignore "already exists" just like the relations above.
-        match self.run_mutating(
-            r#"::hnsw create memory_chunk:embedding_index {
-                dim: 1024,
-                m: 16,
-                dtype: F32,
-                fields: [embedding],
-                distance: Cosine,
-                ef_construction: 200,
-                filter: has_embedding
-            }"#,
-            BTreeMap::new(),
-        ) {
+        match self.run_mutating(&hnsw_index, BTreeMap::new()) {
             Ok(_) => {}
             Err(e) => {
                 let msg = e.to_string();
@@ -321,6 +442,75 @@ impl KnowledgeGraph {
                 // Note: We leave the old aliases column in place (Cozo doesn't support column removal)
                 // It will just be ignored going forward
             }
+
+            // Migration 3: project tag (existing entities stay global)
+            if !col_names.iter().any(|c| c == "project") {
+                if let Err(e) = self.db.run_script(
+                    "::alter entity { +project: String default \"\" }",
+                    BTreeMap::new(),
+                    ScriptMutability::Mutable,
+                ) {
+                    tracing::warn!(error = %e, "Failed to add project column to entity");
+                }
+            }
+
+            // Migration 4: provenance (existing entities have none)
+            if !col_names.iter().any(|c| c == "source") {
+                if let Err(e) = self.db.run_script(
+                    "::alter entity { +source: String default \"\", +confidence: Float? default null }",
+                    BTreeMap::new(),
+                    ScriptMutability::Mutable,
+                ) {
+                    tracing::warn!(error = %e, "Failed to add provenance columns to entity");
+                }
+            }
+        }
+
+        // Migration 4, for relationships
+        if let Ok(cols) = self.db.run_script("::columns relationship", BTreeMap::new(), ScriptMutability::Immutable) {
+            let has_source = cols
+                .rows
+                .iter()
+                .any(|row| matches!(row.first(), Some(DataValue::Str(s)) if s.as_str() == "source"));
+            if !has_source {
+                if let Err(e) = self.db.run_script(
+                    "::alter relationship { +source: String default \"\", +confidence: Float? default null }",
+                    BTreeMap::new(),
+                    ScriptMutability::Mutable,
+                ) {
+                    tracing::warn!(error = %e, "Failed to add provenance columns to relationship");
+                }
+            }
+        }
+
+        if let Ok(cols) = self.db.run_script("::columns memory_chunk", BTreeMap::new(), ScriptMutability::Immutable) {
+            let has_column = |name: &str| {
+                cols.rows
+                    .iter()
+                    .any(|row| matches!(row.first(), Some(DataValue::Str(s)) if s.as_str() == name))
+            };
+
+            // Migration 3, for chunks: project tag (existing chunks stay global)
+            if !has_column("project") {
+                if let Err(e) = self.db.run_script(
+                    "::alter memory_chunk { +project: String default \"\" }",
+                    BTreeMap::new(),
+                    ScriptMutability::Mutable,
+                ) {
+                    tracing::warn!(error = %e, "Failed to add project column to memory_chunk");
+                }
+            }
+
+            // Migration 5: count of near-duplicates folded into each chunk
+            if !has_column("dupe_count") {
+                if let Err(e) = self.db.run_script(
+                    "::alter memory_chunk { +dupe_count: Int default 0 }",
+                    BTreeMap::new(),
+                    ScriptMutability::Mutable,
+                ) {
+                    tracing::warn!(error = %e, "Failed to add dupe_count column to memory_chunk");
+                }
+            }
         }
         
         // Create index on name_lower (runs for both new and migrated DBs, ignores if exists)
@@ -404,7 +594,8 @@ impl KnowledgeGraph {
     // ────────────────────────────────────────────────────────────
 
     /// Store a single entity. Generates UUID if id is empty.
-    /// Also stores any aliases to the entity_alias table.
+    /// Also stores any aliases to the entity_alias table. An existing entity
+    /// keeps its provenance unless `entity.source` is set.
     pub fn store_entity(&self, mut entity: Entity) -> Result<()> {
         if entity.id.is_empty() {
             entity.id = uuid::Uuid::new_v4().to_string();
@@ -417,6 +608,17 @@ impl KnowledgeGraph {
         }
 
         let name_lower = entity.name.to_lowercase();
+        // An existing entity keeps its project tag; a new one gets the active project
+        let project = match self.entity_project(&entity.id)? {
+            Some(project) => project,
+            None => self.project.clone().unwrap_or_default(),
+        };
+        if entity.source.is_empty() {
+            if let Some((source, confidence)) = self.stored_provenance("entity", &entity.id)? {
+                entity.source = source;
+                entity.confidence = confidence;
+            }
+        }
 
         let mut params = BTreeMap::new();
         params.insert("id".to_string(), DataValue::Str(entity.id.clone().into()));
@@ -426,12 +628,15 @@ impl KnowledgeGraph {
         params.insert("properties".to_string(), json_to_datavalue(&entity.properties));
         params.insert("created_at".to_string(), DataValue::from(entity.created_at));
         params.insert("updated_at".to_string(), DataValue::from(entity.updated_at));
+        params.insert("project".to_string(), DataValue::Str(project.into()));
+        params.insert("source".to_string(), DataValue::Str(entity.source.into()));
+        params.insert("confidence".to_string(), opt_f64_to_dv(entity.confidence));
 
         self.run_mutating(
-            r#"?[id, entity_type, name, properties, name_lower, created_at, updated_at] <- [
-                [$id, $entity_type, $name, $properties, $name_lower, $created_at, $updated_at]
+            r#"?[id, entity_type, name, properties, name_lower, created_at, updated_at, project, source, confidence] <- [
+                [$id, $entity_type, $name, $properties, $name_lower, $created_at, $updated_at, $project, $source, $confidence]
             ]
-            :put entity { id, entity_type, name => properties, name_lower, created_at, updated_at }"#,
+            :put entity { id, entity_type, name => properties, name_lower, created_at, updated_at, project, source, confidence }"#,
             params,
         )?;
 
@@ -445,6 +650,26 @@ impl KnowledgeGraph {
         Ok(())
     }
 
+    /// The project tag of the entity with this id, if it exists.
+    fn entity_project(&self, id: &str) -> Result<Option<String>> {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::Str(id.into()));
+        let result = self.run_query("?[project] := *entity{id, project}, id == $id", params)?;
+        Ok(result.rows.first().map(|row| dv_to_string(&row[0])))
+    }
+
+    /// The stored `(source, confidence)` of the entity or relationship
+    /// (`relation`) with this id, if it exists.
+    fn stored_provenance(&self, relation: &str, id: &str) -> Result<Option<(String, Option<f64>)>> {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::Str(id.into()));
+        let result = self.run_query(
+            &format!("?[source, confidence] := *{}{{id, source, confidence}}, id == $id", relation),
+            params,
+        )?;
+        Ok(result.rows.first().map(|row| (dv_to_string(&row[0]), dv_to_opt_f64(&row[1]))))
+    }
+
     /// Add an alias for an entity.
     pub fn store_alias(&self, entity_id: &str, alias: &str) -> Result<()> {
         let alias_lower = alias.to_lowercase();
@@ -488,7 +713,8 @@ impl KnowledgeGraph {
     // Relationship CRUD
     // ────────────────────────────────────────────────────────────
 
-    /// Store a single relationship. Generates UUID if id is empty.
+    /// Store a single relationship. Generates UUID if id is empty. An existing
+    /// relationship keeps its provenance unless `rel.source` is set.
     pub fn store_relationship(&self, mut rel: Relationship) -> Result<()> {
         if rel.id.is_empty() {
             rel.id = uuid::Uuid::new_v4().to_string();
@@ -496,6 +722,12 @@ impl KnowledgeGraph {
         if rel.created_at == 0.0 {
             rel.created_at = now_f64();
         }
+        if rel.source.is_empty() {
+            if let Some((source, confidence)) = self.stored_provenance("relationship", &rel.id)? {
+                rel.source = source;
+                rel.confidence = confidence;
+            }
+        }
 
         let mut params = BTreeMap::new();
         params.insert("id".to_string(), DataValue::Str(rel.id.into()));
@@ -504,12 +736,14 @@ impl KnowledgeGraph {
         params.insert("to_id".to_string(), DataValue::Str(rel.to_id.into()));
         params.insert("properties".to_string(), json_to_datavalue(&rel.properties));
         params.insert("created_at".to_string(), DataValue::from(rel.created_at));
+        params.insert("source".to_string(), DataValue::Str(rel.source.into()));
+        params.insert("confidence".to_string(), opt_f64_to_dv(rel.confidence));
 
         self.run_mutating(
-            r#"?[id, from_id, rel_type, to_id, properties, created_at] <- [
-                [$id, $from_id, $rel_type, $to_id, $properties, $created_at]
+            r#"?[id, from_id, rel_type, to_id, properties, created_at, source, confidence] <- [
+                [$id, $from_id, $rel_type, $to_id, $properties, $created_at, $source, $confidence]
             ]
-            :put relationship { id, from_id, rel_type, to_id => properties, created_at }"#,
+            :put relationship { id, from_id, rel_type, to_id => properties, created_at, source, confidence }"#,
             params,
         )?;
 
@@ -524,6 +758,213 @@ impl KnowledgeGraph {
         Ok(())
     }
 
+    // ────────────────────────────────────────────────────────────
+    // Entity maintenance
+    // ────────────────────────────────────────────────────────────
+
+    /// Merge `merge_id` into `keep_id`.
+    ///
+    /// Relationships and chunk links pointing at the merged entity are rewritten
+    /// onto the kept one, properties are unioned (the kept entity wins on
+    /// conflicts), and the merged entity's name and aliases become aliases of
+    /// the kept entity. The merged entity is then deleted.
+    pub fn merge_entities(&self, keep_id: &str, merge_id: &str) -> Result<Entity> {
+        if keep_id == merge_id {
+            return Err(ImpError::Database("Cannot merge an entity into itself".to_string()));
+        }
+        let mut keep = self.get_entity(keep_id)?.ok_or_else(|| {
+            ImpError::Database(format!("Entity not found: {}", keep_id))
+        })?;
+        let merged = self.get_entity(merge_id)?.ok_or_else(|| {
+            ImpError::Database(format!("Entity not found: {}", merge_id))
+        })?;
+
+        // 1. Relationships: rewrite endpoints, skipping edges that would
+        //    duplicate an existing one or become a self-loop.
+        let mut params = BTreeMap::new();
+        params.insert("merge_id".to_string(), DataValue::Str(merge_id.into()));
+        let result = self.run_query(
+            r#"?[id, from_id, rel_type, to_id, properties, created_at, source, confidence] :=
+                *relationship{id, from_id, rel_type, to_id, properties, created_at, source, confidence},
+                from_id == $merge_id
+            ?[id, from_id, rel_type, to_id, properties, created_at, source, confidence] :=
+                *relationship{id, from_id, rel_type, to_id, properties, created_at, source, confidence},
+                to_id == $merge_id"#,
+            params,
+        )?;
+
+        for row in &result.rows {
+            if row.len() < 8 {
+                continue;
+            }
+            let rel = Relationship {
+                id: dv_to_string(&row[0]),
+                from_id: dv_to_string(&row[1]),
+                rel_type: dv_to_string(&row[2]),
+                to_id: dv_to_string(&row[3]),
+                properties: dv_to_json(&row[4]),
+                created_at: dv_to_f64(&row[5]),
+                source: dv_to_string(&row[6]),
+                confidence: dv_to_opt_f64(&row[7]),
+            };
+            self.remove_relationship_row(&rel)?;
+
+            let remap = |id: &str| if id == merge_id { keep_id.to_string() } else { id.to_string() };
+            let rewritten = Relationship {
+                from_id: remap(&rel.from_id),
+                to_id: remap(&rel.to_id),
+                ..rel
+            };
+            if rewritten.from_id == rewritten.to_id
+                || self.has_relationship(&rewritten.from_id, &rewritten.rel_type, &rewritten.to_id)?
+            {
+                continue;
+            }
+            self.store_relationship(rewritten)?;
+        }
+
+        // 2. Chunk links (both columns are keys, so :put is idempotent)
+        let mut params = BTreeMap::new();
+        params.insert("keep_id".to_string(), DataValue::Str(keep_id.into()));
+        params.insert("merge_id".to_string(), DataValue::Str(merge_id.into()));
+        self.run_mutating(
+            r#"?[chunk_id, entity_id] := *chunk_entity{chunk_id, entity_id: $merge_id}, entity_id = $keep_id
+            :put chunk_entity { chunk_id, entity_id }"#,
+            params.clone(),
+        )?;
+        self.run_mutating(
+            r#"?[chunk_id, entity_id] := *chunk_entity{chunk_id, entity_id}, entity_id == $merge_id
+            :rm chunk_entity { chunk_id, entity_id }"#,
+            params.clone(),
+        )?;
+
+        // 3. Aliases: carry over the merged entity's aliases and its name
+        self.run_mutating(
+            r#"?[entity_id, alias_lower, alias] := *entity_alias{entity_id: $merge_id, alias_lower, alias}, entity_id = $keep_id
+            :put entity_alias { entity_id, alias_lower => alias }"#,
+            params.clone(),
+        )?;
+        self.run_mutating(
+            r#"?[entity_id, alias_lower] := *entity_alias{entity_id, alias_lower}, entity_id == $merge_id
+            :rm entity_alias { entity_id, alias_lower }"#,
+            params,
+        )?;
+        if !merged.name.eq_ignore_ascii_case(&keep.name) {
+            self.store_alias(keep_id, &merged.name)?;
+        }
+
+        // 4. Properties: union, kept entity wins on conflicts
+        let mut properties = properties_to_map(&merged.properties);
+        properties.extend(properties_to_map(&keep.properties));
+        keep.properties = JsonValue::Object(properties);
+        keep.updated_at = now_f64();
+        keep.aliases = Vec::new(); // already stored above
+        self.store_entity(keep.clone())?;
+
+        // 5. Finally drop the merged entity row
+        self.remove_entity_row(&merged)?;
+
+        keep.aliases = self.get_aliases(keep_id).unwrap_or_default();
+        Ok(keep)
+    }
+
+    /// Delete an entity (looked up by name or alias) together with its
+    /// relationships, chunk links, and aliases. Returns the deleted entity,
+    /// or `None` if nothing matched.
+    pub fn delete_entity(&self, name: &str) -> Result<Option<Entity>> {
+        let entity = match self.find_entity_by_name(name)? {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+
+        let mut params = BTreeMap::new();
+        params.insert("eid".to_string(), DataValue::Str(entity.id.clone().into()));
+
+        self.run_mutating(
+            r#"?[id, from_id, rel_type, to_id] :=
+                *relationship{id, from_id, rel_type, to_id}, from_id == $eid
+            ?[id, from_id, rel_type, to_id] :=
+                *relationship{id, from_id, rel_type, to_id}, to_id == $eid
+            :rm relationship { id, from_id, rel_type, to_id }"#,
+            params.clone(),
+        )?;
+        self.run_mutating(
+            r#"?[chunk_id, entity_id] := *chunk_entity{chunk_id, entity_id}, entity_id == $eid
+            :rm chunk_entity { chunk_id, entity_id }"#,
+            params.clone(),
+        )?;
+        self.run_mutating(
+            r#"?[entity_id, alias_lower] := *entity_alias{entity_id, alias_lower}, entity_id == $eid
+            :rm entity_alias { entity_id, alias_lower }"#,
+            params,
+        )?;
+        self.remove_entity_row(&entity)?;
+
+        Ok(Some(entity))
+    }
+
+    /// Fetch a single entity by id, including its aliases.
+    pub fn get_entity(&self, id: &str) -> Result<Option<Entity>> {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::Str(id.into()));
+
+        let result = self.run_query(
+            r#"?[id, entity_type, name, properties, created_at, updated_at, source, confidence] :=
+                *entity{id, entity_type, name, properties, created_at, updated_at, source, confidence},
+                id == $id"#,
+            params,
+        )?;
+
+        Ok(Self::rows_to_entities(&result).into_iter().next().map(|mut entity| {
+            entity.aliases = self.get_aliases(&entity.id).unwrap_or_default();
+            entity
+        }))
+    }
+
+    /// Whether an edge `from_id -[rel_type]-> to_id` already exists (any id).
+    fn has_relationship(&self, from_id: &str, rel_type: &str, to_id: &str) -> Result<bool> {
+        let mut params = BTreeMap::new();
+        params.insert("from_id".to_string(), DataValue::Str(from_id.into()));
+        params.insert("rel_type".to_string(), DataValue::Str(rel_type.into()));
+        params.insert("to_id".to_string(), DataValue::Str(to_id.into()));
+
+        let result = self.run_query(
+            r#"?[id] := *relationship{id, from_id, rel_type, to_id},
+                from_id == $from_id, rel_type == $rel_type, to_id == $to_id"#,
+            params,
+        )?;
+        Ok(!result.rows.is_empty())
+    }
+
+    fn remove_relationship_row(&self, rel: &Relationship) -> Result<()> {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::Str(rel.id.clone().into()));
+        params.insert("from_id".to_string(), DataValue::Str(rel.from_id.clone().into()));
+        params.insert("rel_type".to_string(), DataValue::Str(rel.rel_type.clone().into()));
+        params.insert("to_id".to_string(), DataValue::Str(rel.to_id.clone().into()));
+
+        self.run_mutating(
+            r#"?[id, from_id, rel_type, to_id] <- [[$id, $from_id, $rel_type, $to_id]]
+            :rm relationship { id, from_id, rel_type, to_id }"#,
+            params,
+        )?;
+        Ok(())
+    }
+
+    fn remove_entity_row(&self, entity: &Entity) -> Result<()> {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::Str(entity.id.clone().into()));
+        params.insert("entity_type".to_string(), DataValue::Str(entity.entity_type.clone().into()));
+        params.insert("name".to_string(), DataValue::Str(entity.name.clone().into()));
+
+        self.run_mutating(
+            r#"?[id, entity_type, name] <- [[$id, $entity_type, $name]]
+            :rm entity { id, entity_type, name }"#,
+            params,
+        )?;
+        Ok(())
+    }
+
     // ────────────────────────────────────────────────────────────
     // Queries
     // ────────────────────────────────────────────────────────────
@@ -537,8 +978,8 @@ impl KnowledgeGraph {
 
         // 1. Try search by name_lower column (indexed, new schema)
         let result = self.run_query(
-            r#"?[id, entity_type, name, properties, created_at, updated_at] :=
-                *entity{id, entity_type, name, properties, name_lower, created_at, updated_at},
+            r#"?[id, entity_type, name, properties, created_at, updated_at, source, confidence] :=
+                *entity{id, entity_type, name, properties, name_lower, created_at, updated_at, source, confidence},
                 name_lower == $name_lower"#,
             params.clone(),
         );
@@ -567,10 +1008,10 @@ impl KnowledgeGraph {
 
         // 3. Search by alias_lower (entity_alias table)
         let result = self.run_query(
-            r#"?[id, entity_type, name, properties, created_at, updated_at] :=
+            r#"?[id, entity_type, name, properties, created_at, updated_at, source, confidence] :=
                 *entity_alias{entity_id, alias_lower},
                 alias_lower == $name_lower,
-                *entity{id: entity_id, entity_type, name, properties, created_at, updated_at},
+                *entity{id: entity_id, entity_type, name, properties, created_at, updated_at, source, confidence},
                 id = entity_id"#,
             params,
         );
@@ -585,6 +1026,74 @@ impl KnowledgeGraph {
         Ok(None)
     }
 
+    /// Fuzzy entity search. Ranks entities by substring match and edit
+    /// distance against their name and aliases, and by substring match
+    /// against property values. Returns `(entity, score)` pairs, best first.
+    pub fn search_entities(&self, query: &str, limit: usize) -> Result<Vec<(Entity, f64)>> {
+        let query_lower = query.trim().to_lowercase();
+        if query_lower.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let result = self.run_query(
+            r#"?[id, entity_type, name, properties, created_at, updated_at, source, confidence, project] :=
+                *entity{id, entity_type, name, properties, created_at, updated_at, source, confidence, project}"#,
+            BTreeMap::new(),
+        )?;
+        let in_scope: std::collections::HashSet<String> = result
+            .rows
+            .iter()
+            .filter(|row| self.in_scope(&dv_to_string(&row[8])))
+            .map(|row| dv_to_string(&row[0]))
+            .collect();
+        let alias_rows = self.run_query(
+            "?[entity_id, alias] := *entity_alias{entity_id, alias}",
+            BTreeMap::new(),
+        )?;
+
+        let mut aliases: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+        for row in &alias_rows.rows {
+            if row.len() >= 2 {
+                aliases.entry(dv_to_string(&row[0])).or_default().push(dv_to_string(&row[1]));
+            }
+        }
+
+        let mut scored: Vec<(Entity, f64)> = Self::rows_to_entities(&result)
+            .into_iter()
+            .filter(|entity| in_scope.contains(&entity.id))
+            .filter_map(|mut entity| {
+                entity.aliases = aliases.remove(&entity.id).unwrap_or_default();
+
+                let name_score = std::iter::once(&entity.name)
+                    .chain(entity.aliases.iter())
+                    .map(|n| name_match_score(&query_lower, &n.to_lowercase()))
+                    .fold(0.0, f64::max);
+
+                let props_score = if properties_to_map(&entity.properties)
+                    .values()
+                    .any(|v| match v {
+                        JsonValue::String(s) => s.to_lowercase().contains(&query_lower),
+                        other => other.to_string().to_lowercase().contains(&query_lower),
+                    }) {
+                    0.5
+                } else {
+                    0.0
+                };
+
+                let score = name_score.max(props_score);
+                (score > 0.0).then_some((entity, score))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.name.cmp(&b.0.name))
+        });
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
     /// Get entities related to a given entity (1-2 hops via relationships).
     pub fn get_related(&self, entity_name: &str, max_depth: usize) -> Result<Vec<RelatedEntity>> {
         let entity = match self.find_entity_by_name(entity_name)? {
@@ -597,12 +1106,12 @@ impl KnowledgeGraph {
 
         // 1-hop: direct relationships
         let result = self.run_query(
-            r#"?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction] :=
-                *relationship{from_id: $eid, rel_type, to_id: other_id},
+            r#"?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction, source, confidence] :=
+                *relationship{from_id: $eid, rel_type, to_id: other_id, source, confidence},
                 *entity{id: other_id, entity_type: other_type, name: other_name, properties: other_props, created_at: other_created, updated_at: other_updated},
                 direction = "->"
-            ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction] :=
-                *relationship{from_id: other_id, rel_type, to_id: $eid},
+            ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction, source, confidence] :=
+                *relationship{from_id: other_id, rel_type, to_id: $eid, source, confidence},
                 *entity{id: other_id, entity_type: other_type, name: other_name, properties: other_props, created_at: other_created, updated_at: other_updated},
                 direction = "<-""#,
             params.clone(),
@@ -610,7 +1119,7 @@ impl KnowledgeGraph {
 
         let mut related: Vec<RelatedEntity> = Vec::new();
         for row in &result.rows {
-            if row.len() >= 8 {
+            if row.len() >= 10 {
                 let other = Entity {
                     id: dv_to_string(&row[0]),
                     entity_type: dv_to_string(&row[1]),
@@ -619,11 +1128,16 @@ impl KnowledgeGraph {
                     aliases: Vec::new(), // Load separately if needed
                     created_at: dv_to_f64(&row[4]),
                     updated_at: dv_to_f64(&row[5]),
+                    source: String::new(),
+                    confidence: None,
                 };
                 related.push(RelatedEntity {
                     entity: other,
                     rel_type: dv_to_string(&row[6]),
                     direction: dv_to_string(&row[7]),
+                    via: None,
+                    source: dv_to_string(&row[8]),
+                    confidence: dv_to_opt_f64(&row[9]),
                 });
             }
         }
@@ -641,16 +1155,16 @@ impl KnowledgeGraph {
 
             let result2 = self.run_query(
                 r#"hop1[hop1_id] := hop1_id in $hop1_ids
-                ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction] :=
+                ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction, hop1_id, source, confidence] :=
                     hop1[hop1_id],
-                    *relationship{from_id: hop1_id, rel_type, to_id: other_id},
+                    *relationship{from_id: hop1_id, rel_type, to_id: other_id, source, confidence},
                     *entity{id: other_id, entity_type: other_type, name: other_name, properties: other_props, created_at: other_created, updated_at: other_updated},
                     other_id != $eid,
                     not hop1[other_id],
                     direction = "->"
-                ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction] :=
+                ?[other_id, other_type, other_name, other_props, other_created, other_updated, rel_type, direction, hop1_id, source, confidence] :=
                     hop1[hop1_id],
-                    *relationship{from_id: other_id, rel_type, to_id: hop1_id},
+                    *relationship{from_id: other_id, rel_type, to_id: hop1_id, source, confidence},
                     *entity{id: other_id, entity_type: other_type, name: other_name, properties: other_props, created_at: other_created, updated_at: other_updated},
                     other_id != $eid,
                     not hop1[other_id],
@@ -658,8 +1172,13 @@ impl KnowledgeGraph {
                 params2,
             )?;
 
+            let hop1_names: BTreeMap<String, String> = related
+                .iter()
+                .map(|r| (r.entity.id.clone(), r.entity.name.clone()))
+                .collect();
+
             for row in &result2.rows {
-                if row.len() >= 8 {
+                if row.len() >= 11 {
                     let other = Entity {
                         id: dv_to_string(&row[0]),
                         entity_type: dv_to_string(&row[1]),
@@ -668,11 +1187,16 @@ impl KnowledgeGraph {
                         aliases: Vec::new(), // Load separately if needed
                         created_at: dv_to_f64(&row[4]),
                         updated_at: dv_to_f64(&row[5]),
+                        source: String::new(),
+                        confidence: None,
                     };
                     related.push(RelatedEntity {
                         entity: other,
                         rel_type: dv_to_string(&row[6]),
                         direction: dv_to_string(&row[7]),
+                        via: hop1_names.get(&dv_to_string(&row[8])).cloned(),
+                        source: dv_to_string(&row[9]),
+                        confidence: dv_to_opt_f64(&row[10]),
                     });
                 }
             }
@@ -722,39 +1246,292 @@ impl KnowledgeGraph {
         Ok(SchemaInfo { types, relationships })
     }
 
-    /// Get counts of entities, relationships, and chunks.
-    pub fn stats(&self) -> Result<KnowledgeStats> {
-        let entity_count = self.count_rows("?[count(id)] := *entity{id}")?;
-        let relationship_count = self.count_rows("?[count(id)] := *relationship{id}")?;
-        let chunk_count = self.count_rows("?[count(id)] := *memory_chunk{id}")?;
-        let schema_type_count = self.count_rows("?[count(type_name)] := *schema_type{type_name}")?;
-        let schema_rel_count = self.count_rows("?[count(rel_name)] := *schema_rel{rel_name}")?;
+    /// Add an entity type to the schema, or update its description. An
+    /// existing type keeps its example names and creation time.
+    pub fn put_schema_type(&self, type_name: &str, description: &str) -> Result<()> {
+        let mut params = BTreeMap::new();
+        params.insert("type_name".to_string(), DataValue::Str(type_name.into()));
+        let existing = self.run_query(
+            "?[example_names, created_at] := *schema_type{type_name: $type_name, example_names, created_at}",
+            params.clone(),
+        )?;
+        let (example_names, created_at) = match existing.rows.first() {
+            Some(row) if row.len() >= 2 => (row[0].clone(), row[1].clone()),
+            _ => (DataValue::List(vec![]), DataValue::from(now_f64())),
+        };
 
-        Ok(KnowledgeStats {
-            entity_count,
-            relationship_count,
-            chunk_count,
-            schema_type_count,
-            schema_rel_count,
-        })
+        params.insert("description".to_string(), DataValue::Str(description.into()));
+        params.insert("example_names".to_string(), example_names);
+        params.insert("created_at".to_string(), created_at);
+        self.run_mutating(
+            r#"?[type_name, description, example_names, created_at] <- [
+                [$type_name, $description, $example_names, $created_at]
+            ]
+            :put schema_type { type_name => description, example_names, created_at }"#,
+            params,
+        )?;
+        Ok(())
     }
 
-    // ────────────────────────────────────────────────────────────
-    // Memory chunk embedding methods
-    // ────────────────────────────────────────────────────────────
-
-    /// Store a memory chunk with optional embedding.
-    /// Returns the chunk ID.
-    pub fn store_chunk(&self, content: &str, source_type: &str, source_id: &str) -> Result<String> {
+    /// Add a relationship type to the schema, or replace an existing one.
+    /// Empty `from_types` / `to_types` mean any entity type.
+    pub fn put_schema_rel(
+        &self,
+        rel_name: &str,
+        description: &str,
+        from_types: &[String],
+        to_types: &[String],
+        example_usage: &str,
+    ) -> Result<()> {
+        let as_list = |types: &[String]| DataValue::List(types.iter().map(|t| DataValue::Str(t.as_str().into())).collect());
+        let mut params = BTreeMap::new();
+        params.insert("rel_name".to_string(), DataValue::Str(rel_name.into()));
+        params.insert("description".to_string(), DataValue::Str(description.into()));
+        params.insert("from_types".to_string(), as_list(from_types));
+        params.insert("to_types".to_string(), as_list(to_types));
+        params.insert("example_usage".to_string(), DataValue::Str(example_usage.into()));
+        params.insert("created_at".to_string(), DataValue::from(now_f64()));
+
+        self.run_mutating(
+            r#"?[rel_name, description, from_types, to_types, example_usage, created_at] <- [
+                [$rel_name, $description, $from_types, $to_types, $example_usage, $created_at]
+            ]
+            :put schema_rel { rel_name => description, from_types, to_types, example_usage, created_at }"#,
+            params,
+        )?;
+        Ok(())
+    }
+
+    /// Remove an entity type from the schema. Entities of that type are left
+    /// alone. Returns false if there was no such type.
+    pub fn remove_schema_type(&self, type_name: &str) -> Result<bool> {
+        let mut params = BTreeMap::new();
+        params.insert("type_name".to_string(), DataValue::Str(type_name.into()));
+        let existing = self.run_query("?[type_name] := *schema_type{type_name}, type_name == $type_name", params.clone())?;
+        if existing.rows.is_empty() {
+            return Ok(false);
+        }
+        self.run_mutating("?[type_name] <- [[$type_name]] :rm schema_type { type_name }", params)?;
+        Ok(true)
+    }
+
+    /// Remove a relationship type from the schema. Relationships of that type
+    /// are left alone. Returns false if there was no such type.
+    pub fn remove_schema_rel(&self, rel_name: &str) -> Result<bool> {
+        let mut params = BTreeMap::new();
+        params.insert("rel_name".to_string(), DataValue::Str(rel_name.into()));
+        let existing = self.run_query("?[rel_name] := *schema_rel{rel_name}, rel_name == $rel_name", params.clone())?;
+        if existing.rows.is_empty() {
+            return Ok(false);
+        }
+        self.run_mutating("?[rel_name] <- [[$rel_name]] :rm schema_rel { rel_name }", params)?;
+        Ok(true)
+    }
+
+    /// How many entities have the given type.
+    pub fn count_entities_of_type(&self, entity_type: &str) -> Result<usize> {
+        let mut params = BTreeMap::new();
+        params.insert("entity_type".to_string(), DataValue::Str(entity_type.into()));
+        let result = self.run_query("?[count(id)] := *entity{id, entity_type}, entity_type == $entity_type", params)?;
+        Ok(Self::extract_int(&result, 0, 0).unwrap_or(0) as usize)
+    }
+
+    /// How many relationships have the given type.
+    pub fn count_relationships_of_type(&self, rel_type: &str) -> Result<usize> {
+        let mut params = BTreeMap::new();
+        params.insert("rel_type".to_string(), DataValue::Str(rel_type.into()));
+        let result = self.run_query("?[count(id)] := *relationship{id, rel_type}, rel_type == $rel_type", params)?;
+        Ok(Self::extract_int(&result, 0, 0).unwrap_or(0) as usize)
+    }
+
+    /// Get counts of entities, relationships, and chunks.
+    pub fn stats(&self) -> Result<KnowledgeStats> {
+        let entity_count = self.count_rows("?[count(id)] := *entity{id}")?;
+        let relationship_count = self.count_rows("?[count(id)] := *relationship{id}")?;
+        let chunk_count = self.count_rows("?[count(id)] := *memory_chunk{id}")?;
+        let duplicate_chunks = self.sum_rows("?[sum(dupe_count)] := *memory_chunk{dupe_count}", BTreeMap::new())?;
+        let schema_type_count = self.count_rows("?[count(type_name)] := *schema_type{type_name}")?;
+        let schema_rel_count = self.count_rows("?[count(rel_name)] := *schema_rel{rel_name}")?;
+
+        Ok(KnowledgeStats {
+            entity_count,
+            relationship_count,
+            chunk_count,
+            duplicate_chunks,
+            schema_type_count,
+            schema_rel_count,
+        })
+    }
+
+    /// Counts of the entities and chunks tagged with `project`, and of the
+    /// relationships from those entities. Schema counts are global.
+    pub fn project_stats(&self, project: &str) -> Result<KnowledgeStats> {
+        let count = |query: &str| -> Result<usize> {
+            let mut params = BTreeMap::new();
+            params.insert("project".to_string(), DataValue::Str(project.into()));
+            let result = self.run_query(query, params)?;
+            Ok(Self::extract_int(&result, 0, 0).unwrap_or(0) as usize)
+        };
+
+        Ok(KnowledgeStats {
+            entity_count: count("?[count(id)] := *entity{id, project}, project == $project")?,
+            relationship_count: count(
+                "?[count(id)] := *relationship{id, from_id}, *entity{id: from_id, project}, project == $project",
+            )?,
+            chunk_count: count("?[count(id)] := *memory_chunk{id, project}, project == $project")?,
+            duplicate_chunks: self.sum_rows(
+                "?[sum(dupe_count)] := *memory_chunk{dupe_count, project}, project == $project",
+                BTreeMap::from([("project".to_string(), DataValue::Str(project.into()))]),
+            )?,
+            schema_type_count: self.count_rows("?[count(type_name)] := *schema_type{type_name}")?,
+            schema_rel_count: self.count_rows("?[count(rel_name)] := *schema_rel{rel_name}")?,
+        })
+    }
+
+    // ────────────────────────────────────────────────────────────
+    // Export
+    // ────────────────────────────────────────────────────────────
+
+    /// Write the full entity/relationship graph to `out` as JSON
+    /// (`{"entities": [...], "relationships": [...]}`) or GraphML.
+    /// Records are written one at a time rather than built up in memory.
+    pub fn export<W: Write>(&self, format: ExportFormat, out: &mut W) -> Result<()> {
+        let entities = self.run_query(
+            r#"?[id, entity_type, name, properties, created_at, updated_at, source, confidence] :=
+                *entity{id, entity_type, name, properties, created_at, updated_at, source, confidence}"#,
+            BTreeMap::new(),
+        )?;
+        let relationships = self.run_query(
+            r#"?[id, from_id, rel_type, to_id, properties, created_at, source, confidence] :=
+                *relationship{id, from_id, rel_type, to_id, properties, created_at, source, confidence}"#,
+            BTreeMap::new(),
+        )?;
+
+        match format {
+            ExportFormat::Json => Self::export_json(&entities, &relationships, out),
+            ExportFormat::GraphMl => Self::export_graphml(&entities, &relationships, out),
+        }
+    }
+
+    fn export_json<W: Write>(entities: &NamedRows, relationships: &NamedRows, out: &mut W) -> Result<()> {
+        write!(out, "{{\"entities\": [")?;
+        for (i, mut entity) in Self::rows_to_entities(entities).into_iter().enumerate() {
+            entity.properties = JsonValue::Object(properties_to_map(&entity.properties));
+            write!(out, "{}\n  ", if i == 0 { "" } else { "," })?;
+            serde_json::to_writer(&mut *out, &entity)?;
+        }
+        write!(out, "\n], \"relationships\": [")?;
+        for (i, row) in relationships.rows.iter().filter(|r| r.len() >= 6).enumerate() {
+            let rel = Relationship {
+                id: dv_to_string(&row[0]),
+                from_id: dv_to_string(&row[1]),
+                rel_type: dv_to_string(&row[2]),
+                to_id: dv_to_string(&row[3]),
+                properties: JsonValue::Object(properties_to_map(&dv_to_json(&row[4]))),
+                created_at: dv_to_f64(&row[5]),
+                source: row.get(6).map(dv_to_string).unwrap_or_default(),
+                confidence: row.get(7).and_then(dv_to_opt_f64),
+            };
+            write!(out, "{}\n  ", if i == 0 { "" } else { "," })?;
+            serde_json::to_writer(&mut *out, &rel)?;
+        }
+        writeln!(out, "\n]}}")?;
+        Ok(())
+    }
+
+    fn export_graphml<W: Write>(entities: &NamedRows, relationships: &NamedRows, out: &mut W) -> Result<()> {
+        let entities = Self::rows_to_entities(entities);
+
+        // GraphML needs every attribute key declared before the graph body
+        let mut property_keys: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
+        for entity in &entities {
+            property_keys.extend(properties_to_map(&entity.properties).keys().cloned());
+        }
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(out, r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <key id="type" for="node" attr.name="type" attr.type="string"/>"#)?;
+        for key in &property_keys {
+            writeln!(
+                out,
+                r#"  <key id="prop_{0}" for="node" attr.name="{0}" attr.type="string"/>"#,
+                xml_escape(key)
+            )?;
+        }
+        writeln!(out, r#"  <key id="label" for="edge" attr.name="label" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <graph id="knowledge" edgedefault="directed">"#)?;
+
+        for entity in &entities {
+            writeln!(out, r#"    <node id="{}">"#, xml_escape(&entity.id))?;
+            writeln!(out, r#"      <data key="name">{}</data>"#, xml_escape(&entity.name))?;
+            writeln!(out, r#"      <data key="type">{}</data>"#, xml_escape(&entity.entity_type))?;
+            for (key, value) in properties_to_map(&entity.properties) {
+                let value = match value {
+                    JsonValue::String(s) => s,
+                    other => other.to_string(),
+                };
+                writeln!(
+                    out,
+                    r#"      <data key="prop_{}">{}</data>"#,
+                    xml_escape(&key),
+                    xml_escape(&value)
+                )?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+
+        for row in relationships.rows.iter().filter(|r| r.len() >= 6) {
+            writeln!(
+                out,
+                r#"    <edge id="{}" source="{}" target="{}">"#,
+                xml_escape(&dv_to_string(&row[0])),
+                xml_escape(&dv_to_string(&row[1])),
+                xml_escape(&dv_to_string(&row[3])),
+            )?;
+            writeln!(out, r#"      <data key="label">{}</data>"#, xml_escape(&dv_to_string(&row[2])))?;
+            writeln!(out, "    </edge>")?;
+        }
+
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")?;
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────
+    // Memory chunk embedding methods
+    // ────────────────────────────────────────────────────────────
+
+    /// Store a memory chunk with optional embedding. If a chunk in scope is
+    /// more similar than `[knowledge] chunk_dedup_threshold`, nothing is
+    /// stored: that chunk's `dupe_count` goes up and its id is returned.
+    /// Needs embeddings; without them every chunk is stored.
+    pub fn store_chunk(&self, content: &str, source_type: &str, source_id: &str) -> Result<StoredChunk> {
         let chunk_id = uuid::Uuid::new_v4().to_string();
         let now = now_f64();
 
         // Try to get embedding
         let (embedding_vec, has_embedding) = match Embedder::embed(content) {
             Some(vec) => (vec, true),
-            None => (vec![0.0; 1024], false), // Placeholder vector
+            None => (vec![0.0; Embedder::dimension()], false), // Placeholder vector
         };
 
+        // A near-identical chunk is already stored: count it there instead
+        if has_embedding && self.settings.chunk_dedup {
+            if let Some((id, distance)) = self.nearest_chunk(&embedding_vec)? {
+                if 1.0 - distance > self.settings.chunk_dedup_threshold {
+                    let mut params = BTreeMap::new();
+                    params.insert("id".to_string(), DataValue::Str(id.clone().into()));
+                    self.run_mutating(
+                        r#"?[id, dupe_count] := *memory_chunk{id, dupe_count: old}, id == $id, dupe_count = old + 1
+                        :update memory_chunk { id => dupe_count }"#,
+                        params,
+                    )?;
+                    return Ok(StoredChunk { id, duplicate: true });
+                }
+            }
+        }
+
         let mut params = BTreeMap::new();
         params.insert("id".to_string(), DataValue::Str(chunk_id.clone().into()));
         params.insert("content".to_string(), DataValue::Str(content.into()));
@@ -767,48 +1544,87 @@ impl KnowledgeGraph {
         params.insert("has_embedding".to_string(), DataValue::Bool(has_embedding));
         params.insert("access_count".to_string(), DataValue::from(0i64));
         params.insert("last_accessed".to_string(), DataValue::from(0.0));
+        params.insert("project".to_string(), DataValue::Str(self.project.clone().unwrap_or_default().into()));
 
         self.run_mutating(
-            r#"?[id, content, source_type, source_id, created_at, embedding, has_embedding, access_count, last_accessed] <- [
-                [$id, $content, $source_type, $source_id, $created_at, $embedding, $has_embedding, $access_count, $last_accessed]
+            r#"?[id, content, source_type, source_id, created_at, embedding, has_embedding, access_count, last_accessed, project] <- [
+                [$id, $content, $source_type, $source_id, $created_at, $embedding, $has_embedding, $access_count, $last_accessed, $project]
             ]
-            :put memory_chunk { id => content, source_type, source_id, created_at, embedding, has_embedding, access_count, last_accessed }"#,
+            :put memory_chunk { id => content, source_type, source_id, created_at, embedding, has_embedding, access_count, last_accessed, project }"#,
             params,
         )?;
 
-        Ok(chunk_id)
+        Ok(StoredChunk { id: chunk_id, duplicate: false })
     }
 
-    /// Search for similar chunks using vector similarity.
-    pub fn search_similar(&self, query_text: &str, k: usize) -> Result<Vec<MemoryChunk>> {
-        // Check if embeddings are available
-        if !Embedder::available() {
-            return self.search_chunks_by_text(query_text, k);
+    /// Delete a memory chunk and its entity links. Removing the row from
+    /// `memory_chunk` also drops it from the HNSW index, so vector search
+    /// won't return it afterwards. Returns false if the chunk didn't exist.
+    pub fn delete_chunk(&self, chunk_id: &str) -> Result<bool> {
+        let mut params = BTreeMap::new();
+        params.insert("chunk_id".to_string(), DataValue::Str(chunk_id.into()));
+
+        let existing = self.run_query(
+            "?[id] := *memory_chunk{id}, id == $chunk_id",
+            params.clone(),
+        )?;
+        if existing.rows.is_empty() {
+            return Ok(false);
         }
 
-        let query_embedding = match Embedder::embed(query_text) {
-            Some(vec) => vec,
-            None => return self.search_chunks_by_text(query_text, k),
-        };
+        self.run_mutating(
+            r#"?[chunk_id, entity_id] := *chunk_entity{chunk_id, entity_id}, chunk_id == $chunk_id
+            :rm chunk_entity { chunk_id, entity_id }"#,
+            params.clone(),
+        )?;
+        self.run_mutating(
+            r#"?[id] <- [[$chunk_id]]
+            :rm memory_chunk { id }"#,
+            params,
+        )?;
+
+        Ok(true)
+    }
+
+    /// Chunks older than `max_age_days` that have been retrieved fewer than
+    /// `min_access_count` times. These are the candidates for `prune_chunks`.
+    pub fn prunable_chunks(&self, max_age_days: u64, min_access_count: i64) -> Result<Vec<MemoryChunk>> {
+        let cutoff = now_f64() - (max_age_days as f64) * 86_400.0;
 
         let mut params = BTreeMap::new();
-        params.insert("query_vec".to_string(), DataValue::List(
-            query_embedding.into_iter().map(|f| DataValue::from(f as f64)).collect()
-        ));
-        params.insert("k".to_string(), DataValue::from(k as i64));
+        params.insert("cutoff".to_string(), DataValue::from(cutoff));
+        params.insert("min_access".to_string(), DataValue::from(min_access_count));
 
         let result = self.run_query(
-            r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed] := 
-                ~memory_chunk:embedding_index{
-                    id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed |
-                    query: $query_vec,
-                    k: $k,
-                    ef: 50
-                }"#,
+            r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed] :=
+                *memory_chunk{id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed},
+                created_at < $cutoff,
+                access_count < $min_access"#,
             params,
         )?;
 
-        let chunks = Self::rows_to_chunks(&result);
+        Ok(Self::rows_to_chunks(&result))
+    }
+
+    /// Delete old, rarely retrieved chunks (see `prunable_chunks`).
+    /// Returns the chunks that were removed.
+    pub fn prune_chunks(&self, max_age_days: u64, min_access_count: i64) -> Result<Vec<MemoryChunk>> {
+        let candidates = self.prunable_chunks(max_age_days, min_access_count)?;
+        let mut removed = Vec::with_capacity(candidates.len());
+        for chunk in candidates {
+            if self.delete_chunk(&chunk.id)? {
+                removed.push(chunk);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Search for similar chunks using vector similarity.
+    pub fn search_similar(&self, query_text: &str, k: usize) -> Result<Vec<MemoryChunk>> {
+        let chunks = match self.vector_search(query_text, k)? {
+            Some(chunks) => chunks,
+            None => return self.search_chunks_by_text(query_text, k),
+        };
 
         // Update access counts (best-effort, don't fail search on tracking errors)
         for chunk in &chunks {
@@ -823,16 +1639,106 @@ impl KnowledgeGraph {
     /// the top-k results ranked by relevance. Handles stemming, stop word removal,
     /// and unicode normalization via the bm25 crate's default tokenizer.
     pub fn search_chunks_by_text(&self, query: &str, k: usize) -> Result<Vec<MemoryChunk>> {
+        let matched_chunks = self.bm25_search(query, k)?;
+
+        // Update access counts (best-effort, don't fail search on tracking errors)
+        for chunk in &matched_chunks {
+            let _ = self.increment_access_count(&chunk.id);
+        }
+
+        Ok(matched_chunks)
+    }
+
+    /// Search chunks with both vector similarity and BM25, fusing the two
+    /// rankings with weighted reciprocal-rank fusion. `[knowledge] hybrid_alpha`
+    /// weights the vector ranking (1.0 = vector only, 0.0 = BM25 only).
+    /// Uses whichever signal is available if embeddings are off.
+    pub fn search_hybrid(&self, query: &str, k: usize) -> Result<Vec<MemoryChunk>> {
+        let mut chunks = self.hybrid_candidates(query, k)?;
+        chunks.truncate(k);
+
+        // Update access counts (best-effort, don't fail search on tracking errors)
+        for chunk in &chunks {
+            let _ = self.increment_access_count(&chunk.id);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Fused vector + BM25 ranking, best first, without access tracking.
+    /// May return up to 2×k chunks.
+    fn hybrid_candidates(&self, query: &str, k: usize) -> Result<Vec<MemoryChunk>> {
+        // Over-fetch from each side so fusion has candidates to reorder
+        let candidates = k * 2;
+        let vector = self.vector_search(query, candidates)?.unwrap_or_default();
+        let text = self.bm25_search(query, candidates)?;
+
+        let alpha = self.settings.hybrid_alpha.clamp(0.0, 1.0);
+        Ok(reciprocal_rank_fusion(vector, text, alpha))
+    }
+
+    /// Nearest-neighbour search over the HNSW index, ranked by similarity.
+    /// Returns `None` when no query embedding can be produced.
+    fn vector_search(&self, query_text: &str, k: usize) -> Result<Option<Vec<MemoryChunk>>> {
+        // Check if embeddings are available
+        if !Embedder::available() {
+            return Ok(None);
+        }
+
+        let query_embedding = match Embedder::embed_query(query_text) {
+            Some(vec) => vec,
+            None => return Ok(None),
+        };
+
+        let mut params = BTreeMap::new();
+        params.insert("query_vec".to_string(), DataValue::List(
+            query_embedding.into_iter().map(|f| DataValue::from(f as f64)).collect()
+        ));
+        params.insert("k".to_string(), DataValue::from(k as i64));
+        // Only the active project's chunks and global ones
+        let filter = match self.project {
+            Some(ref project) => {
+                params.insert("project".to_string(), DataValue::Str(project.as_str().into()));
+                "filter: project == '' || project == $project,"
+            }
+            None => "",
+        };
+
+        let result = self.run_query(
+            &format!(
+                r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project, dist] :=
+                ~memory_chunk:embedding_index{{
+                    id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project |
+                    query: $query_vec,
+                    k: $k,
+                    ef: 50,
+                    {}
+                    bind_distance: dist
+                }}
+            :order dist"#,
+                filter
+            ),
+            params,
+        )?;
+
+        Ok(Some(Self::rows_to_chunks(&result)))
+    }
+
+    /// BM25 ranking over all chunk contents, best match first.
+    fn bm25_search(&self, query: &str, k: usize) -> Result<Vec<MemoryChunk>> {
         use bm25::{SearchEngineBuilder, Language};
 
         // Load all chunks from CozoDB
         let result = self.run_query(
-            r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed] := 
-                *memory_chunk{id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed}"#,
+            r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project] :=
+                *memory_chunk{id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project}"#,
             BTreeMap::new(),
         )?;
 
-        let all_chunks = Self::rows_to_chunks(&result);
+        let all_chunks: Vec<MemoryChunk> = Self::rows_to_chunks(&result)
+            .into_iter()
+            .filter(|chunk| self.in_scope(&chunk.project))
+            .collect();
         if all_chunks.is_empty() {
             return Ok(Vec::new());
         }
@@ -843,54 +1749,81 @@ impl KnowledgeGraph {
 
         let results = engine.search(query, k);
 
-        let matched_chunks: Vec<MemoryChunk> = results
+        Ok(results
             .into_iter()
             .filter(|r| r.score > 0.0)
             .filter_map(|r| all_chunks.get(r.document.id as usize).cloned())
-            .collect();
-
-        // Update access counts (best-effort, don't fail search on tracking errors)
-        for chunk in &matched_chunks {
-            let _ = self.increment_access_count(&chunk.id);
-        }
-
-        Ok(matched_chunks)
+            .collect())
     }
 
-    /// Check if a similar chunk already exists (for deduplication).
-    pub fn has_similar_chunk(&self, content: &str, threshold: f32) -> Result<bool> {
-        if !Embedder::available() {
-            return Ok(false); // No way to check similarity without embeddings
-        }
-
-        let embedding = match Embedder::embed(content) {
-            Some(vec) => vec,
-            None => return Ok(false),
-        };
-
+    /// The stored chunk closest to `embedding` that's visible from the active
+    /// project, with its cosine distance. Only embedded chunks are indexed.
+    fn nearest_chunk(&self, embedding: &[f32]) -> Result<Option<(String, f64)>> {
         let mut params = BTreeMap::new();
         params.insert("query_vec".to_string(), DataValue::List(
-            embedding.into_iter().map(|f| DataValue::from(f as f64)).collect()
+            embedding.iter().map(|f| DataValue::from(*f as f64)).collect()
         ));
+        let filter = match self.project {
+            Some(ref project) => {
+                params.insert("project".to_string(), DataValue::Str(project.as_str().into()));
+                "filter: project == '' || project == $project,"
+            }
+            None => "",
+        };
 
         let result = self.run_query(
-            r#"?[similarity] := 
-                ~memory_chunk:embedding_index{
-                    similarity |
+            &format!(
+                r#"?[id, dist] :=
+                ~memory_chunk:embedding_index{{
+                    id |
                     query: $query_vec,
                     k: 1,
-                    ef: 50
-                }"#,
+                    ef: 50,
+                    {}
+                    bind_distance: dist
+                }}"#,
+                filter
+            ),
             params,
         )?;
 
-        if let Some(row) = result.rows.first() {
-            if let Some(similarity) = row.first().and_then(|v| v.get_float()) {
-                return Ok((1.0 - similarity as f32) > threshold);
-            }
-        }
+        Ok(result.rows.first().and_then(|row| {
+            let distance = row.get(1)?.get_float()?;
+            Some((dv_to_string(&row[0]), distance))
+        }))
+    }
+
+    /// Chunks about `entity`: those linked to it in `chunk_entity`, plus
+    /// any whose text mentions its name. Most recently created or used
+    /// first, at most `limit`. Doesn't count as an access.
+    pub fn chunks_for_entity(&self, entity: &Entity, limit: usize) -> Result<Vec<MemoryChunk>> {
+        let mut params = BTreeMap::new();
+        params.insert("eid".to_string(), DataValue::Str(entity.id.clone().into()));
+        params.insert("name".to_string(), DataValue::Str(entity.name.to_lowercase().into()));
 
-        Ok(false)
+        let result = self.run_query(
+            r#"?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project] :=
+                *chunk_entity{chunk_id: id, entity_id: $eid},
+                *memory_chunk{id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project}
+            ?[id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project] :=
+                *memory_chunk{id, content, source_type, source_id, created_at, has_embedding, access_count, last_accessed, project},
+                str_includes(lowercase(content), $name)"#,
+            params,
+        )?;
+
+        let mut chunks: Vec<MemoryChunk> = Self::rows_to_chunks(&result)
+            .into_iter()
+            .filter(|chunk| self.in_scope(&chunk.project))
+            .collect();
+        let last_touched = |c: &MemoryChunk| c.created_at.max(c.last_accessed);
+        chunks.sort_by(|a, b| {
+            last_touched(b)
+                .partial_cmp(&last_touched(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.access_count.cmp(&a.access_count))
+        });
+        chunks.truncate(limit);
+        Ok(chunks)
     }
 
     /// Increment access count for a chunk.
@@ -917,8 +1850,14 @@ impl KnowledgeGraph {
     pub fn retrieve_context(&self, query: &str, max_chunks: usize, max_entities: usize) -> Result<String> {
         let mut context = String::new();
 
-        // 1. Semantic search for relevant chunks
-        let chunks = self.search_similar(query, max_chunks)?;
+        // 1. Hybrid (vector + keyword) search for relevant chunks, over-fetched
+        //    and re-ranked so recent and frequently used chunks surface
+        let candidates = self.hybrid_candidates(query, max_chunks * 3)?;
+        let mut chunks = rerank_chunks(candidates, &self.settings, now_f64());
+        chunks.truncate(max_chunks);
+        for chunk in &chunks {
+            let _ = self.increment_access_count(&chunk.id);
+        }
         if !chunks.is_empty() {
             context.push_str("## Retrieved Knowledge\n\n");
             
@@ -936,12 +1875,12 @@ impl KnowledgeGraph {
         
         // Get all entities and check if their names appear in the query
         let entities_result = self.run_query(
-            "?[id, entity_type, name] := *entity{id, entity_type, name}",
+            "?[id, entity_type, name, project] := *entity{id, entity_type, name, project}",
             BTreeMap::new(),
         )?;
         
         for row in &entities_result.rows {
-            if row.len() >= 3 {
+            if row.len() >= 4 && self.in_scope(&dv_to_string(&row[3])) {
                 let entity_name = dv_to_string(&row[2]);
                 if query_lower.contains(&entity_name.to_lowercase()) {
                     mentioned_entities.push((
@@ -965,12 +1904,14 @@ impl KnowledgeGraph {
                 context.push_str("**Entities:**\n");
             }
             
-            // Get related entities
-            let related = self.get_related(&entity_name, 1)?;
+            // Get related entities (2-hop results follow the direct ones)
+            let related = self.get_related(&entity_name, self.settings.context_depth.clamp(1, 2))?;
+            let (direct, second): (Vec<&RelatedEntity>, Vec<&RelatedEntity>) =
+                related.iter().partition(|r| r.via.is_none());
             
             context.push_str(&format!("- **{}** ({})", entity_name, entity_type));
-            if !related.is_empty() {
-                let connections: Vec<String> = related.iter().take(3).map(|r| {
+            if !direct.is_empty() {
+                let connections: Vec<String> = direct.iter().take(3).map(|r| {
                     format!("{} {} {}", 
                         if r.direction == "->" { "" } else { "←" },
                         r.rel_type,
@@ -980,14 +1921,39 @@ impl KnowledgeGraph {
                 context.push_str(&format!(": {}", connections.join(", ")));
             }
             context.push('\n');
+
+            if !second.is_empty() {
+                let mut connections: Vec<String> = Vec::new();
+                for r in second {
+                    let via = r.via.as_deref().unwrap_or_default();
+                    let line = if r.direction == "->" {
+                        format!("{} {} {}", via, r.rel_type, r.entity.name)
+                    } else {
+                        format!("{} {} {}", r.entity.name, r.rel_type, via)
+                    };
+                    if !connections.contains(&line) {
+                        connections.push(line);
+                    }
+                    if connections.len() >= MAX_SECOND_DEGREE_CONNECTIONS {
+                        break;
+                    }
+                }
+                context.push_str(&format!("  - second-degree: {}\n", connections.join(", ")));
+            }
         }
 
         Ok(context)
     }
 
-    /// Find chunks without embeddings and try to embed them.
+    /// Find chunks without embeddings and try to embed them, `batch_size`
+    /// chunks per embedding call and one `:update` per batch. `on_batch` is
+    /// called after each batch with (chunks done so far, total).
     /// Returns (processed_count, success_count).
-    pub fn backfill_embeddings(&self) -> Result<(usize, usize)> {
+    pub fn backfill_embeddings(
+        &self,
+        batch_size: usize,
+        mut on_batch: impl FnMut(usize, usize),
+    ) -> Result<(usize, usize)> {
         // Get all chunks without embeddings
         let result = self.run_query(
             r#"?[id, content] := 
@@ -996,36 +1962,53 @@ impl KnowledgeGraph {
             BTreeMap::new(),
         )?;
 
+        let pending: Vec<(String, String)> = result
+            .rows
+            .iter()
+            .filter(|row| row.len() >= 2)
+            .map(|row| (dv_to_string(&row[0]), dv_to_string(&row[1])))
+            .collect();
+        let total = pending.len();
+
         let mut processed = 0;
         let mut success = 0;
 
-        for row in &result.rows {
-            if row.len() >= 2 {
-                let chunk_id = dv_to_string(&row[0]);
-                let content = dv_to_string(&row[1]);
+        for batch in pending.chunks(batch_size.max(1)) {
+            let texts: Vec<&str> = batch.iter().map(|(_, content)| content.as_str()).collect();
+            let embeddings = Embedder::embed_batch(&texts);
+            processed += batch.len();
 
-                processed += 1;
+            let rows: Vec<DataValue> = batch
+                .iter()
+                .zip(embeddings)
+                .filter_map(|((chunk_id, _), embedding)| {
+                    let embedding = embedding?;
+                    Some(DataValue::List(vec![
+                        DataValue::Str(chunk_id.clone().into()),
+                        DataValue::List(embedding.into_iter().map(|f| DataValue::from(f as f64)).collect()),
+                        DataValue::Bool(true),
+                    ]))
+                })
+                .collect();
 
-                if let Some(embedding) = Embedder::embed(&content) {
-                    // Update the chunk with embedding
-                    let mut params = BTreeMap::new();
-                    params.insert("chunk_id".to_string(), DataValue::Str(chunk_id.into()));
-                    params.insert("embedding".to_string(), DataValue::List(
-                        embedding.into_iter().map(|f| DataValue::from(f as f64)).collect()
-                    ));
+            if !rows.is_empty() {
+                let count = rows.len();
+                let mut params = BTreeMap::new();
+                params.insert("rows".to_string(), DataValue::List(rows));
 
-                    if let Ok(_) = self.run_mutating(
-                        r#"?[id, embedding, has_embedding] := 
-                            id == $chunk_id,
-                            embedding = $embedding,
-                            has_embedding = true
+                if self
+                    .run_mutating(
+                        r#"?[id, embedding, has_embedding] <- $rows
                         :update memory_chunk { id => embedding, has_embedding }"#,
                         params,
-                    ) {
-                        success += 1;
-                    }
+                    )
+                    .is_ok()
+                {
+                    success += count;
                 }
             }
+
+            on_batch(processed, total);
         }
 
         Ok((processed, success))
@@ -1060,15 +2043,20 @@ impl KnowledgeGraph {
         Ok(Self::extract_int(&result, 0, 0).unwrap_or(0) as usize)
     }
 
+    /// Run a `sum(...)` aggregate; Cozo returns sums as floats.
+    fn sum_rows(&self, query: &str, params: BTreeMap<String, DataValue>) -> Result<usize> {
+        let result = self.run_query(query, params)?;
+        let sum = result.rows.first().and_then(|r| r.first()).and_then(|v| v.get_float());
+        Ok(sum.unwrap_or(0.0) as usize)
+    }
+
     fn extract_int(result: &NamedRows, row: usize, col: usize) -> Option<i64> {
         result.rows.get(row).and_then(|r| r.get(col)).and_then(|v| v.get_int())
     }
 
     /// Convert query rows to Entity structs.
-    /// Expected columns: id, entity_type, name, properties, name_lower, created_at, updated_at
-    /// Note: aliases are NOT included in query results; use get_aliases() to load them separately.
-    /// Convert query rows to Entity structs.
-    /// Expected columns: id, entity_type, name, properties, created_at, updated_at
+    /// Expected columns: id, entity_type, name, properties, created_at, updated_at,
+    /// optionally followed by source, confidence.
     /// Note: aliases are NOT included; use get_aliases() to load them separately.
     fn rows_to_entities(result: &NamedRows) -> Vec<Entity> {
         let mut entities = Vec::new();
@@ -1082,6 +2070,8 @@ impl KnowledgeGraph {
                     aliases: Vec::new(), // Load via get_aliases() if needed
                     created_at: dv_to_f64(&row[4]),
                     updated_at: dv_to_f64(&row[5]),
+                    source: row.get(6).map(dv_to_string).unwrap_or_default(),
+                    confidence: row.get(7).and_then(dv_to_opt_f64),
                 });
             }
         }
@@ -1101,6 +2091,8 @@ impl KnowledgeGraph {
                     has_embedding: dv_to_bool(&row[5]),
                     access_count: dv_to_i64(&row[6]),
                     last_accessed: dv_to_f64(&row[7]),
+                    // Only some queries select the project column
+                    project: row.get(8).map(dv_to_string).unwrap_or_default(),
                 });
             }
         }
@@ -1108,6 +2100,134 @@ impl KnowledgeGraph {
     }
 }
 
+/// Split text into chunks at paragraph boundaries, targeting ~max_chars per
+/// chunk. Paragraphs longer than that are split between sentences, so a
+/// chunk can run slightly over rather than cut a sentence in half (only a
+/// single sentence over twice the target is broken, at a space).
+///
+/// With `overlap > 0`, each chunk starts with the last sentences of the one
+/// before, up to `overlap` chars, so a fact spanning the boundary shows up
+/// whole in at least one chunk.
+pub fn chunk_text(text: &str, max_chars: usize, overlap: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+
+    // (sentence, starts a paragraph)
+    let mut units: Vec<(&str, bool)> = Vec::new();
+    for paragraph in text.split("\n\n") {
+        let trimmed = paragraph.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.len() <= max_chars {
+            units.push((trimmed, true));
+            continue;
+        }
+        let mut first = true;
+        for sentence in split_sentences(trimmed) {
+            for piece in split_at_spaces(sentence, max_chars * 2) {
+                units.push((piece, first));
+                first = false;
+            }
+        }
+    }
+
+    let render = |parts: &[(&str, bool)]| {
+        let mut out = String::new();
+        for (i, (part, para)) in parts.iter().enumerate() {
+            if i > 0 {
+                out.push_str(if *para { "\n\n" } else { " " });
+            }
+            out.push_str(part);
+        }
+        out
+    };
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<(&str, bool)> = Vec::new();
+    // How many of `current`'s units were carried over from the previous chunk
+    let mut carried = 0;
+    let mut len = 0;
+
+    for unit in units {
+        if len + unit.0.len() + 2 > max_chars && current.len() > carried {
+            chunks.push(render(&current));
+
+            // Seed the next chunk with trailing sentences of this one
+            let mut keep = 0;
+            let mut kept_len = 0;
+            for (part, _) in current.iter().rev() {
+                if kept_len + part.len() > overlap {
+                    break;
+                }
+                kept_len += part.len() + 1;
+                keep += 1;
+            }
+            // Never carry the whole chunk over
+            keep = keep.min(current.len() - 1);
+            current.drain(..current.len() - keep);
+            carried = current.len();
+            len = current.iter().map(|(p, _)| p.len() + 2).sum();
+        }
+        len += unit.0.len() + 2;
+        current.push(unit);
+    }
+
+    if current.len() > carried {
+        chunks.push(render(&current));
+    }
+
+    chunks
+}
+
+/// Split a paragraph after sentence-ending punctuation followed by whitespace.
+fn split_sentences(paragraph: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') {
+            if let Some(&(next, w)) = chars.peek() {
+                if w.is_whitespace() {
+                    let sentence = paragraph[start..next].trim();
+                    if !sentence.is_empty() {
+                        sentences.push(sentence);
+                    }
+                    start = next;
+                }
+            }
+        }
+    }
+    let rest = paragraph[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+/// Break `text` at spaces into pieces of at most `max` bytes (a single word
+/// longer than that is kept whole).
+fn split_at_spaces(text: &str, max: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max {
+        let mut cut = max;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        match rest[..cut].rfind(char::is_whitespace) {
+            Some(space) if space > 0 => {
+                pieces.push(rest[..space].trim_end());
+                rest = rest[space..].trim_start();
+            }
+            _ => break,
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
 // ────────────────────────────────────────────────────────────────────
 // Knowledge Queue
 // ────────────────────────────────────────────────────────────────────
@@ -1174,6 +2294,39 @@ pub fn read_queue() -> Result<Vec<QueueEntry>> {
     Ok(entries)
 }
 
+/// Drop queue entries whose content repeats an earlier entry, ignoring case
+/// and whitespace differences. Each survivor keeps the earliest timestamp of
+/// its duplicates and the union of their suggested entities.
+pub fn dedup_queue(entries: Vec<QueueEntry>) -> Vec<QueueEntry> {
+    let normalize = |content: &str| {
+        content.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    };
+
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    let mut deduped: Vec<QueueEntry> = Vec::new();
+
+    for entry in entries {
+        let key = normalize(&entry.content);
+        match seen.get(&key) {
+            Some(&i) => {
+                let kept = &mut deduped[i];
+                kept.timestamp = kept.timestamp.min(entry.timestamp);
+                for name in entry.suggested_entities {
+                    if !kept.suggested_entities.contains(&name) {
+                        kept.suggested_entities.push(name);
+                    }
+                }
+            }
+            None => {
+                seen.insert(key, deduped.len());
+                deduped.push(entry);
+            }
+        }
+    }
+
+    deduped
+}
+
 /// Clear all entries from the queue.
 pub fn clear_queue() -> Result<()> {
     let path = queue_path()?;
@@ -1199,6 +2352,18 @@ fn dv_to_f64(dv: &DataValue) -> f64 {
     dv.get_float().unwrap_or(0.0)
 }
 
+/// A nullable float column; `None` for null.
+fn dv_to_opt_f64(dv: &DataValue) -> Option<f64> {
+    match dv {
+        DataValue::Null => None,
+        other => other.get_float(),
+    }
+}
+
+fn opt_f64_to_dv(value: Option<f64>) -> DataValue {
+    value.map(DataValue::from).unwrap_or(DataValue::Null)
+}
+
 fn dv_to_i64(dv: &DataValue) -> i64 {
     dv.get_int().unwrap_or(0)
 }
@@ -1276,6 +2441,157 @@ fn json_to_datavalue(jv: &JsonValue) -> DataValue {
     }
 }
 
+/// Entity properties as a JSON object. Objects are stored as JSON strings
+/// (see `json_to_datavalue`), so those are parsed back; anything else is empty.
+fn properties_to_map(properties: &JsonValue) -> serde_json::Map<String, JsonValue> {
+    match properties {
+        JsonValue::Object(map) => map.clone(),
+        JsonValue::String(s) => match serde_json::from_str(s) {
+            Ok(JsonValue::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        },
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// Score how well a (lowercased) name matches a (lowercased) query, 0.0–1.0.
+/// Exact > prefix > substring > close edit distance; 0.0 means no match.
+fn name_match_score(query: &str, name: &str) -> f64 {
+    if name == query {
+        return 1.0;
+    }
+    if name.starts_with(query) {
+        return 0.9;
+    }
+    if name.contains(query) {
+        return 0.8;
+    }
+    if query.contains(name) && name.chars().count() >= 3 {
+        return 0.6;
+    }
+
+    let max_len = query.chars().count().max(name.chars().count());
+    if max_len == 0 {
+        return 0.0;
+    }
+    let similarity = 1.0 - levenshtein(query, name) as f64 / max_len as f64;
+    if similarity >= 0.6 {
+        similarity * 0.7
+    } else {
+        0.0
+    }
+}
+
+/// Levenshtein edit distance between two strings (by chars).
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut curr = vec![0; b_chars.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b_chars.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j + 1] + 1).min(curr[j] + 1).min(prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b_chars.len()]
+}
+
+/// Escape text for use in XML content and attribute values.
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Most 2-hop connections listed per entity in `retrieve_context`, to keep
+/// the injected context small.
+const MAX_SECOND_DEGREE_CONNECTIONS: usize = 5;
+
+/// Constant that damps the contribution of top ranks in reciprocal-rank fusion.
+/// 60 is the value from the original RRF paper and works well in practice.
+const RRF_K: f64 = 60.0;
+
+/// Fuse two best-first rankings with weighted reciprocal-rank fusion:
+/// `score = alpha / (RRF_K + rank_a) + (1 - alpha) / (RRF_K + rank_b)`.
+/// Chunks missing from one list only get that list's share. Result is
+/// best-first; ties keep the order of first appearance.
+fn reciprocal_rank_fusion(
+    vector: Vec<MemoryChunk>,
+    text: Vec<MemoryChunk>,
+    alpha: f64,
+) -> Vec<MemoryChunk> {
+    let mut fused: Vec<(MemoryChunk, f64)> = Vec::new();
+    let lists = [(vector, alpha), (text, 1.0 - alpha)];
+
+    for (list, weight) in lists {
+        for (rank, chunk) in list.into_iter().enumerate() {
+            let score = weight / (RRF_K + rank as f64 + 1.0);
+            match fused.iter_mut().find(|(c, _)| c.id == chunk.id) {
+                Some((_, total)) => *total += score,
+                None => fused.push((chunk, score)),
+            }
+        }
+    }
+
+    fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    fused.into_iter().map(|(chunk, _)| chunk).collect()
+}
+
+/// Re-rank best-first search results by combining relevance (from list
+/// position) with recency and access frequency, weighted by
+/// `[knowledge] recency_weight` / `access_weight`:
+///
+/// - relevance: 1.0 for the first result, falling linearly to 0.0
+/// - recency: `0.5^(age / recency_half_life_days)` from `created_at`
+/// - access: `ln(1 + access_count)`, normalized to the most-accessed candidate
+fn rerank_chunks(chunks: Vec<MemoryChunk>, settings: &KnowledgeConfig, now: f64) -> Vec<MemoryChunk> {
+    let n = chunks.len();
+    if n < 2 {
+        return chunks;
+    }
+
+    let half_life_secs = settings.recency_half_life_days.max(0.01) * 86_400.0;
+    let max_access = chunks
+        .iter()
+        .map(|c| (1.0 + c.access_count.max(0) as f64).ln())
+        .fold(0.0, f64::max);
+
+    let mut scored: Vec<(MemoryChunk, f64)> = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(rank, chunk)| {
+            let relevance = 1.0 - rank as f64 / (n - 1) as f64;
+            let age = (now - chunk.created_at).max(0.0);
+            let recency = 0.5f64.powf(age / half_life_secs);
+            let access = if max_access > 0.0 {
+                (1.0 + chunk.access_count.max(0) as f64).ln() / max_access
+            } else {
+                0.0
+            };
+            let score = relevance
+                + settings.recency_weight * recency
+                + settings.access_weight * access;
+            (chunk, score)
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().map(|(chunk, _)| chunk).collect()
+}
+
 /// Current time as f64 (Unix timestamp).
 fn now_f64() -> f64 {
     SystemTime::now()
@@ -1284,6 +2600,28 @@ fn now_f64() -> f64 {
         .as_secs_f64()
 }
 
+/// The `source` recorded on knowledge from a session, e.g.
+/// "session 4f1c…e2 on 2026-03-02". The full id works with `imp chat --session`.
+pub fn provenance(session_id: &str, timestamp: f64) -> String {
+    let date = chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    match (session_id.is_empty(), date.is_empty()) {
+        (true, _) => date,
+        (false, true) => format!("session {}", session_id),
+        (false, false) => format!("session {} on {}", session_id, date),
+    }
+}
+
+/// Whether a Cozo open error is RocksDB refusing the database because
+/// another handle holds its `LOCK` file.
+fn is_lock_error(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    lower.contains("lock") && (lower.contains("resource temporarily unavailable")
+        || lower.contains("lock hold by current process")
+        || lower.contains("while lock file"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1309,4 +2647,197 @@ mod tests {
         // Cleanup
         let _ = fs::remove_dir_all("/tmp/imp-test-knowledge");
     }
+
+    #[test]
+    fn test_dedup_queue_merges_near_identical_entries() {
+        let entry = |content: &str, timestamp: f64, entities: &[&str]| QueueEntry {
+            content: content.to_string(),
+            timestamp,
+            session_id: "session-1".to_string(),
+            suggested_entities: entities.iter().map(|e| e.to_string()).collect(),
+        };
+        let entries = vec![
+            entry("test content", 2.0, &["entity1"]),
+            entry("more content", 3.0, &[]),
+            entry("  Test   CONTENT ", 1.0, &["entity2", "entity1"]),
+        ];
+
+        let deduped = dedup_queue(entries);
+        assert_eq!(deduped.len(), 2);
+        assert_eq!(deduped[0].content, "test content");
+        assert_eq!(deduped[0].timestamp, 1.0);
+        assert_eq!(deduped[0].suggested_entities, vec!["entity1", "entity2"]);
+        assert_eq!(deduped[1].content, "more content");
+    }
+
+    #[test]
+    fn test_properties_to_map() {
+        let obj = serde_json::json!({"a": 1});
+        assert_eq!(properties_to_map(&obj).len(), 1);
+
+        let encoded = JsonValue::String(r#"{"a": 1, "b": "x"}"#.to_string());
+        assert_eq!(properties_to_map(&encoded).len(), 2);
+
+        assert!(properties_to_map(&JsonValue::Null).is_empty());
+        assert!(properties_to_map(&JsonValue::String("plain".to_string())).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_text_overlap_and_size() {
+        let sentences: Vec<String> = (0..40)
+            .map(|i| format!("Fact number {} is about the exchange gateway.", i))
+            .collect();
+        let text = format!("{}\n\nShort closing paragraph.", sentences.join(" "));
+
+        let chunks = chunk_text(&text, 200, 60);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 2 * 200, "chunk too large: {} chars", chunk.len());
+        }
+        // Each chunk starts with the last sentence of the one before
+        for pair in chunks.windows(2) {
+            let last_sentence = split_sentences(&pair[0]).pop().unwrap();
+            assert!(pair[1].starts_with(last_sentence), "{:?} / {:?}", pair[0], pair[1]);
+        }
+        // Nothing is lost
+        for sentence in &sentences {
+            assert!(chunks.iter().any(|c| c.contains(sentence.as_str())));
+        }
+
+        // No overlap: sentences appear exactly once
+        let chunks = chunk_text(&text, 200, 0);
+        let total: usize = chunks.iter().map(|c| c.matches("Fact number").count()).sum();
+        assert_eq!(total, 40);
+
+        // A run-on sentence is broken at spaces rather than kept whole
+        let run_on = "word ".repeat(500);
+        assert!(chunk_text(&run_on, 100, 0).iter().all(|c| c.len() <= 200));
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("nasdaq", "nasdaq"), 0);
+        assert_eq!(levenshtein("nasdq", "nasdaq"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_name_match_score_ordering() {
+        let exact = name_match_score("nasdaq", "nasdaq");
+        let prefix = name_match_score("nasdaq", "nasdaq exchange");
+        let typo = name_match_score("nasdq", "nasdaq");
+        let unrelated = name_match_score("nasdaq", "kubernetes");
+
+        assert!(exact > prefix);
+        assert!(prefix > typo);
+        assert!(typo > 0.0);
+        assert_eq!(unrelated, 0.0);
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let chunk = |id: &str| MemoryChunk {
+            id: id.to_string(),
+            content: String::new(),
+            source_type: String::new(),
+            source_id: String::new(),
+            created_at: 0.0,
+            has_embedding: false,
+            access_count: 0,
+            last_accessed: 0.0,
+            project: String::new(),
+        };
+        let ids = |chunks: Vec<MemoryChunk>| chunks.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+        // "b" is in both lists, so it beats the single-list leaders
+        let fused = reciprocal_rank_fusion(vec![chunk("a"), chunk("b")], vec![chunk("c"), chunk("b")], 0.5);
+        assert_eq!(ids(fused), vec!["b", "a", "c"]);
+
+        // Alpha at the extremes follows one ranking
+        let fused = reciprocal_rank_fusion(vec![chunk("a"), chunk("b")], vec![chunk("b"), chunk("a")], 1.0);
+        assert_eq!(ids(fused), vec!["a", "b"]);
+        let fused = reciprocal_rank_fusion(vec![], vec![chunk("c")], 0.5);
+        assert_eq!(ids(fused), vec!["c"]);
+    }
+
+    #[test]
+    fn test_rerank_chunks_by_recency_and_access() {
+        const DAY: f64 = 86_400.0;
+        let now = 1_000.0 * DAY;
+        let chunk = |id: &str, age_days: f64, access_count: i64| MemoryChunk {
+            id: id.to_string(),
+            content: String::new(),
+            source_type: String::new(),
+            source_id: String::new(),
+            created_at: now - age_days * DAY,
+            has_embedding: false,
+            access_count,
+            last_accessed: 0.0,
+            project: String::new(),
+        };
+        let ids = |chunks: Vec<MemoryChunk>| chunks.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+        let settings = KnowledgeConfig {
+            recency_weight: 1.0,
+            access_weight: 1.0,
+            ..KnowledgeConfig::default()
+        };
+
+        // An old one-off note at the top gives way to recent, well-used facts
+        let ranked = rerank_chunks(
+            vec![chunk("old-once", 365.0, 0), chunk("recent-used", 1.0, 20), chunk("recent-new", 1.0, 0)],
+            &settings,
+            now,
+        );
+        assert_eq!(ids(ranked), vec!["recent-used", "old-once", "recent-new"]);
+
+        // With zero weights, the original similarity order is kept
+        let plain = KnowledgeConfig {
+            recency_weight: 0.0,
+            access_weight: 0.0,
+            ..KnowledgeConfig::default()
+        };
+        let ranked = rerank_chunks(
+            vec![chunk("a", 365.0, 0), chunk("b", 1.0, 20), chunk("c", 0.0, 50)],
+            &plain,
+            now,
+        );
+        assert_eq!(ids(ranked), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape(r#"a<b & "c"'"#), "a&lt;b &amp; &quot;c&quot;&apos;");
+        assert_eq!(xml_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_is_lock_error() {
+        assert!(is_lock_error(
+            "IO error: While lock file: /home/u/.imp/knowledge.cozo/data/LOCK: Resource temporarily unavailable"
+        ));
+        assert!(is_lock_error(
+            "IO error: lock hold by current process, acquire time 1700000000 acquiring thread 1: /tmp/x/LOCK: No locks available"
+        ));
+        assert!(!is_lock_error("Corruption: bad block in table"));
+    }
+
+    #[test]
+    fn test_open_reports_lock_contention() {
+        let path = std::env::temp_dir().join(format!("imp-test-lock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+
+        let first = KnowledgeGraph::open_at(&path, Duration::ZERO).unwrap();
+        let err = KnowledgeGraph::open_at(&path, Duration::from_millis(200))
+            .err()
+            .expect("second open should fail while the first holds the lock");
+        assert!(err.to_string().contains("in use by another imp process"), "{}", err);
+
+        // Once the holder is gone the database opens again
+        drop(first);
+        assert!(KnowledgeGraph::open_at(&path, Duration::ZERO).is_ok());
+
+        let _ = fs::remove_dir_all(&path);
+    }
 }
@@ -0,0 +1,282 @@
+//! Background writes to the knowledge graph.
+//!
+//! `store_knowledge` calls are handed to one worker thread over a bounded
+//! channel, so the chat turn doesn't wait on RocksDB and a session's writes
+//! are applied in the order they were made. The graph is opened per write,
+//! as elsewhere, so other sessions can still get at it in between. If it
+//! can't be opened (another process holds the lock), the write is retried
+//! with backoff rather than dropped. Dropping the writer waits for anything
+//! still queued.
+
+use crate::config::KnowledgeConfig;
+use crate::error::Result;
+use crate::knowledge::{Entity, KnowledgeGraph, Relationship};
+use serde_json::Value as JsonValue;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Writes that can be waiting before `send` blocks the caller.
+const QUEUE_CAPACITY: usize = 64;
+
+/// First and longest wait between attempts to open the graph for a write.
+const RETRY_INITIAL: Duration = Duration::from_millis(100);
+const RETRY_MAX: Duration = Duration::from_secs(5);
+
+/// How long a flush keeps retrying before the remaining writes are reported
+/// as lost.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// One `store_knowledge` call waiting to be written.
+pub struct StoreRequest {
+    /// The tool call's arguments (`entities`, `relationships`, `chunks`).
+    pub arguments: JsonValue,
+    /// Project to tag the knowledge with, or `None` for global.
+    pub project: Option<String>,
+    /// Provenance recorded on new entities and relationships.
+    pub source: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct StoreCounts {
+    pub entities: usize,
+    pub relationships: usize,
+    pub chunks: usize,
+}
+
+pub struct KnowledgeWriter {
+    tx: Option<SyncSender<StoreRequest>>,
+    worker: Option<JoinHandle<()>>,
+    /// Set by `flush`, so the worker stops retrying after `SHUTDOWN_GRACE`.
+    closing: Arc<AtomicBool>,
+}
+
+impl KnowledgeWriter {
+    /// Start the worker thread. `settings` tune the writes (e.g. chunk dedup).
+    pub fn spawn(settings: KnowledgeConfig) -> Self {
+        Self::spawn_with(settings, KnowledgeGraph::open)
+    }
+
+    /// `spawn`, opening the graph with `open` for each write.
+    fn spawn_with(
+        settings: KnowledgeConfig,
+        mut open: impl FnMut() -> Result<KnowledgeGraph> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::sync_channel::<StoreRequest>(QUEUE_CAPACITY);
+        let closing = Arc::new(AtomicBool::new(false));
+        let worker_closing = closing.clone();
+        let worker = std::thread::spawn(move || {
+            let mut lost = 0;
+            for request in rx {
+                if lost > 0 {
+                    lost += 1;
+                    continue;
+                }
+                let Some(kg) = open_with_retry(&mut open, &worker_closing) else {
+                    lost += 1;
+                    continue;
+                };
+                let kg = kg.with_settings(&settings).with_project(request.project.as_deref());
+                let counts = store(&kg, &request.arguments, &request.source);
+                tracing::debug!(?counts, "Stored knowledge");
+            }
+            if lost > 0 {
+                tracing::warn!(lost, "store_knowledge writes were not applied: the knowledge graph stayed unavailable");
+            }
+        });
+        Self { tx: Some(tx), worker: Some(worker), closing }
+    }
+
+    /// Queue a write. Blocks only if the queue is full. Returns `false` if
+    /// the worker is gone and the write was not queued.
+    pub fn send(&self, request: StoreRequest) -> bool {
+        self.tx.as_ref().map_or(false, |tx| tx.send(request).is_ok())
+    }
+
+    /// Wait for every queued write to be applied. Nothing can be sent after.
+    pub fn flush(&mut self) {
+        self.closing.store(true, Ordering::Relaxed);
+        self.tx.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for KnowledgeWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Open the graph with `open`, retrying with backoff while it fails. Gives
+/// up (`None`) only once `closing` has been set for `SHUTDOWN_GRACE`.
+fn open_with_retry(
+    open: &mut impl FnMut() -> Result<KnowledgeGraph>,
+    closing: &AtomicBool,
+) -> Option<KnowledgeGraph> {
+    let mut delay = RETRY_INITIAL;
+    let mut give_up_at = None;
+    loop {
+        match open() {
+            Ok(kg) => return Some(kg),
+            Err(e) => {
+                if closing.load(Ordering::Relaxed) {
+                    let deadline = *give_up_at.get_or_insert_with(|| Instant::now() + SHUTDOWN_GRACE);
+                    if Instant::now() >= deadline {
+                        tracing::warn!(error = %e, "Could not open the knowledge graph for a queued write");
+                        return None;
+                    }
+                }
+                tracing::debug!(error = %e, ?delay, "Knowledge graph unavailable; retrying the write");
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(RETRY_MAX);
+            }
+        }
+    }
+}
+
+/// Store the entities, relationships and chunks in `store_knowledge`
+/// arguments. Entities that already exist are skipped; relationships need
+/// both ends to exist.
+pub fn store(kg: &KnowledgeGraph, arguments: &JsonValue, source: &str) -> StoreCounts {
+    let mut counts = StoreCounts::default();
+
+    // Process entities
+    if let Some(entities) = arguments.get("entities").and_then(|v| v.as_array()) {
+        for entity_val in entities {
+            let name = match entity_val.get("name").and_then(|v| v.as_str()) {
+                Some(n) => n,
+                None => continue,
+            };
+            let entity_type = entity_val
+                .get("type")
+                .and_then(|v| v.as_str())
+                .unwrap_or("concept");
+            let properties = entity_val
+                .get("properties")
+                .cloned()
+                .unwrap_or(JsonValue::Object(serde_json::Map::new()));
+
+            // Skip if entity already exists
+            if let Ok(Some(_)) = kg.find_entity_by_name(name) {
+                continue;
+            }
+
+            let entity = Entity {
+                id: String::new(),
+                entity_type: entity_type.to_string(),
+                name: name.to_string(),
+                properties,
+                aliases: Vec::new(),
+                created_at: 0.0,
+                updated_at: 0.0,
+                source: source.to_string(),
+                confidence: None,
+            };
+
+            if kg.store_entity(entity).is_ok() {
+                counts.entities += 1;
+            }
+        }
+    }
+
+    // Process relationships
+    if let Some(rels) = arguments.get("relationships").and_then(|v| v.as_array()) {
+        for rel_val in rels {
+            let from_name = match rel_val.get("from").and_then(|v| v.as_str()) {
+                Some(n) => n,
+                None => continue,
+            };
+            let to_name = match rel_val.get("to").and_then(|v| v.as_str()) {
+                Some(n) => n,
+                None => continue,
+            };
+            let rel_type = match rel_val.get("rel").and_then(|v| v.as_str()) {
+                Some(r) => r,
+                None => continue,
+            };
+
+            let from = match kg.find_entity_by_name(from_name) {
+                Ok(Some(e)) => e,
+                _ => continue,
+            };
+            let to = match kg.find_entity_by_name(to_name) {
+                Ok(Some(e)) => e,
+                _ => continue,
+            };
+
+            let relationship = Relationship {
+                id: String::new(),
+                from_id: from.id,
+                rel_type: rel_type.to_string(),
+                to_id: to.id,
+                properties: JsonValue::Object(serde_json::Map::new()),
+                created_at: 0.0,
+                source: source.to_string(),
+                confidence: None,
+            };
+
+            if kg.store_relationship(relationship).is_ok() {
+                counts.relationships += 1;
+            }
+        }
+    }
+
+    // Process chunks
+    if let Some(chunks) = arguments.get("chunks").and_then(|v| v.as_array()) {
+        for chunk_val in chunks {
+            let content = match chunk_val.get("content").and_then(|v| v.as_str()) {
+                Some(c) => c,
+                None => continue,
+            };
+            let source = chunk_val
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("conversation");
+
+            if let Ok(stored) = kg.store_chunk(content, source, "live") {
+                if !stored.duplicate {
+                    counts.chunks += 1;
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_store_applied_after_lock_is_released() {
+        let path = std::env::temp_dir().join(format!("imp-test-writer-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let holder = KnowledgeGraph::open_at(&path, Duration::ZERO).unwrap();
+
+        let open_path = path.clone();
+        let mut writer = KnowledgeWriter::spawn_with(KnowledgeConfig::default(), move || {
+            KnowledgeGraph::open_at(&open_path, Duration::ZERO)
+        });
+        assert!(writer.send(StoreRequest {
+            arguments: json!({ "entities": [{ "name": "Imp", "type": "project" }] }),
+            project: None,
+            source: "test".to_string(),
+        }));
+
+        // The write waits out the lock instead of being dropped
+        std::thread::sleep(Duration::from_millis(300));
+        drop(holder);
+        writer.flush();
+
+        let kg = KnowledgeGraph::open_at(&path, Duration::ZERO).unwrap();
+        assert!(kg.find_entity_by_name("Imp").unwrap().is_some());
+        drop(kg);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -1,44 +1,126 @@
 //! Logging infrastructure for Imp.
 //!
-//! Logs go to `~/.imp/logs/imp.log` (rotated daily). Nothing is printed to
-//! stderr/stdout — the terminal stays clean for the user.
+//! Logs go to `~/.imp/logs/imp.log` (rotated daily) at `[logging] level`,
+//! unless `[logging] file = false`. The terminal only gets warnings and
+//! errors, so it stays clean for the user. In chat they go through the
+//! readline printer (see `set_printer`) so they don't garble the prompt.
 //!
-//! Log level is controlled by `IMP_LOG` env var (default: `info`).
-//! Examples: `IMP_LOG=debug`, `IMP_LOG=warn`, `IMP_LOG=imp::tools::mcp=debug`.
+//! `--verbose` raises both to `debug`. `IMP_LOG` or `RUST_LOG` replace the
+//! file filter outright, e.g. `IMP_LOG=debug`, `RUST_LOG=imp::tools::mcp=trace`;
+//! they only reach the terminal together with `--verbose`.
 
-use crate::config::imp_home;
+use crate::agent::SharedPrinter;
+use crate::config::{imp_home, LoggingConfig};
+use crate::redact::Redacting;
+use std::io::{self, Write};
+use std::sync::Mutex;
 use tracing_appender::rolling;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::prelude::*;
 
 /// Initialise the global logger. Safe to call multiple times (subsequent
-/// calls are no-ops because `tracing_subscriber::registry().init()` only
-/// takes effect once).
-pub fn init() {
-    let home = match imp_home() {
-        Ok(h) => h,
-        Err(_) => return, // Can't log if we don't know where home is
+/// calls are no-ops because `tracing_subscriber::registry().try_init()`
+/// only takes effect once).
+pub fn init(verbose: bool) {
+    let settings = LoggingConfig::read_global();
+
+    // Secrets are scrubbed from every log line, in the file and on the terminal
+    let file_layer = settings.file.then(log_dir).flatten().map(|dir| {
+        fmt::layer()
+            .with_writer(Redacting(rolling::daily(dir, "imp.log")))
+            .with_target(true)
+            .with_ansi(false)
+            .with_filter(filter(verbose, &settings.level))
+    });
+
+    let console_filter = if verbose {
+        filter(verbose, "warn")
+    } else {
+        EnvFilter::new("warn")
     };
+    let console_layer = fmt::layer()
+        .with_writer(Redacting(Console))
+        .without_time()
+        .with_target(false)
+        .with_ansi(false)
+        .with_filter(console_filter);
+
+    // This silently no-ops if a subscriber is already set (e.g. in tests)
+    let _ = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(console_layer)
+        .try_init();
+}
 
-    let log_dir = home.join("logs");
-    if let Err(_) = std::fs::create_dir_all(&log_dir) {
-        return;
+/// Printer for terminal log lines while a readline prompt is active.
+static PRINTER: Mutex<Option<SharedPrinter>> = Mutex::new(None);
+
+/// Send terminal log lines through `printer` from now on instead of writing
+/// them to stderr under the prompt.
+pub fn set_printer(printer: SharedPrinter) {
+    if let Ok(mut slot) = PRINTER.lock() {
+        *slot = Some(printer);
     }
+}
 
-    let file_appender = rolling::daily(&log_dir, "imp.log");
+/// The terminal: the readline printer once one is set, stderr before that.
+struct Console;
 
-    let filter = EnvFilter::try_from_env("IMP_LOG")
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+impl<'a> MakeWriter<'a> for Console {
+    type Writer = ConsoleLine;
 
-    let fmt_layer = fmt::layer()
-        .with_writer(file_appender)
-        .with_target(true)
-        .with_ansi(false);
+    fn make_writer(&'a self) -> Self::Writer {
+        ConsoleLine(Vec::new())
+    }
+}
 
-    // This silently no-ops if a subscriber is already set (e.g. in tests)
-    let _ = tracing_subscriber::registry()
-        .with(filter)
-        .with(fmt_layer)
-        .try_init();
+/// One formatted event, printed when the formatter is done with it.
+struct ConsoleLine(Vec<u8>);
+
+impl Write for ConsoleLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleLine {
+    fn drop(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.0).into_owned();
+        let printer = PRINTER.lock().ok().and_then(|slot| slot.clone());
+        if let Some(printer) = printer {
+            if let Ok(mut guard) = printer.lock() {
+                let _ = guard.print(text);
+                return;
+            }
+        }
+        let _ = io::stderr().write_all(text.as_bytes());
+    }
+}
+
+/// `~/.imp/logs`, created if needed. None if it can't be.
+fn log_dir() -> Option<std::path::PathBuf> {
+    let dir = imp_home().ok()?.join("logs");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// `IMP_LOG`, then `RUST_LOG`, then `debug` for `--verbose`, then `default`.
+fn filter(verbose: bool, default: &str) -> EnvFilter {
+    EnvFilter::try_from_env("IMP_LOG")
+        .or_else(|_| EnvFilter::try_from_default_env())
+        .unwrap_or_else(|_| {
+            let level = if verbose { "debug" } else { default };
+            // An invalid [logging] level is reported when the config is checked
+            EnvFilter::try_new(level).unwrap_or_else(|_| EnvFilter::new("info"))
+        })
 }
@@ -4,7 +4,8 @@
 //! assistance for coding, documentation, and task automation.
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
 
 mod agent;
@@ -16,17 +17,25 @@ mod context;
 mod db;
 mod embeddings;
 mod error;
+mod events;
 mod extraction;
 mod highlight;
+mod http;
+mod interrupt;
 mod knowledge;
+mod knowledge_writer;
 mod logging;
+mod oauth;
+mod output;
 mod project;
+mod redact;
 mod subagent;
 mod tmux;
+mod tokens;
 mod tools;
 mod usage;
 
-use cli::{bootstrap, chat, knowledge_cmd, learn, login, oneshot, project_cmd, reflect, tui};
+use cli::{bootstrap, chat, doctor, knowledge_cmd, learn, login, mcp_cmd, oneshot, project_cmd, reflect, tui, usage_cmd};
 
 #[derive(Parser)]
 #[command(name = "imp")]
@@ -37,19 +46,56 @@ use cli::{bootstrap, chat, knowledge_cmd, learn, login, oneshot, project_cmd, re
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Turn off colour and emoji (also when NO_COLOR is set or output is piped)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Log at debug level, to the terminal as well as ~/.imp/logs/imp.log
+    #[arg(long, global = true)]
+    verbose: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Bootstrap wizard — set up your agent for the first time
-    Bootstrap,
+    /// Bootstrap wizard — set up your agent, or edit an existing setup
+    Bootstrap {
+        /// Start over: overwrite config.toml, SOUL.md, USER.md and MEMORY.md (asks first)
+        #[arg(long)]
+        reset: bool,
+    },
     /// Login with OAuth (for Claude Pro/Max subscriptions) or re-authenticate
     Login,
-    /// Ask your agent a question or give it a task
+    /// Ask your agent a question or give it a task (piped stdin is attached)
     Ask {
-        /// The question or task
-        #[arg(required = true)]
+        /// The question or task; may be omitted when input is piped in. Start
+        /// it with `-` to attach piped input to it
         message: Vec<String>,
+
+        /// Use extended thinking for this question, whatever the config says
+        #[arg(long, conflicts_with = "no_think")]
+        think: bool,
+
+        /// Skip extended thinking for a faster answer
+        #[arg(long)]
+        no_think: bool,
+
+        /// Print one JSON object (text, tool calls, usage, session ID) instead of formatted output
+        #[arg(long)]
+        json: bool,
+
+        /// Attach a file (or glob) to the prompt; repeat for more files
+        #[arg(long = "file", value_name = "PATH")]
+        files: Vec<String>,
+
+        /// Plan without changing anything: file writes, edits and mutating commands are simulated
+        #[arg(long)]
+        read_only: bool,
+
+        /// Temperature 0, a fixed seed where supported, no thinking and no
+        /// retrieved knowledge, for repeatable replies
+        #[arg(long, conflicts_with = "think")]
+        deterministic: bool,
     },
     /// Start an interactive chat session
     Chat {
@@ -64,14 +110,74 @@ enum Commands {
         /// Resume a specific session by ID (full or prefix)
         #[arg(long)]
         session: Option<String>,
+
+        /// Search past sessions by content and pick one to resume
+        #[arg(long, conflicts_with_all = ["resume", "continue", "session"])]
+        search: Option<String>,
+
+        /// Print a session's transcript (ID or prefix) instead of chatting
+        #[arg(long, value_name = "SESSION", conflicts_with_all = ["resume", "continue", "session", "search"])]
+        export: Option<String>,
+
+        /// Transcript format for --export: md or json
+        #[arg(long, default_value = "md", requires = "export")]
+        format: String,
+
+        /// Leave thinking blocks out of the exported transcript
+        #[arg(long, requires = "export")]
+        no_thinking: bool,
+
+        /// Use extended thinking this session, whatever the config says
+        #[arg(long, conflicts_with = "no_think")]
+        think: bool,
+
+        /// Turn extended thinking off this session
+        #[arg(long)]
+        no_think: bool,
+
+        /// Run the prompts in FILE (`-` for stdin) as chat turns and exit:
+        /// one per line, or separated by `---` lines. Slash commands work
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["resume", "search", "export"])]
+        exec_file: Option<PathBuf>,
+
+        /// Temperature 0, a fixed seed where supported, no thinking and no
+        /// retrieved knowledge, for repeatable replies
+        #[arg(long, conflicts_with = "think")]
+        deterministic: bool,
     },
     /// Teach your agent something new
-    Learn,
+    Learn {
+        /// Extract knowledge from this file into the knowledge graph without
+        /// prompting (`-` reads stdin; piped stdin works without the flag)
+        #[arg(long, value_name = "PATH")]
+        file: Option<String>,
+    },
     /// Distill daily interaction logs into long-term memory
     Reflect {
         /// Date to reflect on (YYYY-MM-DD, default: today)
-        #[arg(short, long)]
+        #[arg(short, long, conflicts_with_all = ["from", "to", "days"])]
         date: Option<String>,
+        /// First day of a range to reflect on (YYYY-MM-DD)
+        #[arg(long, conflicts_with = "days")]
+        from: Option<String>,
+        /// Last day of the range (YYYY-MM-DD, default: today)
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+        /// Reflect on the last N days, ending today
+        #[arg(long)]
+        days: Option<u32>,
+        /// Show proposed file changes as diffs without writing anything
+        #[arg(long, conflicts_with = "interactive")]
+        dry_run: bool,
+        /// Ask before applying each proposed file change
+        #[arg(long, short)]
+        interactive: bool,
+        /// Re-summarize every conversation, not just those since the last reflect
+        #[arg(long)]
+        force: bool,
+        /// Restore a core file (MEMORY, USER, SOUL, ...) from one of its backups
+        #[arg(long, value_name = "NAME", exclusive = true)]
+        restore: Option<String>,
     },
     /// List available syntax highlighting themes
     Themes,
@@ -85,28 +191,140 @@ enum Commands {
         #[command(subcommand)]
         command: KnowledgeCommands,
     },
+    /// Inspect MCP servers
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommands,
+    },
+    /// Show token usage and cost across sessions
+    Usage {
+        /// Only count usage on or after this date (YYYY-MM-DD)
+        #[arg(long, conflicts_with = "today")]
+        since: Option<String>,
+        /// Only count today's usage
+        #[arg(long)]
+        today: bool,
+        /// Only count sessions in this project
+        #[arg(long)]
+        project: Option<String>,
+    },
     /// TUI for managing multiple agent sessions
     Tui,
+    /// Check auth, the model, the knowledge graph, embeddings, MCP servers and ripgrep
+    Doctor,
 }
 
 #[derive(Subcommand)]
 enum KnowledgeCommands {
     /// Show entity/relationship/chunk counts
-    Stats,
-    /// Show current schema types and relationship types
-    Schema,
-    /// Look up an entity by name and show relationships
+    Stats {
+        /// Only count knowledge tagged with this project
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Show current schema types and relationship types, or edit them
+    Schema {
+        #[command(subcommand)]
+        command: Option<SchemaCommands>,
+    },
+    /// Look up an entity by name and show relationships and notes about it
     Query {
         /// Entity name to look up
         name: String,
+        /// Most notes (memory chunks) about the entity to show
+        #[arg(long, default_value_t = 5)]
+        chunks: usize,
     },
-    /// Search for chunks using semantic or text search
+    /// Fuzzy-search entities, then search chunks using semantic or text search
     Search {
         /// Search query
         query: String,
     },
     /// Backfill embeddings for chunks that don't have them
     BackfillEmbeddings,
+    /// Merge two duplicate entities (prompts which one to keep)
+    Merge {
+        /// First entity name
+        name1: String,
+        /// Second entity name
+        name2: String,
+    },
+    /// Delete an entity along with its relationships and chunk links
+    Delete {
+        /// Entity name (or alias) to delete
+        name: String,
+    },
+    /// Remove old memory chunks that are rarely retrieved
+    Prune {
+        /// Only consider chunks older than this many days
+        #[arg(long, default_value_t = 90)]
+        max_age_days: u64,
+        /// Prune chunks retrieved fewer than this many times
+        #[arg(long, default_value_t = 1)]
+        min_access_count: i64,
+        /// Report what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export the graph to stdout for external visualization
+    Export {
+        /// Output format: json or graphml
+        #[arg(long, short, default_value = "json")]
+        format: String,
+    },
+    /// Import a resource from an MCP server into the knowledge graph
+    ImportMcpResource {
+        /// Resource URI
+        uri: String,
+        /// MCP server to read from (default: the first server that lists the URI)
+        #[arg(long)]
+        server: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// Add an entity type, or update an existing type's description
+    AddType {
+        /// Type name, e.g. "library"
+        name: String,
+        /// What entities of this type are
+        #[arg(long, visible_alias = "desc")]
+        description: String,
+    },
+    /// Add a relationship type, or replace an existing one
+    AddRel {
+        /// Relationship name, e.g. "depends_on"
+        name: String,
+        /// Entity types it can start from (comma-separated or repeated; default: any)
+        #[arg(long, value_delimiter = ',')]
+        from: Vec<String>,
+        /// Entity types it can point to (comma-separated or repeated; default: any)
+        #[arg(long, value_delimiter = ',')]
+        to: Vec<String>,
+        /// What the relationship means
+        #[arg(long = "desc", visible_alias = "description")]
+        description: String,
+        /// Example usage, e.g. "imp depends_on tokio"
+        #[arg(long, default_value = "")]
+        example: String,
+    },
+    /// Remove an entity type (entities of that type are kept)
+    RemoveType {
+        /// Type name
+        name: String,
+    },
+    /// Remove a relationship type (relationships of that type are kept)
+    RemoveRel {
+        /// Relationship name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpCommands {
+    /// Connect to configured servers and show their state, tools and errors
+    Status,
 }
 
 #[derive(Subcommand)]
@@ -124,28 +342,52 @@ enum ProjectCommands {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    logging::init();
     let cli = Cli::parse();
+    logging::init(cli.verbose);
+    output::init(cli.no_color);
 
     match cli.command {
-        Commands::Bootstrap => {
-            bootstrap::run().await?;
+        Commands::Bootstrap { reset } => {
+            bootstrap::run(reset).await?;
         }
         Commands::Login => {
             login::run().await?;
         }
-        Commands::Ask { message } => {
-            let full_message = message.join(" ");
-            oneshot::run(&full_message).await?;
+        Commands::Ask { message, think, no_think, json, files, read_only, deterministic } => {
+            let Some(mut full_message) = oneshot::prompt_with_stdin(&message)? else {
+                Cli::command()
+                    .error(
+                        clap::error::ErrorKind::MissingRequiredArgument,
+                        "Nothing to ask: give a question, or pipe input into 'imp ask'",
+                    )
+                    .exit();
+            };
+            if !files.is_empty() {
+                let attached = tools::builtin::attach_files(&files)?;
+                full_message = format!("{}\n{}", attached.text, full_message);
+            }
+            oneshot::run(&full_message, thinking_override(think, no_think), json, read_only, deterministic).await?;
         }
-        Commands::Chat { resume, r#continue, session } => {
-            chat::run(resume, r#continue, session).await?;
+        Commands::Chat { resume, r#continue, session, search, export, format, no_thinking, think, no_think, exec_file, deterministic } => {
+            let thinking = thinking_override(think, no_think);
+            match (export, exec_file) {
+                (Some(prefix), _) => chat::export(&prefix, &format, !no_thinking)?,
+                (None, Some(file)) => chat::run_script(&file, r#continue, session, thinking, deterministic).await?,
+                (None, None) => chat::run(resume, r#continue, session, search, thinking, deterministic).await?,
+            }
         }
-        Commands::Learn => {
-            learn::run().await?;
+        Commands::Learn { file } => {
+            if file.is_some() || !io::stdin().is_terminal() {
+                learn::from_file(file.as_deref()).await?;
+            } else {
+                learn::run().await?;
+            }
         }
-        Commands::Reflect { date } => {
-            reflect::run(date).await?;
+        Commands::Reflect { date, from, to, days, dry_run, interactive, force, restore } => {
+            match restore {
+                Some(name) => reflect::restore(&name)?,
+                None => reflect::run(date, from, to, days, dry_run, interactive, force).await?,
+            }
         }
         Commands::Themes => {
             let mut themes = highlight::available_themes();
@@ -155,16 +397,29 @@ async fn main() -> Result<()> {
                 println!("  {}", theme);
             }
             println!("\nSet in config.toml:\n\n  [display]\n  theme = \"{}\"", themes.first().unwrap_or(&"base16-ocean.dark".to_string()));
+            println!("\nOr pick one from the terminal background:\n\n  theme = \"auto\"\n  theme_light = \"base16-ocean.light\"\n  theme_dark = \"base16-mocha.dark\"");
         }
         Commands::Knowledge { command } => match command {
-            KnowledgeCommands::Stats => {
-                knowledge_cmd::stats()?;
-            }
-            KnowledgeCommands::Schema => {
-                knowledge_cmd::schema()?;
+            KnowledgeCommands::Stats { project } => {
+                knowledge_cmd::stats(project.as_deref())?;
             }
-            KnowledgeCommands::Query { name } => {
-                knowledge_cmd::query(&name)?;
+            KnowledgeCommands::Schema { command } => match command {
+                None => knowledge_cmd::schema()?,
+                Some(SchemaCommands::AddType { name, description }) => {
+                    knowledge_cmd::schema_add_type(&name, &description)?;
+                }
+                Some(SchemaCommands::AddRel { name, from, to, description, example }) => {
+                    knowledge_cmd::schema_add_rel(&name, &from, &to, &description, &example)?;
+                }
+                Some(SchemaCommands::RemoveType { name }) => {
+                    knowledge_cmd::schema_remove_type(&name)?;
+                }
+                Some(SchemaCommands::RemoveRel { name }) => {
+                    knowledge_cmd::schema_remove_rel(&name)?;
+                }
+            },
+            KnowledgeCommands::Query { name, chunks } => {
+                knowledge_cmd::query(&name, chunks)?;
             }
             KnowledgeCommands::Search { query } => {
                 knowledge_cmd::search(&query)?;
@@ -172,6 +427,21 @@ async fn main() -> Result<()> {
             KnowledgeCommands::BackfillEmbeddings => {
                 knowledge_cmd::backfill_embeddings()?;
             }
+            KnowledgeCommands::Merge { name1, name2 } => {
+                knowledge_cmd::merge(&name1, &name2)?;
+            }
+            KnowledgeCommands::Delete { name } => {
+                knowledge_cmd::delete(&name)?;
+            }
+            KnowledgeCommands::Prune { max_age_days, min_access_count, dry_run } => {
+                knowledge_cmd::prune(max_age_days, min_access_count, dry_run)?;
+            }
+            KnowledgeCommands::Export { format } => {
+                knowledge_cmd::export(&format)?;
+            }
+            KnowledgeCommands::ImportMcpResource { uri, server } => {
+                knowledge_cmd::import_mcp_resource(&uri, server.as_deref()).await?;
+            }
         },
         Commands::Project { command } => match command {
             ProjectCommands::List => {
@@ -184,10 +454,32 @@ async fn main() -> Result<()> {
                 project_cmd::context()?;
             }
         },
+        Commands::Mcp { command } => match command {
+            McpCommands::Status => {
+                mcp_cmd::status().await?;
+            }
+        },
+        Commands::Usage { since, today, project } => {
+            usage_cmd::run(since, today, project)?;
+        }
         Commands::Tui => {
             tui::run()?;
         }
+        Commands::Doctor => {
+            if !doctor::run().await? {
+                std::process::exit(1);
+            }
+        }
     }
 
     Ok(())
 }
+
+/// `--think` / `--no-think` as an override of `[thinking] enabled`.
+fn thinking_override(think: bool, no_think: bool) -> Option<bool> {
+    match (think, no_think) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
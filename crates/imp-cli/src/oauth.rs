@@ -0,0 +1,96 @@
+//! OAuth access-token refresh for Claude Pro/Max subscriptions.
+//!
+//! Access tokens are short-lived; the stored refresh token is exchanged for a
+//! new pair at the token endpoint and written back to `config.toml`. Refreshes
+//! are serialized through a process-wide lock, and whoever gets the lock second
+//! re-reads the config first, so parallel sub-agents share one refresh instead
+//! of each spending (and invalidating) the refresh token.
+
+use crate::config::{Config, OAuthConfig};
+use crate::error::{ImpError, Result};
+use crate::redact;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+const TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+const CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+
+static REFRESH_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Some servers rotate the refresh token, others keep the old one valid.
+    refresh_token: Option<String>,
+    expires_in: i64,
+}
+
+/// Get a fresh access token to replace `stale_access_token`, refreshing it
+/// at the token endpoint unless another client already has. The new tokens
+/// are saved to config and returned. `timeout` bounds the whole token request,
+/// since the shared client only has a connect timeout.
+pub async fn refresh(
+    http: &reqwest::Client,
+    stale_access_token: &str,
+    timeout: Duration,
+) -> Result<OAuthConfig> {
+    let _guard = REFRESH_LOCK.lock().await;
+
+    let mut config = Config::load()?;
+    let current = config.oauth_config().cloned().ok_or_else(|| {
+        ImpError::Config("OAuth configuration missing. Run 'imp login' to set it up.".to_string())
+    })?;
+
+    // Someone else refreshed while we waited for the lock (or in another process)
+    if current.access_token != stale_access_token && !current.is_expired() {
+        return Ok(current);
+    }
+
+    if current.refresh_token.is_empty() {
+        return Err(ImpError::Config(
+            "OAuth token has expired and there is no refresh token to renew it. Run 'imp login' to re-authenticate.".to_string(),
+        ));
+    }
+
+    let response = http
+        .post(TOKEN_URL)
+        .timeout(timeout)
+        .json(&json!({
+            "grant_type": "refresh_token",
+            "refresh_token": current.refresh_token,
+            "client_id": CLIENT_ID,
+        }))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::BAD_REQUEST || status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(ImpError::Config(
+            "OAuth refresh token is invalid or has been revoked. Run 'imp login' to re-authenticate.".to_string(),
+        ));
+    }
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(ImpError::Agent(format!(
+            "OAuth token refresh failed ({}): {}",
+            status,
+            redact::redact(&error_text)
+        )));
+    }
+
+    let tokens: TokenResponse = response.json().await?;
+    redact::register_secret(&tokens.access_token);
+    if let Some(ref token) = tokens.refresh_token {
+        redact::register_secret(token);
+    }
+    let refresh_token = tokens.refresh_token.unwrap_or(current.refresh_token);
+    let expires_at = chrono::Utc::now().timestamp() + tokens.expires_in;
+    config.update_oauth_tokens(tokens.access_token, refresh_token, expires_at)?;
+    tracing::info!("Refreshed OAuth access token");
+
+    config.oauth_config().cloned().ok_or_else(|| {
+        ImpError::Config("OAuth configuration missing after refresh".to_string())
+    })
+}
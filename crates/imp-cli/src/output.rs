@@ -0,0 +1,75 @@
+//! Plain output for pipes, `NO_COLOR` and `--no-color`.
+//!
+//! In plain mode every `console::style` call stops emitting colour, and
+//! `clean` also strips ANSI codes (from markdown rendering and highlighting)
+//! and decorative emoji from status lines. Interactive use on a terminal is
+//! unaffected.
+
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Decide once, at startup, whether output is plain: stdout isn't a terminal,
+/// `NO_COLOR` is set, or `--no-color` was given.
+pub fn init(no_color: bool) {
+    let no_color = no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if no_color {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    PLAIN.store(no_color || !std::io::stdout().is_terminal(), Ordering::Relaxed);
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// `text` without ANSI codes or decorative emoji in plain mode, unchanged otherwise.
+pub fn clean(text: &str) -> Cow<'_, str> {
+    if !is_plain() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(strip_emoji(&console::strip_ansi_codes(text)))
+}
+
+/// Print a status line, cleaned for plain output.
+pub fn line(msg: impl std::fmt::Display) {
+    println!("{}", clean(&msg.to_string()));
+}
+
+/// Remove emoji and pictographs, with the spaces that followed them.
+fn strip_emoji(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_emoji(c) {
+            while chars.next_if(|&next| next == ' ' || next == '\u{fe0f}' || next == '\u{200d}').is_some() {}
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1f000}'..='\u{1faff}'  // emoticons, pictographs, transport, symbols
+        | '\u{2600}'..='\u{27bf}'  // misc symbols and dingbats (⚠ ⚡ ✅ ❌)
+        | '\u{2b50}'..='\u{2b55}'  // stars and circles
+        | '\u{fe0f}' | '\u{200d}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_emoji() {
+        assert_eq!(strip_emoji("🤖 Imp"), "Imp");
+        assert_eq!(strip_emoji("⚠️  Unknown key"), "Unknown key");
+        assert_eq!(strip_emoji("  ✅ Tool completed → done"), "  Tool completed → done");
+        assert_eq!(strip_emoji("─── 1,234 tokens"), "─── 1,234 tokens");
+    }
+}
@@ -0,0 +1,106 @@
+//! Scrub secrets out of text before it is logged or put into an error.
+//!
+//! Two kinds of secret are masked: values registered at runtime (the
+//! configured API key and OAuth tokens, values expanded from `${VAR}` in MCP
+//! config), and anything that looks like a credential — Anthropic keys,
+//! `Bearer` tokens and `x-api-key` headers — even if we never saw it before.
+
+use regex::Regex;
+use std::io::{self, Write};
+use std::sync::{OnceLock, RwLock};
+use tracing_subscriber::fmt::MakeWriter;
+
+const MASK: &str = "[REDACTED]";
+/// Shorter registered values are ignored: masking them would mangle ordinary text.
+const MIN_SECRET_LEN: usize = 8;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+fn patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        vec![
+            (Regex::new(r"sk-ant-[A-Za-z0-9_\-]+").unwrap(), MASK),
+            (Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=\-]+").unwrap(), "${1}[REDACTED]"),
+            (
+                Regex::new(r#"(?i)(x-api-key["']?\s*[:=]\s*["']?)[^\s"',}]+"#).unwrap(),
+                "${1}[REDACTED]",
+            ),
+        ]
+    })
+}
+
+/// Remember a secret value so `redact` masks it wherever it appears.
+pub fn register_secret(value: &str) {
+    let value = value.trim();
+    if value.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|s| s == value) {
+        secrets.push(value.to_string());
+    }
+}
+
+/// Return `text` with registered secrets and credential-shaped strings masked.
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    for secret in SECRETS.read().unwrap().iter() {
+        if out.contains(secret.as_str()) {
+            out = out.replace(secret.as_str(), MASK);
+        }
+    }
+    for (re, replacement) in patterns() {
+        if re.is_match(&out) {
+            out = re.replace_all(&out, *replacement).into_owned();
+        }
+    }
+    out
+}
+
+/// Log writer that redacts each formatted event before passing it on.
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Wraps a `MakeWriter` (e.g. the rolling log file) so everything written through it is redacted.
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_masks_keys_and_tokens() {
+        let body = r#"{"error":"invalid x-api-key","headers":{"x-api-key":"sk-ant-REDACTED"}}"#;
+        let redacted = redact(body);
+        assert!(!redacted.contains("FAKEKEY_123"), "{}", redacted);
+        assert!(redacted.contains("invalid x-api-key"));
+
+        let redacted = redact("Authorization: Bearer abc.def-ghi_jkl");
+        assert_eq!(redacted, "Authorization: Bearer [REDACTED]");
+
+        // A proxy token with no recognisable shape is only masked once registered
+        assert_eq!(redact("key: proxy-token-1234!"), "key: proxy-token-1234!");
+        register_secret("proxy-token-1234");
+        assert_eq!(redact("key: proxy-token-1234!"), "key: [REDACTED]!");
+    }
+}
@@ -5,7 +5,7 @@
 //! Sub-agents run the same agentic loop as the parent but non-interactively,
 //! and they cannot spawn further sub-agents (no recursive spawning).
 
-use crate::client::{ClaudeClient, Message};
+use crate::client::{AnthropicResponse, ClaudeClient, Message};
 use crate::config::{imp_home, Config};
 use crate::db::Database;
 use crate::error::Result;
@@ -14,19 +14,60 @@ use crate::usage::UsageTracker;
 use serde_json::json;
 use std::fs;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
 /// Global counter for unique sub-agent IDs.
 static NEXT_SUBAGENT_ID: AtomicU64 = AtomicU64::new(1);
 
+/// Final outputs longer than this are saved to an artifact file and only
+/// previewed in the summary injected into the parent's conversation.
+const ARTIFACT_THRESHOLD_CHARS: usize = 2000;
+/// Length of that preview.
+const ARTIFACT_PREVIEW_CHARS: usize = 1000;
+
+/// Minimum time between progress reports from one sub-agent.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodic progress report sent by a running sub-agent to its parent.
+#[derive(Debug, Clone)]
+pub struct SubAgentProgress {
+    pub id: u64,
+    pub tool_calls: usize,
+    pub last_action: String,
+    pub tokens_used: u64,
+}
+
+impl SubAgentProgress {
+    /// One-line summary for the chat and `check_agents`.
+    pub fn format_line(&self) -> String {
+        format!(
+            "↳ Sub-agent #{} · {} tool calls · {} tokens · {}",
+            self.id,
+            self.tool_calls,
+            crate::usage::abbreviate_tokens(self.tokens_used as usize),
+            self.last_action
+        )
+    }
+}
+
 /// Result returned when a sub-agent completes (or fails).
 #[derive(Debug, Clone)]
 pub struct SubAgentResult {
     pub id: u64,
     pub task: String,
+    /// Model the sub-agent ran on.
+    pub model: String,
     pub summary: String,
+    /// Full output saved to `~/.imp/subagents/<id>/output.md` when it was
+    /// too long to include in the summary.
+    pub artifact_path: Option<String>,
     pub files_changed: Vec<String>,
     pub input_tokens_used: u64,
     pub output_tokens_used: u64,
+    /// Cache write + read tokens, which also count against the budget.
+    pub cache_tokens_used: u64,
+    pub token_budget: u64,
     pub success: bool,
     pub error: Option<String>,
 }
@@ -36,19 +77,26 @@ impl SubAgentResult {
     pub fn format_report(&self) -> String {
         let status = if self.success { "✅ Completed" } else { "❌ Failed" };
         let mut report = format!(
-            "Sub-agent #{} — {}\nTask: {}\n",
-            self.id, status, self.task
+            "Sub-agent #{} — {}\nTask: {}\nModel: {}\n",
+            self.id, status, self.task, self.model
         );
         if !self.summary.is_empty() {
             report.push_str(&format!("Summary: {}\n", self.summary));
         }
+        if let Some(ref path) = self.artifact_path {
+            report.push_str(&format!("Full output: {} (use file_read to see it)\n", path));
+        }
         if !self.files_changed.is_empty() {
             report.push_str(&format!("Files changed: {}\n", self.files_changed.join(", ")));
         }
-        let total_tokens = self.input_tokens_used + self.output_tokens_used;
+        let total_tokens = self.input_tokens_used + self.output_tokens_used + self.cache_tokens_used;
         report.push_str(&format!(
-            "Tokens used: {} (in: {}, out: {})\n",
-            total_tokens, self.input_tokens_used, self.output_tokens_used
+            "Tokens used: {}/{} budget (in: {}, out: {}, cache: {})\n",
+            total_tokens,
+            self.token_budget,
+            self.input_tokens_used,
+            self.output_tokens_used,
+            self.cache_tokens_used
         ));
         if let Some(ref err) = self.error {
             report.push_str(&format!("Error: {}\n", err));
@@ -61,8 +109,14 @@ impl SubAgentResult {
 pub struct SubAgentHandle {
     pub id: u64,
     pub task: String,
+    pub model: String,
+    pub token_budget: u64,
     pub handle: tokio::task::JoinHandle<SubAgentResult>,
     pub spawned_at: std::time::Instant,
+    /// Progress reports from the running sub-agent.
+    pub progress: mpsc::UnboundedReceiver<SubAgentProgress>,
+    /// The most recent report received, for `check_agents`.
+    pub last_progress: Option<SubAgentProgress>,
 }
 
 /// A sub-agent that runs autonomously to complete a task.
@@ -77,13 +131,18 @@ pub struct SubAgent {
 
 impl SubAgent {
     /// Create a new sub-agent. Does not start execution — call `spawn()` for that.
+    /// Runs on `[subagent] model` if set, otherwise the main model.
     pub fn new(
         task: String,
         working_directory: Option<String>,
         max_tokens_budget: Option<u64>,
         timeout_secs: Option<u64>,
-        config: Config,
+        mut config: Config,
     ) -> Self {
+        if let Some(model) = config.subagent.model.clone() {
+            config.llm.model = model;
+        }
+
         let id = NEXT_SUBAGENT_ID.fetch_add(1, Ordering::SeqCst);
         let cwd = working_directory.unwrap_or_else(|| {
             std::env::current_dir()
@@ -101,22 +160,38 @@ impl SubAgent {
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+
     /// Spawn this sub-agent as a background tokio task. Returns a handle for tracking.
     pub fn spawn(self) -> SubAgentHandle {
         let id = self.id;
         let task = self.task.clone();
+        let model = self.config.llm.model.clone();
         let timeout = std::time::Duration::from_secs(self.timeout_secs);
+        let timeout_model = model.clone();
+        let token_budget = self.max_tokens_budget;
+        let (progress_tx, progress) = mpsc::unbounded_channel();
 
         let handle = tokio::spawn(async move {
-            match tokio::time::timeout(timeout, self.run()).await {
+            match tokio::time::timeout(timeout, self.run(progress_tx)).await {
                 Ok(result) => result,
                 Err(_) => SubAgentResult {
                     id,
                     task: String::new(), // will be overwritten below
+                    model: timeout_model,
                     summary: String::new(),
+                    artifact_path: None,
                     files_changed: Vec::new(),
                     input_tokens_used: 0,
                     output_tokens_used: 0,
+                    cache_tokens_used: 0,
+                    token_budget,
                     success: false,
                     error: Some(format!("Sub-agent timed out after {}s", timeout.as_secs())),
                 },
@@ -126,25 +201,35 @@ impl SubAgent {
         SubAgentHandle {
             id,
             task,
+            model,
+            token_budget,
             handle,
             spawned_at: std::time::Instant::now(),
+            progress,
+            last_progress: None,
         }
     }
 
     /// Entry point for the tokio task. Catches panics/errors and returns a result.
-    async fn run(self) -> SubAgentResult {
+    async fn run(self, progress_tx: mpsc::UnboundedSender<SubAgentProgress>) -> SubAgentResult {
         let id = self.id;
         let task = self.task.clone();
+        let model = self.config.llm.model.clone();
+        let token_budget = self.max_tokens_budget;
 
-        match self.run_inner().await {
+        match self.run_inner(progress_tx).await {
             Ok(result) => result,
             Err(e) => SubAgentResult {
                 id,
                 task,
+                model,
                 summary: String::new(),
+                artifact_path: None,
                 files_changed: Vec::new(),
                 input_tokens_used: 0,
                 output_tokens_used: 0,
+                cache_tokens_used: 0,
+                token_budget,
                 success: false,
                 error: Some(format!("Sub-agent error: {}", e)),
             },
@@ -153,11 +238,21 @@ impl SubAgent {
 
     /// The actual agentic loop. Mirrors Agent::process_message_with_options but simplified:
     /// no streaming, no markdown rendering, no insight distillation, hard iteration limit.
-    async fn run_inner(self) -> Result<SubAgentResult> {
+    async fn run_inner(self, progress_tx: mpsc::UnboundedSender<SubAgentProgress>) -> Result<SubAgentResult> {
         // Each sub-agent gets its own client, tools, and database session
         let mut client = ClaudeClient::new(self.config.clone())?;
 
         let mut tools = ToolRegistry::new();
+        tools.set_default_cwd(Some(std::path::PathBuf::from(&self.working_directory)));
+        tools.set_sandbox_root(self.config.tools.sandbox_root.as_ref().map(std::path::PathBuf::from));
+        tools.set_max_glob_files(self.config.tools.max_glob_files);
+        tools.set_max_output_bytes(self.config.tools.max_output_bytes);
+        tools.set_read_only(self.config.tools.read_only);
+        tools.set_exec_policy(crate::tools::builtin::ExecPolicy::new(
+            &self.config.tools.exec.allow_patterns,
+            &self.config.tools.exec.deny_patterns,
+        )?);
+        tools.set_knowledge_enabled(self.config.knowledge.enabled);
         tools.load_subagent_builtins_with_mcp().await?;
 
         let db = Database::open()?;
@@ -210,12 +305,26 @@ impl SubAgent {
             self.task, self.working_directory
         );
 
+        self.run_turns(&mut client, &mut tools, db, &session_id, system_prompt, progress_tx).await
+    }
+
+    /// Work on the task turn by turn until the model stops calling tools or
+    /// the token budget runs out.
+    async fn run_turns(
+        self,
+        model: &mut impl TurnModel,
+        tools: &mut ToolRegistry,
+        db: Database,
+        session_id: &str,
+        system_prompt: String,
+        progress_tx: mpsc::UnboundedSender<SubAgentProgress>,
+    ) -> Result<SubAgentResult> {
         let mut messages: Vec<Message> = Vec::new();
         messages.push(Message::text("user", &self.task));
 
         // Persist the initial user message
         let _ = db.save_message(
-            &session_id,
+            session_id,
             "user",
             &serde_json::Value::String(self.task.clone()),
             0,
@@ -223,47 +332,57 @@ impl SubAgent {
 
         let mut usage = UsageTracker::new();
         let mut files_changed: Vec<String> = Vec::new();
-        let mut final_text = String::new();
+        // Latest assistant text, reported as partial progress if the budget runs out
+        let mut last_text = String::new();
+        let mut tool_call_count = 0;
+        let mut last_action = String::new();
+        let mut last_report: Option<Instant> = None;
 
-        let system_tokens_estimate = system_prompt.len() / 4;
+        let system_tokens_estimate = crate::tokens::count_text(&system_prompt);
 
-        loop {
+        let final_text = loop {
             // Check token budget before each API call
-            if usage.total_tokens() >= self.max_tokens_budget {
-                final_text = format!(
-                    "Sub-agent stopped: token budget exhausted ({}/{} tokens used)",
-                    usage.total_tokens(),
-                    self.max_tokens_budget
-                );
-                break;
+            if let Some(summary) = budget_exhausted_summary(
+                usage.total_tokens(),
+                self.max_tokens_budget,
+                &last_text,
+                &files_changed,
+            ) {
+                return Ok(SubAgentResult {
+                    id: self.id,
+                    task: self.task,
+                    model: self.config.llm.model,
+                    summary,
+                    artifact_path: None,
+                    files_changed,
+                    input_tokens_used: usage.total_input_tokens,
+                    output_tokens_used: usage.total_output_tokens,
+                    cache_tokens_used: usage.total_cache_creation_tokens + usage.total_cache_read_tokens,
+                    token_budget: self.max_tokens_budget,
+                    success: false,
+                    error: Some("Token budget exhausted".to_string()),
+                });
             }
 
             let tool_schemas = Some(tools.get_tool_schemas().await);
-            let tool_tokens = tool_schemas.as_ref().map_or(0, |t| t.to_string().len() / 4);
-            messages = crate::compaction::compact_if_needed(&messages, system_tokens_estimate, tool_tokens);
-
-            let response = match client
-                .send_message(
-                    messages.clone(),
-                    Some(system_prompt.clone()),
-                    tool_schemas.clone(),
-                    false, // no streaming
-                )
-                .await
-            {
+            let tool_tokens = tool_schemas.as_ref().map_or(0, |t| crate::tokens::count_text(&t.to_string()));
+            if let Some(compacted) = crate::compaction::compact_if_needed(
+                &messages,
+                system_tokens_estimate,
+                tool_tokens,
+                self.config.llm.context_window(),
+                &self.config.compaction,
+            ) {
+                messages = compacted;
+            }
+
+            let response = match model.send_turn(messages.clone(), &system_prompt, tool_schemas.clone()).await {
                 Ok(r) => r,
                 Err(ref e) if is_context_overflow_error(e) => {
                     // Context too long — compact and retry
-                    messages = crate::compaction::compact(&messages);
+                    messages = crate::compaction::compact(&messages, self.config.compaction.strategy);
                     let retry_tools = Some(tools.get_tool_schemas().await);
-                    client
-                        .send_message(
-                            messages.clone(),
-                            Some(system_prompt.clone()),
-                            retry_tools,
-                            false,
-                        )
-                        .await?
+                    model.send_turn(messages.clone(), &system_prompt, retry_tools).await?
                 }
                 Err(e) => return Err(e),
             };
@@ -274,16 +393,19 @@ impl SubAgent {
                 usage.record_cache(resp_usage.cache_creation_input_tokens, resp_usage.cache_read_input_tokens);
             }
 
-            let text_content = client.extract_text_content(&response);
-            let tool_calls = client.extract_tool_calls(&response);
-            let content_blocks = client.extract_content_blocks(&response);
+            let text_content = response.text_content();
+            let tool_calls = response.tool_calls();
+            let content_blocks = response.content_blocks();
+            if !text_content.trim().is_empty() {
+                last_text = text_content.clone();
+            }
 
             // Push the assistant's response
             if !content_blocks.is_empty() {
                 let assistant_content = json!(content_blocks);
                 messages.push(Message::with_content("assistant", assistant_content.clone()));
                 let _ = db.save_message(
-                    &session_id,
+                    session_id,
                     "assistant",
                     &assistant_content,
                     tool_calls.len(),
@@ -292,16 +414,15 @@ impl SubAgent {
 
             // No tool calls → agent is done
             if tool_calls.is_empty() {
-                final_text = text_content;
-                break;
+                break text_content;
             }
 
             // Execute each tool call
             let mut tool_results = Vec::new();
             for tool_call in &tool_calls {
-                // Track file modifications
+                // Track file modifications (read-only mode only simulates them)
                 match tool_call.name.as_str() {
-                    "file_write" | "file_edit" => {
+                    "file_write" | "file_edit" if !tools.read_only() => {
                         if let Some(path) = tool_call.input.get("path").and_then(|v| v.as_str()) {
                             if !files_changed.contains(&path.to_string()) {
                                 files_changed.push(path.to_string());
@@ -319,43 +440,123 @@ impl SubAgent {
                     })
                     .await?;
 
-                let anthropic_result = crate::client::ToolResult {
-                    tool_use_id: result.tool_use_id,
-                    content: if let Some(ref error) = result.error {
-                        error.clone()
-                    } else {
-                        result.content
-                    },
-                    is_error: result.error.is_some().then_some(true),
-                };
-
-                tool_results.push(anthropic_result);
+                tool_results.push(result.into_anthropic());
+                tool_call_count += 1;
+                last_action = crate::agent::format_tool_call(&tool_call.name, &tool_call.input);
+            }
+
+            if last_report.map_or(true, |at| at.elapsed() >= PROGRESS_INTERVAL) {
+                // The parent may have stopped listening; progress is best-effort
+                let _ = progress_tx.send(SubAgentProgress {
+                    id: self.id,
+                    tool_calls: tool_call_count,
+                    last_action: last_action.clone(),
+                    tokens_used: usage.total_tokens(),
+                });
+                last_report = Some(Instant::now());
             }
 
             // Send tool results back for the next iteration
             if !tool_results.is_empty() {
                 let tool_msg = Message::tool_results(tool_results);
-                let _ = db.save_message(&session_id, "user", &tool_msg.content, 0);
+                let _ = db.save_message(session_id, "user", &tool_msg.content, 0);
                 messages.push(tool_msg);
             }
-        }
+        };
 
-        // Extract a concise summary from the final text
-        let summary = extract_summary(&final_text);
+        // Extract a concise summary from the final text, saving long output to a file
+        let (summary, artifact_path) = if final_text.chars().count() > ARTIFACT_THRESHOLD_CHARS {
+            match write_artifact(session_id, &final_text) {
+                Ok(path) => {
+                    let summary = extract_summary(&final_text);
+                    let preview: String = summary.chars().take(ARTIFACT_PREVIEW_CHARS).collect();
+                    (format!("{}... [truncated, full output in artifact]", preview), Some(path))
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to save sub-agent output");
+                    (extract_summary(&final_text), None)
+                }
+            }
+        } else {
+            (extract_summary(&final_text), None)
+        };
 
         Ok(SubAgentResult {
             id: self.id,
             task: self.task,
+            model: self.config.llm.model,
             summary,
+            artifact_path,
             files_changed,
             input_tokens_used: usage.total_input_tokens,
             output_tokens_used: usage.total_output_tokens,
+            cache_tokens_used: usage.total_cache_creation_tokens + usage.total_cache_read_tokens,
+            token_budget: self.max_tokens_budget,
             success: true,
             error: None,
         })
     }
 }
 
+/// Sends a sub-agent's turns to the model: `ClaudeClient`, or a stub in tests.
+trait TurnModel {
+    fn send_turn(
+        &mut self,
+        messages: Vec<Message>,
+        system_prompt: &str,
+        tools: Option<serde_json::Value>,
+    ) -> impl std::future::Future<Output = Result<AnthropicResponse>> + Send;
+}
+
+impl TurnModel for ClaudeClient {
+    fn send_turn(
+        &mut self,
+        messages: Vec<Message>,
+        system_prompt: &str,
+        tools: Option<serde_json::Value>,
+    ) -> impl std::future::Future<Output = Result<AnthropicResponse>> + Send {
+        // No streaming: nobody is watching a sub-agent's output
+        self.send_message(messages, Some(system_prompt.to_string()), tools, false)
+    }
+}
+
+/// Save a sub-agent's full output to `~/.imp/subagents/<session>/output.md`,
+/// named after the sub-agent's own session so no other run overwrites it.
+fn write_artifact(session_id: &str, text: &str) -> Result<String> {
+    let dir = imp_home()?.join("subagents").join(session_id);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("output.md");
+    fs::write(&path, text)?;
+    Ok(path.display().to_string())
+}
+
+/// If `used` has reached `budget`, a summary saying the sub-agent stopped
+/// early, with its latest progress; `None` while there is budget left.
+fn budget_exhausted_summary(
+    used: u64,
+    budget: u64,
+    last_text: &str,
+    files_changed: &[String],
+) -> Option<String> {
+    if used < budget {
+        return None;
+    }
+
+    let mut summary = format!(
+        "Stopped before finishing: token budget exhausted ({}/{} tokens used). The task is incomplete.",
+        used, budget
+    );
+    if !files_changed.is_empty() {
+        summary.push_str(&format!(" Files modified so far: {}.", files_changed.join(", ")));
+    }
+    if last_text.trim().is_empty() {
+        summary.push_str(" No progress was reported before the budget ran out.");
+    } else {
+        summary.push_str(&format!("\nLast progress:\n{}", extract_summary(last_text)));
+    }
+    Some(summary)
+}
+
 /// Extract a summary from the agent's final response.
 /// Looks for "TASK COMPLETE:" prefix; falls back to the full response.
 fn extract_summary(text: &str) -> String {
@@ -380,6 +581,10 @@ fn extract_summary(text: &str) -> String {
 
 /// Check if an error indicates the context/input is too long for the model.
 fn is_context_overflow_error(error: &crate::error::ImpError) -> bool {
+    // Compacting won't help a request that timed out
+    if matches!(error, crate::error::ImpError::Timeout(_)) {
+        return false;
+    }
     let msg = error.to_string().to_lowercase();
     msg.contains("too long")
         || msg.contains("too large")
@@ -390,3 +595,60 @@ fn is_context_overflow_error(error: &crate::error::ImpError) -> bool {
         || msg.contains("exceeds the maximum")
         || msg.contains("request too large")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every turn with a tool call that uses 4,500 tokens.
+    struct OversizedTurns {
+        calls: usize,
+    }
+
+    impl TurnModel for OversizedTurns {
+        fn send_turn(
+            &mut self,
+            _messages: Vec<Message>,
+            _system_prompt: &str,
+            _tools: Option<serde_json::Value>,
+        ) -> impl std::future::Future<Output = Result<AnthropicResponse>> + Send {
+            self.calls += 1;
+            let response = serde_json::from_value(json!({
+                "type": "message",
+                "content": [
+                    { "type": "text", "text": "Found 3 callers so far" },
+                    { "type": "tool_use", "id": "toolu_1", "name": "no_such_tool", "input": {} }
+                ],
+                "stop_reason": "tool_use",
+                "usage": { "input_tokens": 4000, "output_tokens": 500 }
+            }))
+            .unwrap();
+            std::future::ready(Ok(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tiny_budget_halts_after_one_oversized_turn() {
+        let config: Config = toml::from_str("[llm]\nprovider = \"anthropic\"\n").unwrap();
+        let subagent = SubAgent::new("find callers".to_string(), Some(".".to_string()), Some(1_000), None, config);
+        let db = Database::open_in_memory().unwrap();
+        let session_id = db.create_session(Some("subagent-test"), None).unwrap();
+        let mut model = OversizedTurns { calls: 0 };
+        let (progress_tx, _progress) = mpsc::unbounded_channel();
+
+        let result = subagent
+            .run_turns(&mut model, &mut ToolRegistry::new(), db, &session_id, String::new(), progress_tx)
+            .await
+            .unwrap();
+
+        assert_eq!(model.calls, 1);
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Token budget exhausted"));
+        assert!(result.summary.contains("token budget exhausted (4500/1000 tokens used)"), "{}", result.summary);
+        assert!(result.summary.contains("Found 3 callers so far"));
+
+        let report = result.format_report();
+        assert!(report.contains("❌ Failed"));
+        assert!(report.contains("Tokens used: 4500/1000 budget"), "{}", report);
+    }
+}
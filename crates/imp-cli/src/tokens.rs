@@ -0,0 +1,157 @@
+//! Token counting for context budgeting.
+//!
+//! Claude's tokenizer isn't public, so counts come from the `cl100k_base` BPE
+//! via tiktoken — far closer to the real number than a characters/4 guess,
+//! especially for code and non-English text. If the encoder can't be loaded we
+//! fall back to the heuristic. Counts are cached by content hash so re-counting
+//! an unchanged conversation history on every loop iteration is cheap.
+
+use crate::client::Message;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use tiktoken_rs::CoreBPE;
+
+/// Rough fallback ratio when no tokenizer is available.
+const CHARS_PER_TOKEN: usize = 4;
+/// Cached counts are dropped wholesale once the cache grows past this.
+const MAX_CACHED_COUNTS: usize = 8192;
+
+/// Something that can count the tokens in a piece of text.
+pub trait TokenCounter: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+}
+
+/// BPE tokenizer-backed counter.
+pub struct BpeCounter {
+    bpe: CoreBPE,
+}
+
+impl BpeCounter {
+    pub fn new() -> Option<Self> {
+        match tiktoken_rs::cl100k_base() {
+            Ok(bpe) => Some(Self { bpe }),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to load tokenizer, falling back to character estimate");
+                None
+            }
+        }
+    }
+}
+
+impl TokenCounter for BpeCounter {
+    fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+}
+
+/// Characters/4 heuristic, used when the tokenizer can't be loaded.
+pub struct CharEstimateCounter;
+
+impl TokenCounter for CharEstimateCounter {
+    fn count(&self, text: &str) -> usize {
+        text.len() / CHARS_PER_TOKEN
+    }
+}
+
+fn counter() -> &'static dyn TokenCounter {
+    static COUNTER: OnceLock<Box<dyn TokenCounter>> = OnceLock::new();
+    COUNTER
+        .get_or_init(|| match BpeCounter::new() {
+            Some(c) => Box::new(c),
+            None => Box::new(CharEstimateCounter),
+        })
+        .as_ref()
+}
+
+static COUNT_CACHE: OnceLock<Mutex<HashMap<u64, usize>>> = OnceLock::new();
+
+/// Count `text` with `counter`, reusing a previous count for identical text.
+fn cached_count(cache: &Mutex<HashMap<u64, usize>>, counter: &dyn TokenCounter, text: &str) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    let key = hasher.finish();
+
+    if let Some(&n) = cache.lock().unwrap().get(&key) {
+        return n;
+    }
+    let n = counter.count(text);
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= MAX_CACHED_COUNTS {
+        cache.clear();
+    }
+    cache.insert(key, n);
+    n
+}
+
+/// Count the tokens in a piece of text (system prompt, tool schemas, ...).
+pub fn count_text(text: &str) -> usize {
+    let cache = COUNT_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    cached_count(cache, counter(), text)
+}
+
+/// Count the tokens in a single message's content.
+pub fn count_message(message: &Message) -> usize {
+    match &message.content {
+        Value::String(s) => count_text(s),
+        Value::Array(blocks) => blocks
+            .iter()
+            .map(|b| {
+                if let Some(text) = b.get("text").and_then(|t| t.as_str()) {
+                    count_text(text)
+                } else if let Some(thinking) = b.get("thinking").and_then(|t| t.as_str()) {
+                    count_text(thinking)
+                } else if let Some(content) = b.get("content").and_then(|c| c.as_str()) {
+                    count_text(content)
+                } else {
+                    count_text(&b.to_string())
+                }
+            })
+            .sum(),
+        other => count_text(&other.to_string()),
+    }
+}
+
+/// Count the tokens across a whole conversation history.
+pub fn count_messages(messages: &[Message]) -> usize {
+    messages.iter().map(count_message).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingCounter(AtomicUsize);
+
+    impl TokenCounter for CountingCounter {
+        fn count(&self, text: &str) -> usize {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn test_cached_count_reuses_previous_result() {
+        let cache = Mutex::new(HashMap::new());
+        let counter = CountingCounter(AtomicUsize::new(0));
+
+        assert_eq!(cached_count(&cache, &counter, "one two three"), 3);
+        assert_eq!(cached_count(&cache, &counter, "one two three"), 3);
+        assert_eq!(cached_count(&cache, &counter, "four"), 1);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_bpe_counter_counts_tokens() {
+        let counter = BpeCounter::new().expect("cl100k_base is bundled");
+        assert_eq!(counter.count(""), 0);
+        assert!(counter.count("hello world") >= 2);
+        assert!(counter.count("hello world") < "hello world".len());
+    }
+}
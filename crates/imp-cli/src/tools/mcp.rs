@@ -11,7 +11,8 @@
 //!     "github": {
 //!       "command": "npx",
 //!       "args": ["-y", "@modelcontextprotocol/server-github"],
-//!       "env": { "GITHUB_TOKEN": "${GITHUB_TOKEN}" }
+//!       "env": { "GITHUB_TOKEN": "${GITHUB_TOKEN}" },
+//!       "timeout_secs": 120
 //!     },
 //!     "api": {
 //!       "type": "http",
@@ -24,10 +25,14 @@
 
 use crate::config::imp_home;
 use crate::error::{ImpError, Result};
+use crate::redact::{self, redact};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tracing::{info, warn, debug};
 
 // ── MCP config types ─────────────────────────────────────────────────
@@ -39,6 +44,12 @@ pub struct McpJsonFile {
     pub mcp_servers: HashMap<String, McpServerConfig>,
 }
 
+/// Default timeout for MCP tool calls.
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Timeout for the initialize and tools/list handshakes, independent of the
+/// per-server tool timeout, so a hung server can't stall startup.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Configuration for an MCP server.
 /// Transport is auto-detected: `url` → HTTP/SSE, `command` → stdio.
 #[derive(Debug, Deserialize, Clone)]
@@ -56,12 +67,16 @@ pub struct McpServerConfig {
     /// Stdio transport: arguments for the command
     #[serde(default)]
     pub args: Vec<String>,
-    /// Environment variables (supports ${VAR} and ${VAR:-default} expansion)
+    /// Environment variables (supports ${VAR} and ${VAR:-default} expansion; `$${` for a literal `${`)
     #[serde(default)]
     pub env: HashMap<String, String>,
-    /// HTTP headers for HTTP/SSE transport (supports ${VAR} expansion)
+    /// HTTP headers for HTTP/SSE transport (supports ${VAR} and ${VAR:-default} expansion)
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// Timeout for tool calls, in seconds (default 30). The initialize and
+    /// tools/list handshakes always use a fixed 30s timeout.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 impl McpServerConfig {
@@ -71,6 +86,16 @@ impl McpServerConfig {
             || self.transport_type.as_deref() == Some("http")
             || self.transport_type.as_deref() == Some("sse")
     }
+
+    /// Transport name for display: "http" or "stdio".
+    pub fn transport_name(&self) -> &'static str {
+        if self.is_remote() { "http" } else { "stdio" }
+    }
+
+    /// How long a tool call may take before it's abandoned.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS))
+    }
 }
 
 /// Load MCP server configs from `~/.imp/.mcp.json`.
@@ -128,8 +153,24 @@ pub struct McpTool {
     pub input_schema: Value,
 }
 
+/// A resource (document, file, record, ...) advertised by an MCP server.
+#[derive(Debug, Deserialize)]
+pub struct McpResource {
+    pub uri: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "mimeType", default)]
+    pub mime_type: Option<String>,
+}
+
 // ── MCP Server ───────────────────────────────────────────────────────
 
+/// Reconnects allowed in a row (without a successful request in between)
+/// before a server is given up on.
+const MAX_RECONNECT_ATTEMPTS: u32 = 3;
+
 /// A running MCP server connection (either HTTP/SSE or stdio).
 pub struct McpServer {
     name: String,
@@ -139,6 +180,12 @@ pub struct McpServer {
     next_id: AtomicU64,
     /// Session ID returned by the server during initialize (Streamable HTTP).
     session_id: Option<String>,
+    /// Set when the last HTTP request was rejected because the session expired.
+    session_expired: bool,
+    /// Reconnects since the last successful request.
+    reconnect_attempts: u32,
+    /// Capabilities the server advertised in its initialize response.
+    capabilities: Value,
 }
 
 impl McpServer {
@@ -149,6 +196,9 @@ impl McpServer {
             child: None,
             next_id: AtomicU64::new(1),
             session_id: None,
+            session_expired: false,
+            reconnect_attempts: 0,
+            capabilities: Value::Null,
         }
     }
 
@@ -179,7 +229,7 @@ impl McpServer {
             params: None,
         };
 
-        let response = self.send_request(&request).await?;
+        let response = self.send_request(&request, HANDSHAKE_TIMEOUT).await?;
         let tools_value = response
             .result
             .and_then(|r| r.get("tools").cloned())
@@ -199,7 +249,8 @@ impl McpServer {
             params: Some(json!({ "name": tool_name, "arguments": arguments })),
         };
 
-        let response = self.send_request(&request).await?;
+        let timeout = self.config.request_timeout();
+        let response = self.send_request(&request, timeout).await?;
         let content = response
             .result
             .and_then(|r| r.get("content").cloned())
@@ -217,23 +268,159 @@ impl McpServer {
         Ok(content)
     }
 
-    /// Route a request to the appropriate transport.
-    async fn send_request(&mut self, request: &JsonRpcRequest) -> Result<JsonRpcResponse> {
-        let response = if self.config.is_remote() {
-            self.http_send(request).await?
-        } else {
-            self.stdio_send(request).await?
+    /// Whether the server advertised the `resources` capability.
+    pub fn supports_resources(&self) -> bool {
+        self.capabilities.get("resources").is_some()
+    }
+
+    /// List the resources the server exposes, following pagination.
+    pub async fn list_resources(&mut self) -> Result<Vec<McpResource>> {
+        const MAX_PAGES: usize = 20;
+
+        let mut resources = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_PAGES {
+            let request = JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                id: self.next_request_id(),
+                method: "resources/list".to_string(),
+                params: cursor.as_ref().map(|c| json!({ "cursor": c })),
+            };
+            let timeout = self.config.request_timeout();
+            let result = self.send_request(&request, timeout).await?.result.unwrap_or(Value::Null);
+
+            let page: Vec<McpResource> = result
+                .get("resources")
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| ImpError::Tool(format!("MCP '{}': failed to parse resources: {}", self.name, e)))?
+                .unwrap_or_default();
+            resources.extend(page);
+
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(String::from);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Read a resource's contents as text. Binary contents are described
+    /// rather than returned.
+    pub async fn read_resource(&mut self, uri: &str) -> Result<String> {
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: self.next_request_id(),
+            method: "resources/read".to_string(),
+            params: Some(json!({ "uri": uri })),
+        };
+        let timeout = self.config.request_timeout();
+        let response = self.send_request(&request, timeout).await?;
+
+        let contents = response
+            .result
+            .and_then(|r| r.get("contents").cloned())
+            .and_then(|c| c.as_array().cloned())
+            .unwrap_or_default();
+
+        let parts: Vec<String> = contents
+            .iter()
+            .map(|item| match item.get("text").and_then(|t| t.as_str()) {
+                Some(text) => text.to_string(),
+                None => format!(
+                    "[binary content: {}, {} bytes base64]",
+                    item.get("mimeType").and_then(|m| m.as_str()).unwrap_or("unknown type"),
+                    item.get("blob").and_then(|b| b.as_str()).map_or(0, str::len)
+                ),
+            })
+            .collect();
+
+        if parts.is_empty() {
+            return Err(ImpError::Tool(format!("MCP '{}': resource '{}' has no contents", self.name, uri)));
+        }
+        Ok(parts.join("\n\n"))
+    }
+
+    /// Send a request, transparently reconnecting (once per request) if the
+    /// stdio process has died or the HTTP session has expired.
+    async fn send_request(&mut self, request: &JsonRpcRequest, timeout: Duration) -> Result<JsonRpcResponse> {
+        // A stdio server that died since the last call is restarted up front
+        if !self.config.is_remote() && self.stdio_exited() {
+            warn!(server = %self.name, "MCP server process has exited, restarting");
+            self.reconnect().await?;
+        }
+
+        let response = match self.transport_send(request, timeout).await {
+            Ok(response) => response,
+            Err(e) if self.connection_lost() => {
+                warn!(server = %self.name, error = %e, "MCP connection lost, reconnecting");
+                self.reconnect().await?;
+                self.transport_send(request, timeout).await?
+            }
+            Err(e) => return Err(e),
         };
+        self.reconnect_attempts = 0;
 
         if let Some(ref err) = response.error {
             return Err(ImpError::Tool(format!(
                 "MCP '{}' error: {}",
-                self.name, err.message
+                self.name, redact(&err.message)
             )));
         }
         Ok(response)
     }
 
+    /// Route a request to the appropriate transport.
+    async fn transport_send(&mut self, request: &JsonRpcRequest, timeout: Duration) -> Result<JsonRpcResponse> {
+        if self.config.is_remote() {
+            self.http_send(request, timeout).await
+        } else {
+            self.stdio_send(request, timeout).await
+        }
+    }
+
+    /// Whether a failed request was caused by a dead process or expired session,
+    /// rather than by the request itself.
+    fn connection_lost(&mut self) -> bool {
+        if self.config.is_remote() {
+            self.session_expired
+        } else {
+            self.stdio_exited()
+        }
+    }
+
+    /// Whether the stdio child process is gone.
+    fn stdio_exited(&mut self) -> bool {
+        match self.child.as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        }
+    }
+
+    /// Tear down the connection and redo the initialize handshake: respawn
+    /// the process for stdio, start a new session for HTTP.
+    async fn reconnect(&mut self) -> Result<()> {
+        if self.reconnect_attempts >= MAX_RECONNECT_ATTEMPTS {
+            return Err(ImpError::Tool(format!(
+                "MCP '{}': server keeps disconnecting, gave up after {} reconnect attempts. Check the server and restart imp.",
+                self.name, MAX_RECONNECT_ATTEMPTS
+            )));
+        }
+        self.reconnect_attempts += 1;
+        self.session_expired = false;
+
+        if self.config.is_remote() {
+            self.session_id = None;
+        } else if let Some(mut child) = self.child.take() {
+            let _ = child.start_kill();
+        }
+
+        self.start().await?;
+        info!(server = %self.name, attempt = self.reconnect_attempts, "MCP server reconnected");
+        Ok(())
+    }
+
     // ── SSE transport ────────────────────────────────────────────────
 
     async fn sse_initialize(&mut self) -> Result<()> {
@@ -248,7 +435,8 @@ impl McpServer {
                 "clientInfo": { "name": "imp", "version": "0.1.0" }
             })),
         };
-        self.http_send(&request).await?;
+        let response = self.http_send(&request, HANDSHAKE_TIMEOUT).await?;
+        self.capabilities = initialize_capabilities(response);
 
         // Send notifications/initialized (required by MCP spec)
         self.http_send_notification("notifications/initialized", json!({})).await?;
@@ -261,7 +449,7 @@ impl McpServer {
             ImpError::Tool(format!("MCP '{}': missing url for HTTP transport", self.name))
         })?;
 
-        let client = reqwest::Client::builder()
+        let client = crate::http::client_builder()?
             .timeout(std::time::Duration::from_secs(10))
             .build()
             .map_err(|e| ImpError::Tool(format!("MCP '{}': HTTP client error: {}", self.name, e)))?;
@@ -315,13 +503,14 @@ impl McpServer {
         Ok(())
     }
 
-    async fn http_send(&mut self, request: &JsonRpcRequest) -> Result<JsonRpcResponse> {
+    async fn http_send(&mut self, request: &JsonRpcRequest, timeout: Duration) -> Result<JsonRpcResponse> {
+        self.session_expired = false;
         let url = self.config.url.as_ref().ok_or_else(|| {
             ImpError::Tool(format!("MCP '{}': missing url for HTTP transport", self.name))
         })?;
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+        let client = crate::http::client_builder()?
+            .timeout(timeout)
             .connect_timeout(std::time::Duration::from_secs(10))
             .build()
             .map_err(|e| ImpError::Tool(format!("MCP '{}': HTTP client error: {}", self.name, e)))?;
@@ -348,20 +537,28 @@ impl McpServer {
             .await
             .map_err(|e| {
                 if e.is_timeout() {
-                    ImpError::Tool(format!("MCP '{}': connection timed out (10s connect / 30s total)", self.name))
+                    ImpError::Tool(format!(
+                        "MCP '{}': request timed out (10s connect / {}s total; set timeout_secs in .mcp.json to allow longer)",
+                        self.name,
+                        timeout.as_secs()
+                    ))
                 } else if e.is_connect() {
-                    ImpError::Tool(format!("MCP '{}': connection refused — is the server running? ({})", self.name, e))
+                    ImpError::Tool(format!("MCP '{}': connection refused — is the server running? ({})", self.name, redact(&e.to_string())))
                 } else {
-                    ImpError::Tool(format!("MCP '{}': HTTP error: {}", self.name, e))
+                    ImpError::Tool(format!("MCP '{}': HTTP error: {}", self.name, redact(&e.to_string())))
                 }
             })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
+            // Servers answer 404 (or 400 "invalid session") once our Mcp-Session-Id is stale
+            self.session_expired = self.session_id.is_some()
+                && (status == reqwest::StatusCode::NOT_FOUND
+                    || (status == reqwest::StatusCode::BAD_REQUEST && body.to_lowercase().contains("session")));
             return Err(ImpError::Tool(format!(
                 "MCP '{}': HTTP {}: {}",
-                self.name, status, body
+                self.name, status, redact(&body)
             )));
         }
 
@@ -394,7 +591,7 @@ impl McpServer {
         Err(ImpError::Tool(format!(
             "MCP '{}': could not parse response: {}",
             self.name,
-            &text[..text.len().min(200)]
+            redact(&text[..text.len().min(200)])
         )))
     }
 
@@ -436,14 +633,15 @@ impl McpServer {
                 "clientInfo": { "name": "imp", "version": "0.1.0" }
             })),
         };
-        self.stdio_send(&request).await?;
+        let response = self.stdio_send(&request, HANDSHAKE_TIMEOUT).await?;
+        self.capabilities = initialize_capabilities(response);
 
         // Send notifications/initialized (required by MCP spec)
         self.stdio_send_notification("notifications/initialized", json!({})).await?;
         Ok(())
     }
 
-    async fn stdio_send(&mut self, request: &JsonRpcRequest) -> Result<JsonRpcResponse> {
+    async fn stdio_send(&mut self, request: &JsonRpcRequest, timeout: Duration) -> Result<JsonRpcResponse> {
         use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
         let child = self.child.as_mut().ok_or_else(|| {
@@ -473,13 +671,16 @@ impl McpServer {
         let mut reader = BufReader::new(stdout);
         let mut line = String::new();
 
-        match tokio::time::timeout(
-            std::time::Duration::from_secs(30),
-            reader.read_line(&mut line),
-        ).await {
+        match tokio::time::timeout(timeout, reader.read_line(&mut line)).await {
             Ok(Ok(_)) => {}
             Ok(Err(e)) => return Err(ImpError::Tool(format!("MCP '{}': read error: {}", self.name, e))),
-            Err(_) => return Err(ImpError::Tool(format!("MCP '{}': timed out waiting for response (30s)", self.name))),
+            Err(_) => {
+                return Err(ImpError::Tool(format!(
+                    "MCP '{}': timed out waiting for response ({}s; set timeout_secs in .mcp.json to allow longer)",
+                    self.name,
+                    timeout.as_secs()
+                )))
+            }
         }
 
         if line.trim().is_empty() {
@@ -489,7 +690,7 @@ impl McpServer {
         serde_json::from_str(&line)
             .map_err(|e| ImpError::Tool(format!(
                 "MCP '{}': failed to parse response: {} — raw: {}",
-                self.name, e, &line[..line.len().min(200)]
+                self.name, e, redact(&line[..line.len().min(200)])
             )))
     }
 }
@@ -511,16 +712,115 @@ struct McpInitResult {
     tools: Vec<McpTool>,
 }
 
+/// Delay before retrying a server that failed to connect; doubles with each
+/// failed attempt up to `RETRY_MAX_DELAY`.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(10);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// How long to wait before the next attempt after `attempts` failures.
+fn retry_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(16);
+    (RETRY_BASE_DELAY * 2u32.pow(doublings)).min(RETRY_MAX_DELAY)
+}
+
+/// A server that failed to connect, kept so `status()` can report it and
+/// `retry_failed()` can try again.
+struct McpInitFailure {
+    name: String,
+    config: McpServerConfig,
+    error: String,
+    attempts: u32,
+    /// When to try again; `None` if retrying can't help (invalid config).
+    next_retry: Option<tokio::time::Instant>,
+}
+
+/// A background init task and the server it is connecting.
+struct PendingInit {
+    name: String,
+    config: McpServerConfig,
+    /// Attempts so far, including this one.
+    attempts: u32,
+    handle: tokio::task::JoinHandle<std::result::Result<McpInitResult, String>>,
+}
+
+/// Connection state of a configured MCP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpConnectionState {
+    Connected,
+    /// A retry after a failed connection is in progress.
+    Connecting,
+    /// The server process has exited since it connected (stdio only).
+    Disconnected,
+    Failed,
+}
+
+impl std::fmt::Display for McpConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Connected => "connected",
+            Self::Connecting => "connecting",
+            Self::Disconnected => "disconnected",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+/// One row of `McpRegistry::status()`.
+#[derive(Debug, Clone)]
+pub struct McpServerStatus {
+    pub name: String,
+    pub transport: &'static str,
+    pub state: McpConnectionState,
+    pub enabled: bool,
+    pub tool_count: usize,
+    /// Why the server last failed to connect, if it did.
+    pub error: Option<String>,
+    /// Failed connection attempts (0 once connected).
+    pub attempts: u32,
+    /// Time until the next reconnection attempt, for failed servers.
+    pub next_retry: Option<Duration>,
+}
+
+/// Separates the server name from the tool name in namespaced MCP tool names.
+const TOOL_NAMESPACE_SEPARATOR: &str = "__";
+
+/// The API's limit on tool name length.
+const MAX_TOOL_NAME_CHARS: usize = 64;
+
+/// The name an MCP tool is advertised under: `<server>__<tool>`, so tools
+/// with the same name on different servers don't collide. Characters the
+/// API doesn't allow in tool names (`[a-zA-Z0-9_-]`) are replaced, and names
+/// over `MAX_TOOL_NAME_CHARS` are cut short with a hash of the full name so
+/// they stay distinct.
+fn namespaced_tool_name(server: &str, tool: &str) -> String {
+    let name: String = format!("{}{}{}", server, TOOL_NAMESPACE_SEPARATOR, tool)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    if name.len() <= MAX_TOOL_NAME_CHARS {
+        return name;
+    }
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let suffix = format!("_{:08x}", hasher.finish() as u32);
+    format!("{}{}", &name[..MAX_TOOL_NAME_CHARS - suffix.len()], suffix)
+}
+
 /// Manages all MCP server connections and routes tool calls.
 /// Supports background initialization — servers connect in parallel.
 /// Tools are lazy-loaded: servers connect at startup but their tools
 /// are only exposed to the LLM after explicitly enabled via `enable_server()`.
 pub struct McpRegistry {
     servers: Vec<McpServer>,
-    /// Maps tool name → index into servers vec (only for enabled servers)
-    tool_routing: HashMap<String, usize>,
+    /// Maps namespaced tool name → (index into servers vec, the tool's name
+    /// on that server). Only for enabled servers.
+    tool_routing: HashMap<String, (usize, String)>,
     /// Background init tasks that haven't been resolved yet
-    pending: Vec<tokio::task::JoinHandle<Option<McpInitResult>>>,
+    pending: Vec<PendingInit>,
+    /// Retries of failed servers, collected without blocking
+    retrying: Vec<PendingInit>,
+    /// Servers that failed to connect
+    failed: Vec<McpInitFailure>,
     /// All discovered tools per server (server_name → tools)
     available_tools: HashMap<String, Vec<McpTool>>,
     /// Set of enabled server names
@@ -535,6 +835,8 @@ impl McpRegistry {
             servers: Vec::new(),
             tool_routing: HashMap::new(),
             pending: Vec::new(),
+            retrying: Vec::new(),
+            failed: Vec::new(),
             available_tools: HashMap::new(),
             enabled_servers: std::collections::HashSet::new(),
             server_indices: HashMap::new(),
@@ -551,75 +853,156 @@ impl McpRegistry {
         for (name, config) in mcp_configs {
             if config.url.is_none() && config.command.is_none() {
                 warn!(server = %name, "MCP server needs either 'url' or 'command', skipping");
+                self.failed.push(McpInitFailure {
+                    name: name.clone(),
+                    config: config.clone(),
+                    error: "needs either 'url' or 'command'".to_string(),
+                    attempts: 1,
+                    next_retry: None,
+                });
                 continue;
             }
 
-            let name = name.clone();
-            let config = config.clone();
-
-            let handle = tokio::spawn(async move {
-                let mut server = McpServer::new(name.clone(), config);
-
-                if let Err(e) = server.start().await {
-                    warn!(server = %name, error = %e, "MCP server initialize failed");
-                    return None;
-                }
-
-                match server.list_tools().await {
-                    Ok(tools) => {
-                        let tool_names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
-                        let tool_count = tools.len();
-                        info!(server = %name, tool_count, tools = %if tool_names.len() <= 5 { tool_names.join(", ") }
-                            else { format!("{}, ... +{} more", tool_names[..4].join(", "), tool_names.len() - 4) },
-                            "MCP server ready"
-                        );
-                        Some(McpInitResult { name, server, tools })
-                    }
-                    Err(e) => {
-                        warn!(server = %name, error = %e, "MCP list tools failed");
-                        None
-                    }
-                }
-            });
-
-            self.pending.push(handle);
+            self.pending.push(spawn_init(name.clone(), config.clone(), 1));
         }
     }
 
     /// Resolve any pending background MCP init tasks.
-    /// Waits up to `timeout` for all pending servers to finish.
+    /// Waits up to 30s in total for all pending servers to finish; servers
+    /// that fail or are still connecting by then are recorded as failed.
+    /// Failed servers are retried in the background once their backoff has
+    /// elapsed (see `retry_failed()`); those retries are never waited on.
     /// Called automatically before the first LLM call via `get_tool_schemas()`.
     pub async fn resolve_pending(&mut self) {
+        self.collect_retries().await;
+        self.retry_failed();
+
         if self.pending.is_empty() {
             return;
         }
 
-        let handles = std::mem::take(&mut self.pending);
+        let pending = std::mem::take(&mut self.pending);
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(30);
 
-        // Wait for all pending tasks with a timeout
-        let results = match tokio::time::timeout(
-            std::time::Duration::from_secs(30),
-            futures::future::join_all(handles),
-        ).await {
-            Ok(results) => results,
-            Err(_) => {
-                warn!("MCP: timed out waiting for servers (30s)");
-                return;
-            }
-        };
+        for PendingInit { name, config, attempts, mut handle } in pending {
+            let error = match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(Ok(init))) => {
+                    self.add_server(init);
+                    continue;
+                }
+                Ok(Ok(Err(e))) => e,
+                Ok(Err(e)) => format!("init task failed: {}", e),
+                Err(_) => {
+                    handle.abort();
+                    warn!(server = %name, "MCP: timed out waiting for server (30s)");
+                    "timed out connecting (30s)".to_string()
+                }
+            };
+            self.record_failure(name, config, attempts, error);
+        }
+    }
 
-        for result in results {
-            if let Ok(Some(init)) = result {
-                let server_idx = self.servers.len();
-                let server_name = init.name.clone();
-                
-                // Store tools for later enabling (lazy loading)
-                self.available_tools.insert(server_name.clone(), init.tools);
-                self.server_indices.insert(server_name, server_idx);
-                self.servers.push(init.server);
+    /// Start another connection attempt for each failed server whose
+    /// backoff has elapsed.
+    pub fn retry_failed(&mut self) {
+        let now = tokio::time::Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.failed)
+            .into_iter()
+            .partition(|f| f.next_retry.is_some_and(|at| at <= now));
+        self.failed = waiting;
+
+        for failure in due {
+            info!(server = %failure.name, attempt = failure.attempts + 1, "Retrying MCP server");
+            self.retrying.push(spawn_init(failure.name, failure.config, failure.attempts + 1));
+        }
+    }
+
+    /// Pick up retries that have finished, leaving the rest running.
+    async fn collect_retries(&mut self) {
+        let (finished, running): (Vec<_>, Vec<_>) = std::mem::take(&mut self.retrying)
+            .into_iter()
+            .partition(|p| p.handle.is_finished());
+        self.retrying = running;
+
+        for PendingInit { name, config, attempts, handle } in finished {
+            match handle.await {
+                Ok(Ok(init)) => {
+                    info!(server = %name, attempts, "MCP server connected after retry");
+                    self.add_server(init);
+                }
+                Ok(Err(e)) => self.record_failure(name, config, attempts, e),
+                Err(e) => self.record_failure(name, config, attempts, format!("init task failed: {}", e)),
             }
         }
     }
+
+    fn record_failure(&mut self, name: String, config: McpServerConfig, attempts: u32, error: String) {
+        let delay = retry_delay(attempts);
+        debug!(server = %name, attempts, retry_in_secs = delay.as_secs(), "MCP server will be retried");
+        self.failed.push(McpInitFailure {
+            name,
+            config,
+            error,
+            attempts,
+            next_retry: Some(tokio::time::Instant::now() + delay),
+        });
+    }
+
+    /// Per-server connection state, tool counts and init errors, sorted by name.
+    pub async fn status(&mut self) -> Vec<McpServerStatus> {
+        self.resolve_pending().await;
+
+        let mut statuses: Vec<McpServerStatus> = Vec::new();
+        for server in &mut self.servers {
+            let state = if !server.config.is_remote() && server.stdio_exited() {
+                McpConnectionState::Disconnected
+            } else {
+                McpConnectionState::Connected
+            };
+            statuses.push(McpServerStatus {
+                name: server.name.clone(),
+                transport: server.config.transport_name(),
+                state,
+                enabled: self.enabled_servers.contains(&server.name),
+                tool_count: self.available_tools.get(&server.name).map_or(0, Vec::len),
+                error: None,
+                attempts: 0,
+                next_retry: None,
+            });
+        }
+        let now = tokio::time::Instant::now();
+        statuses.extend(self.failed.iter().map(|f| McpServerStatus {
+            name: f.name.clone(),
+            transport: f.config.transport_name(),
+            state: McpConnectionState::Failed,
+            enabled: false,
+            tool_count: 0,
+            error: Some(f.error.clone()),
+            attempts: f.attempts,
+            next_retry: f.next_retry.map(|at| at.saturating_duration_since(now)),
+        }));
+        statuses.extend(self.retrying.iter().map(|p| McpServerStatus {
+            name: p.name.clone(),
+            transport: p.config.transport_name(),
+            state: McpConnectionState::Connecting,
+            enabled: false,
+            tool_count: 0,
+            error: None,
+            attempts: p.attempts - 1,
+            next_retry: None,
+        }));
+
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Store a connected server's tools for later enabling (lazy loading).
+    fn add_server(&mut self, init: McpInitResult) {
+        let server_idx = self.servers.len();
+        self.available_tools.insert(init.name.clone(), init.tools);
+        self.server_indices.insert(init.name, server_idx);
+        self.servers.push(init.server);
+    }
     
     /// List all available MCP servers and their tools (for discovery).
     /// Returns (server_name, tool_count, [(tool_name, description, params)])
@@ -686,9 +1069,12 @@ impl McpRegistry {
         let tools = self.available_tools.get(server_name)
             .ok_or_else(|| ImpError::Tool(format!("No tools found for server '{}'", server_name)))?;
         
-        // Add tools to routing
+        // Add tools to routing under their namespaced names
         for tool in tools {
-            self.tool_routing.insert(tool.name.clone(), *server_idx);
+            self.tool_routing.insert(
+                namespaced_tool_name(server_name, &tool.name),
+                (*server_idx, tool.name.clone()),
+            );
         }
         
         self.enabled_servers.insert(server_name.to_string());
@@ -722,7 +1108,12 @@ impl McpRegistry {
                 tool.description.clone()
             };
             
-            summary.push_str(&format!("- {}({}) — {}\n", tool.name, param_strs.join(", "), desc));
+            summary.push_str(&format!(
+                "- {}({}) — {}\n",
+                namespaced_tool_name(server_name, &tool.name),
+                param_strs.join(", "),
+                desc
+            ));
         }
         
         Ok(summary)
@@ -745,7 +1136,7 @@ impl McpRegistry {
             if let Some(tools) = self.available_tools.get(server_name) {
                 for tool in tools {
                     schemas.push(json!({
-                        "name": tool.name,
+                        "name": namespaced_tool_name(server_name, &tool.name),
                         "description": tool.description,
                         "input_schema": tool.input_schema
                     }));
@@ -768,34 +1159,269 @@ impl McpRegistry {
     pub async fn call_tool(&mut self, tool_name: &str, arguments: &Value) -> Result<String> {
         self.resolve_pending().await;
 
-        let server_idx = self
-            .tool_routing
-            .get(tool_name)
-            .copied()
+        let (server_idx, server_tool) = self
+            .route(tool_name)
+            .map(|(idx, name)| (idx, name.to_string()))
             .ok_or_else(|| ImpError::Tool(format!("MCP tool '{}' not found", tool_name)))?;
 
-        self.servers[server_idx].call_tool(tool_name, arguments).await
+        self.servers[server_idx].call_tool(&server_tool, arguments).await
     }
 
     /// Check if a tool name belongs to an MCP server.
     pub fn has_tool(&self, tool_name: &str) -> bool {
-        self.tool_routing.contains_key(tool_name)
+        self.route(tool_name).is_some()
+    }
+
+    /// A connected server by name.
+    async fn server_mut(&mut self, server_name: &str) -> Result<&mut McpServer> {
+        self.resolve_pending().await;
+        let idx = *self.server_indices.get(server_name).ok_or_else(|| {
+            ImpError::Tool(format!(
+                "MCP server '{}' not found. Available: {}",
+                server_name,
+                self.server_indices.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+        Ok(&mut self.servers[idx])
+    }
+
+    /// Whether a server exposes resources at all.
+    pub async fn supports_resources(&mut self, server_name: &str) -> Result<bool> {
+        Ok(self.server_mut(server_name).await?.supports_resources())
+    }
+
+    /// List a server's resources.
+    pub async fn list_resources(&mut self, server_name: &str) -> Result<Vec<McpResource>> {
+        self.server_mut(server_name).await?.list_resources().await
+    }
+
+    /// Read a resource from a server.
+    pub async fn read_resource(&mut self, server_name: &str, uri: &str) -> Result<String> {
+        self.server_mut(server_name).await?.read_resource(uri).await
+    }
+
+    /// Find which server lists a resource with this URI.
+    pub async fn find_resource_server(&mut self, uri: &str) -> Result<String> {
+        self.resolve_pending().await;
+        let mut names: Vec<String> = self.server_indices.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            if !self.supports_resources(&name).await? {
+                continue;
+            }
+            match self.list_resources(&name).await {
+                Ok(resources) if resources.iter().any(|r| r.uri == uri) => return Ok(name),
+                Ok(_) => {}
+                Err(e) => warn!(server = %name, error = %e, "MCP list resources failed"),
+            }
+        }
+        Err(ImpError::Tool(format!(
+            "No connected MCP server lists resource '{}'. Pass --server to read it from a specific server.",
+            uri
+        )))
+    }
+
+    /// Find the server for a tool and the tool's name on that server. Takes
+    /// the namespaced name, or (for compatibility) a bare tool name as long
+    /// as only one enabled server has a tool by that name.
+    fn route(&self, tool_name: &str) -> Option<(usize, &str)> {
+        if let Some((idx, name)) = self.tool_routing.get(tool_name) {
+            return Some((*idx, name.as_str()));
+        }
+        let mut matches = self.tool_routing.values().filter(|(_, name)| name == tool_name);
+        match (matches.next(), matches.next()) {
+            (Some((idx, name)), None) => Some((*idx, name.as_str())),
+            _ => None,
+        }
     }
 }
 
 // ── Helpers ──────────────────────────────────────────────────────────
 
-/// Expand `${VAR}` patterns in a string from environment variables.
-fn expand_env_var(value: &str) -> String {
-    let mut result = value.to_string();
-    while let Some(start) = result.find("${") {
-        if let Some(end) = result[start..].find('}') {
-            let var_name = &result[start + 2..start + end];
-            let env_value = std::env::var(var_name).unwrap_or_default();
-            result.replace_range(start..start + end + 1, &env_value);
+/// Connect to a server and discover its tools in a background task.
+fn spawn_init(name: String, config: McpServerConfig, attempts: u32) -> PendingInit {
+    let task_config = config.clone();
+    let server_name = name.clone();
+    let handle = tokio::spawn(async move {
+        let mut server = McpServer::new(name.clone(), task_config);
+
+        if let Err(e) = server.start().await {
+            warn!(server = %name, error = %e, "MCP server initialize failed");
+            return Err(format!("initialize failed: {}", e));
+        }
+
+        match server.list_tools().await {
+            Ok(tools) => {
+                let tool_names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+                let tool_count = tools.len();
+                info!(server = %name, tool_count, tools = %if tool_names.len() <= 5 { tool_names.join(", ") }
+                    else { format!("{}, ... +{} more", tool_names[..4].join(", "), tool_names.len() - 4) },
+                    "MCP server ready"
+                );
+                Ok(McpInitResult { name, server, tools })
+            }
+            Err(e) => {
+                warn!(server = %name, error = %e, "MCP list tools failed");
+                Err(format!("tools/list failed: {}", e))
+            }
+        }
+    });
+
+    PendingInit { name: server_name, config, attempts, handle }
+}
+
+/// The `capabilities` object from an initialize response.
+fn initialize_capabilities(response: JsonRpcResponse) -> Value {
+    response
+        .result
+        .and_then(|r| r.get("capabilities").cloned())
+        .unwrap_or(Value::Null)
+}
+
+/// Expand `${VAR}` and `${VAR:-default}` patterns in a string from
+/// environment variables. `$${` produces a literal `${`.
+pub(crate) fn expand_env_var(value: &str) -> String {
+    expand_with(value, |name| {
+        let env_value = std::env::var(name).ok()?;
+        // Expanded values are usually tokens: keep them out of logs and errors
+        redact::register_secret(&env_value);
+        Some(env_value)
+    })
+}
+
+/// `expand_env_var` with the variable lookup supplied by the caller.
+/// As in the shell, the default applies when the variable is unset or empty;
+/// an unset variable with no default expands to an empty string.
+fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        if let Some(after) = tail.strip_prefix("$${") {
+            result.push_str("${");
+            rest = after;
+        } else if let (Some(body), Some(end)) = (tail.strip_prefix("${"), tail.find('}')) {
+            let expr = &body[..end - 2];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expr, None),
+            };
+            let expanded = lookup(name)
+                .filter(|v| !v.is_empty() || default.is_none())
+                .or_else(|| default.map(String::from))
+                .unwrap_or_default();
+            result.push_str(&expanded);
+            rest = &tail[end + 1..];
         } else {
-            break;
+            // A lone `$` or an unterminated `${` is kept as-is
+            result.push('$');
+            rest = &tail[1..];
         }
     }
+    result.push_str(rest);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str) -> McpTool {
+        McpTool { name: name.to_string(), description: String::new(), input_schema: json!({}) }
+    }
+
+    fn server_with_tools(name: &str, tools: &[&str]) -> McpInitResult {
+        let config = McpServerConfig {
+            transport_type: None,
+            url: None,
+            command: Some("true".to_string()),
+            args: Vec::new(),
+            env: HashMap::new(),
+            headers: HashMap::new(),
+            timeout_secs: None,
+        };
+        McpInitResult {
+            name: name.to_string(),
+            server: McpServer::new(name.to_string(), config),
+            tools: tools.iter().map(|t| tool(t)).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_with_same_name_are_namespaced() {
+        let mut registry = McpRegistry::new();
+        registry.add_server(server_with_tools("github", &["search", "create_issue"]));
+        registry.add_server(server_with_tools("docs", &["search"]));
+        registry.enable_server("github").await.unwrap();
+        registry.enable_server("docs").await.unwrap();
+
+        let names: Vec<String> = registry
+            .get_tool_schemas()
+            .await
+            .iter()
+            .map(|s| s["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["docs__search", "github__create_issue", "github__search"]);
+
+        let github = registry.server_indices["github"];
+        let docs = registry.server_indices["docs"];
+        assert_eq!(registry.route("github__search"), Some((github, "search")));
+        assert_eq!(registry.route("docs__search"), Some((docs, "search")));
+        // Bare names resolve only when unambiguous
+        assert_eq!(registry.route("create_issue"), Some((github, "create_issue")));
+        assert_eq!(registry.route("search"), None);
+    }
+
+    #[test]
+    fn test_namespaced_tool_name_fits_the_api() {
+        assert_eq!(namespaced_tool_name("my server", "get.page"), "my_server__get_page");
+
+        let long = namespaced_tool_name("github", &"very_long_tool_name_".repeat(5));
+        assert_eq!(long.len(), MAX_TOOL_NAME_CHARS);
+        assert!(long.starts_with("github__very_long_tool_name_"));
+        assert_ne!(long, namespaced_tool_name("github", &"very_long_tool_name_".repeat(6)));
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "TOKEN" => Some("abc123".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_set_var() {
+        assert_eq!(expand_with("Bearer ${TOKEN}", lookup), "Bearer abc123");
+        assert_eq!(expand_with("${TOKEN:-fallback}", lookup), "abc123");
+    }
+
+    #[test]
+    fn test_expand_unset_with_default() {
+        assert_eq!(expand_with("localhost:${PORT:-8080}", lookup), "localhost:8080");
+        assert_eq!(expand_with("${EMPTY:-x}", lookup), "x");
+        assert_eq!(expand_with("${GITHUB_TOKEN:-}", lookup), "");
+    }
+
+    #[test]
+    fn test_expand_unset_without_default() {
+        assert_eq!(expand_with("a${MISSING}b", lookup), "ab");
+    }
+
+    #[test]
+    fn test_expand_escape_and_literals() {
+        assert_eq!(expand_with("$${TOKEN}", lookup), "${TOKEN}");
+        assert_eq!(expand_with("cost $5 ${TOKEN", lookup), "cost $5 ${TOKEN");
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_to_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(10));
+        assert_eq!(retry_delay(2), Duration::from_secs(20));
+        assert_eq!(retry_delay(4), Duration::from_secs(80));
+        assert_eq!(retry_delay(6), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+    }
+}
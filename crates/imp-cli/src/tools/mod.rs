@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{warn, debug};
 
 pub mod builtin;
@@ -30,7 +30,7 @@ pub struct ToolMeta {
     pub parameters: HashMap<String, ParameterDef>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct ParameterDef {
     #[serde(rename = "type")]
     pub param_type: String,
@@ -38,6 +38,14 @@ pub struct ParameterDef {
     pub required: bool,
     pub default: Option<Value>,
     pub description: Option<String>,
+    /// Allowed values (`enum = ["md", "json"]` in tool TOML).
+    #[serde(default, rename = "enum")]
+    pub enum_values: Vec<Value>,
+    /// Inclusive bounds for `integer` and `number` parameters.
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    /// JSON Schema for the elements of an `array` parameter.
+    pub items: Option<Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +53,12 @@ pub struct ToolHandler {
     pub kind: String,
     pub command: Option<String>,
     pub script: Option<String>,
+    /// Shell tools: seconds before the command is killed (default 300).
+    pub timeout_secs: Option<u64>,
+    /// Shell tools: extra environment variables for the command. Values
+    /// support `${VAR}` and `${VAR:-default}` expansion, as in MCP config.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -61,9 +75,44 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+impl ToolResult {
+    /// Convert to the Anthropic `tool_result` block. Normally an error replaces
+    /// the content, but results that carry both (e.g. a non-zero `exec`) keep
+    /// their content so the model sees the full output.
+    pub fn into_anthropic(self) -> crate::client::ToolResult {
+        let is_error = self.error.is_some().then_some(true);
+        let content = match self.error {
+            Some(error) if self.content.is_empty() => error,
+            _ => self.content,
+        };
+        crate::client::ToolResult {
+            tool_use_id: self.tool_use_id,
+            content,
+            is_error,
+        }
+    }
+}
+
 pub struct ToolRegistry {
     tools: HashMap<String, ToolDefinition>,
     mcp_registry: McpRegistry,
+    /// Working directory for shell commands when the call doesn't give one.
+    default_cwd: Option<PathBuf>,
+    /// Shell commands may not run outside this directory (`[tools] sandbox_root`).
+    sandbox_root: Option<PathBuf>,
+    /// Most files a `file_read_glob` call may read (`[tools] max_glob_files`).
+    max_glob_files: usize,
+    /// Tool output is clamped to this many bytes (`[tools] max_output_bytes`).
+    max_output_bytes: usize,
+    /// Where the full text of clamped output is saved; created on first use
+    /// and removed when the registry is dropped.
+    output_dir: Option<PathBuf>,
+    /// Which command strings `exec` may run (`[tools.exec]`).
+    exec_policy: builtin::ExecPolicy,
+    /// Simulate writes, edits and mutating commands instead of running them.
+    read_only: bool,
+    /// Offer the knowledge graph tools (`[knowledge] enabled`).
+    knowledge_enabled: bool,
 }
 
 impl ToolRegistry {
@@ -71,9 +120,67 @@ impl ToolRegistry {
         Self {
             tools: HashMap::new(),
             mcp_registry: McpRegistry::new(),
+            default_cwd: None,
+            sandbox_root: None,
+            max_glob_files: builtin::DEFAULT_MAX_GLOB_FILES,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            output_dir: None,
+            exec_policy: builtin::ExecPolicy::default(),
+            read_only: false,
+            knowledge_enabled: true,
         }
     }
 
+    /// Set the default working directory for `exec` and shell tools.
+    pub fn set_default_cwd(&mut self, cwd: Option<PathBuf>) {
+        self.default_cwd = cwd;
+    }
+
+    /// Restrict shell working directories to `root`.
+    pub fn set_sandbox_root(&mut self, root: Option<PathBuf>) {
+        self.sandbox_root = root;
+    }
+
+    /// Limit how many files one `file_read_glob` call may read.
+    pub fn set_max_glob_files(&mut self, max: usize) {
+        self.max_glob_files = max;
+    }
+
+    /// Clamp every tool result to `max` bytes (0 = no limit).
+    pub fn set_max_output_bytes(&mut self, max: usize) {
+        self.max_output_bytes = max;
+    }
+
+    /// Restrict which command strings `exec` may run.
+    pub fn set_exec_policy(&mut self, policy: builtin::ExecPolicy) {
+        self.exec_policy = policy;
+    }
+
+    /// Turn read-only mode on or off: `file_write`, `file_edit`, shell tools
+    /// and `exec` commands that aren't known to be read-only get a simulated
+    /// result instead of running.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Offer `queue_knowledge`, `store_knowledge`, `search_knowledge` and
+    /// `add_alias` to the model. Must be set before the builtins are loaded.
+    pub fn set_knowledge_enabled(&mut self, enabled: bool) {
+        self.knowledge_enabled = enabled;
+    }
+
+    fn resolve_cwd(&self, requested: Option<&str>) -> Result<Option<PathBuf>> {
+        builtin::resolve_cwd(
+            requested,
+            self.default_cwd.as_deref(),
+            self.sandbox_root.as_deref(),
+        )
+    }
+
     pub async fn load_from_directory<P: AsRef<Path>>(
         &mut self,
         tools_dir: P,
@@ -136,18 +243,25 @@ impl ToolRegistry {
         let mut builtins = vec![
             self.create_exec_tool(),
             self.create_file_read_tool(),
+            self.create_file_read_glob_tool(),
             self.create_file_write_tool(),
             self.create_file_edit_tool(),
             self.create_search_code_tool(),
             self.create_list_files_tool(),
             self.create_spawn_agent_tool(),
             self.create_check_agents_tool(),
-            self.create_queue_knowledge_tool(),
-            self.create_store_knowledge_tool(),
-            self.create_search_knowledge_tool(),
-            self.create_add_alias_tool(),
         ];
-        
+        if self.knowledge_enabled {
+            // Executed by the Agent itself (except queue_knowledge), but the
+            // schemas are registered here so the model sees them
+            builtins.extend([
+                self.create_queue_knowledge_tool(),
+                self.create_store_knowledge_tool(),
+                self.create_search_knowledge_tool(),
+                self.create_add_alias_tool(),
+            ]);
+        }
+
         for tool in builtins {
             self.tools.insert(tool.tool.name.clone(), tool);
         }
@@ -158,6 +272,7 @@ impl ToolRegistry {
         let tools = vec![
             self.create_list_mcp_servers_tool(),
             self.create_enable_mcp_server_tool(),
+            self.create_mcp_read_resource_tool(),
         ];
         for tool in tools {
             self.tools.insert(tool.tool.name.clone(), tool);
@@ -167,18 +282,20 @@ impl ToolRegistry {
     /// Load only the core builtin tools (no spawn_agent / check_agents).
     /// Used by sub-agents to prevent recursive spawning.
     pub fn load_subagent_builtins(&mut self) {
-        let builtins = vec![
+        let mut builtins = vec![
             self.create_exec_tool(),
             self.create_file_read_tool(),
+            self.create_file_read_glob_tool(),
             self.create_file_write_tool(),
             self.create_file_edit_tool(),
             self.create_search_code_tool(),
             self.create_list_files_tool(),
-            self.create_queue_knowledge_tool(),
-            self.create_store_knowledge_tool(),
-            self.create_search_knowledge_tool(),
-            self.create_add_alias_tool(),
         ];
+        // Sub-agents can only queue knowledge: the other knowledge tools are
+        // executed by the main Agent, which sub-agents don't go through
+        if self.knowledge_enabled {
+            builtins.push(self.create_queue_knowledge_tool());
+        }
 
         for tool in builtins {
             self.tools.insert(tool.tool.name.clone(), tool);
@@ -203,52 +320,13 @@ impl ToolRegistry {
         Ok(())
     }
 
-    pub async fn get_tool_schemas(&mut self) -> Value {
-        let mut schemas = Vec::new();
-
-        // Sort tools by name for deterministic ordering (required for prompt caching)
-        let mut sorted_tools: Vec<_> = self.tools.values().collect();
-        sorted_tools.sort_by(|a, b| a.tool.name.cmp(&b.tool.name));
-
-        // Add builtin and custom tools
-        for tool_def in sorted_tools {
-            let mut properties = serde_json::Map::new();
-            let mut required = Vec::new();
-
-            // Sort parameters by name for deterministic ordering
-            let mut sorted_params: Vec<_> = tool_def.tool.parameters.iter().collect();
-            sorted_params.sort_by(|a, b| a.0.cmp(b.0));
-
-            for (param_name, param_def) in sorted_params {
-                if param_def.required {
-                    required.push(param_name.clone());
-                }
-
-                let mut param_schema = serde_json::Map::new();
-                param_schema.insert("type".to_string(), Value::String(param_def.param_type.clone()));
-
-                if let Some(ref desc) = param_def.description {
-                    param_schema.insert("description".to_string(), Value::String(desc.clone()));
-                }
-
-                properties.insert(param_name.clone(), Value::Object(param_schema));
-            }
-
-            // Sort required array for deterministic ordering
-            required.sort();
-
-            let schema = json!({
-                "name": tool_def.tool.name,
-                "description": tool_def.tool.description,
-                "input_schema": {
-                    "type": "object",
-                    "properties": properties,
-                    "required": required
-                }
-            });
+    /// Connection status of every configured MCP server (for `/mcp`).
+    pub async fn mcp_status(&mut self) -> Vec<mcp::McpServerStatus> {
+        self.mcp_registry.status().await
+    }
 
-            schemas.push(schema);
-        }
+    pub async fn get_tool_schemas(&mut self) -> Value {
+        let mut schemas = self.builtin_tool_schemas();
 
         // Add MCP tool schemas
         let mcp_schemas = self.mcp_registry.get_tool_schemas().await;
@@ -259,6 +337,11 @@ impl ToolRegistry {
 
     /// Synchronous version — does not include MCP tools.
     pub fn get_tool_schemas_sync(&self) -> Value {
+        Value::Array(self.builtin_tool_schemas())
+    }
+
+    /// Schemas of the builtin and custom tools.
+    fn builtin_tool_schemas(&self) -> Vec<Value> {
         let mut schemas = Vec::new();
 
         // Sort tools by name for deterministic ordering (required for prompt caching)
@@ -284,6 +367,18 @@ impl ToolRegistry {
                 if let Some(ref desc) = param_def.description {
                     param_schema.insert("description".to_string(), Value::String(desc.clone()));
                 }
+                if let Some(ref items) = param_def.items {
+                    param_schema.insert("items".to_string(), items.clone());
+                }
+                if !param_def.enum_values.is_empty() {
+                    param_schema.insert("enum".to_string(), Value::Array(param_def.enum_values.clone()));
+                }
+                if let Some(min) = param_def.minimum {
+                    param_schema.insert("minimum".to_string(), json_bound(&param_def.param_type, min));
+                }
+                if let Some(max) = param_def.maximum {
+                    param_schema.insert("maximum".to_string(), json_bound(&param_def.param_type, max));
+                }
 
                 properties.insert(param_name.clone(), Value::Object(param_schema));
             }
@@ -304,10 +399,85 @@ impl ToolRegistry {
             schemas.push(schema);
         }
 
-        Value::Array(schemas)
+        schemas
     }
 
+    /// Run a tool call. Whatever the tool returns is clamped to
+    /// `max_output_bytes` here, so no single result can flood the context.
     pub async fn execute_tool(&mut self, tool_call: &ToolCall) -> Result<ToolResult> {
+        let mut result = self.execute_tool_unclamped(tool_call).await?;
+        if self.max_output_bytes > 0 && result.content.len() > self.max_output_bytes {
+            // Keep the whole thing so the model can page through it
+            let saved = self.save_full_output(&tool_call.id, &result.content);
+            result.content = clamp_output(result.content, self.max_output_bytes, saved.as_deref());
+        }
+        result.error = result.error.map(|e| clamp_output(e, self.max_output_bytes, None));
+        Ok(result)
+    }
+
+    /// Write `content` to `<output_dir>/<tool_use_id>.txt`. Returns None
+    /// (and logs) if it couldn't be saved.
+    fn save_full_output(&mut self, tool_use_id: &str, content: &str) -> Option<PathBuf> {
+        let dir = self.output_dir.get_or_insert_with(|| {
+            let unique = uuid::Uuid::new_v4().simple().to_string();
+            std::env::temp_dir().join(format!("imp-tool-output-{}", &unique[..12]))
+        });
+        let name: String = tool_use_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}.txt", if name.is_empty() { "output" } else { &name }));
+        match fs::create_dir_all(&*dir).and_then(|_| fs::write(&path, content)) {
+            Ok(()) => Some(path),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to save full tool output");
+                None
+            }
+        }
+    }
+
+    async fn execute_tool_unclamped(&mut self, tool_call: &ToolCall) -> Result<ToolResult> {
+        // Check and coerce arguments against the tool's parameters before any
+        // handler sees them (MCP tools are validated by their server)
+        let checked;
+        let tool_call = match self.tools.get(&tool_call.name) {
+            Some(tool_def) => match check_arguments(&tool_def.tool.parameters, &tool_call.arguments) {
+                Ok(arguments) => {
+                    checked = ToolCall {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        arguments,
+                    };
+                    &checked
+                }
+                Err(problems) => {
+                    return Ok(ToolResult {
+                        tool_use_id: tool_call.id.clone(),
+                        content: String::new(),
+                        error: Some(format!(
+                            "Invalid arguments for {}:\n- {}",
+                            tool_call.name,
+                            problems.join("\n- ")
+                        )),
+                    });
+                }
+            },
+            None => tool_call,
+        };
+
+        if self.read_only {
+            if let Some(result) = self.simulate_read_only(tool_call).await {
+                return Ok(match result {
+                    Ok(content) => ToolResult { tool_use_id: tool_call.id.clone(), content, error: None },
+                    Err(e) => ToolResult {
+                        tool_use_id: tool_call.id.clone(),
+                        content: String::new(),
+                        error: Some(e.to_string()),
+                    },
+                });
+            }
+        }
+
         // Handle MCP management tools specially (need mcp_registry access)
         if tool_call.name == "list_mcp_servers" {
             let servers = self.mcp_registry.list_available_servers().await;
@@ -366,6 +536,59 @@ impl ToolRegistry {
             }
         }
         
+        if tool_call.name == "mcp_read_resource" {
+            return Ok(match self.mcp_read_resource(&tool_call.arguments).await {
+                Ok(content) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    content,
+                    error: None,
+                },
+                Err(e) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    content: String::new(),
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+
+        // exec reports non-zero exits as output plus an error flag, not an ImpError
+        if tool_call.name == "exec" && self.tools.contains_key("exec") {
+            let requested = tool_call.arguments.get("cwd").and_then(|v| v.as_str());
+            let output = match self.resolve_cwd(requested) {
+                Ok(cwd) => builtin::exec_command(&tool_call.arguments, cwd.as_deref(), &self.exec_policy).await,
+                Err(e) => Err(e),
+            };
+            return Ok(match output {
+                Ok(output) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    error: (!output.success())
+                        .then(|| format!("Command exited with code {}", output.exit_code)),
+                    content: output.format(),
+                },
+                Err(e) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    content: String::new(),
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+
+        if tool_call.name == "file_read_glob" && self.tools.contains_key("file_read_glob") {
+            let result = builtin::file_read_glob(&tool_call.arguments, self.max_glob_files);
+            return Ok(match result {
+                Ok(content) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    content,
+                    error: None,
+                },
+                Err(e) => ToolResult {
+                    tool_use_id: tool_call.id.clone(),
+                    content: String::new(),
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+
         // Check if it's a built-in or custom tool
         if let Some(tool_def) = self.tools.get(&tool_call.name) {
             let result = match tool_def.handler.kind.as_str() {
@@ -375,7 +598,18 @@ impl ToolRegistry {
                 "shell" => {
                     if let Some(ref command_template) = tool_def.handler.command {
                         let command = self.render_template(command_template, &tool_call.arguments)?;
-                        execute_shell_command(&command, None).await
+                        match self.resolve_cwd(None) {
+                            Ok(cwd) => {
+                                execute_shell_command(
+                                    &command,
+                                    cwd.as_deref(),
+                                    tool_def.handler.timeout_secs,
+                                    &tool_def.handler.env,
+                                )
+                                .await
+                            }
+                            Err(e) => Err(e),
+                        }
                     } else {
                         Err(ImpError::Tool("Shell handler missing command".to_string()))
                     }
@@ -414,6 +648,34 @@ impl ToolRegistry {
         }
     }
 
+    /// In read-only mode, the stand-in result for a call that could change
+    /// something, or `None` if the call may run for real. MCP tools are
+    /// left alone.
+    async fn simulate_read_only(&self, tool_call: &ToolCall) -> Option<Result<String>> {
+        let tool_def = self.tools.get(&tool_call.name)?;
+        match (tool_def.handler.kind.as_str(), tool_call.name.as_str()) {
+            ("builtin", "file_write" | "file_edit") => {
+                Some(builtin::simulate(&tool_call.name, &tool_call.arguments).await)
+            }
+            ("builtin", "exec") => {
+                let command = tool_call.arguments.get("command").and_then(|v| v.as_str())?;
+                if builtin::is_read_only_command(command) {
+                    None
+                } else {
+                    Some(builtin::simulate("exec", &tool_call.arguments).await)
+                }
+            }
+            ("shell", _) => {
+                let template = tool_def.handler.command.as_ref()?;
+                Some(
+                    self.render_template(template, &tool_call.arguments)
+                        .map(|command| builtin::simulated_command(&command)),
+                )
+            }
+            _ => None,
+        }
+    }
+
     fn render_template(&self, template: &str, args: &Value) -> Result<String> {
         let mut result = template.to_string();
 
@@ -432,12 +694,187 @@ impl ToolRegistry {
     }
 }
 
-async fn execute_shell_command(command: &str, timeout_secs: Option<u64>) -> Result<String> {
-    let child = tokio::process::Command::new("sh")
-        .arg("-c")
+impl Drop for ToolRegistry {
+    fn drop(&mut self) {
+        // Saved tool output only matters for the session that produced it
+        if let Some(dir) = &self.output_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Default for `[tools] max_output_bytes`.
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Cut `text` down to about `max_bytes` by keeping its start and end and
+/// replacing the middle with a marker giving the full size and, if the
+/// full text was saved to `saved`, which lines to read from there.
+/// 0 = no limit.
+fn clamp_output(text: String, max_bytes: usize, saved: Option<&Path>) -> String {
+    if max_bytes == 0 || text.len() <= max_bytes {
+        return text;
+    }
+
+    let mut head_end = max_bytes / 2;
+    while !text.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+    let mut tail_start = text.len() - max_bytes / 2;
+    while !text.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+
+    let recover = match saved {
+        Some(path) => {
+            // 1-based lines the elided middle spans in the saved file
+            let first = text[..head_end].matches('\n').count() + 1;
+            let last = text[..tail_start].matches('\n').count() + 1;
+            format!(
+                "; full output saved to {} — file_read it with offset={} limit={} for the elided part",
+                path.display(),
+                first,
+                last - first + 1
+            )
+        }
+        None => String::new(),
+    };
+    format!(
+        "{}\n\n[… {} bytes elided — output was {} bytes in total{} …]\n\n{}",
+        &text[..head_end],
+        tail_start - head_end,
+        text.len(),
+        recover,
+        &text[tail_start..]
+    )
+}
+
+/// Check `args` against a tool's parameter definitions: required parameters
+/// must be present, missing optional ones get their default, values of the
+/// wrong JSON type are coerced where that's unambiguous (`"30"` for an
+/// integer, `"true"` for a boolean), and `enum`/`minimum`/`maximum` are
+/// enforced. Returns every problem found otherwise.
+/// Arguments not in `params` are passed through untouched.
+fn check_arguments(params: &HashMap<String, ParameterDef>, args: &Value) -> std::result::Result<Value, Vec<String>> {
+    let mut map = match args {
+        Value::Object(map) => map.clone(),
+        Value::Null => serde_json::Map::new(),
+        other => return Err(vec![format!("arguments must be an object, got {}", other)]),
+    };
+
+    let mut names: Vec<_> = params.keys().collect();
+    names.sort();
+    let mut problems = Vec::new();
+    for name in names {
+        let def = &params[name];
+        match map.remove(name.as_str()).filter(|v| !v.is_null()) {
+            Some(value) => match coerce_argument(&def.param_type, value) {
+                Ok(value) => {
+                    if let Some(problem) = check_constraints(def, &value) {
+                        problems.push(format!("'{}' {}", name, problem));
+                    }
+                    map.insert(name.clone(), value);
+                }
+                Err(value) => problems.push(format!(
+                    "'{}' should be {} {}, got {}",
+                    name,
+                    if def.param_type.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" },
+                    def.param_type,
+                    value
+                )),
+            },
+            None if def.required => problems.push(format!("missing required parameter '{}'", name)),
+            None => {
+                if let Some(ref default) = def.default {
+                    map.insert(name.clone(), default.clone());
+                }
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(Value::Object(map))
+    } else {
+        Err(problems)
+    }
+}
+
+/// What's wrong with `value` given the parameter's `enum` and bounds, if anything.
+fn check_constraints(def: &ParameterDef, value: &Value) -> Option<String> {
+    if !def.enum_values.is_empty() && !def.enum_values.contains(value) {
+        let allowed: Vec<String> = def.enum_values.iter().map(Value::to_string).collect();
+        return Some(format!("should be one of {}, got {}", allowed.join(", "), value));
+    }
+    let n = value.as_f64()?;
+    match (def.minimum, def.maximum) {
+        (Some(min), _) if n < min => Some(format!("should be at least {}, got {}", min, value)),
+        (_, Some(max)) if n > max => Some(format!("should be at most {}, got {}", max, value)),
+        _ => None,
+    }
+}
+
+/// A schema bound as JSON, written as an integer for `integer` parameters.
+fn json_bound(param_type: &str, bound: f64) -> Value {
+    if param_type == "integer" && bound.fract() == 0.0 {
+        json!(bound as i64)
+    } else {
+        json!(bound)
+    }
+}
+
+/// Convert `value` to the JSON Schema type `param_type`, or give it back if
+/// it can't be. Unknown types are accepted as-is.
+fn coerce_argument(param_type: &str, value: Value) -> std::result::Result<Value, Value> {
+    match (param_type, value) {
+        ("string", v @ Value::String(_)) => Ok(v),
+        ("string", Value::Number(n)) => Ok(Value::String(n.to_string())),
+        ("string", Value::Bool(b)) => Ok(Value::String(b.to_string())),
+        ("integer", Value::Number(n)) if n.is_i64() || n.is_u64() => Ok(Value::Number(n)),
+        ("integer", Value::Number(n)) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 => Ok(json!(f as i64)),
+            _ => Err(Value::Number(n)),
+        },
+        ("integer", Value::String(s)) => s.trim().parse::<i64>().map(|i| json!(i)).map_err(|_| Value::String(s)),
+        ("number", v @ Value::Number(_)) => Ok(v),
+        ("number", Value::String(s)) => match s.trim().parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(json!(f)),
+            _ => Err(Value::String(s)),
+        },
+        ("boolean", v @ Value::Bool(_)) => Ok(v),
+        ("boolean", Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(Value::String(s)),
+        },
+        ("array", v @ Value::Array(_)) => Ok(v),
+        ("object", v @ Value::Object(_)) => Ok(v),
+        // Models sometimes send a JSON-encoded array or object as a string
+        ("array" | "object", Value::String(s)) => match serde_json::from_str::<Value>(&s) {
+            Ok(v) if (param_type == "array") == v.is_array() && (v.is_array() || v.is_object()) => Ok(v),
+            _ => Err(Value::String(s)),
+        },
+        ("string" | "integer" | "number" | "boolean" | "array" | "object", v) => Err(v),
+        (_, v) => Ok(v),
+    }
+}
+
+async fn execute_shell_command(
+    command: &str,
+    cwd: Option<&Path>,
+    timeout_secs: Option<u64>,
+    env: &HashMap<String, String>,
+) -> Result<String> {
+    let mut cmd = tokio::process::Command::new("sh");
+    cmd.arg("-c")
         .arg(command)
         .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    for (key, value) in env {
+        cmd.env(key, mcp::expand_env_var(value));
+    }
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+    let child = cmd
         .spawn()
         .map_err(|e| ImpError::Tool(format!("Failed to spawn command: {}", e)))?;
 
@@ -471,7 +908,7 @@ impl ToolRegistry {
         ToolDefinition {
             tool: ToolMeta {
                 name: "exec".to_string(),
-                description: "Execute a shell command. Commands have a default timeout of 300s (5 minutes). Use timeout_secs for long-running operations.".to_string(),
+                description: "Execute a shell command. Always returns the exit_code plus separate stdout and stderr, even when the command fails. Commands have a default timeout of 300s (5 minutes). Use timeout_secs for long-running operations.".to_string(),
                 parameters: {
                     let mut params = HashMap::new();
                     params.insert("command".to_string(), ParameterDef {
@@ -479,12 +916,22 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("The shell command to execute".to_string()),
+                        ..Default::default()
                     });
                     params.insert("timeout_secs".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: None,
                         description: Some("Timeout in seconds. Default: 300 (5 minutes). Set higher for long-running builds or operations.".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
+                    });
+                    params.insert("cwd".to_string(), ParameterDef {
+                        param_type: "string".to_string(),
+                        required: false,
+                        default: None,
+                        description: Some("Working directory to run the command in. Use this instead of prefixing the command with `cd`. Default: the session's working directory.".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -493,6 +940,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -509,18 +958,23 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Path to the file to read".to_string()),
+                        ..Default::default()
                     });
                     params.insert("offset".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: None,
                         description: Some("Line number to start reading from (1-indexed). Default: 1".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
                     });
                     params.insert("limit".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: None,
                         description: Some("Maximum number of lines to read. Default: entire file".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
                     });
                     params
                 },
@@ -529,6 +983,46 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
+            },
+        }
+    }
+
+    fn create_file_read_glob_tool(&self) -> ToolDefinition {
+        ToolDefinition {
+            tool: ToolMeta {
+                name: "file_read_glob".to_string(),
+                description: format!(
+                    "Read every file matching a glob pattern (e.g. 'src/*.rs', 'crates/**/Cargo.toml') in one call, with line numbers and a header per file. Hidden and .gitignore/.impignore'd files are skipped. Refuses patterns matching more than {} files; use file_read for single files.",
+                    self.max_glob_files
+                ),
+                parameters: {
+                    let mut params = HashMap::new();
+                    params.insert("pattern".to_string(), ParameterDef {
+                        param_type: "string".to_string(),
+                        required: true,
+                        default: None,
+                        description: Some("Glob pattern, relative to the working directory or absolute. '*' stays within one directory, '**' crosses directories".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("max_lines".to_string(), ParameterDef {
+                        param_type: "integer".to_string(),
+                        required: false,
+                        default: None,
+                        description: Some("Read at most this many lines from each file. Default: whole file".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
+                    });
+                    params
+                },
+            },
+            handler: ToolHandler {
+                kind: "builtin".to_string(),
+                command: None,
+                script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -545,12 +1039,14 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Path to the file to write".to_string()),
+                        ..Default::default()
                     });
                     params.insert("content".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: true,
                         default: None,
                         description: Some("Content to write to the file".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -559,6 +1055,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -575,18 +1073,21 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Path to the file to edit".to_string()),
+                        ..Default::default()
                     });
                     params.insert("old_text".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: true,
                         default: None,
                         description: Some("Exact text to find (must match exactly one location, including whitespace)".to_string()),
+                        ..Default::default()
                     });
                     params.insert("new_text".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: true,
                         default: None,
                         description: Some("Replacement text".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -595,6 +1096,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -603,7 +1106,11 @@ impl ToolRegistry {
         ToolDefinition {
             tool: ToolMeta {
                 name: "search_code".to_string(),
-                description: "Search for text across all files using ripgrep. Searches all file types by default (code, config, docs, etc). Skips .git, node_modules, target, etc. Results include line numbers and context.".to_string(),
+                description: if builtin::ripgrep_available() {
+                    "Search for text across all files using ripgrep. Searches all file types by default (code, config, docs, etc). Skips .git, node_modules, target, etc. Results include line numbers and context.".to_string()
+                } else {
+                    "Search for text across all files (ripgrep isn't installed, so a built-in search is used: the query is a Rust regex, .gitignore and .impignore are honoured, binary files are skipped). Searches all file types by default. Skips .git, node_modules, target, etc. Results include line numbers and context.".to_string()
+                },
                 parameters: {
                     let mut params = HashMap::new();
                     params.insert("query".to_string(), ParameterDef {
@@ -611,18 +1118,28 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Text or regex pattern to search for".to_string()),
+                        ..Default::default()
                     });
                     params.insert("path".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: false,
                         default: Some(Value::String(".".to_string())),
                         description: Some("Directory to search in (default: current directory)".to_string()),
+                        ..Default::default()
                     });
                     params.insert("file_type".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: false,
                         default: None,
                         description: Some("Optional file extension filter (e.g. 'rs', 'toml', 'md'). Omit to search all files.".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("with_symbol".to_string(), ParameterDef {
+                        param_type: "boolean".to_string(),
+                        required: false,
+                        default: Some(Value::Bool(false)),
+                        description: Some("Tag each match with its enclosing function/struct/class, e.g. '[in fn main]' (Rust, Python, JS/TS, Go; heuristic). Default: false".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -631,6 +1148,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -639,7 +1158,7 @@ impl ToolRegistry {
         ToolDefinition {
             tool: ToolMeta {
                 name: "list_files".to_string(),
-                description: "List files and directories".to_string(),
+                description: "List files and directories with their sizes, or a whole tree with recursive=true. Skips hidden files and anything matched by .gitignore or .impignore.".to_string(),
                 parameters: {
                     let mut params = HashMap::new();
                     params.insert("path".to_string(), ParameterDef {
@@ -647,6 +1166,44 @@ impl ToolRegistry {
                         required: false,
                         default: Some(Value::String(".".to_string())),
                         description: Some("Directory to list (default: current directory)".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("recursive".to_string(), ParameterDef {
+                        param_type: "boolean".to_string(),
+                        required: false,
+                        default: Some(Value::Bool(false)),
+                        description: Some("List subdirectories too, as an indented tree. Use this to see a project's layout in one call. Default: false".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("max_depth".to_string(), ParameterDef {
+                        param_type: "integer".to_string(),
+                        required: false,
+                        default: Some(Value::Number(serde_json::Number::from(3))),
+                        description: Some("How many directory levels to descend when recursive. Default: 3".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
+                    });
+                    params.insert("max_entries".to_string(), ParameterDef {
+                        param_type: "integer".to_string(),
+                        required: false,
+                        default: Some(Value::Number(serde_json::Number::from(500))),
+                        description: Some("Stop listing after this many entries. Default: 500".to_string()),
+                        minimum: Some(1.0),
+                        ..Default::default()
+                    });
+                    params.insert("show_hidden".to_string(), ParameterDef {
+                        param_type: "boolean".to_string(),
+                        required: false,
+                        default: Some(Value::Bool(false)),
+                        description: Some("Include dotfiles and dot-directories. Default: false".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("respect_gitignore".to_string(), ParameterDef {
+                        param_type: "boolean".to_string(),
+                        required: false,
+                        default: Some(Value::Bool(true)),
+                        description: Some("Skip files ignored by .gitignore. Set false to see build artifacts like target/ or node_modules/. Default: true".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -655,6 +1212,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -671,24 +1230,28 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Clear, complete description of what the sub-agent should do. Include ALL context needed - sub-agents cannot ask clarifying questions.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("max_tokens_budget".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: Some(Value::Number(serde_json::Number::from(200000))),
                         description: Some("Maximum total token budget (input + output) for this sub-agent. Default: 200000. Scale based on task complexity: ~50k for small edits, ~200k for moderate work, ~500k+ for large codebase exploration or multi-file refactors.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("working_directory".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: false,
                         default: None,
                         description: Some("Working directory for the sub-agent's shell commands. Defaults to current directory.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("timeout_secs".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: Some(Value::Number(serde_json::Number::from(600))),
                         description: Some("Wall-clock timeout in seconds. The sub-agent is killed if it exceeds this. Default: 600 (10 minutes). Use higher values for complex multi-file tasks.".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -697,6 +1260,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -712,6 +1277,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -728,18 +1295,22 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("The content/fact to queue for knowledge extraction. Should be a clear, self-contained statement.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("session_id".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: false,
                         default: Some(Value::String("unknown".to_string())),
                         description: Some("The current session ID for tracking provenance.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("suggested_entities".to_string(), ParameterDef {
                         param_type: "array".to_string(),
                         required: false,
                         default: Some(Value::Array(vec![])),
                         description: Some("Optional list of entity names you think are mentioned in the content.".to_string()),
+                        items: Some(json!({"type": "string"})),
+                        ..Default::default()
                     });
                     params
                 },
@@ -748,6 +1319,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -764,18 +1337,21 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Search query — used for semantic/text search over memory chunks.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("entity".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: false,
                         default: None,
                         description: Some("Optional entity name to look up directly (exact match). Returns the entity and its relationships.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("max_results".to_string(), ParameterDef {
                         param_type: "integer".to_string(),
                         required: false,
                         default: Some(Value::Number(serde_json::Number::from(10))),
                         description: Some("Maximum number of memory chunks to return (default: 10).".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -784,6 +1360,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -799,19 +1377,64 @@ impl ToolRegistry {
                         param_type: "array".to_string(),
                         required: false,
                         default: Some(Value::Array(vec![])),
-                        description: Some("Entities to store. Each: {\"name\": \"...\", \"type\": \"person|project|protocol|exchange|tool|concept|...\", \"properties\": {}}".to_string()),
+                        description: Some("Entities to store. Entities that already exist are skipped.".to_string()),
+                        items: Some(json!({
+                            "type": "object",
+                            "properties": {
+                                "name": {"type": "string"},
+                                "type": {
+                                    "type": "string",
+                                    "description": "person|project|protocol|exchange|tool|concept|... (default: concept)"
+                                },
+                                "properties": {"type": "object"}
+                            },
+                            "required": ["name"]
+                        })),
+                        ..Default::default()
                     });
                     params.insert("relationships".to_string(), ParameterDef {
                         param_type: "array".to_string(),
                         required: false,
                         default: Some(Value::Array(vec![])),
-                        description: Some("Relationships to store. Each: {\"from\": \"entity_name\", \"rel\": \"works_on|uses|authored|related_to|part_of|...\", \"to\": \"entity_name\"}".to_string()),
+                        description: Some("Relationships to store. Both entities must already exist or be in `entities`.".to_string()),
+                        items: Some(json!({
+                            "type": "object",
+                            "properties": {
+                                "from": {"type": "string", "description": "Entity name"},
+                                "rel": {
+                                    "type": "string",
+                                    "description": "works_on|uses|authored|related_to|part_of|..."
+                                },
+                                "to": {"type": "string", "description": "Entity name"}
+                            },
+                            "required": ["from", "rel", "to"]
+                        })),
+                        ..Default::default()
                     });
                     params.insert("chunks".to_string(), ParameterDef {
                         param_type: "array".to_string(),
                         required: false,
                         default: Some(Value::Array(vec![])),
-                        description: Some("Memory chunks to store for semantic search. Each: {\"content\": \"self-contained factual text\", \"source\": \"conversation|document|...\"}".to_string()),
+                        description: Some("Memory chunks to store for semantic search.".to_string()),
+                        items: Some(json!({
+                            "type": "object",
+                            "properties": {
+                                "content": {"type": "string", "description": "Self-contained factual text"},
+                                "source": {
+                                    "type": "string",
+                                    "description": "conversation|document|... (default: conversation)"
+                                }
+                            },
+                            "required": ["content"]
+                        })),
+                        ..Default::default()
+                    });
+                    params.insert("global".to_string(), ParameterDef {
+                        param_type: "boolean".to_string(),
+                        required: false,
+                        default: Some(Value::Bool(false)),
+                        description: Some("Store as cross-project knowledge (e.g. the user's preferences). By default, knowledge stored inside a project is tagged with it and only retrieved there.".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -820,6 +1443,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -836,12 +1461,14 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("The name of the existing entity to add an alias for.".to_string()),
+                        ..Default::default()
                     });
                     params.insert("alias".to_string(), ParameterDef {
                         param_type: "string".to_string(),
                         required: true,
                         default: None,
                         description: Some("The alias to add for the entity.".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -850,10 +1477,48 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
     
+    /// Read an MCP resource, or list the server's resources when no URI is given.
+    async fn mcp_read_resource(&mut self, arguments: &serde_json::Value) -> Result<String> {
+        let server_name = arguments
+            .get("server")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ImpError::Tool("Missing required parameter: server".to_string()))?;
+
+        if !self.mcp_registry.supports_resources(server_name).await? {
+            return Ok(format!("MCP server '{}' does not support resources.", server_name));
+        }
+
+        if let Some(uri) = arguments.get("uri").and_then(|v| v.as_str()) {
+            return self.mcp_registry.read_resource(server_name, uri).await;
+        }
+
+        let resources = self.mcp_registry.list_resources(server_name).await?;
+        if resources.is_empty() {
+            return Ok(format!("MCP server '{}' has no resources.", server_name));
+        }
+        let mut output = format!("Resources on '{}':\n\n", server_name);
+        for r in resources {
+            output.push_str(&format!("- {}", r.uri));
+            if !r.name.is_empty() {
+                output.push_str(&format!(" ({})", r.name));
+            }
+            if let Some(ref mime) = r.mime_type {
+                output.push_str(&format!(" [{}]", mime));
+            }
+            if !r.description.is_empty() {
+                output.push_str(&format!(" — {}", r.description));
+            }
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
     fn create_list_mcp_servers_tool(&self) -> ToolDefinition {
         ToolDefinition {
             tool: ToolMeta {
@@ -865,6 +1530,8 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
@@ -881,6 +1548,7 @@ impl ToolRegistry {
                         required: true,
                         default: None,
                         description: Some("Name of the MCP server to enable (from list_mcp_servers output).".to_string()),
+                        ..Default::default()
                     });
                     params
                 },
@@ -889,7 +1557,201 @@ impl ToolRegistry {
                 kind: "builtin".to_string(),
                 command: None,
                 script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
             },
         }
     }
-}
\ No newline at end of file
+
+    fn create_mcp_read_resource_tool(&self) -> ToolDefinition {
+        ToolDefinition {
+            tool: ToolMeta {
+                name: "mcp_read_resource".to_string(),
+                description: "Read a resource (file, document, record, ...) exposed by an MCP server. Omit uri to list the server's resources. The server does not need to be enabled.".to_string(),
+                parameters: {
+                    let mut params = HashMap::new();
+                    params.insert("server".to_string(), ParameterDef {
+                        param_type: "string".to_string(),
+                        required: true,
+                        default: None,
+                        description: Some("Name of the MCP server (from list_mcp_servers output).".to_string()),
+                        ..Default::default()
+                    });
+                    params.insert("uri".to_string(), ParameterDef {
+                        param_type: "string".to_string(),
+                        required: false,
+                        default: None,
+                        description: Some("URI of the resource to read. Omit to list available resources.".to_string()),
+                        ..Default::default()
+                    });
+                    params
+                },
+            },
+            handler: ToolHandler {
+                kind: "builtin".to_string(),
+                command: None,
+                script: None,
+                timeout_secs: None,
+                env: HashMap::new(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_tool_parses() {
+        let def: ToolDefinition =
+            toml::from_str(include_str!("../../../../templates/tools/deploy_staging.toml")).unwrap();
+        assert_eq!(def.handler.timeout_secs, Some(1800));
+        assert_eq!(def.handler.env["DEPLOY_ENV"], "staging");
+        assert_eq!(def.tool.parameters["service"].enum_values, vec![json!("api"), json!("worker")]);
+
+        // Older tool files without timeout_secs or env still load
+        let def: ToolDefinition = toml::from_str(
+            "[tool]\nname = \"t\"\ndescription = \"d\"\n[tool.parameters]\n\n[handler]\nkind = \"shell\"\ncommand = \"true\"\n",
+        )
+        .unwrap();
+        assert_eq!(def.handler.timeout_secs, None);
+        assert!(def.handler.env.is_empty());
+    }
+
+    fn params(defs: &[(&str, &str, bool, Option<Value>)]) -> HashMap<String, ParameterDef> {
+        defs.iter()
+            .map(|(name, param_type, required, default)| {
+                (
+                    name.to_string(),
+                    ParameterDef {
+                        param_type: param_type.to_string(),
+                        required: *required,
+                        default: default.clone(),
+                        description: None,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_knowledge_tool_schemas() {
+        let mut registry = ToolRegistry::new();
+        registry.load_builtin_tools();
+        let schemas = registry.get_tool_schemas_sync();
+        let store = schemas
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["name"] == "store_knowledge")
+            .expect("store_knowledge is offered");
+        let entities = &store["input_schema"]["properties"]["entities"];
+        assert_eq!(entities["items"]["required"], json!(["name"]));
+
+        let mut registry = ToolRegistry::new();
+        registry.set_knowledge_enabled(false);
+        registry.load_builtin_tools();
+        assert!(!registry.tools.contains_key("store_knowledge"));
+        assert!(!registry.tools.contains_key("queue_knowledge"));
+        assert!(registry.tools.contains_key("spawn_agent"));
+    }
+
+    #[test]
+    fn test_clamp_output() {
+        assert_eq!(clamp_output("short".to_string(), 100, None), "short");
+        assert_eq!(clamp_output("x".repeat(1000), 0, None).len(), 1000);
+
+        let text = format!("{}{}{}", "a".repeat(600), "é".repeat(200), "z".repeat(600));
+        let clamped = clamp_output(text.clone(), 1000, None);
+        assert!(clamped.starts_with(&"a".repeat(500)));
+        assert!(clamped.ends_with(&"z".repeat(500)));
+        assert!(clamped.contains("bytes elided — output was 1600 bytes in total"), "{}", clamped);
+        assert!(clamped.len() < text.len());
+
+        let lines: String = (1..=100).map(|i| format!("line {:03}\n", i)).collect();
+        let clamped = clamp_output(lines, 200, Some(Path::new("/tmp/out.txt")));
+        assert!(
+            clamped.contains("full output saved to /tmp/out.txt — file_read it with offset=12 limit=78"),
+            "{}",
+            clamped
+        );
+    }
+
+    #[test]
+    fn test_check_arguments_missing_required() {
+        let defs = params(&[("command", "string", true, None), ("path", "string", true, None)]);
+        let problems = check_arguments(&defs, &json!({ "path": null })).unwrap_err();
+        assert_eq!(
+            problems,
+            vec!["missing required parameter 'command'", "missing required parameter 'path'"]
+        );
+    }
+
+    #[test]
+    fn test_check_arguments_applies_defaults() {
+        let defs = params(&[
+            ("path", "string", true, None),
+            ("max_entries", "integer", false, Some(json!(500))),
+            ("recursive", "boolean", false, None),
+        ]);
+        let args = check_arguments(&defs, &json!({ "path": "src", "extra": 1 })).unwrap();
+        assert_eq!(args, json!({ "path": "src", "max_entries": 500, "extra": 1 }));
+    }
+
+    #[test]
+    fn test_check_arguments_coerces_and_rejects_types() {
+        let defs = params(&[
+            ("timeout_secs", "integer", false, None),
+            ("recursive", "boolean", false, None),
+            ("paths", "array", false, None),
+            ("name", "string", false, None),
+        ]);
+        let args = check_arguments(
+            &defs,
+            &json!({ "timeout_secs": "30", "recursive": "True", "paths": "[\"a\", \"b\"]", "name": 7 }),
+        )
+        .unwrap();
+        assert_eq!(args, json!({ "timeout_secs": 30, "recursive": true, "paths": ["a", "b"], "name": "7" }));
+
+        let problems =
+            check_arguments(&defs, &json!({ "timeout_secs": "soon", "recursive": 1, "paths": "a" })).unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "'paths' should be an array, got \"a\"",
+                "'recursive' should be a boolean, got 1",
+                "'timeout_secs' should be an integer, got \"soon\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_arguments_enforces_enum_and_bounds() {
+        let mut defs = params(&[("format", "string", false, None), ("depth", "integer", false, None)]);
+        defs.get_mut("format").unwrap().enum_values = vec![json!("md"), json!("json")];
+        let depth = defs.get_mut("depth").unwrap();
+        depth.minimum = Some(1.0);
+        depth.maximum = Some(10.0);
+
+        assert!(check_arguments(&defs, &json!({ "format": "json", "depth": "10" })).is_ok());
+        let problems = check_arguments(&defs, &json!({ "format": "xml", "depth": 0 })).unwrap_err();
+        assert_eq!(
+            problems,
+            vec!["'depth' should be at least 1, got 0", "'format' should be one of \"md\", \"json\", got \"xml\""]
+        );
+        let problems = check_arguments(&defs, &json!({ "depth": 11 })).unwrap_err();
+        assert_eq!(problems, vec!["'depth' should be at most 10, got 11"]);
+    }
+
+    #[tokio::test]
+    async fn test_shell_tool_env_is_expanded() {
+        std::env::set_var("IMP_TEST_TOOL_REGION", "eu-west-1");
+        let env = HashMap::from([
+            ("TARGET".to_string(), "${IMP_TEST_TOOL_REGION}/${IMP_TEST_TOOL_UNSET:-blue}".to_string()),
+        ]);
+        let output = execute_shell_command("echo \"$TARGET\"", None, Some(10), &env).await.unwrap();
+        assert_eq!(output.trim(), "eu-west-1/blue");
+    }
+}
@@ -1,3 +1,6 @@
+use crate::config::ModelPricing;
+use std::collections::HashMap;
+
 /// Token usage tracking for a session, including prompt caching.
 #[derive(Debug, Default, Clone)]
 pub struct UsageTracker {
@@ -6,32 +9,85 @@ pub struct UsageTracker {
     pub total_cache_creation_tokens: u64,
     pub total_cache_read_tokens: u64,
     pub request_count: u32,
+    /// Tokenizer estimate of the most recent request's full context
+    /// (system prompt + tool schemas + messages).
+    pub context_tokens: usize,
+    /// Dollar cost accumulated per request at the rates of the model in use,
+    /// so switching models mid-session keeps earlier turns priced correctly.
+    total_cost: f64,
+    /// Whether any recorded usage came from a model without known pricing.
+    unpriced: bool,
     model: Option<String>,
+    pricing: Option<ModelPricing>,
+    /// `[pricing]` entries from config, consulted before the built-in table.
+    pricing_overrides: HashMap<String, ModelPricing>,
+    /// The same numbers broken down by model, for persisting at session end.
+    by_model: Vec<ModelUsage>,
 }
 
-/// Per-million-token pricing for a model.
-struct Pricing {
-    input: f64,
-    output: f64,
-    cache_write: f64,
-    cache_read: f64,
+/// Usage attributed to a single model within a session.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ModelUsage {
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub requests: u32,
+    /// `None` when the model has no known pricing.
+    pub cost: Option<f64>,
 }
 
-fn pricing_for_model(model: &str) -> Pricing {
-    // Normalise: strip date suffixes, lowercase
-    let m = model.to_lowercase();
-    if m.contains("opus-4-5") || m.contains("opus-4.5") {
-        Pricing { input: 5.0, output: 25.0, cache_write: 6.25, cache_read: 0.50 }
-    } else if m.contains("opus") {
-        // Claude 4 Opus
-        Pricing { input: 15.0, output: 75.0, cache_write: 18.75, cache_read: 1.50 }
-    } else if m.contains("sonnet") {
-        Pricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.30 }
-    } else if m.contains("haiku") {
-        Pricing { input: 0.80, output: 4.0, cache_write: 1.0, cache_read: 0.08 }
-    } else {
-        // Unknown model — use Sonnet as a safe middle ground
-        Pricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.30 }
+impl ModelUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+    }
+}
+
+/// Built-in rates in USD per million tokens, keyed by a model-name fragment.
+/// Cache writes are 1.25× input (5-minute TTL), cache reads 0.1× input.
+const BUILTIN_PRICING: &[(&str, ModelPricing)] = &[
+    ("opus-4-5", ModelPricing { input: 5.0, output: 25.0, cache_write: 6.25, cache_read: 0.50 }),
+    ("opus-4", ModelPricing { input: 15.0, output: 75.0, cache_write: 18.75, cache_read: 1.50 }),
+    ("sonnet-4", ModelPricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.30 }),
+    ("3-7-sonnet", ModelPricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.30 }),
+    ("haiku-4-5", ModelPricing { input: 1.0, output: 5.0, cache_write: 1.25, cache_read: 0.10 }),
+    ("3-5-haiku", ModelPricing { input: 0.80, output: 4.0, cache_write: 1.0, cache_read: 0.08 }),
+];
+
+/// Look up pricing for a model: config overrides first, then the built-in
+/// table. The longest matching key wins, so `opus-4-5` beats `opus-4`.
+/// Returns `None` for unknown models rather than guessing.
+fn pricing_for_model(model: &str, overrides: &HashMap<String, ModelPricing>) -> Option<ModelPricing> {
+    // Normalise: lowercase, and accept "opus-4.5" style names
+    let m = model.to_lowercase().replace('.', "-");
+    let longest_match = |entries: &mut dyn Iterator<Item = (&str, ModelPricing)>| {
+        entries
+            .filter(|(key, _)| m.contains(&key.to_lowercase().replace('.', "-")))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, p)| p)
+    };
+    longest_match(&mut overrides.iter().map(|(k, p)| (k.as_str(), *p)))
+        .or_else(|| longest_match(&mut BUILTIN_PRICING.iter().copied()))
+}
+
+impl ModelPricing {
+    /// Dollar cost of a request with the given token counts.
+    pub fn cost(&self, input: u64, output: u64, cache_creation: u64, cache_read: u64) -> f64 {
+        (input as f64 * self.input
+            + output as f64 * self.output
+            + cache_creation as f64 * self.cache_write
+            + cache_read as f64 * self.cache_read)
+            / 1_000_000.0
+    }
+}
+
+/// Render a cost, or a note when some of the usage couldn't be priced.
+fn format_cost(cost: f64, unpriced: bool) -> String {
+    match (unpriced, cost > 0.0) {
+        (false, _) => format!("${:.4}", cost),
+        (true, false) => "(no pricing)".to_string(),
+        (true, true) => format!("${:.4} + usage with no pricing", cost),
     }
 }
 
@@ -40,19 +96,71 @@ impl UsageTracker {
         Self::default()
     }
 
+    /// A tracker that prices models using `[pricing]` config overrides
+    /// before falling back to the built-in table.
+    pub fn with_pricing(overrides: HashMap<String, ModelPricing>) -> Self {
+        Self { pricing_overrides: overrides, ..Self::default() }
+    }
+
     pub fn set_model(&mut self, model: &str) {
         self.model = Some(model.to_string());
+        self.pricing = pricing_for_model(model, &self.pricing_overrides);
     }
 
     pub fn record(&mut self, input_tokens: u32, output_tokens: u32) {
         self.total_input_tokens += input_tokens as u64;
         self.total_output_tokens += output_tokens as u64;
         self.request_count += 1;
+        let entry = self.model_usage();
+        entry.input_tokens += input_tokens as u64;
+        entry.output_tokens += output_tokens as u64;
+        entry.requests += 1;
+        self.add_cost(input_tokens as u64, output_tokens as u64, 0, 0);
     }
 
     pub fn record_cache(&mut self, cache_creation: u32, cache_read: u32) {
         self.total_cache_creation_tokens += cache_creation as u64;
         self.total_cache_read_tokens += cache_read as u64;
+        let entry = self.model_usage();
+        entry.cache_creation_tokens += cache_creation as u64;
+        entry.cache_read_tokens += cache_read as u64;
+        self.add_cost(0, 0, cache_creation as u64, cache_read as u64);
+    }
+
+    fn add_cost(&mut self, input: u64, output: u64, cache_creation: u64, cache_read: u64) {
+        if input + output + cache_creation + cache_read == 0 {
+            return;
+        }
+        match self.pricing {
+            Some(p) => {
+                let cost = p.cost(input, output, cache_creation, cache_read);
+                self.total_cost += cost;
+                *self.model_usage().cost.get_or_insert(0.0) += cost;
+            }
+            None => self.unpriced = true,
+        }
+    }
+
+    /// The breakdown entry for the current model, created on first use.
+    fn model_usage(&mut self) -> &mut ModelUsage {
+        let model = self.model.clone().unwrap_or_else(|| "unknown".to_string());
+        let idx = match self.by_model.iter().position(|u| u.model == model) {
+            Some(idx) => idx,
+            None => {
+                self.by_model.push(ModelUsage { model, ..Default::default() });
+                self.by_model.len() - 1
+            }
+        };
+        &mut self.by_model[idx]
+    }
+
+    /// Usage so far, per model used in this session.
+    pub fn by_model(&self) -> &[ModelUsage] {
+        &self.by_model
+    }
+
+    pub fn record_context(&mut self, tokens: usize) {
+        self.context_tokens = tokens;
     }
 
     pub fn total_tokens(&self) -> u64 {
@@ -62,35 +170,29 @@ impl UsageTracker {
             + self.total_cache_read_tokens
     }
 
-    fn pricing(&self) -> Pricing {
-        pricing_for_model(self.model.as_deref().unwrap_or("opus-4-5"))
+    /// Dollar cost so far, or `None` if no model used had known pricing.
+    pub fn total_cost(&self) -> Option<f64> {
+        (!self.unpriced || self.total_cost > 0.0).then_some(self.total_cost)
     }
 
-    pub fn estimated_cost(&self) -> f64 {
-        let p = self.pricing();
-        let input_cost = (self.total_input_tokens as f64 / 1_000_000.0) * p.input;
-        let output_cost = (self.total_output_tokens as f64 / 1_000_000.0) * p.output;
-        let cache_write_cost = (self.total_cache_creation_tokens as f64 / 1_000_000.0) * p.cache_write;
-        let cache_read_cost = (self.total_cache_read_tokens as f64 / 1_000_000.0) * p.cache_read;
-        input_cost + output_cost + cache_write_cost + cache_read_cost
-    }
-
-    /// Format a single response's usage for display.
+    /// Format a single response's usage for display, priced at the current model's rates.
     pub fn format_response_usage(
+        &self,
         input_tokens: u32,
         output_tokens: u32,
         cache_creation: u32,
         cache_read: u32,
-        model: Option<&str>,
     ) -> String {
-        let p = pricing_for_model(model.unwrap_or("opus-4-5"));
         let total = input_tokens as u64 + output_tokens as u64
             + cache_creation as u64 + cache_read as u64;
 
-        let cost = (input_tokens as f64 / 1_000_000.0) * p.input
-            + (output_tokens as f64 / 1_000_000.0) * p.output
-            + (cache_creation as f64 / 1_000_000.0) * p.cache_write
-            + (cache_read as f64 / 1_000_000.0) * p.cache_read;
+        let cost = match self.pricing {
+            Some(p) => format_cost(
+                p.cost(input_tokens as u64, output_tokens as u64, cache_creation as u64, cache_read as u64),
+                false,
+            ),
+            None => format_cost(0.0, true),
+        };
 
         let mut parts = vec![
             format!("in: {}", input_tokens),
@@ -100,7 +202,7 @@ impl UsageTracker {
             parts.push(format!("cache: +{}w/{}r", cache_creation, cache_read));
         }
 
-        format!("tokens: {} ({}) · ${:.4}", total, parts.join(", "), cost)
+        format!("tokens: {} ({}) · {}", total, parts.join(", "), cost)
     }
 
     /// Format session totals for display.
@@ -117,11 +219,102 @@ impl UsageTracker {
         }
 
         format!(
-            "Session: {} tokens ({}) · ${:.4} · {} requests",
+            "Session: {} tokens ({}) · {} · {} requests",
             self.total_tokens(),
             parts.join(", "),
-            self.estimated_cost(),
+            format_cost(self.total_cost, self.unpriced),
             self.request_count
         )
     }
+
+    /// Share of the context window used by the last request, as a percentage.
+    pub fn context_percent(&self, limit: usize) -> usize {
+        if limit > 0 { self.context_tokens * 100 / limit } else { 0 }
+    }
+
+    /// Format the current context estimate against the model's context limit.
+    pub fn format_context(&self, limit: usize) -> String {
+        format!(
+            "Context: ~{} / {} tokens ({}%)",
+            self.context_tokens,
+            limit,
+            self.context_percent(limit)
+        )
+    }
+
+    /// Compact context gauge shown after each response, e.g. `ctx: 48k/200k (24%)`.
+    pub fn format_context_gauge(&self, limit: usize) -> String {
+        format!(
+            "ctx: {}/{} ({}%)",
+            abbreviate_tokens(self.context_tokens),
+            abbreviate_tokens(limit),
+            self.context_percent(limit)
+        )
+    }
+}
+
+/// Abbreviate a token count for display: 950, 48k, 1M, 1.5M.
+pub fn abbreviate_tokens(n: usize) -> String {
+    if n >= 1_000_000 {
+        let m = format!("{:.1}", n as f64 / 1_000_000.0);
+        format!("{}M", m.trim_end_matches(".0"))
+    } else if n >= 1_000 {
+        format!("{}k", n / 1_000)
+    } else {
+        n.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_cost_includes_cache_reads() {
+        let mut usage = UsageTracker::new();
+        usage.set_model("claude-sonnet-4-5-20250929");
+        usage.record(1_000_000, 100_000);
+        usage.record_cache(200_000, 2_000_000);
+
+        // 1M in × $3 + 100k out × $15 + 200k writes × $3.75 + 2M reads × $0.30
+        let expected = 3.0 + 1.5 + 0.75 + 0.6;
+        assert!((usage.total_cost - expected).abs() < 1e-9, "cost was {}", usage.total_cost);
+        assert!(usage.format_session_total().contains("$5.8500"));
+    }
+
+    #[test]
+    fn test_pricing_prefers_overrides_and_longest_match() {
+        let opus_4_5 = pricing_for_model("claude-opus-4-5-20251101", &HashMap::new()).unwrap();
+        assert_eq!(opus_4_5.input, 5.0);
+
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "opus".to_string(),
+            ModelPricing { input: 1.0, output: 2.0, cache_write: 1.25, cache_read: 0.1 },
+        );
+        let overridden = pricing_for_model("claude-opus-4-5-20251101", &overrides).unwrap();
+        assert_eq!(overridden.input, 1.0);
+    }
+
+    #[test]
+    fn test_pricing_for_full_model_ids() {
+        let cases = [
+            ("claude-sonnet-4-5-20250929", 3.0),
+            ("claude-3-7-sonnet-20250219", 3.0),
+            ("claude-haiku-4-5-20251001", 1.0),
+            ("claude-3-5-haiku-20241022", 0.80),
+        ];
+        for (model, input) in cases {
+            let pricing = pricing_for_model(model, &HashMap::new());
+            assert_eq!(pricing.map(|p| p.input), Some(input), "{}", model);
+        }
+    }
+
+    #[test]
+    fn test_unknown_model_reports_no_pricing() {
+        let mut usage = UsageTracker::new();
+        usage.set_model("some-local-llama");
+        usage.record(1000, 500);
+        assert!(usage.format_session_total().contains("(no pricing)"));
+    }
 }